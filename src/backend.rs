@@ -0,0 +1,378 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::clock::Clock;
+use ::anyhow::{bail, Result};
+use ::demikernel::{LibOS, OperationResult as LibOSResult, QDesc, QToken};
+use ::std::{
+    fmt,
+    net::{SocketAddr, SocketAddrV4},
+    ops::Deref,
+    str::FromStr,
+    time::Duration,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Backend Kind
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackendKind {
+    /// Demikernel libOS.
+    LibOS,
+    /// Sockets of the operating system.
+    Posix,
+    /// In-memory network.
+    Loopback,
+    /// Simulated network, for testing.
+    Simulator,
+}
+
+/// Failure
+///
+/// Failures of every backend are reported alike, with the same fields as those of the libOS.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fail {
+    /// Error number.
+    pub errno: libc::c_int,
+    /// Cause of the failure.
+    pub cause: String,
+}
+
+/// Received Buffer
+///
+/// Wraps the buffers that backends hand out on pops, which are owned by the libOS, or plain
+/// vectors for other backends.
+pub struct Buffer(Box<dyn Deref<Target = [u8]>>);
+
+/// Operation Result
+///
+/// Outcome of a completed operation, as returned by the libOS.
+#[derive(Debug)]
+pub enum OperationResult {
+    /// Connection established.
+    Connect,
+    /// Connection accepted, on a new socket, along with the address of the peer if the backend
+    /// reports it.
+    Accept(QDesc, Option<SocketAddr>),
+    /// Data pushed.
+    Push,
+    /// Data popped, along with the address of the sender on datagram sockets.
+    Pop(Option<SocketAddr>, Buffer),
+    /// Operation failed.
+    Failed(Fail),
+}
+
+//==============================================================================
+// Traits
+//==============================================================================
+
+/// Network Backend
+///
+/// Queue-based socket operations, after those of the libOS, that applications are written
+/// against. Operations that may not complete right away return a token, which is then waited on.
+pub trait Backend {
+    /// Creates a backend.
+    fn create() -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Asserts if sockets of several backends of this type may bind the same port, in which case
+    /// traffic is balanced between them.
+    fn shares_ports() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+
+    /// Asserts if waits of backends of this type may time out. Otherwise, they block until some
+    /// operation completes.
+    fn has_timed_waits() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+
+    /// Asserts if backends of this type report the address of the peer of accepted connections.
+    fn reports_peers() -> bool
+    where
+        Self: Sized,
+    {
+        true
+    }
+
+    /// Creates a socket.
+    fn socket(
+        &mut self,
+        domain: libc::c_int,
+        socket_type: libc::c_int,
+        protocol: libc::c_int,
+    ) -> Result<QDesc, Fail>;
+
+    /// Binds a socket to a local address.
+    fn bind(&mut self, qd: QDesc, local: SocketAddr) -> Result<(), Fail>;
+
+    /// Binds a socket to a local address that sockets of other backends of this type may bind as
+    /// well, on backends that share ports.
+    fn bind_shared(&mut self, _qd: QDesc, _local: SocketAddr) -> Result<(), Fail> {
+        Err(Fail::new(libc::EOPNOTSUPP, "port sharing not supported"))
+    }
+
+    /// Marks a socket as passive, so that it accepts connections.
+    fn listen(&mut self, qd: QDesc, backlog: usize) -> Result<(), Fail>;
+
+    /// Accepts a connection on a passive socket.
+    fn accept(&mut self, qd: QDesc) -> Result<QToken, Fail>;
+
+    /// Connects a socket to a remote address.
+    fn connect(&mut self, qd: QDesc, remote: SocketAddr) -> Result<QToken, Fail>;
+
+    /// Pushes a copy of `data` to a connected socket.
+    fn push(&mut self, qd: QDesc, data: &[u8]) -> Result<QToken, Fail>;
+
+    /// Pushes a copy of `data` to a remote address.
+    fn pushto(&mut self, qd: QDesc, data: &[u8], to: SocketAddr) -> Result<QToken, Fail>;
+
+    /// Pops data from a socket.
+    fn pop(&mut self, qd: QDesc) -> Result<QToken, Fail>;
+
+    /// Waits for an operation to complete.
+    fn wait(&mut self, qt: QToken) -> Result<(QDesc, OperationResult), Fail>;
+
+    /// Waits for any of several operations to complete, returning the index of its token.
+    fn wait_any(&mut self, qts: &[QToken]) -> Result<(usize, QDesc, OperationResult), Fail>;
+
+    /// Waits for any of several operations to complete, like [Backend::wait_any], but for at
+    /// most `timeout`, if any. Waits that time out fail with `ETIMEDOUT`.
+    fn wait_any_timeout(
+        &mut self,
+        qts: &[QToken],
+        timeout: Option<Duration>,
+    ) -> Result<(usize, QDesc, OperationResult), Fail> {
+        match timeout {
+            None => self.wait_any(qts),
+            Some(_) => Err(Fail::new(libc::EOPNOTSUPP, "timed waits not supported")),
+        }
+    }
+
+    /// Shuts the sending side of a connected socket down, so that the peer sees the end of the
+    /// stream while data may still flow the other way.
+    fn shutdown(&mut self, qd: QDesc) -> Result<(), Fail>;
+
+    /// Closes a socket.
+    fn close(&mut self, qd: QDesc) -> Result<(), Fail>;
+
+    /// Returns the underlying libOS, for features that only it offers, such as zero-copy.
+    fn as_libos(&mut self) -> Option<&mut LibOS> {
+        None
+    }
+
+    /// Returns the clock that applications should read time from. Backends that simulate time
+    /// hand out a clock that follows it.
+    fn clock(&self) -> Clock {
+        Clock::new()
+    }
+
+    /// Returns the status of a run, once the application is done with the target backend.
+    /// Backends that check applications, such as the simulator, fail if a check did.
+    fn status(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Failures
+impl Fail {
+    /// Creates a failure.
+    pub fn new(errno: libc::c_int, cause: &str) -> Self {
+        Self {
+            errno,
+            cause: cause.to_string(),
+        }
+    }
+
+    /// Asserts if the target failure reports that the backend shut down. No operation completes
+    /// after that, so applications should wind down.
+    pub fn is_shutdown(&self) -> bool {
+        self.errno == libc::ESHUTDOWN
+    }
+
+    /// Asserts if the target failure reports that a wait timed out.
+    pub fn is_timeout(&self) -> bool {
+        self.errno == libc::ETIMEDOUT
+    }
+}
+
+/// Associate Functions for Received Buffers
+impl Buffer {
+    /// Wraps a buffer.
+    pub fn new<T: Deref<Target = [u8]> + 'static>(buf: T) -> Self {
+        Self(Box::new(buf))
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+impl FromStr for BackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "libos" => Ok(BackendKind::LibOS),
+            "posix" => Ok(BackendKind::Posix),
+            "loopback" => Ok(BackendKind::Loopback),
+            "sim" => Ok(BackendKind::Simulator),
+            _ => bail!("invalid backend"),
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s: &str = match self {
+            BackendKind::LibOS => "libos",
+            BackendKind::Posix => "posix",
+            BackendKind::Loopback => "loopback",
+            BackendKind::Simulator => "sim",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Deref for Buffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Buffer({} B)", self.len())
+    }
+}
+
+/// Backend Trait Implementation for the LibOS
+impl Backend for LibOS {
+    fn create() -> Result<Self> {
+        Ok(LibOS::new())
+    }
+
+    /// Results of accepts carry no address.
+    fn reports_peers() -> bool {
+        false
+    }
+
+    fn socket(
+        &mut self,
+        domain: libc::c_int,
+        socket_type: libc::c_int,
+        protocol: libc::c_int,
+    ) -> Result<QDesc, Fail> {
+        if domain != libc::AF_INET {
+            return Err(unsupported());
+        }
+        LibOS::socket(self, domain, socket_type, protocol).map_err(|e| Fail::new(e.errno, &e.cause))
+    }
+
+    fn bind(&mut self, qd: QDesc, local: SocketAddr) -> Result<(), Fail> {
+        LibOS::bind(self, qd, ipv4(local)?).map_err(|e| Fail::new(e.errno, &e.cause))
+    }
+
+    fn listen(&mut self, qd: QDesc, backlog: usize) -> Result<(), Fail> {
+        LibOS::listen(self, qd, backlog).map_err(|e| Fail::new(e.errno, &e.cause))
+    }
+
+    fn accept(&mut self, qd: QDesc) -> Result<QToken, Fail> {
+        LibOS::accept(self, qd).map_err(|e| Fail::new(e.errno, &e.cause))
+    }
+
+    fn connect(&mut self, qd: QDesc, remote: SocketAddr) -> Result<QToken, Fail> {
+        LibOS::connect(self, qd, ipv4(remote)?).map_err(|e| Fail::new(e.errno, &e.cause))
+    }
+
+    fn push(&mut self, qd: QDesc, data: &[u8]) -> Result<QToken, Fail> {
+        LibOS::push2(self, qd, data).map_err(|e| Fail::new(e.errno, &e.cause))
+    }
+
+    fn pushto(&mut self, qd: QDesc, data: &[u8], to: SocketAddr) -> Result<QToken, Fail> {
+        LibOS::pushto2(self, qd, data, ipv4(to)?).map_err(|e| Fail::new(e.errno, &e.cause))
+    }
+
+    fn pop(&mut self, qd: QDesc) -> Result<QToken, Fail> {
+        LibOS::pop(self, qd).map_err(|e| Fail::new(e.errno, &e.cause))
+    }
+
+    fn wait(&mut self, qt: QToken) -> Result<(QDesc, OperationResult), Fail> {
+        let (_, qd, result) = Backend::wait_any(self, &[qt])?;
+        Ok((qd, result))
+    }
+
+    fn wait_any(&mut self, qts: &[QToken]) -> Result<(usize, QDesc, OperationResult), Fail> {
+        let (i, qd, result) = match LibOS::wait_any2(self, qts) {
+            Ok((i, qd, result)) => (i, qd, result),
+            Err(e) => return Err(Fail::new(e.errno, &e.cause)),
+        };
+        let result: OperationResult = match result {
+            LibOSResult::Connect => OperationResult::Connect,
+            LibOSResult::Accept(qd) => OperationResult::Accept(qd, None),
+            LibOSResult::Push => OperationResult::Push,
+            LibOSResult::Pop(addr, buf) => {
+                OperationResult::Pop(addr.map(SocketAddr::V4), Buffer::new(buf))
+            }
+            LibOSResult::Failed(e) => OperationResult::Failed(Fail::new(e.errno, &e.cause)),
+        };
+        Ok((i, qd, result))
+    }
+
+    /// The libOS closes both sides of a connection at once.
+    fn shutdown(&mut self, _qd: QDesc) -> Result<(), Fail> {
+        Err(Fail::new(libc::EOPNOTSUPP, "half-close not supported"))
+    }
+
+    fn close(&mut self, qd: QDesc) -> Result<(), Fail> {
+        LibOS::close(self, qd).map_err(|e| Fail::new(e.errno, &e.cause))
+    }
+
+    fn as_libos(&mut self) -> Option<&mut LibOS> {
+        Some(self)
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns the socket domain of an address.
+pub fn domain(addr: &SocketAddr) -> libc::c_int {
+    match addr {
+        SocketAddr::V4(_) => libc::AF_INET,
+        SocketAddr::V6(_) => libc::AF_INET6,
+    }
+}
+
+/// Converts an address for the libOS, which only supports IPv4.
+pub fn ipv4(addr: SocketAddr) -> Result<SocketAddrV4, Fail> {
+    match addr {
+        SocketAddr::V4(addr) => Ok(addr),
+        SocketAddr::V6(_) => Err(unsupported()),
+    }
+}
+
+/// Returns the failure reported when IPv6 is used on the libOS.
+fn unsupported() -> Fail {
+    Fail::new(libc::EAFNOSUPPORT, "IPv6 is not supported by the libOS")
+}
@@ -0,0 +1,595 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::schedule::Rng;
+use ::anyhow::{bail, Context, Result};
+use ::log::{info, warn};
+use ::std::{
+    fmt, fs,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Backend Selection Policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// Backends are picked in turn.
+    RoundRobin,
+    /// Backends are picked at random.
+    Random,
+    /// The backend with the fewest sessions is picked.
+    LeastLoaded,
+    /// Backends are picked by consistent hashing of the source address.
+    SourceHash,
+}
+
+/// Backend
+#[derive(Debug)]
+pub struct Backend {
+    /// Address of the backend.
+    addr: SocketAddr,
+    /// Number of sessions assigned to the backend.
+    nsessions: usize,
+    /// Number of packets sent to the backend.
+    nsent: usize,
+    /// Number of packets received from the backend.
+    nreceived: usize,
+    /// Oldest packet sent to the backend that has not been answered yet.
+    pending_since: Option<Instant>,
+    /// Instant at which the backend was deemed unhealthy.
+    down_since: Option<Instant>,
+    /// Is the backend refusing new sessions?
+    draining: bool,
+}
+
+/// Load Balancer
+///
+/// Picks a backend for every new session. Backends that stop replying for longer than the health
+/// timeout are skipped until the same amount of time has passed, after which they are given
+/// another chance. When every backend is unhealthy, all of them are used. Health tracking is
+/// only meant for backends that answer every request, as the others would be deemed unhealthy.
+/// Draining backends keep their sessions but never get new ones.
+#[derive(Debug)]
+pub struct Balancer {
+    /// Backends.
+    backends: Vec<Backend>,
+    /// Selection policy.
+    policy: Policy,
+    /// Health timeout (`None` disables health tracking).
+    health_timeout: Option<Duration>,
+    /// Next backend for round-robin selection.
+    next: usize,
+    /// Random number generator for random selection.
+    rng: Rng,
+    /// Consistent hashing ring of `(hash, backend)` pairs, sorted by hash.
+    ring: Vec<(u64, usize)>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Backends
+impl Backend {
+    /// Creates a backend.
+    fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            nsessions: 0,
+            nsent: 0,
+            nreceived: 0,
+            pending_since: None,
+            down_since: None,
+            draining: false,
+        }
+    }
+
+    /// Returns the address of the target backend.
+    pub fn get_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the number of sessions assigned to the target backend.
+    pub fn get_nsessions(&self) -> usize {
+        self.nsessions
+    }
+
+    /// Returns the number of packets sent to the target backend.
+    pub fn get_nsent(&self) -> usize {
+        self.nsent
+    }
+
+    /// Returns the number of packets received from the target backend.
+    pub fn get_nreceived(&self) -> usize {
+        self.nreceived
+    }
+
+    /// Asserts if the target backend is deemed healthy.
+    pub fn is_healthy(&self) -> bool {
+        self.down_since.is_none()
+    }
+
+    /// Asserts if the target backend is draining.
+    pub fn is_draining(&self) -> bool {
+        self.draining
+    }
+}
+
+/// Associate Functions for Load Balancers
+impl Balancer {
+    /// Number of points of each backend on the consistent hashing ring.
+    const VIRTUAL_NODES: usize = 128;
+
+    /// Creates a load balancer.
+    pub fn new(
+        addrs: &[SocketAddr],
+        policy: Policy,
+        health_timeout: Option<Duration>,
+    ) -> Result<Self> {
+        if addrs.is_empty() {
+            bail!("no backends")
+        }
+
+        let backends: Vec<Backend> = addrs.iter().map(|addr| Backend::new(*addr)).collect();
+
+        let seed: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_nanos() as u64)
+            .unwrap_or(0x5eed);
+
+        let mut balancer: Balancer = Self {
+            backends,
+            policy,
+            health_timeout,
+            next: 0,
+            rng: Rng::new(seed),
+            ring: Vec::new(),
+        };
+        balancer.build_ring();
+        Ok(balancer)
+    }
+
+    /// Builds the consistent hashing ring.
+    fn build_ring(&mut self) {
+        self.ring = Vec::with_capacity(self.backends.len() * Self::VIRTUAL_NODES);
+        for (i, backend) in self.backends.iter().enumerate() {
+            for v in 0..Self::VIRTUAL_NODES {
+                let hash: u64 = fnv1a(format!("{}#{}", backend.addr, v).as_bytes());
+                self.ring.push((hash, i));
+            }
+        }
+        self.ring.sort_unstable();
+    }
+
+    /// Replaces the list of backends with `(address, draining)` pairs. Backends that are left out
+    /// are drained rather than removed, so that indices of existing sessions stay valid.
+    pub fn update(&mut self, backends: &[(SocketAddr, bool)]) {
+        for backend in self.backends.iter_mut() {
+            backend.draining = true;
+        }
+        for (addr, draining) in backends {
+            match self.find(*addr) {
+                Some(i) => self.backends[i].draining = *draining,
+                None => {
+                    let mut backend: Backend = Backend::new(*addr);
+                    backend.draining = *draining;
+                    self.backends.push(backend);
+                }
+            }
+        }
+        self.build_ring();
+    }
+
+    /// Marks a backend as healthy or not, as reported by an external health check.
+    pub fn set_healthy(&mut self, i: usize, healthy: bool, now: Instant) {
+        let backend: &mut Backend = &mut self.backends[i];
+        match (healthy, backend.down_since) {
+            (true, Some(_)) => {
+                backend.down_since = None;
+                info!("backend {:?} is up", backend.addr);
+            }
+            (false, None) => {
+                backend.down_since = Some(now);
+                warn!("backend {:?} is down", backend.addr);
+            }
+            _ => (),
+        }
+    }
+
+    /// Returns the backends of the target load balancer.
+    pub fn get_backends(&self) -> &[Backend] {
+        &self.backends
+    }
+
+    /// Returns the address of a backend.
+    pub fn get_addr(&self, i: usize) -> SocketAddr {
+        self.backends[i].addr
+    }
+
+    /// Returns the index of the backend that has a given address.
+    pub fn find(&self, addr: SocketAddr) -> Option<usize> {
+        self.backends.iter().position(|b| b.addr == addr)
+    }
+
+    /// Asserts if a backend may be sent new sessions.
+    pub fn is_usable(&self, i: usize) -> bool {
+        let live = |b: &Backend| !b.draining;
+        live(&self.backends[i])
+            && (self.backends[i].is_healthy()
+                || self
+                    .backends
+                    .iter()
+                    .filter(|b| live(b))
+                    .all(|b| !b.is_healthy()))
+    }
+
+    /// Picks a backend for a new session and assigns the session to it. Source hashing relies on
+    /// the address of the client, and falls back to round-robin without it. `None` means that
+    /// every backend is draining.
+    pub fn select(&mut self, client: Option<SocketAddr>) -> Option<usize> {
+        let n: usize = self.backends.len();
+        let i: usize = match (self.policy, client) {
+            (Policy::SourceHash, Some(client)) => {
+                let hash: u64 = match client.ip() {
+                    IpAddr::V4(ip) => fnv1a(&ip.octets()),
+                    IpAddr::V6(ip) => fnv1a(&ip.octets()),
+                };
+                let start: usize = self.ring.partition_point(|(h, _)| *h < hash);
+                (0..self.ring.len())
+                    .map(|k| self.ring[(start + k) % self.ring.len()].1)
+                    .find(|&i| self.is_usable(i))?
+            }
+            (Policy::RoundRobin, _) | (Policy::SourceHash, None) => {
+                let i: usize = (0..n)
+                    .map(|k| (self.next + k) % n)
+                    .find(|&i| self.is_usable(i))?;
+                self.next = i + 1;
+                i
+            }
+            (Policy::Random, _) => {
+                let usable: Vec<usize> = (0..n).filter(|&i| self.is_usable(i)).collect();
+                if usable.is_empty() {
+                    return None;
+                }
+                usable[(self.rng.next_u64() % usable.len() as u64) as usize]
+            }
+            (Policy::LeastLoaded, _) => (0..n)
+                .filter(|&i| self.is_usable(i))
+                .min_by_key(|&i| self.backends[i].nsessions)?,
+        };
+        self.backends[i].nsessions += 1;
+        Some(i)
+    }
+
+    /// Releases a session that was assigned to a backend.
+    pub fn release(&mut self, i: usize) {
+        self.backends[i].nsessions = self.backends[i].nsessions.saturating_sub(1);
+    }
+
+    /// Moves a session of `client` away from an unusable backend. Returns the new backend, if
+    /// any.
+    pub fn reassign(&mut self, i: usize, client: SocketAddr) -> Option<usize> {
+        if self.is_usable(i) {
+            return None;
+        }
+        let j: usize = self.select(Some(client))?;
+        self.release(i);
+        Some(j)
+    }
+
+    /// Accounts a packet sent to a backend.
+    pub fn on_send(&mut self, i: usize, now: Instant) {
+        let backend: &mut Backend = &mut self.backends[i];
+        backend.nsent += 1;
+        if backend.pending_since.is_none() {
+            backend.pending_since = Some(now);
+        }
+    }
+
+    /// Accounts a packet received from a backend.
+    pub fn on_reply(&mut self, i: usize) {
+        let backend: &mut Backend = &mut self.backends[i];
+        backend.nreceived += 1;
+        backend.pending_since = None;
+        if backend.down_since.take().is_some() {
+            info!("backend {:?} is up", backend.addr);
+        }
+    }
+
+    /// Updates the health of backends.
+    pub fn check_health(&mut self, now: Instant) {
+        let timeout: Duration = match self.health_timeout {
+            Some(timeout) => timeout,
+            None => return,
+        };
+        for backend in self.backends.iter_mut() {
+            match (backend.down_since, backend.pending_since) {
+                // Give unhealthy backends another chance after a while.
+                (Some(down_since), _) if now.saturating_duration_since(down_since) > timeout => {
+                    backend.down_since = None;
+                    backend.pending_since = None;
+                    info!("backend {:?} is being probed", backend.addr);
+                }
+                // Backends that do not reply are deemed unhealthy.
+                (None, Some(pending_since))
+                    if now.saturating_duration_since(pending_since) > timeout =>
+                {
+                    backend.down_since = Some(now);
+                    warn!("backend {:?} is down", backend.addr);
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// From String Trait Implementation for Backend Selection Policies
+impl FromStr for Policy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "round-robin" => Ok(Policy::RoundRobin),
+            "random" => Ok(Policy::Random),
+            "least-loaded" | "least-connections" => Ok(Policy::LeastLoaded),
+            "source-hash" => Ok(Policy::SourceHash),
+            _ => bail!("invalid backend selection policy"),
+        }
+    }
+}
+
+/// Display Trait Implementation for Backend Selection Policies
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s: &str = match self {
+            Policy::RoundRobin => "round-robin",
+            Policy::Random => "random",
+            Policy::LeastLoaded => "least-loaded",
+            Policy::SourceHash => "source-hash",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Computes the FNV-1a hash of some bytes. Unlike the hasher of the standard library, it is stable
+/// across runs and builds, so that clients keep mapping to the same backends. FNV-1a barely mixes
+/// the last bytes of its input, so the hash goes through the finalizer of MurmurHash3, without
+/// which addresses of the same subnet would land next to each other on the ring.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = bytes
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash: u64, b: &u8| {
+            (hash ^ *b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+/// Loads a list of backends from a file, as `(address, draining)` pairs. Each line holds an
+/// address, optionally followed by `drain`. Empty lines and `#` comments are ignored.
+pub fn load_backends(path: &str) -> Result<Vec<(SocketAddr, bool)>> {
+    let contents: String =
+        fs::read_to_string(path).with_context(|| format!("failed to read {:?}", path))?;
+    let mut backends: Vec<(SocketAddr, bool)> = Vec::new();
+    for (n, line) in contents.lines().enumerate() {
+        let line: &str = line.split('#').next().unwrap_or_default().trim();
+        let mut tokens = line.split_whitespace();
+        let addr: SocketAddr = match tokens.next() {
+            Some(addr) => addr
+                .parse()
+                .with_context(|| format!("{}:{}: invalid address", path, n + 1))?,
+            None => continue,
+        };
+        let draining: bool = match (tokens.next(), tokens.next()) {
+            (None, None) => false,
+            (Some("drain"), None) => true,
+            _ => bail!(
+                "{}:{}: expected an address optionally followed by drain",
+                path,
+                n + 1
+            ),
+        };
+        backends.push((addr, draining));
+    }
+    Ok(backends)
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::std::env;
+
+    /// Returns the address of a backend.
+    fn backend(port: u16) -> SocketAddr {
+        SocketAddr::from(([10, 0, 0, 1], port))
+    }
+
+    /// Returns the address of a client.
+    fn client(host: u8) -> SocketAddr {
+        SocketAddr::from(([192, 0, 2, host], 1234))
+    }
+
+    /// Creates a load balancer over three backends.
+    fn balancer(policy: Policy, health_timeout: Option<Duration>) -> Balancer {
+        Balancer::new(
+            &[backend(1), backend(2), backend(3)],
+            policy,
+            health_timeout,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn policies_parse() {
+        for policy in ["round-robin", "random", "least-loaded", "source-hash"] {
+            assert_eq!(policy.parse::<Policy>().unwrap().to_string(), policy);
+        }
+        assert_eq!(
+            "least-connections".parse::<Policy>().unwrap(),
+            Policy::LeastLoaded
+        );
+        assert!("fastest".parse::<Policy>().is_err());
+        assert!(Balancer::new(&[], Policy::RoundRobin, None).is_err());
+    }
+
+    #[test]
+    fn round_robin_takes_turns() {
+        let mut balancer: Balancer = balancer(Policy::RoundRobin, None);
+        let picks: Vec<Option<usize>> = (0..4).map(|_| balancer.select(None)).collect();
+        assert_eq!(picks, [Some(0), Some(1), Some(2), Some(0)]);
+        assert_eq!(balancer.get_backends()[0].get_nsessions(), 2);
+    }
+
+    #[test]
+    fn least_loaded_fills_the_emptiest_backend() {
+        let mut balancer: Balancer = balancer(Policy::LeastLoaded, None);
+        assert_eq!(balancer.select(None), Some(0));
+        assert_eq!(balancer.select(None), Some(1));
+        balancer.release(0);
+        assert_eq!(balancer.select(None), Some(0));
+        assert_eq!(balancer.select(None), Some(2));
+        assert_eq!(balancer.select(None), Some(0));
+    }
+
+    #[test]
+    fn source_hashing_is_sticky() {
+        let mut balancer: Balancer = balancer(Policy::SourceHash, None);
+        let picks: Vec<Option<usize>> =
+            (1..=32).map(|h| balancer.select(Some(client(h)))).collect();
+        for (h, pick) in (1..=32).zip(&picks) {
+            assert_eq!(balancer.select(Some(client(h))), *pick);
+        }
+        // Clients spread over backends.
+        for i in 0..3 {
+            assert!(picks.contains(&Some(i)));
+        }
+
+        // Only clients of a drained backend move.
+        balancer.update(&[(backend(1), false), (backend(2), true), (backend(3), false)]);
+        for (h, pick) in (1..=32).zip(&picks) {
+            let moved: Option<usize> = balancer.select(Some(client(h)));
+            match pick {
+                Some(1) => assert_ne!(moved, Some(1)),
+                _ => assert_eq!(moved, *pick),
+            }
+        }
+    }
+
+    #[test]
+    fn random_picks_usable_backends() {
+        let mut balancer: Balancer = balancer(Policy::Random, None);
+        balancer.update(&[(backend(1), true), (backend(2), false), (backend(3), true)]);
+        for _ in 0..32 {
+            assert_eq!(balancer.select(None), Some(1));
+        }
+        balancer.update(&[]);
+        assert_eq!(balancer.select(None), None);
+    }
+
+    #[test]
+    fn updates_add_and_drain_backends() {
+        let mut balancer: Balancer = balancer(Policy::RoundRobin, None);
+        balancer.update(&[(backend(3), false), (backend(4), false)]);
+        assert_eq!(balancer.find(backend(4)), Some(3));
+        assert!(balancer.get_backends()[0].is_draining());
+        assert!(!balancer.get_backends()[2].is_draining());
+        let picks: Vec<Option<usize>> = (0..4).map(|_| balancer.select(None)).collect();
+        assert_eq!(picks, [Some(2), Some(3), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn silent_backends_are_skipped_then_probed() {
+        let timeout: Duration = Duration::from_secs(5);
+        let mut balancer: Balancer = balancer(Policy::RoundRobin, Some(timeout));
+        let start: Instant = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        // The first backend does not reply, the second does.
+        balancer.on_send(0, at(0));
+        balancer.on_send(1, at(0));
+        balancer.on_reply(1);
+        balancer.check_health(at(5));
+        assert!(balancer.get_backends()[0].is_healthy());
+        balancer.check_health(at(6));
+        assert!(!balancer.get_backends()[0].is_healthy());
+        assert!(balancer.get_backends()[1].is_healthy());
+        assert!(!balancer.is_usable(0));
+
+        // Sessions move away from it.
+        assert_eq!(balancer.reassign(0, client(1)), Some(1));
+        assert_eq!(balancer.reassign(1, client(1)), None);
+
+        // It is given another chance after a while, and a reply brings it back for good.
+        balancer.check_health(at(12));
+        assert!(balancer.get_backends()[0].is_healthy());
+        balancer.set_healthy(0, false, at(12));
+        assert!(!balancer.is_usable(0));
+        balancer.on_reply(0);
+        assert!(balancer.is_usable(0));
+    }
+
+    #[test]
+    fn health_is_not_tracked_by_default() {
+        let mut balancer: Balancer = balancer(Policy::RoundRobin, None);
+        let start: Instant = Instant::now();
+        balancer.on_send(0, start);
+        balancer.check_health(start + Duration::from_secs(3600));
+        assert!(balancer.get_backends()[0].is_healthy());
+        assert_eq!(balancer.get_backends()[0].get_nsent(), 1);
+        assert_eq!(balancer.get_backends()[0].get_nreceived(), 0);
+    }
+
+    #[test]
+    fn unhealthy_backends_are_used_when_all_are() {
+        let mut balancer: Balancer = balancer(Policy::RoundRobin, None);
+        let now: Instant = Instant::now();
+        for i in 0..3 {
+            balancer.set_healthy(i, false, now);
+        }
+        assert!((0..3).all(|i| balancer.is_usable(i)));
+        balancer.set_healthy(2, true, now);
+        assert_eq!(balancer.select(None), Some(2));
+        assert_eq!(balancer.select(None), Some(2));
+    }
+
+    #[test]
+    fn backend_files_load() {
+        let path: String = env::temp_dir()
+            .join(format!("demikernel-backends-{}.txt", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        fs::write(&path, "# backends\n10.0.0.1:1\n\n10.0.0.1:2 drain # old\n").unwrap();
+        let backends: Vec<(SocketAddr, bool)> = load_backends(&path).unwrap();
+        assert_eq!(backends, [(backend(1), false), (backend(2), true)]);
+
+        fs::write(&path, "10.0.0.1:1 keep\n").unwrap();
+        assert!(load_backends(&path).is_err());
+        fs::write(&path, "10.0.0.1\n").unwrap();
+        assert!(load_backends(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+#![cfg_attr(feature = "strict", deny(warnings))]
+#![deny(clippy::all)]
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+mod tcp_dump;
+mod tcp_echo;
+mod tcp_lb;
+mod tcp_pktgen;
+mod tcp_relay;
+mod udp_dump;
+mod udp_echo;
+mod udp_pktgen;
+mod udp_relay;
+
+use ::anyhow::Result;
+use ::clap::Command;
+use ::std::{env, path::Path};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Applet
+///
+/// An application linked into `demi`. It runs either as `demi <name> [ARGS]`, or through a
+/// symlink to `demi` that is named after it, as the standalone binary that it used to be.
+struct Applet {
+    /// Name of the applet.
+    name: &'static str,
+    /// Description of the applet.
+    about: &'static str,
+    /// Runs the applet with the given command line.
+    main: fn(Vec<String>) -> Result<()>,
+}
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Applets.
+static APPLETS: [Applet; 9] = [
+    Applet {
+        name: "tcp-dump",
+        about: tcp_dump::ABOUT,
+        main: tcp_dump::main,
+    },
+    Applet {
+        name: "tcp-echo",
+        about: tcp_echo::ABOUT,
+        main: tcp_echo::main,
+    },
+    Applet {
+        name: "tcp-lb",
+        about: tcp_lb::ABOUT,
+        main: tcp_lb::main,
+    },
+    Applet {
+        name: "tcp-pktgen",
+        about: tcp_pktgen::ABOUT,
+        main: tcp_pktgen::main,
+    },
+    Applet {
+        name: "tcp-relay",
+        about: tcp_relay::ABOUT,
+        main: tcp_relay::main,
+    },
+    Applet {
+        name: "udp-dump",
+        about: udp_dump::ABOUT,
+        main: udp_dump::main,
+    },
+    Applet {
+        name: "udp-echo",
+        about: udp_echo::ABOUT,
+        main: udp_echo::main,
+    },
+    Applet {
+        name: "udp-pktgen",
+        about: udp_pktgen::ABOUT,
+        main: udp_pktgen::main,
+    },
+    Applet {
+        name: "udp-relay",
+        about: udp_relay::ABOUT,
+        main: udp_relay::main,
+    },
+];
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Looks up an applet by name.
+fn find(name: &str) -> Option<&'static Applet> {
+    APPLETS.iter().find(|applet| applet.name == name)
+}
+
+fn main() -> Result<()> {
+    let mut argv: Vec<String> = env::args().collect();
+    let bin_name: String = match argv.first() {
+        Some(arg0) => Path::new(arg0)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        None => String::new(),
+    };
+
+    // Invoked through a symlink named after an applet.
+    if let Some(applet) = find(&bin_name) {
+        return (applet.main)(argv);
+    }
+
+    // Invoked as `demi <applet> [ARGS]`.
+    if let Some(applet) = argv.get(1).and_then(|name| find(name)) {
+        argv.remove(0);
+        argv[0] = format!("{} {}", bin_name, applet.name);
+        return (applet.main)(argv);
+    }
+
+    // Otherwise, report usage.
+    Command::new("demi")
+        .author("Pedro Henrique Penna <ppenna@microsoft.com>")
+        .about("Runs Demikernel applications, also through symlinks named after them.")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommands(
+            APPLETS
+                .iter()
+                .map(|applet| Command::new(applet.name).about(applet.about)),
+        )
+        .get_matches_from(argv);
+    Ok(())
+}
@@ -0,0 +1,1206 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Result};
+use ::apps::backend::{domain, Backend, BackendKind, OperationResult};
+use ::apps::clock::Clock;
+use ::apps::config::Config;
+use ::apps::control::{ControlSocket, Controller, Controllers, Request};
+use ::apps::dashboard::{Dashboard, Sample};
+use ::apps::endpoint::resolve;
+use ::apps::event;
+use ::apps::logging::Logging;
+use ::apps::loopback::Loopback;
+use ::apps::placement::Placement;
+use ::apps::posix::Posix;
+use ::apps::sim::Simulator;
+use ::apps::timer::{TimerId, TimerWheel};
+use ::apps::tracer::{Traced, Tracing};
+use ::apps::{
+    scenario::Scenario,
+    schedule::{Phase, PhaseStatistics, Rate, Rng, Schedule, SizeDistribution},
+    sga::{self, ZeroCopyStatistics},
+    workers::{self, WorkerStatistics, Workers},
+};
+use ::clap::{Arg, ArgMatches, Command};
+use ::demikernel::{demi_opcode_t, demi_qresult_t, demi_sgarray_t};
+use ::demikernel::{LibOS, QDesc, QToken};
+use ::log::{info, Level};
+use ::std::collections::HashMap;
+use ::std::net::SocketAddr;
+use ::std::sync::Arc;
+use ::std::time::{Duration, Instant};
+
+//==============================================================================
+// Program Arguments
+//==============================================================================
+
+/// Program Arguments
+#[derive(Clone, Debug)]
+pub struct ProgramArguments {
+    /// Local socket address.
+    local: Option<SocketAddr>,
+    /// Remote socket address.
+    remote: Option<SocketAddr>,
+    /// Buffer size (in bytes).
+    bufsize: usize,
+    /// Peer type.
+    peer_type: String,
+    /// Traffic schedule of the client, loaded from a scenario file.
+    schedule: Option<Schedule>,
+    /// Echo received buffers without copying them?
+    zero_copy: bool,
+    /// Number of workers.
+    workers: usize,
+    /// Backend to run on.
+    io_backend: BackendKind,
+    /// Placement of the process.
+    placement: Placement,
+    /// Tracing of operations.
+    tracing: Tracing,
+    /// Show a live dashboard?
+    tui: bool,
+    /// Control socket.
+    control_socket: ControlSocket,
+}
+
+/// Associate functions for Program Arguments
+impl ProgramArguments {
+    /// Default buffer size.
+    const DEFAULT_BUFSIZE: usize = 1024;
+
+    /// Parses the program arguments from the command line `argv`.
+    pub fn new(
+        app_name: &str,
+        app_author: &str,
+        app_about: &str,
+        argv: Vec<String>,
+    ) -> Result<Self> {
+        let command: Command = Command::new(app_name)
+            .author(app_author)
+            .about(app_about)
+            .arg(
+                Arg::new("local")
+                    .long("local")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("HOST:PORT")
+                    .help("Sets local address"),
+            )
+            .arg(
+                Arg::new("remote")
+                    .long("remote")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("HOST:PORT")
+                    .help("Sets remote address"),
+            )
+            .arg(
+                Arg::new("peer")
+                    .long("peer")
+                    .takes_value(true)
+                    .value_name("server|client")
+                    .help("Sets peer type"),
+            )
+            .arg(
+                Arg::new("peer_type")
+                    .index(1)
+                    .required(false)
+                    .value_name("server|client")
+                    .help("Sets peer type, as with --peer"),
+            )
+            .arg(
+                Arg::new("bufsize")
+                    .long("bufsize")
+                    .takes_value(true)
+                    .required(true)
+                    .value_name("SIZE")
+                    .help("Sets buffer size"),
+            )
+            .arg(
+                Arg::new("scenario")
+                    .long("scenario")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("FILE")
+                    .help("Loads client traffic phases from a TOML or YAML scenario file"),
+            )
+            .arg(
+                Arg::new("zero_copy")
+                    .long("zero_copy")
+                    .takes_value(false)
+                    .required(false)
+                    .help("Echoes received buffers without copying them (server only)"),
+            )
+            .arg(
+                Arg::new("workers")
+                    .long("workers")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("COUNT")
+                    .help(
+                        "Sets number of server threads, which share the port on the POSIX backend \
+                         and listen on consecutive ports on the libOS (server only)",
+                    ),
+            )
+            .arg(
+                Arg::new("io_backend")
+                    .long("io_backend")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("libos|posix|loopback|sim")
+                    .help("Sets backend to run on"),
+            )
+            .args(Placement::args())
+            .args(Logging::args())
+            .args(Tracing::args())
+            .args(Dashboard::args())
+            .args(ControlSocket::args())
+            .args(Config::args());
+        let matches: ArgMatches = Config::get_matches_from(command, argv)?;
+
+        // Default arguments.
+        let mut args: ProgramArguments = ProgramArguments {
+            local: None,
+            remote: None,
+            bufsize: Self::DEFAULT_BUFSIZE,
+            peer_type: "server".to_string(),
+            schedule: None,
+            zero_copy: matches.is_present("zero_copy"),
+            workers: 1,
+            io_backend: BackendKind::LibOS,
+            placement: Placement::from_matches(&matches)?,
+            tracing: Tracing::from_matches(&matches)?,
+            tui: matches.is_present("tui"),
+            control_socket: ControlSocket::from_matches(&matches)?,
+        };
+
+        // Local address.
+        if let Some(addr) = matches.value_of("local") {
+            args.set_local_addr(addr)?;
+        }
+
+        // Remote address.
+        if let Some(addr) = matches.value_of("remote") {
+            args.set_remote_addr(addr)?;
+        }
+
+        // Buffer size.
+        if let Some(bufsize) = matches.value_of("bufsize") {
+            args.set_bufsize(bufsize)?;
+        }
+
+        // Peer type
+        if let Some(peer_type) = matches.value_of("peer_type").or(matches.value_of("peer")) {
+            args.set_peer_type(peer_type.to_string())?;
+        }
+
+        // Scenario.
+        if let Some(path) = matches.value_of("scenario") {
+            args.set_scenario(path)?;
+        }
+
+        // Number of workers.
+        if let Some(workers) = matches.value_of("workers") {
+            args.set_workers(workers)?;
+        }
+
+        // Backend.
+        if let Some(backend) = matches.value_of("io_backend") {
+            args.set_io_backend(backend)?;
+        }
+        if args.zero_copy && args.io_backend != BackendKind::LibOS {
+            bail!("zero-copy requires the libos backend");
+        }
+
+        Ok(args)
+    }
+
+    /// Returns the placement parameter stored in the target program arguments.
+    pub fn get_placement(&self) -> &Placement {
+        &self.placement
+    }
+
+    /// Returns the tracing parameter stored in the target program arguments.
+    pub fn get_tracing(&self) -> &Tracing {
+        &self.tracing
+    }
+
+    /// Returns the dashboard parameter stored in the target program arguments.
+    pub fn is_tui(&self) -> bool {
+        self.tui
+    }
+
+    /// Returns the control socket parameter stored in the target program arguments.
+    pub fn get_control_socket(&self) -> &ControlSocket {
+        &self.control_socket
+    }
+
+    /// Returns the backend parameter stored in the target program arguments.
+    pub fn get_io_backend(&self) -> BackendKind {
+        self.io_backend
+    }
+
+    /// Returns the buffer size parameter stored in the target program arguments.
+    pub fn get_bufsize(&self) -> usize {
+        self.bufsize
+    }
+
+    /// Returns the peer type.
+    pub fn get_peer_type(&self) -> String {
+        self.peer_type.to_string()
+    }
+
+    /// Returns the client traffic schedule stored in the target program arguments.
+    pub fn get_schedule(&self) -> Option<Schedule> {
+        self.schedule.clone()
+    }
+
+    /// Asserts if received buffers should be echoed without copying them.
+    pub fn is_zero_copy(&self) -> bool {
+        self.zero_copy
+    }
+
+    /// Returns the number of workers parameter stored in the target program arguments.
+    pub fn get_workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Returns the local endpoint address parameter stored in the target program arguments.
+    pub fn get_local(&self) -> Option<SocketAddr> {
+        self.local
+    }
+
+    /// Returns the remote endpoint address parameter stored in the target program arguments.
+    pub fn get_remote(&self) -> Option<SocketAddr> {
+        self.remote
+    }
+
+    /// Sets the buffer size parameter in the target program arguments.
+    fn set_bufsize(&mut self, bufsize_str: &str) -> Result<()> {
+        let bufsize: usize = bufsize_str.parse()?;
+        if bufsize > 0 {
+            self.bufsize = bufsize;
+            Ok(())
+        } else {
+            bail!("invalid buffer size")
+        }
+    }
+
+    /// Sets the peer type.
+    fn set_peer_type(&mut self, peer_type: String) -> Result<()> {
+        if peer_type != "server" && peer_type != "client" {
+            bail!("invalid peer type")
+        } else {
+            self.peer_type = peer_type;
+            Ok(())
+        }
+    }
+
+    /// Loads the client traffic schedule from a scenario file. Phases without a size use the
+    /// buffer size parameter and phases without a rate push as fast as echoes come back.
+    fn set_scenario(&mut self, path: &str) -> Result<()> {
+        let default: Phase = Phase::new(
+            None,
+            Rate::Interval(Duration::ZERO),
+            SizeDistribution::Fixed(self.bufsize),
+            1,
+        )?;
+        self.schedule = Some(Scenario::load(path)?.to_schedule(&default)?);
+        Ok(())
+    }
+
+    /// Sets the number of workers parameter in the target program arguments.
+    fn set_workers(&mut self, workers_str: &str) -> Result<()> {
+        let workers: usize = workers_str.parse()?;
+        if workers > 0 {
+            self.workers = workers;
+            Ok(())
+        } else {
+            bail!("invalid number of workers")
+        }
+    }
+
+    /// Sets the local address and port number parameters in the target program arguments.
+    fn set_local_addr(&mut self, addr: &str) -> Result<()> {
+        self.local = Some(resolve(addr)?);
+        Ok(())
+    }
+
+    /// Sets the remote address and port number parameters in the target program arguments.
+    fn set_remote_addr(&mut self, addr: &str) -> Result<()> {
+        self.remote = Some(resolve(addr)?);
+        Ok(())
+    }
+
+    /// Sets the backend parameter in the target program arguments.
+    fn set_io_backend(&mut self, backend_str: &str) -> Result<()> {
+        self.io_backend = backend_str.parse()?;
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Application
+//==============================================================================
+
+/// Timers of the Run Loop
+#[derive(Debug)]
+enum Timer {
+    /// Emits statistics.
+    Log,
+    /// Pushes a burst of messages.
+    Push,
+}
+
+/// Application
+struct Application<B: Backend> {
+    /// Underlying libOS.
+    libos: B,
+    // Local socket descriptor.
+    sockqd: QDesc,
+    /// Buffer size.
+    bufsize: usize,
+    /// Is server?
+    is_server: bool,
+    /// Remote endpoint of the client.
+    remote: Option<SocketAddr>,
+    /// Traffic schedule of the client.
+    schedule: Option<Schedule>,
+    /// Echo received buffers without copying them?
+    zero_copy: bool,
+    /// Statistics published when running as a worker.
+    stats: Option<Arc<WorkerStatistics>>,
+    /// Controller of the control socket, if any.
+    controller: Option<Controller>,
+    /// Live dashboard, if any.
+    dashboard: Option<Dashboard>,
+}
+
+/// Associated Functions for the Application
+impl<B: Backend> Application<B> {
+    /// Logging interval (in seconds).
+    const LOG_INTERVAL: u64 = 5;
+
+    /// Granularity of timers.
+    const TIMER_GRANULARITY: Duration = Duration::from_micros(1);
+
+    /// Number of slots in the timer wheel.
+    const TIMER_SLOTS: usize = 4096;
+
+    /// Seed for drawing message sizes, fixed so that runs are reproducible.
+    const SEED: u64 = 0x65;
+
+    /// Instantiates a client application.
+    fn new_client(mut libos: B, args: &ProgramArguments) -> Result<Self> {
+        let bufsize: usize = args.get_bufsize();
+        if let Some(remote) = args.get_remote() {
+            // Create TCP socket.
+            let sockqd: QDesc = match libos.socket(domain(&remote), libc::SOCK_STREAM, 0) {
+                Ok(qd) => qd,
+                Err(e) => panic!("failed to create socket: {:?}", e.cause),
+            };
+
+            // Setup connection.
+            let qt: QToken = match libos.connect(sockqd, remote) {
+                Ok(qt) => qt,
+                Err(e) => panic!("failed to connect socket: {:?}", e.cause),
+            };
+            match libos.wait(qt) {
+                Ok((_, OperationResult::Connect)) => {
+                    event!(Level::Debug, sockqd, Some(remote), "connected!")
+                }
+                Err(e) => panic!("operation failed: {:?}", e),
+                _ => panic!("unexpected result"),
+            }
+
+            info!("Remote Address: {:?}", remote);
+
+            return Ok(Self {
+                libos,
+                sockqd,
+                bufsize,
+                is_server: false,
+                remote: Some(remote),
+                schedule: args.get_schedule(),
+                zero_copy: false,
+                stats: None,
+                controller: None,
+                dashboard: None,
+            });
+        };
+
+        bail!("missing remote address")
+    }
+
+    /// Instantiates a server application.
+    fn new_server(mut libos: B, args: &ProgramArguments) -> Result<Self> {
+        let bufsize: usize = args.get_bufsize();
+        if let Some(local) = args.get_local() {
+            // Create TCP socket.
+            let sockqd: QDesc = match libos.socket(domain(&local), libc::SOCK_STREAM, 0) {
+                Ok(qd) => qd,
+                Err(e) => panic!("failed to create socket: {:?}", e.cause),
+            };
+
+            // Bind to local address.
+            match workers::bind(&mut libos, sockqd, local, args.get_workers()) {
+                Ok(()) => (),
+                Err(e) => panic!("failed to bind socket: {:?}", e.cause),
+            };
+
+            // Mark socket as a passive one.
+            match libos.listen(sockqd, 16) {
+                Ok(()) => (),
+                Err(e) => panic!("failed to listen socket: {:?}", e.cause),
+            }
+
+            info!("Local Address: {:?}", local);
+
+            return Ok(Self {
+                libos,
+                sockqd,
+                bufsize,
+                is_server: true,
+                remote: None,
+                schedule: None,
+                zero_copy: args.is_zero_copy(),
+                stats: None,
+                controller: None,
+                dashboard: None,
+            });
+        }
+
+        bail!("missing local address")
+    }
+
+    /// Instantiates the application.
+    pub fn new(libos: B, args: &ProgramArguments) -> Result<Self> {
+        let peer_type: String = args.get_peer_type();
+
+        if peer_type == "server" {
+            Self::new_server(libos, args)
+        } else {
+            Self::new_client(libos, args)
+        }
+    }
+
+    /// Makes the target application publish its statistics instead of dumping them.
+    pub fn set_worker_statistics(&mut self, stats: Arc<WorkerStatistics>) {
+        self.stats = Some(stats);
+    }
+
+    /// Makes the target application apply commands of a control socket.
+    pub fn set_controller(&mut self, controller: Option<Controller>) {
+        self.controller = controller;
+    }
+
+    /// Makes the target application show a live dashboard instead of dumping statistics.
+    pub fn set_dashboard(&mut self, dashboard: Option<Dashboard>) {
+        self.dashboard = dashboard;
+    }
+
+    /// Runs the target echo server.
+    pub fn run_server(&mut self) -> Result<()> {
+        if self.zero_copy {
+            return self.run_server_zero_copy();
+        }
+
+        let clock: Clock = self.libos.clock();
+        let start: Instant = clock.now();
+        let mut nbytes: usize = 0;
+        let mut since: Instant = start;
+        let mut connections: Vec<QDesc> = Vec::new();
+        let mut peers: HashMap<QDesc, SocketAddr> = HashMap::new();
+        let mut last: Instant = start;
+        let mut sample: Sample = Sample::default();
+        let mut qtokens: Vec<QToken> = Vec::new();
+        let mut timers: TimerWheel<Timer> =
+            TimerWheel::new(Self::TIMER_GRANULARITY, Self::TIMER_SLOTS, start);
+        timers.schedule(start + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+
+        // Accept first connection.
+        let qt: QToken = match self.libos.accept(self.sockqd) {
+            Ok(qt) => qt,
+            Err(e) => panic!("failed to accept connection on socket: {:?}", e.cause),
+        };
+        qtokens.push(qt);
+
+        loop {
+            // Apply commands of the control socket.
+            if let Some(command) = self.controller.as_ref().and_then(Controller::poll) {
+                let now: Instant = clock.now();
+                match command.get_request().clone() {
+                    Request::Stats => {
+                        command.reply(&format!(
+                            "bytes: {}\nelapsed_us: {}\nconnections: {}",
+                            nbytes,
+                            (now - since).as_micros(),
+                            connections.len()
+                        ));
+                    }
+                    Request::Connections => {
+                        let lines: Vec<String> = connections
+                            .iter()
+                            .map(|qd| format!("qd {}", u32::from(*qd)))
+                            .collect();
+                        command.reply(&lines.join("\n"));
+                    }
+                    Request::ResetCounters => {
+                        nbytes = 0;
+                        since = now;
+                        command.reply("ok");
+                    }
+                    Request::Shutdown => {
+                        command.reply("ok");
+                        break;
+                    }
+                    _ => command.unsupported(),
+                }
+            }
+
+            // Publish statistics when running as a worker, or dump them.
+            if let Some(stats) = &self.stats {
+                stats.set_nbytes(nbytes);
+            } else {
+                let now: Instant = clock.now();
+                for timer in timers.expire(now) {
+                    match timer {
+                        Timer::Log => {
+                            if let Some(dashboard) = &self.dashboard {
+                                sample.set_nconnections(connections.len());
+                                dashboard.update(sample.close(now - last));
+                                last = now;
+                            } else {
+                                let elapsed: Duration = now - since;
+                                info!("{:?} B / {:?} us", nbytes, elapsed.as_micros());
+                            }
+                            timers.schedule(
+                                now + Duration::from_secs(Self::LOG_INTERVAL),
+                                Timer::Log,
+                            );
+                        }
+                        Timer::Push => unreachable!("only scenario clients push on timers"),
+                    }
+                }
+            }
+
+            // Wait for traffic, waking up in time to refresh the dashboard.
+            let timeout: Option<Duration> = match self.dashboard {
+                Some(_) => timers
+                    .next_deadline()
+                    .map(|deadline| deadline.saturating_duration_since(clock.now())),
+                None => None,
+            };
+            let timeout: Option<Duration> =
+                Controller::timeout::<B>(self.controller.as_ref(), timeout);
+            let (i, qd, result) = match self.libos.wait_any_timeout(&qtokens, timeout) {
+                Ok((i, qd, result)) => (i, qd, result),
+                Err(e) if e.is_timeout() => continue,
+                Err(e) if e.is_shutdown() => break,
+                Err(e) => panic!("operation failed: {:?}", e),
+            };
+            qtokens.swap_remove(i);
+
+            // Parse result.
+            match result {
+                OperationResult::Accept(qd, peer) => {
+                    event!(Level::Debug, qd, peer, "connection accepted!");
+                    connections.push(qd);
+                    if let Some(peer) = peer {
+                        peers.insert(qd, peer);
+                    }
+                    // Accept another connection.
+                    let qt: QToken = match self.libos.accept(self.sockqd) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to accept connection on socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                    // Pop first packet.
+                    let qt: QToken = match self.libos.pop(qd) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                }
+                // The client closed its connection, or the connection failed.
+                OperationResult::Pop(_, buf) if buf.is_empty() => {
+                    event!(
+                        Level::Debug,
+                        qd,
+                        peers.get(&qd).copied(),
+                        "connection closed"
+                    );
+                    connections.retain(|c| *c != qd);
+                    peers.remove(&qd);
+                    self.close(qd);
+                }
+                OperationResult::Failed(e) if qd != self.sockqd => {
+                    event!(
+                        Level::Warn,
+                        qd,
+                        peers.get(&qd).copied(),
+                        "connection aborted: {:?}",
+                        e.cause
+                    );
+                    connections.retain(|c| *c != qd);
+                    peers.remove(&qd);
+                    self.close(qd);
+                }
+                // Pop completed.
+                OperationResult::Pop(_, buf) => {
+                    nbytes += buf.len();
+                    if self.dashboard.is_some() {
+                        sample.record(buf.len(), None, peers.get(&qd).copied());
+                    }
+                    let qt: QToken = match self.libos.push(qd, &buf) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to push data to socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                }
+                // Push completed.
+                OperationResult::Push => {
+                    // Pop another packet.
+                    let qt: QToken = match self.libos.pop(qd) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                }
+                OperationResult::Failed(e) => panic!("operation failed: {:?}", e),
+                _ => panic!("unexpected result"),
+            }
+        }
+
+        // Close connections, and dump final statistics.
+        for qd in connections.into_iter().chain([self.sockqd]) {
+            self.close(qd);
+        }
+        let elapsed: Duration = clock.now() - since;
+        info!("{:?} B / {:?} us", nbytes, elapsed.as_micros());
+
+        self.libos.status()
+    }
+
+    /// Runs the target echo server, sending received buffers back as they are.
+    ///
+    /// Each buffer is released once its push completes.
+    fn run_server_zero_copy(&mut self) -> Result<()> {
+        let clock: Clock = self.libos.clock();
+        let libos: &mut LibOS = sga::libos(&mut self.libos);
+        let start: Instant = clock.now();
+        let mut nbytes: usize = 0;
+        let mut since: Instant = start;
+        let mut connections: Vec<QDesc> = Vec::new();
+        let mut qtokens: Vec<QToken> = Vec::new();
+        let mut inflight: Vec<(QToken, demi_sgarray_t)> = Vec::new();
+        let mut zc_stats: ZeroCopyStatistics = ZeroCopyStatistics::default();
+        let mut timers: TimerWheel<Timer> =
+            TimerWheel::new(Self::TIMER_GRANULARITY, Self::TIMER_SLOTS, start);
+        timers.schedule(start + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+
+        // Accept first connection.
+        let qt: QToken = match libos.accept(self.sockqd) {
+            Ok(qt) => qt,
+            Err(e) => panic!("failed to accept connection on socket: {:?}", e.cause),
+        };
+        qtokens.push(qt);
+
+        loop {
+            // Apply commands of the control socket.
+            if let Some(command) = self.controller.as_ref().and_then(Controller::poll) {
+                let now: Instant = clock.now();
+                match command.get_request().clone() {
+                    Request::Stats => {
+                        command.reply(&format!(
+                            "bytes: {}\nelapsed_us: {}\nconnections: {}",
+                            nbytes,
+                            (now - since).as_micros(),
+                            connections.len()
+                        ));
+                    }
+                    Request::Connections => {
+                        let lines: Vec<String> = connections
+                            .iter()
+                            .map(|qd| format!("qd {}", u32::from(*qd)))
+                            .collect();
+                        command.reply(&lines.join("\n"));
+                    }
+                    Request::ResetCounters => {
+                        nbytes = 0;
+                        since = now;
+                        command.reply("ok");
+                    }
+                    Request::Shutdown => {
+                        command.reply("ok");
+                        break;
+                    }
+                    _ => command.unsupported(),
+                }
+            }
+
+            // Publish statistics when running as a worker, or dump them.
+            if let Some(stats) = &self.stats {
+                stats.set_nbytes(nbytes);
+            } else {
+                let now: Instant = clock.now();
+                for timer in timers.expire(now) {
+                    match timer {
+                        Timer::Log => {
+                            let elapsed: Duration = now - since;
+                            info!(
+                                "{:?} B / {:?} us ({})",
+                                nbytes,
+                                elapsed.as_micros(),
+                                zc_stats
+                            );
+                            timers.schedule(
+                                now + Duration::from_secs(Self::LOG_INTERVAL),
+                                Timer::Log,
+                            );
+                        }
+                        Timer::Push => unreachable!("only scenario clients push on timers"),
+                    }
+                }
+            }
+
+            let (i, qr): (usize, demi_qresult_t) = match libos.wait_any(&qtokens) {
+                Ok((i, qr)) => (i, qr),
+                Err(e) if e.errno == libc::ESHUTDOWN => break,
+                Err(e) => panic!("operation failed: {:?}", e),
+            };
+            let qt: QToken = qtokens.swap_remove(i);
+            let qd: QDesc = sga::qr_qd(&qr);
+
+            // Parse result.
+            match qr.qr_opcode {
+                demi_opcode_t::DEMI_OPC_ACCEPT => {
+                    event!(
+                        Level::Debug,
+                        sga::qr_accepted(&qr),
+                        None,
+                        "connection accepted!"
+                    );
+                    connections.push(sga::qr_accepted(&qr));
+                    // Accept another connection.
+                    let qt: QToken = match libos.accept(self.sockqd) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to accept connection on socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                    // Pop first packet.
+                    let qt: QToken = match libos.pop(sga::qr_accepted(&qr)) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                }
+                // Pop completed.
+                demi_opcode_t::DEMI_OPC_POP => {
+                    let buf: demi_sgarray_t = sga::qr_sga(&qr);
+                    // The client closed its connection.
+                    if sga::sga_len(&buf) == 0 {
+                        event!(Level::Debug, qd, None, "connection closed");
+                        sga::sga_free(libos, buf);
+                        connections.retain(|c| *c != qd);
+                        if let Err(e) = libos.close(qd) {
+                            event!(
+                                Level::Warn,
+                                qd,
+                                None,
+                                "failed to close socket: {:?}",
+                                e.cause
+                            );
+                        }
+                        continue;
+                    }
+                    nbytes += sga::sga_len(&buf);
+                    let qt: QToken = match libos.push(qd, &buf) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to push data to socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                    inflight.push((qt, buf));
+                    zc_stats.record(sga::sga_len(&buf));
+                }
+                // Push completed.
+                demi_opcode_t::DEMI_OPC_PUSH => {
+                    if let Some(j) = inflight.iter().position(|(t, _)| *t == qt) {
+                        let (_, buf): (QToken, demi_sgarray_t) = inflight.swap_remove(j);
+                        sga::sga_free(libos, buf);
+                    }
+                    // Pop another packet.
+                    let qt: QToken = match libos.pop(qd) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                }
+                demi_opcode_t::DEMI_OPC_FAILED => panic!("operation failed"),
+                _ => panic!("unexpected result"),
+            }
+        }
+
+        // Release buffers that are still in flight, close connections, and dump final statistics.
+        for (_, buf) in inflight {
+            sga::sga_free(libos, buf);
+        }
+        for qd in connections.into_iter().chain([self.sockqd]) {
+            self.close(qd);
+        }
+        let elapsed: Duration = clock.now() - since;
+        info!(
+            "{:?} B / {:?} us ({})",
+            nbytes,
+            elapsed.as_micros(),
+            zc_stats
+        );
+
+        self.libos.status()
+    }
+
+    /// Runs the target application.
+    pub fn run_client(&mut self) -> Result<()> {
+        let clock: Clock = self.libos.clock();
+        let start: Instant = clock.now();
+        let mut nbytes: usize = 0;
+        let mut timers: TimerWheel<Timer> =
+            TimerWheel::new(Self::TIMER_GRANULARITY, Self::TIMER_SLOTS, start);
+        timers.schedule(start + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+        let data: Vec<u8> = Self::mkbuf(self.bufsize, 0x65);
+
+        loop {
+            // Dump statistics.
+            let now: Instant = clock.now();
+            for timer in timers.expire(now) {
+                match timer {
+                    Timer::Log => {
+                        let elapsed: Duration = now - start;
+                        info!("{:?} B / {:?} us", nbytes, elapsed.as_micros());
+                        timers.schedule(now + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+                    }
+                    Timer::Push => unreachable!("only scenario clients push on timers"),
+                }
+            }
+
+            let qt: QToken = match self.libos.push(self.sockqd, &data) {
+                Ok(qt) => qt,
+                Err(e) => panic!("failed to push data to socket: {:?}", e.cause),
+            };
+            match self.libos.wait(qt) {
+                Ok((_, OperationResult::Push)) => (),
+                Err(e) if e.is_shutdown() => break,
+                Err(e) => panic!("operation failed: {:?}", e.cause),
+                _ => panic!("unexpected result"),
+            };
+            nbytes += self.bufsize;
+
+            // Drain packets.
+            let qt: QToken = match self.libos.pop(self.sockqd) {
+                Ok(qt) => qt,
+                Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+            };
+            match self.libos.wait(qt) {
+                Ok((_, OperationResult::Pop(_, buf))) => {
+                    nbytes += buf.len();
+                }
+                Err(e) if e.is_shutdown() => break,
+                Err(e) => panic!("operation failed: {:?}", e.cause),
+                _ => panic!("unexpected result"),
+            }
+        }
+
+        self.libos.status()
+    }
+
+    /// Runs the target client application, following a traffic schedule.
+    ///
+    /// On every tick, up to a burst of messages is pushed, each on a connection that is not
+    /// waiting for an echo. A connection pushes again only once its previous message has been
+    /// entirely echoed back, so ticks may be delayed while waiting for echoes.
+    pub fn run_client_scenario(&mut self) -> Result<()> {
+        let mut schedule: Schedule = match self.schedule.take() {
+            Some(schedule) => schedule,
+            None => return Ok(()),
+        };
+        let clock: Clock = self.libos.clock();
+        let start: Instant = clock.now();
+        let mut nbytes: usize = 0;
+        let mut qtokens: Vec<QToken> = Vec::new();
+        let mut timers: TimerWheel<Timer> =
+            TimerWheel::new(Self::TIMER_GRANULARITY, Self::TIMER_SLOTS, start);
+        timers.schedule(start + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+        let data: Vec<u8> = Self::mkbuf(schedule.get_max_bufsize(), 0x65);
+        let mut rng: Rng = Rng::new(Self::SEED);
+        let mut stats: PhaseStatistics = PhaseStatistics::new(&schedule, start);
+        let mut next: usize = 0;
+
+        // Open extra connections, keeping track of bytes that are yet to be echoed.
+        let mut connections: Vec<(QDesc, usize)> = vec![(self.sockqd, 0)];
+        while connections.len() < schedule.get_max_connections() {
+            connections.push((self.connect(), 0));
+        }
+
+        schedule.restart(start);
+        let mut current: usize = 0;
+        let mut push: TimerId = timers.schedule(start, Timer::Push);
+        loop {
+            // Get the current phase, and push right away when it changes.
+            let now: Instant = clock.now();
+            let phase: &Phase = match schedule.poll(now) {
+                Some((i, phase)) => {
+                    if i != current {
+                        current = i;
+                        timers.cancel(push);
+                        push = timers.schedule(now, Timer::Push);
+                    }
+                    stats.switch(i, now);
+                    phase
+                }
+                None => break,
+            };
+
+            for timer in timers.expire(now) {
+                match timer {
+                    // Dump statistics.
+                    Timer::Log => {
+                        let elapsed: Duration = now - start;
+                        let (label, phase_nbytes, phase_elapsed): (&str, usize, Duration) =
+                            stats.current(now);
+                        info!(
+                            "[{}] {:?} B / {:?} us ({:?} B / {:?} us in phase)",
+                            label,
+                            nbytes,
+                            elapsed.as_micros(),
+                            phase_nbytes,
+                            phase_elapsed.as_micros()
+                        );
+                        timers.schedule(now + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+                    }
+                    // Push a burst of messages on idle connections, and pace the next one.
+                    Timer::Push => {
+                        let interval: Duration = match phase.get_interval() {
+                            Some(interval) => interval,
+                            // Nothing to push in this phase, check again later.
+                            None => {
+                                push = timers.schedule(now + Self::TIMER_GRANULARITY, Timer::Push);
+                                continue;
+                            }
+                        };
+                        push = timers.schedule(now + interval, Timer::Push);
+                        let n: usize = phase.get_connections();
+                        for _ in 0..phase.get_burst() {
+                            let idle: Option<usize> = (0..n)
+                                .map(|k| (next + k) % n)
+                                .find(|&c| connections[c].1 == 0);
+                            let c: usize = match idle {
+                                Some(c) => c,
+                                None => break,
+                            };
+                            let bufsize: usize = phase.get_sizes().sample(&mut rng);
+                            let qt: QToken = match self
+                                .libos
+                                .push(connections[c].0, &data[..bufsize])
+                            {
+                                Ok(qt) => qt,
+                                Err(e) => panic!("failed to push data to socket: {:?}", e.cause),
+                            };
+                            qtokens.push(qt);
+                            connections[c].1 = bufsize;
+                            next = c + 1;
+                        }
+                    }
+                }
+            }
+
+            if qtokens.is_empty() {
+                continue;
+            }
+
+            let (i, qd, result) = match self.libos.wait_any(&qtokens) {
+                Ok((i, qd, result)) => (i, qd, result),
+                Err(e) if e.is_shutdown() => break,
+                Err(e) => panic!("operation failed: {:?}", e),
+            };
+            qtokens.swap_remove(i);
+
+            let c: usize = match connections.iter().position(|(sockqd, _)| *sockqd == qd) {
+                Some(c) => c,
+                None => panic!("unknown connection"),
+            };
+
+            // Parse result.
+            match result {
+                // Push completed.
+                OperationResult::Push => {
+                    nbytes += connections[c].1;
+                    stats.record(connections[c].1);
+                }
+                // Pop completed.
+                OperationResult::Pop(_, buf) => {
+                    nbytes += buf.len();
+                    stats.record(buf.len());
+                    connections[c].1 = connections[c].1.saturating_sub(buf.len());
+                    if connections[c].1 == 0 {
+                        continue;
+                    }
+                }
+                OperationResult::Failed(e) => panic!("operation failed: {:?}", e),
+                _ => panic!("unexpected result"),
+            }
+
+            // Drain the echo.
+            let qt: QToken = match self.libos.pop(qd) {
+                Ok(qt) => qt,
+                Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+            };
+            qtokens.push(qt);
+        }
+
+        stats.dump(clock.now());
+        self.libos.status()
+    }
+
+    /// Opens an extra connection to the remote endpoint of the client.
+    fn connect(&mut self) -> QDesc {
+        let remote: SocketAddr = match self.remote {
+            Some(remote) => remote,
+            None => panic!("missing remote address"),
+        };
+
+        // Create TCP socket.
+        let sockqd: QDesc = match self.libos.socket(domain(&remote), libc::SOCK_STREAM, 0) {
+            Ok(qd) => qd,
+            Err(e) => panic!("failed to create socket: {:?}", e.cause),
+        };
+
+        // Setup connection.
+        let qt: QToken = match self.libos.connect(sockqd, remote) {
+            Ok(qt) => qt,
+            Err(e) => panic!("failed to connect socket: {:?}", e.cause),
+        };
+        match self.libos.wait(qt) {
+            Ok((_, OperationResult::Connect)) => {
+                event!(Level::Debug, sockqd, Some(remote), "connected!")
+            }
+            Err(e) => panic!("operation failed: {:?}", e),
+            _ => panic!("unexpected result"),
+        }
+
+        sockqd
+    }
+
+    /// Closes a socket, warning if it fails.
+    fn close(&mut self, qd: QDesc) {
+        if let Err(e) = self.libos.close(qd) {
+            event!(
+                Level::Warn,
+                qd,
+                None,
+                "failed to close socket: {:?}",
+                e.cause
+            );
+        }
+    }
+
+    /// Makes a buffer.
+    fn mkbuf(bufsize: usize, fill_char: u8) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::<u8>::with_capacity(bufsize);
+
+        for _ in 0..bufsize {
+            data.push(fill_char);
+        }
+
+        data
+    }
+
+    /// Asserts if the target application is running on server mode or not.
+    fn is_server(&self) -> bool {
+        self.is_server
+    }
+
+    /// Asserts if the target application follows a traffic schedule or not.
+    fn has_schedule(&self) -> bool {
+        self.schedule.is_some()
+    }
+}
+
+//==============================================================================
+
+/// Runs the application over a backend of type `B`.
+fn start<B: Backend + 'static>(args: ProgramArguments) -> Result<()> {
+    if args.is_tui() && !B::has_timed_waits() {
+        bail!("the dashboard needs timed waits, which the backend does not offer")
+    }
+    if args.is_tui() && (args.get_peer_type() != "server" || args.get_workers() > 1) {
+        bail!("the dashboard is only supported by servers without workers")
+    }
+
+    // Run one echo server per worker.
+    if args.get_workers() > 1 {
+        if args.get_peer_type() != "server" {
+            bail!("workers are only supported by servers, clients open connections in scenarios")
+        }
+        let controllers: Controllers = args
+            .get_control_socket()
+            .start_workers(args.get_workers())?;
+        let workers: Workers = Workers::spawn(args.get_workers(), move |i, stats| {
+            let mut args: ProgramArguments = args.clone();
+            args.local = args
+                .local
+                .map(|addr| workers::worker_addr::<B>(addr, i))
+                .transpose()?;
+            let mut app: Application<B> = Application::new(B::create()?, &args)?;
+            app.set_worker_statistics(stats);
+            app.set_controller(controllers.take(i));
+            app.run_server()
+        })?;
+        return workers.report(Duration::from_secs(Application::<B>::LOG_INTERVAL));
+    }
+
+    if args.get_peer_type() != "server" && args.get_control_socket().is_enabled() {
+        bail!("control sockets are only supported by servers")
+    }
+    let libos: B = B::create()?;
+    let mut app: Application<B> = Application::new(libos, &args)?;
+
+    if app.is_server() {
+        app.set_controller(args.get_control_socket().start()?);
+        if args.is_tui() {
+            app.set_dashboard(Some(Dashboard::start("tcp-echo")?));
+        }
+        app.run_server()
+    } else if app.has_schedule() {
+        app.run_client_scenario()
+    } else {
+        app.run_client()
+    }
+}
+
+/// Describes the application.
+pub const ABOUT: &str = "Echoes TCP packets.";
+
+/// Runs the application with the given command line.
+pub fn main(argv: Vec<String>) -> Result<()> {
+    let args: ProgramArguments = ProgramArguments::new(
+        "tcp-echo",
+        "Pedro Henrique Penna <ppenna@microsoft.com>",
+        ABOUT,
+        argv,
+    )?;
+
+    args.get_placement().apply()?;
+    args.get_tracing().start()?;
+
+    match args.get_io_backend() {
+        BackendKind::LibOS => start::<Traced<LibOS>>(args),
+        BackendKind::Posix => start::<Traced<Posix>>(args),
+        BackendKind::Loopback => start::<Traced<Loopback>>(args),
+        BackendKind::Simulator => start::<Traced<Simulator>>(args),
+    }
+}
@@ -0,0 +1,946 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Result};
+use ::apps::backend::{domain, Backend, BackendKind, OperationResult};
+use ::apps::balancer::{load_backends, Balancer, Policy};
+use ::apps::clock::Clock;
+use ::apps::config::Config;
+use ::apps::control::{ControlSocket, Controller, Request};
+use ::apps::dashboard::{Dashboard, Sample};
+use ::apps::endpoint::resolve;
+use ::apps::event;
+use ::apps::logging::Logging;
+use ::apps::loopback::Loopback;
+use ::apps::placement::Placement;
+use ::apps::posix::Posix;
+use ::apps::sim::Simulator;
+use ::apps::splice::{Closed, Splicer};
+use ::apps::timer::{TimerId, TimerWheel};
+use ::apps::tracer::{Traced, Tracing};
+use ::clap::{Arg, ArgMatches, Command};
+use ::demikernel::{LibOS, QDesc, QToken};
+use ::log::{error, info, Level};
+use ::std::collections::HashMap;
+use ::std::net::SocketAddr;
+use ::std::str::FromStr;
+use ::std::sync::atomic::{AtomicBool, Ordering};
+use ::std::time::{Duration, Instant};
+
+//==============================================================================
+// Program Arguments
+//==============================================================================
+
+/// Program Arguments
+#[derive(Debug)]
+pub struct ProgramArguments {
+    /// Local socket address.
+    local: SocketAddr,
+    /// Backend socket addresses given on the command line.
+    backends: Vec<SocketAddr>,
+    /// Path to the backends file.
+    backends_file: Option<String>,
+    /// Backend selection policy.
+    policy: Policy,
+    /// Interval between health checks (in seconds).
+    health_interval: u64,
+    /// Maximum number of bytes in flight per direction of a connection.
+    window: usize,
+    /// Backend to run on.
+    io_backend: BackendKind,
+    /// Placement of the process.
+    placement: Placement,
+    /// Tracing of operations.
+    tracing: Tracing,
+    /// Show a live dashboard?
+    tui: bool,
+    /// Control socket.
+    control_socket: ControlSocket,
+}
+
+/// Associate functions for Program Arguments
+impl ProgramArguments {
+    /// Default local address.
+    const DEFAULT_LOCAL: &'static str = "127.0.0.1:12345";
+
+    /// Default interval between health checks (in seconds). Health checks are opt-in, as they
+    /// need timed waits, which the libOS does not offer.
+    const DEFAULT_HEALTH_INTERVAL: u64 = 0;
+
+    /// Default maximum number of bytes in flight per direction of a connection.
+    const DEFAULT_WINDOW: usize = 64 * 1024;
+
+    /// Parses the program arguments from the command line `argv`.
+    pub fn new(
+        app_name: &str,
+        app_author: &str,
+        app_about: &str,
+        argv: Vec<String>,
+    ) -> Result<Self> {
+        let command: Command = Command::new(app_name)
+            .author(app_author)
+            .about(app_about)
+            .arg(
+                Arg::new("local")
+                    .long("local")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("HOST:PORT")
+                    .help("Sets local address"),
+            )
+            .arg(
+                Arg::new("backend")
+                    .long("backend")
+                    .takes_value(true)
+                    .required_unless_present("backends")
+                    .multiple_occurrences(true)
+                    .value_name("HOST:PORT")
+                    .help("Adds the address of a backend server"),
+            )
+            .arg(
+                Arg::new("backends")
+                    .long("backends")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("FILE")
+                    .help("Sets backends file, reloaded on SIGHUP"),
+            )
+            .arg(
+                Arg::new("policy")
+                    .long("policy")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("round-robin|least-connections|source-hash")
+                    .help("Sets backend selection policy"),
+            )
+            .arg(
+                Arg::new("health_interval")
+                    .long("health_interval")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("SECONDS")
+                    .help("Sets interval between health checks (default: off)"),
+            )
+            .arg(
+                Arg::new("window")
+                    .long("window")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("BYTES")
+                    .help("Sets maximum number of bytes in flight per direction of a connection"),
+            )
+            .arg(
+                Arg::new("io_backend")
+                    .long("io_backend")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("libos|posix|loopback|sim")
+                    .help("Sets backend to run on"),
+            )
+            .args(Placement::args())
+            .args(Logging::args())
+            .args(Tracing::args())
+            .args(Dashboard::args())
+            .args(ControlSocket::args())
+            .args(Config::args());
+        let matches: ArgMatches = Config::get_matches_from(command, argv)?;
+
+        // Default arguments.
+        let mut args: ProgramArguments = ProgramArguments {
+            local: SocketAddr::from_str(Self::DEFAULT_LOCAL)?,
+            backends: Vec::new(),
+            backends_file: None,
+            policy: Policy::RoundRobin,
+            health_interval: Self::DEFAULT_HEALTH_INTERVAL,
+            window: Self::DEFAULT_WINDOW,
+            io_backend: BackendKind::LibOS,
+            placement: Placement::from_matches(&matches)?,
+            tracing: Tracing::from_matches(&matches)?,
+            tui: matches.is_present("tui"),
+            control_socket: ControlSocket::from_matches(&matches)?,
+        };
+
+        // Local address.
+        if let Some(addr) = matches.value_of("local") {
+            args.set_local_addr(addr)?;
+        }
+
+        // Backend addresses.
+        if let Some(addrs) = matches.values_of("backend") {
+            for addr in addrs {
+                args.add_backend_addr(addr)?;
+            }
+        }
+        if let Some(path) = matches.value_of("backends") {
+            args.backends_file = Some(path.to_string());
+        }
+
+        // Backend selection policy.
+        if let Some(policy) = matches.value_of("policy") {
+            args.set_policy(policy)?;
+        }
+
+        // Health check interval.
+        if let Some(health_interval) = matches.value_of("health_interval") {
+            args.health_interval = health_interval.parse()?;
+        }
+
+        // Window.
+        if let Some(window) = matches.value_of("window") {
+            args.set_window(window)?;
+        }
+
+        // Backend.
+        if let Some(backend) = matches.value_of("io_backend") {
+            args.set_io_backend(backend)?;
+        }
+
+        Ok(args)
+    }
+
+    /// Returns the placement parameter stored in the target program arguments.
+    pub fn get_placement(&self) -> &Placement {
+        &self.placement
+    }
+
+    /// Returns the tracing parameter stored in the target program arguments.
+    pub fn get_tracing(&self) -> &Tracing {
+        &self.tracing
+    }
+
+    /// Returns the dashboard parameter stored in the target program arguments.
+    pub fn is_tui(&self) -> bool {
+        self.tui
+    }
+
+    /// Returns the control socket parameter stored in the target program arguments.
+    pub fn get_control_socket(&self) -> &ControlSocket {
+        &self.control_socket
+    }
+
+    /// Returns the backend parameter stored in the target program arguments.
+    pub fn get_io_backend(&self) -> BackendKind {
+        self.io_backend
+    }
+
+    /// Returns the local endpoint address parameter stored in the target program arguments.
+    pub fn get_local(&self) -> SocketAddr {
+        self.local
+    }
+
+    /// Returns the backend addresses parameter stored in the target program arguments.
+    pub fn get_backends(&self) -> &[SocketAddr] {
+        &self.backends
+    }
+
+    /// Returns the backends file parameter stored in the target program arguments.
+    pub fn get_backends_file(&self) -> Option<&str> {
+        self.backends_file.as_deref()
+    }
+
+    /// Returns the backend selection policy parameter stored in the target program arguments.
+    pub fn get_policy(&self) -> Policy {
+        self.policy
+    }
+
+    /// Returns the health check interval parameter stored in the target program arguments.
+    pub fn get_health_interval(&self) -> u64 {
+        self.health_interval
+    }
+
+    /// Returns the window parameter stored in the target program arguments.
+    pub fn get_window(&self) -> usize {
+        self.window
+    }
+
+    /// Sets the local address and port number parameters in the target program arguments.
+    fn set_local_addr(&mut self, addr: &str) -> Result<()> {
+        self.local = resolve(addr)?;
+        Ok(())
+    }
+
+    /// Adds a backend address and port number to the target program arguments.
+    fn add_backend_addr(&mut self, addr: &str) -> Result<()> {
+        let addr: SocketAddr = resolve(addr)?;
+        if self.backends.contains(&addr) {
+            bail!("duplicate backend address")
+        }
+        self.backends.push(addr);
+        Ok(())
+    }
+
+    /// Sets the backend selection policy parameter in the target program arguments.
+    fn set_policy(&mut self, policy_str: &str) -> Result<()> {
+        match policy_str.parse()? {
+            Policy::Random => bail!("invalid backend selection policy"),
+            policy => self.policy = policy,
+        }
+        Ok(())
+    }
+
+    /// Sets the window parameter in the target program arguments.
+    fn set_window(&mut self, window_str: &str) -> Result<()> {
+        let window: usize = window_str.parse()?;
+        if window > 0 {
+            self.window = window;
+            Ok(())
+        } else {
+            bail!("invalid window")
+        }
+    }
+
+    /// Sets the backend parameter in the target program arguments.
+    fn set_io_backend(&mut self, backend_str: &str) -> Result<()> {
+        self.io_backend = backend_str.parse()?;
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Application
+//==============================================================================
+
+/// Health Check Probe
+#[derive(Debug)]
+struct Probe {
+    /// Index of the probed backend.
+    backend: usize,
+    /// Socket descriptor.
+    qd: QDesc,
+    /// Pending connection.
+    qt: QToken,
+    /// Timer that fails the probe if the backend does not accept the connection in time.
+    timeout: TimerId,
+}
+
+/// Timers of the Run Loop
+#[derive(Debug)]
+enum Timer {
+    /// Emits statistics.
+    Log,
+    /// Starts a round of health checks.
+    Health,
+    /// Times out the health check that waits on a connection.
+    Probe(QToken),
+}
+
+/// Application
+struct Application<B: Backend> {
+    /// Underlying libOS.
+    libos: B,
+    // Local socket descriptor.
+    sockqd: QDesc,
+    /// Backends.
+    balancer: Balancer,
+    /// Relayed connections.
+    splicer: Splicer,
+    /// Backends given on the command line.
+    static_backends: Vec<SocketAddr>,
+    /// Path to the backends file.
+    backends_file: Option<String>,
+    /// Interval between health checks.
+    health_interval: Option<Duration>,
+    /// Clock read in the run loop.
+    clock: Clock,
+    /// Pending timers.
+    timers: TimerWheel<Timer>,
+    /// Pending health checks.
+    probes: Vec<Probe>,
+    /// Live dashboard, if any.
+    dashboard: Option<Dashboard>,
+    /// Controller of the control socket, if any.
+    controller: Option<Controller>,
+}
+
+/// Associated Functions for the Application
+impl<B: Backend> Application<B> {
+    /// Logging interval (in seconds).
+    const LOG_INTERVAL: u64 = 5;
+
+    /// Granularity of timers.
+    const TIMER_GRANULARITY: Duration = Duration::from_millis(1);
+
+    /// Number of slots in the timer wheel.
+    const TIMER_SLOTS: usize = 8192;
+
+    /// Instantiates the application.
+    pub fn new(mut libos: B, args: &ProgramArguments) -> Result<Self> {
+        if args.get_health_interval() > 0 && !B::has_timed_waits() {
+            bail!("health checks need timed waits, which the backend does not offer")
+        }
+        if args.get_policy() == Policy::SourceHash && !B::reports_peers() {
+            bail!("source-hash needs the address of clients, which the backend does not report")
+        }
+
+        // Extract arguments.
+        let local: SocketAddr = args.get_local();
+        let mut backends: Vec<(SocketAddr, bool)> = args
+            .get_backends()
+            .iter()
+            .map(|addr| (*addr, false))
+            .collect();
+        if let Some(path) = args.get_backends_file() {
+            backends.extend(load_backends(path)?);
+        }
+        let addrs: Vec<SocketAddr> = backends.iter().map(|(addr, _)| *addr).collect();
+        let mut balancer: Balancer = Balancer::new(&addrs, args.get_policy(), None)?;
+        balancer.update(&backends);
+        let health_interval: Option<Duration> = match args.get_health_interval() {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+
+        // Create TCP socket.
+        let sockqd: QDesc = match libos.socket(domain(&local), libc::SOCK_STREAM, 0) {
+            Ok(qd) => qd,
+            Err(e) => panic!("failed to create socket: {:?}", e.cause),
+        };
+
+        // Bind to local address.
+        match libos.bind(sockqd, local) {
+            Ok(()) => (),
+            Err(e) => panic!("failed to bind socket: {:?}", e.cause),
+        };
+
+        // Mark socket as a passive one.
+        match libos.listen(sockqd, 16) {
+            Ok(()) => (),
+            Err(e) => panic!("failed to listen socket: {:?}", e.cause),
+        }
+
+        // Reload backends on SIGHUP.
+        if args.get_backends_file().is_some() {
+            unsafe { libc::signal(libc::SIGHUP, on_sighup as *const () as libc::sighandler_t) };
+        }
+
+        info!("Local Address:  {:?}", local);
+        for backend in balancer.get_backends() {
+            info!(
+                "Backend Address: {:?}{}",
+                backend.get_addr(),
+                if backend.is_draining() {
+                    " (draining)"
+                } else {
+                    ""
+                }
+            );
+        }
+        info!("Backend Policy: {}", args.get_policy());
+
+        let clock: Clock = libos.clock();
+        let now: Instant = clock.now();
+        Ok(Self {
+            libos,
+            sockqd,
+            balancer,
+            splicer: Splicer::new(args.get_window()),
+            static_backends: args.get_backends().to_vec(),
+            backends_file: args.get_backends_file().map(str::to_string),
+            health_interval,
+            clock,
+            timers: TimerWheel::new(Self::TIMER_GRANULARITY, Self::TIMER_SLOTS, now),
+            probes: Vec::new(),
+            dashboard: None,
+            controller: None,
+        })
+    }
+
+    /// Makes the target load balancer apply commands of a control socket.
+    pub fn set_controller(&mut self, controller: Option<Controller>) {
+        self.controller = controller;
+    }
+
+    /// Makes the target load balancer show a live dashboard instead of dumping statistics.
+    pub fn set_dashboard(&mut self, dashboard: Option<Dashboard>) {
+        self.dashboard = dashboard;
+    }
+
+    /// Runs the target load balancer.
+    ///
+    /// Every accepted connection is paired with a new connection to a backend, and bytes are
+    /// copied in both directions until both sides close.
+    pub fn run(&mut self) -> Result<()> {
+        let start: Instant = self.clock.now();
+        let mut since: Instant = start;
+        let mut nbytes: usize = 0;
+        let mut nclosed: usize = 0;
+        let mut nrejected: usize = 0;
+        let mut last: Instant = start;
+        let mut sample: Sample = Sample::default();
+        let mut peers: HashMap<QDesc, SocketAddr> = HashMap::new();
+        let mut qtokens: Vec<QToken> = Vec::new();
+        self.timers
+            .schedule(start + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+        if self.health_interval.is_some() {
+            self.timers.schedule(start, Timer::Health);
+        }
+
+        // Accept first connection.
+        let qt: QToken = match self.libos.accept(self.sockqd) {
+            Ok(qt) => qt,
+            Err(e) => panic!("failed to accept connection on socket: {:?}", e.cause),
+        };
+        qtokens.push(qt);
+
+        loop {
+            let now: Instant = self.clock.now();
+
+            // Apply commands of the control socket. Resetting counters leaves those of open
+            // connections untouched.
+            if let Some(command) = self.controller.as_ref().and_then(Controller::poll) {
+                match command.get_request().clone() {
+                    Request::Stats => {
+                        let active: usize = self
+                            .splicer
+                            .statistics()
+                            .iter()
+                            .map(|s| s.nbytes_up + s.nbytes_down)
+                            .sum();
+                        command.reply(&format!(
+                            "bytes: {}\nelapsed_us: {}\nconnections: {}\nclosed: {}\nrejected: {}",
+                            nbytes + active,
+                            (now - since).as_micros(),
+                            self.splicer.len(),
+                            nclosed,
+                            nrejected
+                        ));
+                    }
+                    Request::Connections => {
+                        let lines: Vec<String> = self
+                            .splicer
+                            .statistics()
+                            .iter()
+                            .map(|s| {
+                                format!(
+                                    "#{} -> {}: {:?} B up, {:?} B down{}",
+                                    s.id,
+                                    self.balancer.get_addr(s.tag),
+                                    s.nbytes_up,
+                                    s.nbytes_down,
+                                    if s.connecting { " (connecting)" } else { "" }
+                                )
+                            })
+                            .collect();
+                        command.reply(&lines.join("\n"));
+                    }
+                    Request::ResetCounters => {
+                        nbytes = 0;
+                        nclosed = 0;
+                        nrejected = 0;
+                        since = now;
+                        command.reply("ok");
+                    }
+                    Request::Shutdown => {
+                        command.reply("ok");
+                        break;
+                    }
+                    _ => command.unsupported(),
+                }
+            }
+
+            for timer in self.timers.expire(now) {
+                match timer {
+                    // Refresh the dashboard.
+                    Timer::Log if self.dashboard.is_some() => {
+                        if let Some(dashboard) = &self.dashboard {
+                            sample.set_nconnections(self.splicer.len());
+                            dashboard.update(sample.close(now - last));
+                            last = now;
+                        }
+                        self.timers
+                            .schedule(now + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+                    }
+                    // Dump statistics.
+                    Timer::Log => {
+                        let elapsed: Duration = now - since;
+                        let active: usize = self
+                            .splicer
+                            .statistics()
+                            .iter()
+                            .map(|s| s.nbytes_up + s.nbytes_down)
+                            .sum();
+                        info!(
+                            "{:?} B / {:?} us ({} connections, {} closed, {} rejected)",
+                            nbytes + active,
+                            elapsed.as_micros(),
+                            self.splicer.len(),
+                            nclosed,
+                            nrejected
+                        );
+                        for backend in self.balancer.get_backends() {
+                            info!(
+                                "  backend {:?}: {} connections{}{}",
+                                backend.get_addr(),
+                                backend.get_nsessions(),
+                                if backend.is_healthy() { "" } else { " (down)" },
+                                if backend.is_draining() {
+                                    " (draining)"
+                                } else {
+                                    ""
+                                }
+                            );
+                        }
+                        self.timers
+                            .schedule(now + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+                    }
+                    // Start a new round of health checks.
+                    Timer::Health => self.start_probes(&mut qtokens, now),
+                    // Backends that do not accept connections in time are deemed unhealthy.
+                    Timer::Probe(qt) => {
+                        if let Some(i) = qtokens.iter().position(|t| *t == qt) {
+                            qtokens.swap_remove(i);
+                        }
+                        self.finish_probe(qt, false);
+                    }
+                }
+            }
+
+            // Reload backends.
+            if RELOAD.swap(false, Ordering::Relaxed) {
+                self.reload_backends();
+            }
+
+            // Wake up when the next timer is due, if the backend can.
+            let timeout: Option<Duration> = match B::has_timed_waits() {
+                true => self
+                    .timers
+                    .next_deadline()
+                    .map(|deadline| deadline.saturating_duration_since(self.clock.now())),
+                false => None,
+            };
+            let timeout: Option<Duration> =
+                Controller::timeout::<B>(self.controller.as_ref(), timeout);
+
+            let (i, qd, result) = match self.libos.wait_any_timeout(&qtokens, timeout) {
+                Ok((i, qd, result)) => (i, qd, result),
+                Err(e) if e.is_timeout() => continue,
+                Err(e) if e.is_shutdown() => break,
+                Err(e) => panic!("operation failed: {:?}", e),
+            };
+            let qt: QToken = qtokens.swap_remove(i);
+
+            // Parse result.
+            match result {
+                // Accept completed, so connect to a backend.
+                OperationResult::Accept(client, peer) if qd == self.sockqd => {
+                    // Accept another connection.
+                    let qt: QToken = match self.libos.accept(self.sockqd) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to accept connection on socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+
+                    match (self.open(client, peer, &mut qtokens), peer) {
+                        (true, Some(peer)) => {
+                            peers.insert(client, peer);
+                        }
+                        (true, None) => (),
+                        (false, _) => nrejected += 1,
+                    }
+                }
+                OperationResult::Failed(e) if qd == self.sockqd => {
+                    panic!("operation failed: {:?}", e)
+                }
+                // Health check completed.
+                result if self.probes.iter().any(|p| p.qt == qt) => {
+                    let healthy: bool = matches!(result, OperationResult::Connect);
+                    self.finish_probe(qt, healthy);
+                }
+                // Operation on a relayed connection.
+                result => {
+                    if let (Some(_), OperationResult::Pop(_, buf)) = (&self.dashboard, &result) {
+                        sample.record(buf.len(), None, peers.get(&qd).copied());
+                    }
+                    let closed: Option<Closed> =
+                        self.splicer
+                            .handle(&mut self.libos, qt, qd, result, &mut qtokens);
+                    let closed: Closed = match closed {
+                        Some(closed) => closed,
+                        None => continue,
+                    };
+                    let remote: SocketAddr = self.balancer.get_addr(closed.tag);
+                    self.balancer.release(closed.tag);
+                    nbytes += closed.nbytes_up + closed.nbytes_down;
+                    nclosed += 1;
+                    peers.remove(&closed.client);
+                    match closed.error {
+                        Some(e) => event!(
+                            Level::Warn,
+                            closed.client,
+                            Some(remote),
+                            "connection #{} aborted: {}",
+                            closed.id,
+                            e
+                        ),
+                        None => event!(
+                            Level::Debug,
+                            closed.client,
+                            Some(remote),
+                            "connection #{} closed: {:?} B up, {:?} B down in {:?} us",
+                            closed.id,
+                            closed.nbytes_up,
+                            closed.nbytes_down,
+                            closed.duration.as_micros()
+                        ),
+                    }
+                }
+            }
+        }
+
+        // Close relayed connections, pending health checks and the socket, and dump final
+        // statistics.
+        for closed in self.splicer.close_all(&mut self.libos, &mut qtokens) {
+            self.balancer.release(closed.tag);
+            nbytes += closed.nbytes_up + closed.nbytes_down;
+            nclosed += 1;
+        }
+        let probes: Vec<QDesc> = self.probes.drain(..).map(|p| p.qd).collect();
+        for qd in probes.into_iter().chain([self.sockqd]) {
+            if let Err(e) = self.libos.close(qd) {
+                event!(
+                    Level::Warn,
+                    qd,
+                    None,
+                    "failed to close socket: {:?}",
+                    e.cause
+                );
+            }
+        }
+        let elapsed: Duration = self.clock.now() - since;
+        info!(
+            "{:?} B / {:?} us ({} closed, {} rejected)",
+            nbytes,
+            elapsed.as_micros(),
+            nclosed,
+            nrejected
+        );
+
+        self.libos.status()
+    }
+
+    /// Opens a connection to a backend on behalf of an accepted client, whose address is `peer`
+    /// if known. Returns `false` if no backend could take it.
+    fn open(&mut self, client: QDesc, peer: Option<SocketAddr>, qtokens: &mut Vec<QToken>) -> bool {
+        let backend: usize = match self.balancer.select(peer) {
+            Some(backend) => backend,
+            None => {
+                event!(Level::Warn, client, None, "no backend available");
+                if let Err(e) = self.libos.close(client) {
+                    event!(
+                        Level::Warn,
+                        client,
+                        None,
+                        "failed to close socket: {:?}",
+                        e.cause
+                    );
+                }
+                return false;
+            }
+        };
+        let remote: SocketAddr = self.balancer.get_addr(backend);
+
+        // Create TCP socket.
+        let server: QDesc = match self.libos.socket(domain(&remote), libc::SOCK_STREAM, 0) {
+            Ok(qd) => qd,
+            Err(e) => panic!("failed to create socket: {:?}", e.cause),
+        };
+
+        // Setup connection.
+        let qt: QToken = match self.libos.connect(server, remote) {
+            Ok(qt) => qt,
+            Err(e) => {
+                event!(
+                    Level::Warn,
+                    server,
+                    Some(remote),
+                    "failed to connect socket: {:?}",
+                    e.cause
+                );
+                self.balancer.release(backend);
+                for qd in [client, server] {
+                    if let Err(e) = self.libos.close(qd) {
+                        event!(
+                            Level::Warn,
+                            qd,
+                            None,
+                            "failed to close socket: {:?}",
+                            e.cause
+                        );
+                    }
+                }
+                return false;
+            }
+        };
+        qtokens.push(qt);
+
+        let id: u64 = self.splicer.insert(client, server, qt, backend);
+        event!(
+            Level::Debug,
+            client,
+            Some(remote),
+            "connection #{} accepted for {:?}",
+            id,
+            remote
+        );
+        true
+    }
+
+    /// Starts a round of health checks and schedules the next one. Probes that are not
+    /// answered within an interval time out.
+    fn start_probes(&mut self, qtokens: &mut Vec<QToken>, now: Instant) {
+        let interval: Duration = match self.health_interval {
+            Some(interval) => interval,
+            None => return,
+        };
+        self.timers.schedule(now + interval, Timer::Health);
+
+        for backend in 0..self.balancer.get_backends().len() {
+            // Draining backends never get new connections, so there is no point in probing them.
+            if self.balancer.get_backends()[backend].is_draining()
+                || self.probes.iter().any(|p| p.backend == backend)
+            {
+                continue;
+            }
+            let remote: SocketAddr = self.balancer.get_addr(backend);
+            let qd: QDesc = match self.libos.socket(domain(&remote), libc::SOCK_STREAM, 0) {
+                Ok(qd) => qd,
+                Err(e) => panic!("failed to create socket: {:?}", e.cause),
+            };
+            match self.libos.connect(qd, remote) {
+                Ok(qt) => {
+                    qtokens.push(qt);
+                    let timeout: TimerId = self.timers.schedule(now + interval, Timer::Probe(qt));
+                    self.probes.push(Probe {
+                        backend,
+                        qd,
+                        qt,
+                        timeout,
+                    });
+                }
+                Err(_) => {
+                    self.balancer.set_healthy(backend, false, now);
+                    if let Err(e) = self.libos.close(qd) {
+                        event!(
+                            Level::Warn,
+                            qd,
+                            Some(remote),
+                            "failed to close socket: {:?}",
+                            e.cause
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Records the outcome of a health check and closes its connection.
+    fn finish_probe(&mut self, qt: QToken, healthy: bool) {
+        let i: usize = match self.probes.iter().position(|p| p.qt == qt) {
+            Some(i) => i,
+            None => return,
+        };
+        let probe: Probe = self.probes.swap_remove(i);
+        self.timers.cancel(probe.timeout);
+        self.balancer
+            .set_healthy(probe.backend, healthy, self.clock.now());
+        if let Err(e) = self.libos.close(probe.qd) {
+            event!(
+                Level::Warn,
+                probe.qd,
+                None,
+                "failed to close socket: {:?}",
+                e.cause
+            );
+        }
+    }
+
+    /// Reloads the backends file. Backends that are left out are drained, so existing
+    /// connections are kept.
+    fn reload_backends(&mut self) {
+        let path: &str = match &self.backends_file {
+            Some(path) => path,
+            None => return,
+        };
+        let mut backends: Vec<(SocketAddr, bool)> = self
+            .static_backends
+            .iter()
+            .map(|addr| (*addr, false))
+            .collect();
+        match load_backends(path) {
+            Ok(loaded) => backends.extend(loaded),
+            Err(e) => {
+                error!("failed to reload backends from {}: {:?}", path, e);
+                return;
+            }
+        }
+        self.balancer.update(&backends);
+        info!("backends reloaded from {}", path);
+        for backend in self.balancer.get_backends() {
+            info!(
+                "  {:?}{}",
+                backend.get_addr(),
+                if backend.is_draining() {
+                    " (draining)"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+}
+
+//==============================================================================
+// Signal Handling
+//==============================================================================
+
+/// Set when backends should be reloaded.
+static RELOAD: AtomicBool = AtomicBool::new(false);
+
+/// Requests backends to be reloaded. They are reloaded the next time the load balancer wakes up.
+extern "C" fn on_sighup(_: libc::c_int) {
+    RELOAD.store(true, Ordering::Relaxed);
+}
+
+//==============================================================================
+
+/// Runs the application over a backend of type `B`.
+fn start<B: Backend>(args: ProgramArguments) -> Result<()> {
+    if args.is_tui() && !B::has_timed_waits() {
+        bail!("the dashboard needs timed waits, which the backend does not offer")
+    }
+    let libos: B = B::create()?;
+
+    let mut app: Application<B> = Application::new(libos, &args)?;
+    app.set_controller(args.get_control_socket().start()?);
+    if args.is_tui() {
+        app.set_dashboard(Some(Dashboard::start("tcp-lb")?));
+    }
+    app.run()
+}
+
+/// Describes the application.
+pub const ABOUT: &str = "Load balances TCP connections.";
+
+/// Runs the application with the given command line.
+pub fn main(argv: Vec<String>) -> Result<()> {
+    let args: ProgramArguments = ProgramArguments::new(
+        "tcp-lb",
+        "Pedro Henrique Penna <ppenna@microsoft.com>",
+        ABOUT,
+        argv,
+    )?;
+
+    args.get_placement().apply()?;
+    args.get_tracing().start()?;
+
+    match args.get_io_backend() {
+        BackendKind::LibOS => start::<Traced<LibOS>>(args),
+        BackendKind::Posix => start::<Traced<Posix>>(args),
+        BackendKind::Loopback => start::<Traced<Loopback>>(args),
+        BackendKind::Simulator => start::<Traced<Simulator>>(args),
+    }
+}
@@ -0,0 +1,605 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Result};
+use ::apps::backend::{domain, Backend, BackendKind, OperationResult};
+use ::apps::clock::Clock;
+use ::apps::config::Config;
+use ::apps::control::{ControlSocket, Controller, Request};
+use ::apps::dashboard::{Dashboard, Sample};
+use ::apps::endpoint::resolve;
+use ::apps::event;
+use ::apps::logging::Logging;
+use ::apps::loopback::Loopback;
+use ::apps::placement::Placement;
+use ::apps::posix::Posix;
+use ::apps::sim::Simulator;
+use ::apps::splice::{Closed, FlowStatistics, Splicer};
+use ::apps::timer::TimerWheel;
+use ::apps::tracer::{Traced, Tracing};
+use ::clap::{Arg, ArgMatches, Command};
+use ::demikernel::{LibOS, QDesc, QToken};
+use ::log::{debug, info, Level};
+use ::std::collections::HashMap;
+use ::std::net::SocketAddr;
+use ::std::str::FromStr;
+use ::std::time::{Duration, Instant};
+
+//==============================================================================
+// Program Arguments
+//==============================================================================
+
+/// Program Arguments
+#[derive(Debug)]
+pub struct ProgramArguments {
+    /// Local socket address.
+    local: SocketAddr,
+    /// Remote socket address.
+    remote: SocketAddr,
+    /// Maximum number of bytes in flight per direction of a connection.
+    window: usize,
+    /// Backend to run on.
+    io_backend: BackendKind,
+    /// Placement of the process.
+    placement: Placement,
+    /// Tracing of operations.
+    tracing: Tracing,
+    /// Show a live dashboard?
+    tui: bool,
+    /// Control socket.
+    control_socket: ControlSocket,
+}
+
+/// Associate functions for Program Arguments
+impl ProgramArguments {
+    /// Default local address.
+    const DEFAULT_LOCAL: &'static str = "127.0.0.1:12345";
+
+    /// Default host address.
+    const DEFAULT_REMOTE: &'static str = "127.0.0.1:23456";
+
+    /// Default maximum number of bytes in flight per direction of a connection.
+    const DEFAULT_WINDOW: usize = 64 * 1024;
+
+    /// Parses the program arguments from the command line `argv`.
+    pub fn new(
+        app_name: &str,
+        app_author: &str,
+        app_about: &str,
+        argv: Vec<String>,
+    ) -> Result<Self> {
+        let command: Command = Command::new(app_name)
+            .author(app_author)
+            .about(app_about)
+            .arg(
+                Arg::new("local")
+                    .long("local")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("HOST:PORT")
+                    .help("Sets local address"),
+            )
+            .arg(
+                Arg::new("remote")
+                    .long("remote")
+                    .takes_value(true)
+                    .required(true)
+                    .value_name("HOST:PORT")
+                    .help("Sets remote address"),
+            )
+            .arg(
+                Arg::new("window")
+                    .long("window")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("BYTES")
+                    .help("Sets maximum number of bytes in flight per direction of a connection"),
+            )
+            .arg(
+                Arg::new("io_backend")
+                    .long("io_backend")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("libos|posix|loopback|sim")
+                    .help("Sets backend to run on"),
+            )
+            .args(Placement::args())
+            .args(Logging::args())
+            .args(Tracing::args())
+            .args(Dashboard::args())
+            .args(ControlSocket::args())
+            .args(Config::args());
+        let matches: ArgMatches = Config::get_matches_from(command, argv)?;
+
+        // Default arguments.
+        let mut args: ProgramArguments = ProgramArguments {
+            local: SocketAddr::from_str(Self::DEFAULT_LOCAL)?,
+            remote: SocketAddr::from_str(Self::DEFAULT_REMOTE)?,
+            window: Self::DEFAULT_WINDOW,
+            io_backend: BackendKind::LibOS,
+            placement: Placement::from_matches(&matches)?,
+            tracing: Tracing::from_matches(&matches)?,
+            tui: matches.is_present("tui"),
+            control_socket: ControlSocket::from_matches(&matches)?,
+        };
+
+        // Local address.
+        if let Some(addr) = matches.value_of("local") {
+            args.set_local_addr(addr)?;
+        }
+
+        // Remote address.
+        if let Some(addr) = matches.value_of("remote") {
+            args.set_remote_addr(addr)?;
+        }
+
+        // Window.
+        if let Some(window) = matches.value_of("window") {
+            args.set_window(window)?;
+        }
+
+        // Backend.
+        if let Some(backend) = matches.value_of("io_backend") {
+            args.set_io_backend(backend)?;
+        }
+
+        Ok(args)
+    }
+
+    /// Returns the placement parameter stored in the target program arguments.
+    pub fn get_placement(&self) -> &Placement {
+        &self.placement
+    }
+
+    /// Returns the tracing parameter stored in the target program arguments.
+    pub fn get_tracing(&self) -> &Tracing {
+        &self.tracing
+    }
+
+    /// Returns the dashboard parameter stored in the target program arguments.
+    pub fn is_tui(&self) -> bool {
+        self.tui
+    }
+
+    /// Returns the control socket parameter stored in the target program arguments.
+    pub fn get_control_socket(&self) -> &ControlSocket {
+        &self.control_socket
+    }
+
+    /// Returns the backend parameter stored in the target program arguments.
+    pub fn get_io_backend(&self) -> BackendKind {
+        self.io_backend
+    }
+
+    /// Returns the local endpoint address parameter stored in the target program arguments.
+    pub fn get_local(&self) -> SocketAddr {
+        self.local
+    }
+
+    /// Returns the remote endpoint address parameter stored in the target program arguments.
+    pub fn get_remote(&self) -> SocketAddr {
+        self.remote
+    }
+
+    /// Returns the window parameter stored in the target program arguments.
+    pub fn get_window(&self) -> usize {
+        self.window
+    }
+
+    /// Sets the local address and port number parameters in the target program arguments.
+    fn set_local_addr(&mut self, addr: &str) -> Result<()> {
+        self.local = resolve(addr)?;
+        Ok(())
+    }
+
+    /// Sets the remote address and port number parameters in the target program arguments.
+    fn set_remote_addr(&mut self, addr: &str) -> Result<()> {
+        self.remote = resolve(addr)?;
+        Ok(())
+    }
+
+    /// Sets the window parameter in the target program arguments.
+    fn set_window(&mut self, window_str: &str) -> Result<()> {
+        let window: usize = window_str.parse()?;
+        if window > 0 {
+            self.window = window;
+            Ok(())
+        } else {
+            bail!("invalid window")
+        }
+    }
+
+    /// Sets the backend parameter in the target program arguments.
+    fn set_io_backend(&mut self, backend_str: &str) -> Result<()> {
+        self.io_backend = backend_str.parse()?;
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Application
+//==============================================================================
+
+/// Timers of the Run Loop
+#[derive(Debug)]
+enum Timer {
+    /// Emits statistics.
+    Log,
+}
+
+/// Application
+struct Application<B: Backend> {
+    /// Underlying libOS.
+    libos: B,
+    // Local socket descriptor.
+    sockqd: QDesc,
+    /// Remote endpoint.
+    remote: SocketAddr,
+    /// Relayed connections.
+    splicer: Splicer,
+    /// Controller of the control socket, if any.
+    controller: Option<Controller>,
+    /// Live dashboard, if any.
+    dashboard: Option<Dashboard>,
+}
+
+/// Associated Functions for the Application
+impl<B: Backend> Application<B> {
+    /// Logging interval (in seconds).
+    const LOG_INTERVAL: u64 = 5;
+
+    /// Granularity of timers.
+    const TIMER_GRANULARITY: Duration = Duration::from_millis(1);
+
+    /// Number of slots in the timer wheel.
+    const TIMER_SLOTS: usize = 8192;
+
+    /// Instantiates the application.
+    pub fn new(mut libos: B, args: &ProgramArguments) -> Self {
+        // Extract arguments.
+        let local: SocketAddr = args.get_local();
+        let remote: SocketAddr = args.get_remote();
+
+        // Create TCP socket.
+        let sockqd: QDesc = match libos.socket(domain(&local), libc::SOCK_STREAM, 0) {
+            Ok(qd) => qd,
+            Err(e) => panic!("failed to create socket: {:?}", e.cause),
+        };
+
+        // Bind to local address.
+        match libos.bind(sockqd, local) {
+            Ok(()) => (),
+            Err(e) => panic!("failed to bind socket: {:?}", e.cause),
+        };
+
+        // Mark socket as a passive one.
+        match libos.listen(sockqd, 16) {
+            Ok(()) => (),
+            Err(e) => panic!("failed to listen socket: {:?}", e.cause),
+        }
+
+        info!("Local Address:  {:?}", local);
+        info!("Remote Address: {:?}", remote);
+
+        Self {
+            libos,
+            sockqd,
+            remote,
+            splicer: Splicer::new(args.get_window()),
+            controller: None,
+            dashboard: None,
+        }
+    }
+
+    /// Makes the target application apply commands of a control socket.
+    pub fn set_controller(&mut self, controller: Option<Controller>) {
+        self.controller = controller;
+    }
+
+    /// Makes the target application show a live dashboard instead of dumping statistics.
+    pub fn set_dashboard(&mut self, dashboard: Option<Dashboard>) {
+        self.dashboard = dashboard;
+    }
+
+    /// Runs the target relay server.
+    ///
+    /// Every accepted connection is paired with a new connection to the remote, and bytes are
+    /// copied in both directions until both sides close.
+    pub fn run(&mut self) -> Result<()> {
+        let clock: Clock = self.libos.clock();
+        let start: Instant = clock.now();
+        let mut nbytes: usize = 0;
+        let mut nclosed: usize = 0;
+        let mut since: Instant = start;
+        let mut last: Instant = start;
+        let mut sample: Sample = Sample::default();
+        let mut peers: HashMap<QDesc, SocketAddr> = HashMap::new();
+        let mut qtokens: Vec<QToken> = Vec::new();
+        let mut timers: TimerWheel<Timer> =
+            TimerWheel::new(Self::TIMER_GRANULARITY, Self::TIMER_SLOTS, start);
+        timers.schedule(start + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+
+        // Accept first connection.
+        let qt: QToken = match self.libos.accept(self.sockqd) {
+            Ok(qt) => qt,
+            Err(e) => panic!("failed to accept connection on socket: {:?}", e.cause),
+        };
+        qtokens.push(qt);
+
+        loop {
+            let now: Instant = clock.now();
+
+            // Apply commands of the control socket. Resetting counters leaves those of open
+            // connections untouched.
+            if let Some(command) = self.controller.as_ref().and_then(Controller::poll) {
+                match command.get_request().clone() {
+                    Request::Stats => {
+                        let stats: Vec<FlowStatistics> = self.splicer.statistics();
+                        let active: usize = stats.iter().map(|s| s.nbytes_up + s.nbytes_down).sum();
+                        command.reply(&format!(
+                            "bytes: {}\nelapsed_us: {}\nconnections: {}\nclosed: {}",
+                            nbytes + active,
+                            (now - since).as_micros(),
+                            self.splicer.len(),
+                            nclosed
+                        ));
+                    }
+                    Request::Connections => {
+                        let lines: Vec<String> = self
+                            .splicer
+                            .statistics()
+                            .iter()
+                            .map(|s| {
+                                format!(
+                                    "#{}: {:?} B up, {:?} B down{}",
+                                    s.id,
+                                    s.nbytes_up,
+                                    s.nbytes_down,
+                                    if s.connecting { " (connecting)" } else { "" }
+                                )
+                            })
+                            .collect();
+                        command.reply(&lines.join("\n"));
+                    }
+                    Request::ResetCounters => {
+                        nbytes = 0;
+                        nclosed = 0;
+                        since = now;
+                        command.reply("ok");
+                    }
+                    Request::Shutdown => {
+                        command.reply("ok");
+                        break;
+                    }
+                    _ => command.unsupported(),
+                }
+            }
+
+            // Dump statistics.
+            for timer in timers.expire(now) {
+                match timer {
+                    Timer::Log if self.dashboard.is_some() => {
+                        if let Some(dashboard) = &self.dashboard {
+                            sample.set_nconnections(self.splicer.len());
+                            dashboard.update(sample.close(now - last));
+                            last = now;
+                        }
+                        timers.schedule(now + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+                    }
+                    Timer::Log => {
+                        let elapsed: Duration = now - since;
+                        let stats: Vec<FlowStatistics> = self.splicer.statistics();
+                        let active: usize = stats.iter().map(|s| s.nbytes_up + s.nbytes_down).sum();
+                        info!(
+                            "{:?} B / {:?} us ({} connections, {} closed)",
+                            nbytes + active,
+                            elapsed.as_micros(),
+                            self.splicer.len(),
+                            nclosed
+                        );
+                        for s in stats {
+                            debug!(
+                                "  #{}: {:?} B up, {:?} B down{}",
+                                s.id,
+                                s.nbytes_up,
+                                s.nbytes_down,
+                                if s.connecting { " (connecting)" } else { "" }
+                            );
+                        }
+                        timers.schedule(now + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+                    }
+                }
+            }
+
+            // Wait for traffic, waking up in time to refresh the dashboard.
+            let timeout: Option<Duration> = match self.dashboard {
+                Some(_) => timers
+                    .next_deadline()
+                    .map(|deadline| deadline.saturating_duration_since(clock.now())),
+                None => None,
+            };
+            let timeout: Option<Duration> =
+                Controller::timeout::<B>(self.controller.as_ref(), timeout);
+            let (i, qd, result) = match self.libos.wait_any_timeout(&qtokens, timeout) {
+                Ok((i, qd, result)) => (i, qd, result),
+                Err(e) if e.is_timeout() => continue,
+                Err(e) if e.is_shutdown() => break,
+                Err(e) => panic!("operation failed: {:?}", e),
+            };
+            let qt: QToken = qtokens.swap_remove(i);
+
+            // Parse result.
+            match result {
+                // Accept completed, so connect to the remote.
+                OperationResult::Accept(client, peer) if qd == self.sockqd => {
+                    // Accept another connection.
+                    let qt: QToken = match self.libos.accept(self.sockqd) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to accept connection on socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+
+                    if let (true, Some(peer)) = (self.open(client, &mut qtokens), peer) {
+                        peers.insert(client, peer);
+                    }
+                }
+                OperationResult::Failed(e) if qd == self.sockqd => {
+                    panic!("operation failed: {:?}", e)
+                }
+                // Operation on a relayed connection.
+                result => {
+                    if let (Some(_), OperationResult::Pop(_, buf)) = (&self.dashboard, &result) {
+                        sample.record(buf.len(), None, peers.get(&qd).copied());
+                    }
+                    let closed: Option<Closed> =
+                        self.splicer
+                            .handle(&mut self.libos, qt, qd, result, &mut qtokens);
+                    let closed: Closed = match closed {
+                        Some(closed) => closed,
+                        None => continue,
+                    };
+                    nbytes += closed.nbytes_up + closed.nbytes_down;
+                    nclosed += 1;
+                    peers.remove(&closed.client);
+                    match closed.error {
+                        Some(e) => event!(
+                            Level::Warn,
+                            closed.client,
+                            Some(self.remote),
+                            "connection #{} aborted: {}",
+                            closed.id,
+                            e
+                        ),
+                        None => event!(
+                            Level::Debug,
+                            closed.client,
+                            Some(self.remote),
+                            "connection #{} closed: {:?} B up, {:?} B down in {:?} us",
+                            closed.id,
+                            closed.nbytes_up,
+                            closed.nbytes_down,
+                            closed.duration.as_micros()
+                        ),
+                    }
+                }
+            }
+        }
+
+        // Close relayed connections and the socket, and dump final statistics.
+        for closed in self.splicer.close_all(&mut self.libos, &mut qtokens) {
+            nbytes += closed.nbytes_up + closed.nbytes_down;
+            nclosed += 1;
+        }
+        if let Err(e) = self.libos.close(self.sockqd) {
+            event!(
+                Level::Warn,
+                self.sockqd,
+                None,
+                "failed to close socket: {:?}",
+                e.cause
+            );
+        }
+        let elapsed: Duration = clock.now() - since;
+        info!(
+            "{:?} B / {:?} us ({} closed)",
+            nbytes,
+            elapsed.as_micros(),
+            nclosed
+        );
+
+        self.libos.status()
+    }
+
+    /// Opens a connection to the remote on behalf of an accepted client. Returns whether the
+    /// connection is relayed.
+    fn open(&mut self, client: QDesc, qtokens: &mut Vec<QToken>) -> bool {
+        // Create TCP socket.
+        let server: QDesc = match self
+            .libos
+            .socket(domain(&self.remote), libc::SOCK_STREAM, 0)
+        {
+            Ok(qd) => qd,
+            Err(e) => panic!("failed to create socket: {:?}", e.cause),
+        };
+
+        // Setup connection.
+        let qt: QToken = match self.libos.connect(server, self.remote) {
+            Ok(qt) => qt,
+            Err(e) => {
+                event!(
+                    Level::Warn,
+                    server,
+                    Some(self.remote),
+                    "failed to connect socket: {:?}",
+                    e.cause
+                );
+                for qd in [client, server] {
+                    if let Err(e) = self.libos.close(qd) {
+                        event!(
+                            Level::Warn,
+                            qd,
+                            None,
+                            "failed to close socket: {:?}",
+                            e.cause
+                        );
+                    }
+                }
+                return false;
+            }
+        };
+        qtokens.push(qt);
+
+        let id: u64 = self.splicer.insert(client, server, qt, 0);
+        event!(
+            Level::Debug,
+            client,
+            Some(self.remote),
+            "connection #{} accepted",
+            id
+        );
+        true
+    }
+}
+
+//==============================================================================
+
+/// Runs the application over a backend of type `B`.
+fn start<B: Backend>(args: ProgramArguments) -> Result<()> {
+    if args.is_tui() && !B::has_timed_waits() {
+        bail!("the dashboard needs timed waits, which the backend does not offer")
+    }
+    let libos: B = B::create()?;
+    let mut app: Application<B> = Application::new(libos, &args);
+    app.set_controller(args.get_control_socket().start()?);
+    if args.is_tui() {
+        app.set_dashboard(Some(Dashboard::start("tcp-relay")?));
+    }
+    app.run()
+}
+
+/// Describes the application.
+pub const ABOUT: &str = "Relays TCP connections.";
+
+/// Runs the application with the given command line.
+pub fn main(argv: Vec<String>) -> Result<()> {
+    let args: ProgramArguments = ProgramArguments::new(
+        "tcp-relay",
+        "Pedro Henrique Penna <ppenna@microsoft.com>",
+        ABOUT,
+        argv,
+    )?;
+
+    args.get_placement().apply()?;
+    args.get_tracing().start()?;
+
+    match args.get_io_backend() {
+        BackendKind::LibOS => start::<Traced<LibOS>>(args),
+        BackendKind::Posix => start::<Traced<Posix>>(args),
+        BackendKind::Loopback => start::<Traced<Loopback>>(args),
+        BackendKind::Simulator => start::<Traced<Simulator>>(args),
+    }
+}
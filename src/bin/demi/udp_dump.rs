@@ -0,0 +1,362 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Result};
+use ::apps::backend::{domain, Backend, BackendKind, OperationResult};
+use ::apps::clock::Clock;
+use ::apps::config::Config;
+use ::apps::control::{ControlSocket, Controller, Request};
+use ::apps::dashboard::{Dashboard, Sample};
+use ::apps::endpoint::resolve;
+use ::apps::logging::Logging;
+use ::apps::loopback::Loopback;
+use ::apps::placement::Placement;
+use ::apps::posix::Posix;
+use ::apps::sim::Simulator;
+use ::apps::timer::TimerWheel;
+use ::apps::tracer::{Traced, Tracing};
+use ::clap::{Arg, ArgMatches, Command};
+use ::demikernel::{LibOS, QDesc, QToken};
+use ::log::{info, warn};
+use ::std::time::{Duration, Instant};
+use ::std::{net::SocketAddr, str::FromStr};
+
+//==============================================================================
+// Program Arguments
+//==============================================================================
+
+/// Program Arguments
+#[derive(Debug)]
+struct ProgramArguments {
+    /// Local socket address.
+    local: SocketAddr,
+    /// Backend to run on.
+    io_backend: BackendKind,
+    /// Placement of the process.
+    placement: Placement,
+    /// Tracing of operations.
+    tracing: Tracing,
+    /// Show a live dashboard?
+    tui: bool,
+    /// Control socket.
+    control_socket: ControlSocket,
+}
+
+/// Associate functions for Program Arguments
+impl ProgramArguments {
+    /// Default local address.
+    const DEFAULT_LOCAL: &'static str = "127.0.0.1:12345";
+
+    /// Parses the program arguments from the command line `argv`.
+    pub fn new(
+        app_name: &str,
+        app_author: &str,
+        app_about: &str,
+        argv: Vec<String>,
+    ) -> Result<Self> {
+        let command: Command = Command::new(app_name)
+            .author(app_author)
+            .about(app_about)
+            .arg(
+                Arg::new("local")
+                    .long("local")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("HOST:PORT")
+                    .help("Sets local address"),
+            )
+            .arg(
+                Arg::new("io_backend")
+                    .long("io_backend")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("libos|posix|loopback|sim")
+                    .help("Sets backend to run on"),
+            )
+            .args(Placement::args())
+            .args(Logging::args())
+            .args(Tracing::args())
+            .args(Dashboard::args())
+            .args(ControlSocket::args())
+            .args(Config::args());
+        let matches: ArgMatches = Config::get_matches_from(command, argv)?;
+
+        // Default arguments.
+        let mut args: ProgramArguments = ProgramArguments {
+            local: SocketAddr::from_str(Self::DEFAULT_LOCAL)?,
+            io_backend: BackendKind::LibOS,
+            placement: Placement::from_matches(&matches)?,
+            tracing: Tracing::from_matches(&matches)?,
+            tui: matches.is_present("tui"),
+            control_socket: ControlSocket::from_matches(&matches)?,
+        };
+
+        // Local address.
+        if let Some(addr) = matches.value_of("local") {
+            args.set_local_addr(addr)?;
+        }
+
+        // Backend.
+        if let Some(backend) = matches.value_of("io_backend") {
+            args.set_io_backend(backend)?;
+        }
+
+        Ok(args)
+    }
+
+    /// Returns the placement parameter stored in the target program arguments.
+    pub fn get_placement(&self) -> &Placement {
+        &self.placement
+    }
+
+    /// Returns the tracing parameter stored in the target program arguments.
+    pub fn get_tracing(&self) -> &Tracing {
+        &self.tracing
+    }
+
+    /// Returns the dashboard parameter stored in the target program arguments.
+    pub fn is_tui(&self) -> bool {
+        self.tui
+    }
+
+    /// Returns the control socket parameter stored in the target program arguments.
+    pub fn get_control_socket(&self) -> &ControlSocket {
+        &self.control_socket
+    }
+
+    /// Returns the backend parameter stored in the target program arguments.
+    pub fn get_io_backend(&self) -> BackendKind {
+        self.io_backend
+    }
+
+    /// Returns the local endpoint address parameter stored in the target program arguments.
+    pub fn get_local(&self) -> SocketAddr {
+        self.local
+    }
+
+    /// Sets the local address and port number parameters in the target program arguments.
+    fn set_local_addr(&mut self, addr: &str) -> Result<()> {
+        self.local = resolve(addr)?;
+        Ok(())
+    }
+
+    /// Sets the backend parameter in the target program arguments.
+    fn set_io_backend(&mut self, backend_str: &str) -> Result<()> {
+        self.io_backend = backend_str.parse()?;
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Application
+//==============================================================================
+
+/// Timers of the Run Loop
+#[derive(Debug)]
+enum Timer {
+    /// Emits statistics.
+    Log,
+}
+
+/// Application
+struct Application<B: Backend> {
+    /// Underlying libOS.
+    libos: B,
+    /// Local socket descriptor.
+    sockqd: QDesc,
+    /// Live dashboard, if any.
+    dashboard: Option<Dashboard>,
+    /// Controller of the control socket, if any.
+    controller: Option<Controller>,
+}
+
+/// Associated Functions for the Application
+impl<B: Backend> Application<B> {
+    /// Logging interval (in seconds).
+    const LOG_INTERVAL: u64 = 5;
+
+    /// Granularity of timers.
+    const TIMER_GRANULARITY: Duration = Duration::from_millis(1);
+
+    /// Number of slots in the timer wheel.
+    const TIMER_SLOTS: usize = 8192;
+
+    /// Instantiates the application.
+    pub fn new(mut libos: B, args: &ProgramArguments, dashboard: Option<Dashboard>) -> Self {
+        // Extract arguments.
+        let local: SocketAddr = args.get_local();
+
+        // Create UDP socket.
+        let sockqd: QDesc = match libos.socket(domain(&local), libc::SOCK_DGRAM, 0) {
+            Ok(qd) => qd,
+            Err(e) => panic!("failed to create socket: {:?}", e.cause),
+        };
+
+        // Bind to local address.
+        match libos.bind(sockqd, local) {
+            Ok(()) => (),
+            Err(e) => panic!("failed to bind socket: {:?}", e.cause),
+        };
+
+        info!("Local Address: {:?}", local);
+
+        Self {
+            libos,
+            sockqd,
+            dashboard,
+            controller: None,
+        }
+    }
+
+    /// Makes the target application apply commands of a control socket.
+    pub fn set_controller(&mut self, controller: Option<Controller>) {
+        self.controller = controller;
+    }
+
+    /// Runs the target application.
+    pub fn run(&mut self) -> Result<()> {
+        let clock: Clock = self.libos.clock();
+        let start: Instant = clock.now();
+        let mut since: Instant = start;
+        let mut nbytes: usize = 0;
+        let mut last: Instant = start;
+        let mut sample: Sample = Sample::default();
+        let mut timers: TimerWheel<Timer> =
+            TimerWheel::new(Self::TIMER_GRANULARITY, Self::TIMER_SLOTS, start);
+        timers.schedule(start + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+
+        // Pop first packet, timing pops only if there is someone to show them to.
+        let mut issued: Option<Instant> = self.dashboard.as_ref().map(|_| clock.now());
+        let mut qt: QToken = self.pop();
+
+        loop {
+            let now: Instant = clock.now();
+
+            // Apply commands of the control socket.
+            if let Some(command) = self.controller.as_ref().and_then(Controller::poll) {
+                match command.get_request().clone() {
+                    Request::Stats => {
+                        command.reply(&format!(
+                            "bytes: {}\nelapsed_us: {}",
+                            nbytes,
+                            (now - since).as_micros()
+                        ));
+                    }
+                    Request::ResetCounters => {
+                        nbytes = 0;
+                        since = now;
+                        command.reply("ok");
+                    }
+                    Request::Shutdown => {
+                        command.reply("ok");
+                        break;
+                    }
+                    _ => command.unsupported(),
+                }
+            }
+
+            // Dump statistics.
+            for timer in timers.expire(now) {
+                match timer {
+                    Timer::Log => {
+                        if let Some(dashboard) = &self.dashboard {
+                            dashboard.update(sample.close(now - last));
+                            last = now;
+                        } else {
+                            let elapsed: Duration = now - since;
+                            info!("{:?} B / {:?} us", nbytes, elapsed.as_micros());
+                        }
+                        timers.schedule(now + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+                    }
+                }
+            }
+
+            // Drain packets, waking up in time to refresh the dashboard.
+            let timeout: Option<Duration> = match self.dashboard {
+                Some(_) => timers
+                    .next_deadline()
+                    .map(|deadline| deadline.saturating_duration_since(clock.now())),
+                None => None,
+            };
+            let timeout: Option<Duration> =
+                Controller::timeout::<B>(self.controller.as_ref(), timeout);
+            match self.libos.wait_any_timeout(&[qt], timeout) {
+                Ok((_, _, OperationResult::Pop(addr, buf))) => {
+                    nbytes += buf.len();
+                    if let Some(issued) = issued {
+                        sample.record(buf.len(), Some(clock.now() - issued), addr);
+                    }
+                    issued = issued.map(|_| clock.now());
+                    qt = self.pop();
+                }
+                Err(e) if e.is_timeout() => continue,
+                Err(e) if e.is_shutdown() => break,
+                Err(e) => panic!("operation failed: {:?}", e.cause),
+                _ => panic!("unexpected result"),
+            }
+        }
+
+        // Close the socket, and dump final statistics.
+        if let Err(e) = self.libos.close(self.sockqd) {
+            warn!("failed to close socket: {:?}", e.cause);
+        }
+        let elapsed: Duration = clock.now() - since;
+        info!("{:?} B / {:?} us", nbytes, elapsed.as_micros());
+
+        self.libos.status()
+    }
+
+    /// Pops data from the socket.
+    fn pop(&mut self) -> QToken {
+        match self.libos.pop(self.sockqd) {
+            Ok(qt) => qt,
+            Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+        }
+    }
+}
+
+//==============================================================================
+
+/// Runs the application over a backend of type `B`.
+fn start<B: Backend>(args: ProgramArguments) -> Result<()> {
+    if args.is_tui() && !B::has_timed_waits() {
+        bail!("the dashboard needs timed waits, which the backend does not offer")
+    }
+    let libos: B = B::create()?;
+    let dashboard: Option<Dashboard> = if args.is_tui() {
+        Some(Dashboard::start("udp-dump")?)
+    } else {
+        None
+    };
+
+    let mut app: Application<B> = Application::new(libos, &args, dashboard);
+    app.set_controller(args.get_control_socket().start()?);
+    app.run()
+}
+
+/// Describes the application.
+pub const ABOUT: &str = "Dumps incoming packets on a UDP port.";
+
+/// Runs the application with the given command line.
+pub fn main(argv: Vec<String>) -> Result<()> {
+    let args: ProgramArguments = ProgramArguments::new(
+        "udp-dump",
+        "Pedro Henrique Penna <ppenna@microsoft.com>",
+        ABOUT,
+        argv,
+    )?;
+
+    args.get_placement().apply()?;
+    args.get_tracing().start()?;
+
+    match args.get_io_backend() {
+        BackendKind::LibOS => start::<Traced<LibOS>>(args),
+        BackendKind::Posix => start::<Traced<Posix>>(args),
+        BackendKind::Loopback => start::<Traced<Loopback>>(args),
+        BackendKind::Simulator => start::<Traced<Simulator>>(args),
+    }
+}
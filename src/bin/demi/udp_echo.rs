@@ -0,0 +1,603 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Result};
+use ::apps::backend::{domain, Backend, BackendKind, OperationResult};
+use ::apps::clock::Clock;
+use ::apps::config::Config;
+use ::apps::control::{ControlSocket, Controller, Controllers, Request};
+use ::apps::endpoint::resolve;
+use ::apps::logging::Logging;
+use ::apps::loopback::Loopback;
+use ::apps::placement::Placement;
+use ::apps::posix::Posix;
+use ::apps::sga::{self, ZeroCopyStatistics};
+use ::apps::sim::Simulator;
+use ::apps::timer::TimerWheel;
+use ::apps::tracer::{Traced, Tracing};
+use ::apps::workers::{self, WorkerStatistics, Workers};
+use ::clap::{Arg, ArgMatches, Command};
+use ::demikernel::{demi_opcode_t, demi_qresult_t, demi_sgarray_t};
+use ::demikernel::{LibOS, QDesc, QToken};
+use ::log::{info, warn};
+use ::std::sync::Arc;
+use ::std::time::{Duration, Instant};
+use ::std::{net::SocketAddr, str::FromStr};
+
+//==============================================================================
+// Program Arguments
+//==============================================================================
+
+/// Program Arguments
+#[derive(Clone, Debug)]
+pub struct ProgramArguments {
+    /// Local socket address.
+    local: SocketAddr,
+    /// Remote socket address.
+    remote: SocketAddr,
+    /// Echo received buffers without copying them?
+    zero_copy: bool,
+    /// Number of workers.
+    workers: usize,
+    /// Backend to run on.
+    io_backend: BackendKind,
+    /// Placement of the process.
+    placement: Placement,
+    /// Tracing of operations.
+    tracing: Tracing,
+    /// Control socket.
+    control_socket: ControlSocket,
+}
+
+/// Associate functions for Program Arguments
+impl ProgramArguments {
+    /// Default local address.
+    const DEFAULT_LOCAL: &'static str = "127.0.0.1:12345";
+
+    /// Default host address.
+    const DEFAULT_REMOTE: &'static str = "127.0.0.1:23456";
+
+    /// Parses the program arguments from the command line `argv`.
+    pub fn new(
+        app_name: &str,
+        app_author: &str,
+        app_about: &str,
+        argv: Vec<String>,
+    ) -> Result<Self> {
+        let command: Command = Command::new(app_name)
+            .author(app_author)
+            .about(app_about)
+            .arg(
+                Arg::new("local")
+                    .long("local")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("HOST:PORT")
+                    .help("Sets local address"),
+            )
+            .arg(
+                Arg::new("remote")
+                    .long("remote")
+                    .takes_value(true)
+                    .required(true)
+                    .value_name("HOST:PORT")
+                    .help("Sets remote address"),
+            )
+            .arg(
+                Arg::new("zero_copy")
+                    .long("zero_copy")
+                    .takes_value(false)
+                    .required(false)
+                    .help("Echoes received buffers without copying them"),
+            )
+            .arg(
+                Arg::new("workers")
+                    .long("workers")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("COUNT")
+                    .help(
+                        "Sets number of worker threads, which share the port on the POSIX backend \
+                         and listen on consecutive ports on the libOS",
+                    ),
+            )
+            .arg(
+                Arg::new("io_backend")
+                    .long("io_backend")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("libos|posix|loopback|sim")
+                    .help("Sets backend to run on"),
+            )
+            .args(Placement::args())
+            .args(Logging::args())
+            .args(Tracing::args())
+            .args(ControlSocket::args())
+            .args(Config::args());
+        let matches: ArgMatches = Config::get_matches_from(command, argv)?;
+
+        // Default arguments.
+        let mut args: ProgramArguments = ProgramArguments {
+            local: SocketAddr::from_str(Self::DEFAULT_LOCAL)?,
+            remote: SocketAddr::from_str(Self::DEFAULT_REMOTE)?,
+            zero_copy: matches.is_present("zero_copy"),
+            workers: 1,
+            io_backend: BackendKind::LibOS,
+            placement: Placement::from_matches(&matches)?,
+            tracing: Tracing::from_matches(&matches)?,
+            control_socket: ControlSocket::from_matches(&matches)?,
+        };
+
+        // Local address.
+        if let Some(addr) = matches.value_of("local") {
+            args.set_local_addr(addr)?;
+        }
+
+        // Remote address.
+        if let Some(addr) = matches.value_of("remote") {
+            args.set_remote_addr(addr)?;
+        }
+
+        // Number of workers.
+        if let Some(workers) = matches.value_of("workers") {
+            args.set_workers(workers)?;
+        }
+
+        // Backend.
+        if let Some(backend) = matches.value_of("io_backend") {
+            args.set_io_backend(backend)?;
+        }
+        if args.zero_copy && args.io_backend != BackendKind::LibOS {
+            bail!("zero-copy requires the libos backend");
+        }
+
+        Ok(args)
+    }
+
+    /// Returns the placement parameter stored in the target program arguments.
+    pub fn get_placement(&self) -> &Placement {
+        &self.placement
+    }
+
+    /// Returns the tracing parameter stored in the target program arguments.
+    pub fn get_tracing(&self) -> &Tracing {
+        &self.tracing
+    }
+
+    /// Returns the control socket parameter stored in the target program arguments.
+    pub fn get_control_socket(&self) -> &ControlSocket {
+        &self.control_socket
+    }
+
+    /// Returns the backend parameter stored in the target program arguments.
+    pub fn get_io_backend(&self) -> BackendKind {
+        self.io_backend
+    }
+
+    /// Returns the local endpoint address parameter stored in the target program arguments.
+    pub fn get_local(&self) -> SocketAddr {
+        self.local
+    }
+
+    /// Returns the remote endpoint address parameter stored in the target program arguments.
+    pub fn get_remote(&self) -> SocketAddr {
+        self.remote
+    }
+
+    /// Asserts if received buffers should be echoed without copying them.
+    pub fn is_zero_copy(&self) -> bool {
+        self.zero_copy
+    }
+
+    /// Returns the number of workers parameter stored in the target program arguments.
+    pub fn get_workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Sets the local address and port number parameters in the target program arguments.
+    fn set_local_addr(&mut self, addr: &str) -> Result<()> {
+        self.local = resolve(addr)?;
+        Ok(())
+    }
+
+    /// Sets the remote address and port number parameters in the target program arguments.
+    fn set_remote_addr(&mut self, addr: &str) -> Result<()> {
+        self.remote = resolve(addr)?;
+        Ok(())
+    }
+
+    /// Sets the number of workers parameter in the target program arguments.
+    fn set_workers(&mut self, workers_str: &str) -> Result<()> {
+        let workers: usize = workers_str.parse()?;
+        if workers > 0 {
+            self.workers = workers;
+            Ok(())
+        } else {
+            bail!("invalid number of workers")
+        }
+    }
+
+    /// Sets the backend parameter in the target program arguments.
+    fn set_io_backend(&mut self, backend_str: &str) -> Result<()> {
+        self.io_backend = backend_str.parse()?;
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Application
+//==============================================================================
+
+/// Timers of the Run Loop
+#[derive(Debug)]
+enum Timer {
+    /// Emits statistics.
+    Log,
+}
+
+/// Application
+struct Application<B: Backend> {
+    /// Underlying libOS.
+    libos: B,
+    // Local socket descriptor.
+    sockqd: QDesc,
+    /// Remote endpoint.
+    remote: SocketAddr,
+    /// Echo received buffers without copying them?
+    zero_copy: bool,
+    /// Statistics published when running as a worker.
+    stats: Option<Arc<WorkerStatistics>>,
+    /// Controller of the control socket, if any.
+    controller: Option<Controller>,
+}
+
+/// Associated Functions for the Application
+impl<B: Backend> Application<B> {
+    /// Logging interval (in seconds).
+    const LOG_INTERVAL: u64 = 5;
+
+    /// Granularity of timers.
+    const TIMER_GRANULARITY: Duration = Duration::from_millis(1);
+
+    /// Number of slots in the timer wheel.
+    const TIMER_SLOTS: usize = 8192;
+
+    /// Instantiates the application.
+    pub fn new(mut libos: B, args: &ProgramArguments) -> Self {
+        // Extract arguments.
+        let local: SocketAddr = args.get_local();
+        let remote: SocketAddr = args.get_remote();
+
+        // Create UDP socket.
+        let sockqd: QDesc = match libos.socket(domain(&local), libc::SOCK_DGRAM, 0) {
+            Ok(qd) => qd,
+            Err(e) => panic!("failed to create socket: {:?}", e.cause),
+        };
+
+        // Bind to local address.
+        match workers::bind(&mut libos, sockqd, local, args.get_workers()) {
+            Ok(()) => (),
+            Err(e) => panic!("failed to bind socket: {:?}", e.cause),
+        };
+
+        info!("Local Address: {:?}", local);
+
+        Self {
+            libos,
+            sockqd,
+            remote,
+            zero_copy: args.is_zero_copy(),
+            stats: None,
+            controller: None,
+        }
+    }
+
+    /// Makes the target application publish its statistics instead of dumping them.
+    pub fn set_worker_statistics(&mut self, stats: Arc<WorkerStatistics>) {
+        self.stats = Some(stats);
+    }
+
+    /// Makes the target application apply commands of a control socket.
+    pub fn set_controller(&mut self, controller: Option<Controller>) {
+        self.controller = controller;
+    }
+
+    /// Runs the target echo server.
+    pub fn run(&mut self) -> Result<()> {
+        if self.zero_copy {
+            return self.run_zero_copy();
+        }
+
+        let clock: Clock = self.libos.clock();
+        let start: Instant = clock.now();
+        let mut nbytes: usize = 0;
+        let mut since: Instant = start;
+        let mut qtokens: Vec<QToken> = Vec::new();
+        let mut timers: TimerWheel<Timer> =
+            TimerWheel::new(Self::TIMER_GRANULARITY, Self::TIMER_SLOTS, start);
+        timers.schedule(start + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+
+        // Pop first packet.
+        let qt: QToken = match self.libos.pop(self.sockqd) {
+            Ok(qt) => qt,
+            Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+        };
+        qtokens.push(qt);
+
+        loop {
+            // Apply commands of the control socket.
+            if let Some(command) = self.controller.as_ref().and_then(Controller::poll) {
+                let now: Instant = clock.now();
+                match command.get_request().clone() {
+                    Request::Stats => {
+                        command.reply(&format!(
+                            "bytes: {}\nelapsed_us: {}",
+                            nbytes,
+                            (now - since).as_micros()
+                        ));
+                    }
+                    Request::ResetCounters => {
+                        nbytes = 0;
+                        since = now;
+                        command.reply("ok");
+                    }
+                    Request::Shutdown => {
+                        command.reply("ok");
+                        break;
+                    }
+                    _ => command.unsupported(),
+                }
+            }
+
+            // Publish statistics when running as a worker, or dump them.
+            if let Some(stats) = &self.stats {
+                stats.set_nbytes(nbytes);
+            } else {
+                let now: Instant = clock.now();
+                for timer in timers.expire(now) {
+                    match timer {
+                        Timer::Log => {
+                            let elapsed: Duration = now - since;
+                            info!("{:?} B / {:?} us", nbytes, elapsed.as_micros());
+                            timers.schedule(
+                                now + Duration::from_secs(Self::LOG_INTERVAL),
+                                Timer::Log,
+                            );
+                        }
+                    }
+                }
+            }
+
+            // TODO: add type annotation to the following variable once we drop generics on OperationResult.
+            let timeout: Option<Duration> =
+                Controller::timeout::<B>(self.controller.as_ref(), None);
+            let (i, _, result) = match self.libos.wait_any_timeout(&qtokens, timeout) {
+                Ok((i, qd, result)) => (i, qd, result),
+                Err(e) if e.is_timeout() => continue,
+                Err(e) if e.is_shutdown() => break,
+                Err(e) => panic!("operation failed: {:?}", e),
+            };
+            qtokens.swap_remove(i);
+
+            // Parse result.
+            match result {
+                // Pop completed.
+                OperationResult::Pop(_, buf) => {
+                    nbytes += buf.len();
+                    // Push packet back.
+                    let qt: QToken = match self.libos.pushto(self.sockqd, &buf, self.remote) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to push data to socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                }
+                // Push completed.
+                OperationResult::Push => {
+                    // Pop another packet.
+                    let qt: QToken = match self.libos.pop(self.sockqd) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                }
+                OperationResult::Failed(e) => panic!("operation failed: {:?}", e),
+                _ => panic!("unexpected result"),
+            };
+        }
+
+        // Close the socket, and dump final statistics.
+        self.close();
+        let elapsed: Duration = clock.now() - since;
+        info!("{:?} B / {:?} us", nbytes, elapsed.as_micros());
+
+        self.libos.status()
+    }
+
+    /// Runs the target echo server, sending received buffers back as they are.
+    ///
+    /// Each buffer is released once its push completes.
+    fn run_zero_copy(&mut self) -> Result<()> {
+        let clock: Clock = self.libos.clock();
+        let libos: &mut LibOS = sga::libos(&mut self.libos);
+        let start: Instant = clock.now();
+        let mut nbytes: usize = 0;
+        let mut since: Instant = start;
+        let mut qtokens: Vec<QToken> = Vec::new();
+        let mut inflight: Vec<(QToken, demi_sgarray_t)> = Vec::new();
+        let mut zc_stats: ZeroCopyStatistics = ZeroCopyStatistics::default();
+        let mut timers: TimerWheel<Timer> =
+            TimerWheel::new(Self::TIMER_GRANULARITY, Self::TIMER_SLOTS, start);
+        timers.schedule(start + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+
+        // Pop first packet.
+        let qt: QToken = match libos.pop(self.sockqd) {
+            Ok(qt) => qt,
+            Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+        };
+        qtokens.push(qt);
+
+        loop {
+            // Apply commands of the control socket.
+            if let Some(command) = self.controller.as_ref().and_then(Controller::poll) {
+                let now: Instant = clock.now();
+                match command.get_request().clone() {
+                    Request::Stats => {
+                        command.reply(&format!(
+                            "bytes: {}\nelapsed_us: {}",
+                            nbytes,
+                            (now - since).as_micros()
+                        ));
+                    }
+                    Request::ResetCounters => {
+                        nbytes = 0;
+                        since = now;
+                        command.reply("ok");
+                    }
+                    Request::Shutdown => {
+                        command.reply("ok");
+                        break;
+                    }
+                    _ => command.unsupported(),
+                }
+            }
+
+            // Publish statistics when running as a worker, or dump them.
+            if let Some(stats) = &self.stats {
+                stats.set_nbytes(nbytes);
+            } else {
+                let now: Instant = clock.now();
+                for timer in timers.expire(now) {
+                    match timer {
+                        Timer::Log => {
+                            let elapsed: Duration = now - since;
+                            info!(
+                                "{:?} B / {:?} us ({})",
+                                nbytes,
+                                elapsed.as_micros(),
+                                zc_stats
+                            );
+                            timers.schedule(
+                                now + Duration::from_secs(Self::LOG_INTERVAL),
+                                Timer::Log,
+                            );
+                        }
+                    }
+                }
+            }
+
+            let (i, qr): (usize, demi_qresult_t) = match libos.wait_any(&qtokens) {
+                Ok((i, qr)) => (i, qr),
+                Err(e) if e.errno == libc::ESHUTDOWN => break,
+                Err(e) => panic!("operation failed: {:?}", e),
+            };
+            let qt: QToken = qtokens.swap_remove(i);
+
+            // Parse result.
+            match qr.qr_opcode {
+                // Pop completed.
+                demi_opcode_t::DEMI_OPC_POP => {
+                    let buf: demi_sgarray_t = sga::qr_sga(&qr);
+                    nbytes += sga::sga_len(&buf);
+                    // Push packet back.
+                    let qt: QToken = match sga::pushto(libos, self.sockqd, &buf, self.remote) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to push data to socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                    inflight.push((qt, buf));
+                    zc_stats.record(sga::sga_len(&buf));
+                }
+                // Push completed.
+                demi_opcode_t::DEMI_OPC_PUSH => {
+                    if let Some(j) = inflight.iter().position(|(t, _)| *t == qt) {
+                        let (_, buf): (QToken, demi_sgarray_t) = inflight.swap_remove(j);
+                        sga::sga_free(libos, buf);
+                    }
+                    // Pop another packet.
+                    let qt: QToken = match libos.pop(self.sockqd) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                }
+                demi_opcode_t::DEMI_OPC_FAILED => panic!("operation failed"),
+                _ => panic!("unexpected result"),
+            };
+        }
+
+        // Release buffers that are still in flight, close the socket, and dump final statistics.
+        for (_, buf) in inflight {
+            sga::sga_free(libos, buf);
+        }
+        self.close();
+        let elapsed: Duration = clock.now() - since;
+        info!(
+            "{:?} B / {:?} us ({})",
+            nbytes,
+            elapsed.as_micros(),
+            zc_stats
+        );
+
+        self.libos.status()
+    }
+
+    /// Closes the socket, warning if it fails.
+    fn close(&mut self) {
+        if let Err(e) = self.libos.close(self.sockqd) {
+            warn!("failed to close socket: {:?}", e.cause);
+        }
+    }
+}
+
+//==============================================================================
+
+/// Runs the application over a backend of type `B`.
+fn start<B: Backend + 'static>(args: ProgramArguments) -> Result<()> {
+    // Run one echo server per worker.
+    if args.get_workers() > 1 {
+        let controllers: Controllers = args
+            .get_control_socket()
+            .start_workers(args.get_workers())?;
+        let workers: Workers = Workers::spawn(args.get_workers(), move |i, stats| {
+            let mut args: ProgramArguments = args.clone();
+            args.local = workers::worker_addr::<B>(args.local, i)?;
+            let mut app: Application<B> = Application::new(B::create()?, &args);
+            app.set_worker_statistics(stats);
+            app.set_controller(controllers.take(i));
+            app.run()
+        })?;
+        return workers.report(Duration::from_secs(Application::<B>::LOG_INTERVAL));
+    }
+
+    let libos: B = B::create()?;
+    let mut app: Application<B> = Application::new(libos, &args);
+    app.set_controller(args.get_control_socket().start()?);
+    app.run()
+}
+
+/// Describes the application.
+pub const ABOUT: &str = "Echoes UDP packets.";
+
+/// Runs the application with the given command line.
+pub fn main(argv: Vec<String>) -> Result<()> {
+    let args: ProgramArguments = ProgramArguments::new(
+        "udp-echo",
+        "Pedro Henrique Penna <ppenna@microsoft.com>",
+        ABOUT,
+        argv,
+    )?;
+
+    args.get_placement().apply()?;
+    args.get_tracing().start()?;
+
+    match args.get_io_backend() {
+        BackendKind::LibOS => start::<Traced<LibOS>>(args),
+        BackendKind::Posix => start::<Traced<Posix>>(args),
+        BackendKind::Loopback => start::<Traced<Loopback>>(args),
+        BackendKind::Simulator => start::<Traced<Simulator>>(args),
+    }
+}
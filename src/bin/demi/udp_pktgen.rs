@@ -0,0 +1,755 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Result};
+use ::apps::backend::{domain, Backend, BackendKind, OperationResult};
+use ::apps::clock::Clock;
+use ::apps::config::Config;
+use ::apps::control::{ControlSocket, Controller, Request};
+use ::apps::endpoint::resolve;
+use ::apps::logging::Logging;
+use ::apps::loopback::Loopback;
+use ::apps::placement::Placement;
+use ::apps::posix::Posix;
+use ::apps::sim::Simulator;
+use ::apps::timer::{TimerId, TimerWheel};
+use ::apps::tracer::{Traced, Tracing};
+use ::apps::{
+    scenario::Scenario,
+    schedule::{Phase, PhaseStatistics, Rate, Rng, Schedule, SizeDistribution},
+    sga::{self, SgaPool, ZeroCopyStatistics},
+};
+use ::clap::{Arg, ArgMatches, Command};
+use ::demikernel::{demi_sgarray_t, LibOS, QDesc, QToken};
+use ::log::{info, warn};
+use ::std::time::{Duration, Instant};
+use ::std::{net::SocketAddr, str::FromStr};
+
+//==============================================================================
+// Program Arguments
+//==============================================================================
+
+/// Program Arguments
+#[derive(Debug)]
+pub struct ProgramArguments {
+    /// Local socket address.
+    local: SocketAddr,
+    /// Remote socket address.
+    remote: SocketAddr,
+    /// Buffer size (in bytes).
+    bufsize: usize,
+    /// Injection rate (in micro-seconds).
+    injection_rate: u64,
+    /// Number of back-to-back pushes per tick.
+    burst: usize,
+    /// Traffic schedule.
+    schedule: Option<Schedule>,
+    /// Push scatter-gather arrays instead of copying buffers?
+    zero_copy: bool,
+    /// Number of buffers in the pool used in zero-copy mode.
+    pool_size: usize,
+    /// Backend to run on.
+    io_backend: BackendKind,
+    /// Placement of the process.
+    placement: Placement,
+    /// Tracing of operations.
+    tracing: Tracing,
+    /// Control socket.
+    control_socket: ControlSocket,
+}
+
+/// Associate functions for Program Arguments
+impl ProgramArguments {
+    /// Default local address.
+    const DEFAULT_LOCAL: &'static str = "127.0.0.1:12345";
+
+    /// Default host address.
+    const DEFAULT_REMOTE: &'static str = "127.0.0.1:23456";
+
+    // Default buffer size.
+    const DEFAULT_BUFSIZE: usize = 1024;
+
+    // Default injection rate.
+    const DEFAULT_INJECTION_RATE: u64 = 100;
+
+    // Default burst size.
+    const DEFAULT_BURST: usize = 1;
+
+    // Default number of buffers in the pool.
+    const DEFAULT_POOL_SIZE: usize = 64;
+
+    /// Parses the program arguments from the command line `argv`.
+    pub fn new(
+        app_name: &str,
+        app_author: &str,
+        app_about: &str,
+        argv: Vec<String>,
+    ) -> Result<Self> {
+        let command: Command = Command::new(app_name)
+            .author(app_author)
+            .about(app_about)
+            .arg(
+                Arg::new("local")
+                    .long("local")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("HOST:PORT")
+                    .help("Sets local address"),
+            )
+            .arg(
+                Arg::new("remote")
+                    .long("remote")
+                    .takes_value(true)
+                    .required(true)
+                    .value_name("HOST:PORT")
+                    .help("Sets remote address"),
+            )
+            .arg(
+                Arg::new("bufsize")
+                    .long("bufsize")
+                    .takes_value(true)
+                    .required_unless_present_any(["phase", "scenario"])
+                    .value_name("SIZE")
+                    .help("Sets buffer size"),
+            )
+            .arg(
+                Arg::new("injection_rate")
+                    .long("injection_rate")
+                    .takes_value(true)
+                    .required_unless_present_any(["phase", "scenario"])
+                    .value_name("RATE")
+                    .help("Sets packet injection rate"),
+            )
+            .arg(
+                Arg::new("burst")
+                    .long("burst")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("COUNT")
+                    .help("Sets number of back-to-back pushes per tick"),
+            )
+            .arg(
+                Arg::new("phase")
+                    .long("phase")
+                    .takes_value(true)
+                    .required(false)
+                    .multiple_occurrences(true)
+                    .value_name(
+                        "duration=TIME,rate=TIME,size=SIZE,burst=COUNT,on=TIME,off=TIME|off",
+                    )
+                    .help("Appends a traffic phase to the schedule"),
+            )
+            .arg(
+                Arg::new("scenario")
+                    .long("scenario")
+                    .takes_value(true)
+                    .required(false)
+                    .conflicts_with("phase")
+                    .value_name("FILE")
+                    .help("Loads traffic phases from a TOML or YAML scenario file"),
+            )
+            .arg(
+                Arg::new("zero_copy")
+                    .long("zero_copy")
+                    .takes_value(false)
+                    .required(false)
+                    .help("Pushes buffers allocated by the libOS instead of copying them"),
+            )
+            .arg(
+                Arg::new("pool_size")
+                    .long("pool_size")
+                    .takes_value(true)
+                    .required(false)
+                    .requires("zero_copy")
+                    .value_name("COUNT")
+                    .help("Sets number of buffers cycled through in zero-copy mode"),
+            )
+            .arg(
+                Arg::new("io_backend")
+                    .long("io_backend")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("libos|posix|loopback|sim")
+                    .help("Sets backend to run on"),
+            )
+            .args(Placement::args())
+            .args(Logging::args())
+            .args(Tracing::args())
+            .args(ControlSocket::args())
+            .args(Config::args());
+        let matches: ArgMatches = Config::get_matches_from(command, argv)?;
+
+        // Default arguments.
+        let mut args: ProgramArguments = ProgramArguments {
+            local: SocketAddr::from_str(Self::DEFAULT_LOCAL)?,
+            remote: SocketAddr::from_str(Self::DEFAULT_REMOTE)?,
+            bufsize: Self::DEFAULT_BUFSIZE,
+            injection_rate: Self::DEFAULT_INJECTION_RATE,
+            burst: Self::DEFAULT_BURST,
+            schedule: None,
+            zero_copy: matches.is_present("zero_copy"),
+            pool_size: Self::DEFAULT_POOL_SIZE,
+            io_backend: BackendKind::LibOS,
+            placement: Placement::from_matches(&matches)?,
+            tracing: Tracing::from_matches(&matches)?,
+            control_socket: ControlSocket::from_matches(&matches)?,
+        };
+
+        // Local address.
+        if let Some(addr) = matches.value_of("local") {
+            args.set_local_addr(addr)?;
+        }
+
+        // Remote address.
+        if let Some(addr) = matches.value_of("remote") {
+            args.set_remote_addr(addr)?;
+        }
+
+        // Buffer size.
+        if let Some(bufsize) = matches.value_of("bufsize") {
+            args.set_bufsize(bufsize)?;
+        }
+
+        // Injection rate.
+        if let Some(injection_rate) = matches.value_of("injection_rate") {
+            args.set_injection_rate(injection_rate)?;
+        }
+
+        // Burst size.
+        if let Some(burst) = matches.value_of("burst") {
+            args.set_burst(burst)?;
+        }
+
+        // Pool size.
+        if let Some(pool_size) = matches.value_of("pool_size") {
+            args.set_pool_size(pool_size)?;
+        }
+
+        // Traffic schedule.
+        let specs: Vec<&str> = matches
+            .values_of("phase")
+            .map(|specs| specs.collect())
+            .unwrap_or_default();
+        args.set_schedule(specs, matches.value_of("scenario"))?;
+
+        // Backend.
+        if let Some(backend) = matches.value_of("io_backend") {
+            args.set_io_backend(backend)?;
+        }
+        if args.zero_copy && args.io_backend != BackendKind::LibOS {
+            bail!("zero-copy requires the libos backend");
+        }
+
+        Ok(args)
+    }
+
+    /// Returns the placement parameter stored in the target program arguments.
+    pub fn get_placement(&self) -> &Placement {
+        &self.placement
+    }
+
+    /// Returns the tracing parameter stored in the target program arguments.
+    pub fn get_tracing(&self) -> &Tracing {
+        &self.tracing
+    }
+
+    /// Returns the control socket parameter stored in the target program arguments.
+    pub fn get_control_socket(&self) -> &ControlSocket {
+        &self.control_socket
+    }
+
+    /// Returns the backend parameter stored in the target program arguments.
+    pub fn get_io_backend(&self) -> BackendKind {
+        self.io_backend
+    }
+
+    /// Returns the local endpoint address parameter stored in the target program arguments.
+    pub fn get_local(&self) -> SocketAddr {
+        self.local
+    }
+
+    /// Returns the remote endpoint address parameter stored in the target program arguments.
+    pub fn get_remote(&self) -> SocketAddr {
+        self.remote
+    }
+
+    /// Returns the traffic schedule stored in the target program arguments.
+    pub fn get_schedule(&self) -> Option<Schedule> {
+        self.schedule.clone()
+    }
+
+    /// Asserts if scatter-gather arrays should be pushed instead of copying buffers.
+    pub fn is_zero_copy(&self) -> bool {
+        self.zero_copy
+    }
+
+    /// Returns the pool size parameter stored in the target program arguments.
+    pub fn get_pool_size(&self) -> usize {
+        self.pool_size
+    }
+
+    /// Sets the local address and port number parameters in the target program arguments.
+    fn set_local_addr(&mut self, addr: &str) -> Result<()> {
+        self.local = resolve(addr)?;
+        Ok(())
+    }
+
+    /// Sets the remote address and port number parameters in the target program arguments.
+    fn set_remote_addr(&mut self, addr: &str) -> Result<()> {
+        self.remote = resolve(addr)?;
+        Ok(())
+    }
+
+    /// Sets the buffer size parameter in the target program arguments.
+    fn set_bufsize(&mut self, bufsize_str: &str) -> Result<()> {
+        let bufsize: usize = bufsize_str.parse()?;
+        if bufsize > 0 {
+            self.bufsize = bufsize;
+            Ok(())
+        } else {
+            bail!("invalid buffer size")
+        }
+    }
+
+    /// Sets the injection rate parameter in the target program arguments.
+    fn set_injection_rate(&mut self, injection_rate_str: &str) -> Result<()> {
+        let injection_rate: u64 = injection_rate_str.parse()?;
+        if injection_rate > 0 {
+            self.injection_rate = injection_rate;
+            Ok(())
+        } else {
+            bail!("invalid injection rate")
+        }
+    }
+
+    /// Sets the burst size parameter in the target program arguments.
+    fn set_burst(&mut self, burst_str: &str) -> Result<()> {
+        let burst: usize = burst_str.parse()?;
+        if burst > 0 {
+            self.burst = burst;
+            Ok(())
+        } else {
+            bail!("invalid burst size")
+        }
+    }
+
+    /// Sets the pool size parameter in the target program arguments.
+    fn set_pool_size(&mut self, pool_size_str: &str) -> Result<()> {
+        let pool_size: usize = pool_size_str.parse()?;
+        if pool_size > 0 {
+            self.pool_size = pool_size;
+            Ok(())
+        } else {
+            bail!("invalid pool size")
+        }
+    }
+
+    /// Sets the traffic schedule in the target program arguments.
+    ///
+    /// Phases come either from the command line or from a scenario file. When none is given, a
+    /// single never-ending phase is built from the buffer size, injection rate and burst size
+    /// parameters. These also provide defaults for keys missing in a phase.
+    fn set_schedule(&mut self, specs: Vec<&str>, scenario: Option<&str>) -> Result<()> {
+        let default: Phase = Phase::new(
+            None,
+            Rate::Interval(Duration::from_nanos(self.injection_rate)),
+            SizeDistribution::Fixed(self.bufsize),
+            self.burst,
+        )?;
+        let schedule: Schedule = if let Some(path) = scenario {
+            Scenario::load(path)?.to_schedule(&default)?
+        } else if specs.is_empty() {
+            Schedule::new(vec![default], true)?
+        } else {
+            let phases: Vec<Phase> = specs
+                .iter()
+                .map(|spec| Phase::parse(spec, &default))
+                .collect::<Result<Vec<Phase>>>()?;
+            Schedule::new(phases, true)?
+        };
+        self.schedule = Some(schedule);
+        Ok(())
+    }
+
+    /// Sets the backend parameter in the target program arguments.
+    fn set_io_backend(&mut self, backend_str: &str) -> Result<()> {
+        self.io_backend = backend_str.parse()?;
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Application
+//==============================================================================
+
+/// Timers of the Run Loop
+#[derive(Debug)]
+enum Timer {
+    /// Emits statistics.
+    Log,
+    /// Pushes a burst of packets.
+    Push,
+}
+
+/// Application
+struct Application<B: Backend> {
+    /// Underlying libOS.
+    libos: B,
+    // Local socket descriptor.
+    sockqd: QDesc,
+    /// Remote endpoint.
+    remote: SocketAddr,
+    /// Traffic schedule.
+    schedule: Schedule,
+    /// Push scatter-gather arrays instead of copying buffers?
+    zero_copy: bool,
+    /// Number of buffers in the pool used in zero-copy mode.
+    pool_size: usize,
+    /// Controller of the control socket, if any.
+    controller: Option<Controller>,
+}
+
+/// Associated Functions for the Application
+impl<B: Backend> Application<B> {
+    /// Logging interval (in seconds).
+    const LOG_INTERVAL: u64 = 5;
+
+    /// Granularity of timers.
+    const TIMER_GRANULARITY: Duration = Duration::from_micros(1);
+
+    /// Number of slots in the timer wheel.
+    const TIMER_SLOTS: usize = 4096;
+
+    /// Seed for drawing packet sizes, fixed so that runs are reproducible.
+    const SEED: u64 = 0x65;
+
+    /// Maximum number of pushes in flight when buffers are copied.
+    const MAX_PUSHES: usize = 1024;
+
+    /// Instantiates the application.
+    pub fn new(mut libos: B, args: &ProgramArguments) -> Result<Self> {
+        // Extract arguments.
+        let local: SocketAddr = args.get_local();
+        let remote: SocketAddr = args.get_remote();
+        let schedule: Schedule = match args.get_schedule() {
+            Some(schedule) => schedule,
+            None => bail!("missing traffic schedule"),
+        };
+
+        // Create UDP socket.
+        let sockqd: QDesc = match libos.socket(domain(&local), libc::SOCK_DGRAM, 1) {
+            Ok(qd) => qd,
+            Err(e) => panic!("failed to create socket: {:?}", e.cause),
+        };
+
+        // Bind to local address.
+        match libos.bind(sockqd, local) {
+            Ok(()) => (),
+            Err(e) => panic!("failed to bind socket: {:?}", e.cause),
+        };
+
+        info!("Local Address:  {:?}", local);
+        info!("Remote Address: {:?}", remote);
+
+        for (i, phase) in schedule.get_phases().iter().enumerate() {
+            info!("Phase {}: {}", i, phase);
+        }
+
+        Ok(Self {
+            libos,
+            sockqd,
+            remote,
+            schedule,
+            zero_copy: args.is_zero_copy(),
+            pool_size: args.get_pool_size(),
+            controller: None,
+        })
+    }
+
+    /// Makes the target application apply commands of a control socket.
+    pub fn set_controller(&mut self, controller: Option<Controller>) {
+        self.controller = controller;
+    }
+
+    /// Runs the target application.
+    pub fn run(&mut self) -> Result<()> {
+        let clock: Clock = self.libos.clock();
+        let start: Instant = clock.now();
+        let mut nbytes: usize = 0;
+        let mut since: Instant = start;
+        let mut timers: TimerWheel<Timer> =
+            TimerWheel::new(Self::TIMER_GRANULARITY, Self::TIMER_SLOTS, start);
+        timers.schedule(start + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+        let data: Vec<u8> = Self::mkbuf(self.schedule.get_max_bufsize(), 0x65);
+        let mut pushes: Vec<QToken> = Vec::new();
+        let mut bufs: Vec<Option<usize>> = Vec::new();
+        let mut rng: Rng = Rng::new(Self::SEED);
+        let mut stats: PhaseStatistics = PhaseStatistics::new(&self.schedule, start);
+        let mut zc_stats: ZeroCopyStatistics = ZeroCopyStatistics::default();
+        let mut pool: Option<SgaPool> = if self.zero_copy {
+            let len: usize = self.schedule.get_max_bufsize();
+            Some(SgaPool::new(
+                sga::libos(&mut self.libos),
+                self.pool_size,
+                len,
+                0x65,
+            ))
+        } else {
+            None
+        };
+
+        self.schedule.restart(start);
+        let mut current: usize = 0;
+        let mut push: TimerId = timers.schedule(start, Timer::Push);
+        'run: loop {
+            let now: Instant = clock.now();
+
+            // Apply commands of the control socket.
+            if let Some(command) = self.controller.as_ref().and_then(Controller::poll) {
+                match command.get_request().clone() {
+                    Request::Stats => {
+                        let (label, phase_nbytes, phase_elapsed): (&str, usize, Duration) =
+                            stats.current(now);
+                        command.reply(&format!(
+                            "bytes: {}\nelapsed_us: {}\nphase: {}\nphase_bytes: {}\n\
+                             phase_elapsed_us: {}",
+                            nbytes,
+                            (now - since).as_micros(),
+                            label,
+                            phase_nbytes,
+                            phase_elapsed.as_micros()
+                        ));
+                    }
+                    // Override the rate of all phases, and push right away.
+                    Request::SetRate(rate) => {
+                        self.schedule.set_rate(rate);
+                        timers.cancel(push);
+                        push = timers.schedule(now, Timer::Push);
+                        info!("rate set to {}", rate);
+                        command.reply("ok");
+                    }
+                    Request::ResetCounters => {
+                        nbytes = 0;
+                        since = now;
+                        stats.reset(now);
+                        command.reply("ok");
+                    }
+                    Request::Shutdown => {
+                        command.reply("ok");
+                        break;
+                    }
+                    _ => command.unsupported(),
+                }
+            }
+
+            // Get the current phase, and push right away when it changes.
+            let phase: &Phase = match self.schedule.poll(now) {
+                Some((i, phase)) => {
+                    if i != current {
+                        current = i;
+                        timers.cancel(push);
+                        push = timers.schedule(now, Timer::Push);
+                    }
+                    stats.switch(i, now);
+                    phase
+                }
+                None => break,
+            };
+
+            for timer in timers.expire(now) {
+                match timer {
+                    // Dump statistics.
+                    Timer::Log => {
+                        let elapsed: Duration = now - since;
+                        let (label, phase_nbytes, phase_elapsed): (&str, usize, Duration) =
+                            stats.current(now);
+                        info!(
+                            "[{}] {:?} B / {:?} us ({:?} B / {:?} us in phase)",
+                            label,
+                            nbytes,
+                            elapsed.as_micros(),
+                            phase_nbytes,
+                            phase_elapsed.as_micros()
+                        );
+                        if let Some(pool) = &pool {
+                            info!("  {} ({})", zc_stats, pool);
+                        }
+                        timers.schedule(now + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+                    }
+                    // Push a burst of packets, and pace the next one.
+                    Timer::Push => {
+                        let interval: Duration = match phase.get_interval() {
+                            Some(interval) => interval,
+                            // Nothing to push in this phase, check again later.
+                            None => {
+                                push = timers.schedule(now + Self::TIMER_GRANULARITY, Timer::Push);
+                                continue;
+                            }
+                        };
+                        push = timers.schedule(now + interval, Timer::Push);
+                        let mut pushed: usize = 0;
+                        for _ in 0..phase.get_burst() {
+                            let bufsize: usize = phase.get_sizes().sample(&mut rng);
+                            let (result, index) = match pool.as_mut() {
+                                Some(pool) => {
+                                    // Wait for a push to give its buffer back if the pool ran
+                                    // dry.
+                                    let (i, buf): (usize, demi_sgarray_t) = match pool.get() {
+                                        Some(entry) => entry,
+                                        None => {
+                                            if !Self::reap(
+                                                &mut self.libos,
+                                                &mut pushes,
+                                                &mut bufs,
+                                                Some(pool),
+                                            ) {
+                                                break 'run;
+                                            }
+                                            pool.get().expect("empty buffer pool")
+                                        }
+                                    };
+                                    zc_stats.record(bufsize);
+                                    let buf: demi_sgarray_t = sga::sga_truncate(&buf, bufsize);
+                                    (
+                                        sga::pushto(
+                                            &mut self.libos,
+                                            self.sockqd,
+                                            &buf,
+                                            self.remote,
+                                        ),
+                                        Some(i),
+                                    )
+                                }
+                                None => {
+                                    if pushes.len() >= Self::MAX_PUSHES
+                                        && !Self::reap(
+                                            &mut self.libos,
+                                            &mut pushes,
+                                            &mut bufs,
+                                            None,
+                                        )
+                                    {
+                                        break 'run;
+                                    }
+                                    (
+                                        self.libos.pushto(
+                                            self.sockqd,
+                                            &data[..bufsize],
+                                            self.remote,
+                                        ),
+                                        None,
+                                    )
+                                }
+                            };
+                            let qt: QToken = match result {
+                                Ok(qt) => qt,
+                                Err(e) => panic!("failed to push data to socket: {:?}", e.cause),
+                            };
+                            pushes.push(qt);
+                            bufs.push(index);
+                            pushed += bufsize;
+                        }
+                        nbytes += pushed;
+                        stats.record(pushed);
+                    }
+                }
+            }
+
+            // Reap a push that completed, recycling its buffer.
+            if !pushes.is_empty()
+                && !Self::reap(&mut self.libos, &mut pushes, &mut bufs, pool.as_mut())
+            {
+                break;
+            }
+        }
+
+        while !pushes.is_empty() {
+            Self::reap(&mut self.libos, &mut pushes, &mut bufs, pool.as_mut());
+        }
+        stats.dump(clock.now());
+        if let Some(pool) = pool {
+            info!("{} ({})", zc_stats, pool);
+            pool.release(sga::libos(&mut self.libos));
+        }
+        // Close the socket.
+        if let Err(e) = self.libos.close(self.sockqd) {
+            warn!("failed to close socket: {:?}", e.cause);
+        }
+
+        self.libos.status()
+    }
+
+    /// Waits for any pending push to complete, giving its buffer back to the pool. Returns `false`
+    /// if the backend shut down, after which no push is pending anymore.
+    fn reap(
+        libos: &mut B,
+        pushes: &mut Vec<QToken>,
+        bufs: &mut Vec<Option<usize>>,
+        pool: Option<&mut SgaPool>,
+    ) -> bool {
+        let i: usize = match libos.wait_any(pushes) {
+            Ok((i, _, OperationResult::Push)) => i,
+            Err(e) if e.is_shutdown() => {
+                pushes.clear();
+                bufs.clear();
+                return false;
+            }
+            Err(e) => panic!("operation failed: {:?}", e.cause),
+            _ => panic!("unexpected result"),
+        };
+        pushes.swap_remove(i);
+        if let (Some(pool), Some(buf)) = (pool, bufs.swap_remove(i)) {
+            pool.put(buf);
+        }
+        true
+    }
+
+    /// Makes a buffer.
+    fn mkbuf(bufsize: usize, fill_char: u8) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::<u8>::with_capacity(bufsize);
+
+        for _ in 0..bufsize {
+            data.push(fill_char);
+        }
+
+        data
+    }
+}
+
+//==============================================================================
+
+/// Runs the application over a backend of type `B`.
+fn start<B: Backend>(args: ProgramArguments) -> Result<()> {
+    let libos: B = B::create()?;
+
+    let mut app: Application<B> = Application::new(libos, &args)?;
+    app.set_controller(args.get_control_socket().start()?);
+    app.run()
+}
+
+/// Describes the application.
+pub const ABOUT: &str = "Generates UDP traffic.";
+
+/// Runs the application with the given command line.
+pub fn main(argv: Vec<String>) -> Result<()> {
+    let args: ProgramArguments = ProgramArguments::new(
+        "udp-pktgen",
+        "Pedro Henrique Penna <ppenna@microsoft.com>",
+        ABOUT,
+        argv,
+    )?;
+
+    args.get_placement().apply()?;
+    args.get_tracing().start()?;
+
+    match args.get_io_backend() {
+        BackendKind::LibOS => start::<Traced<LibOS>>(args),
+        BackendKind::Posix => start::<Traced<Posix>>(args),
+        BackendKind::Loopback => start::<Traced<Loopback>>(args),
+        BackendKind::Simulator => start::<Traced<Simulator>>(args),
+    }
+}
@@ -0,0 +1,1630 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Result};
+use ::apps::backend::{domain, Backend, BackendKind, OperationResult};
+use ::apps::balancer::{Balancer, Policy};
+use ::apps::clock::Clock;
+use ::apps::config::Config;
+use ::apps::control::{ControlSocket, Controller, Controllers, Request};
+use ::apps::dashboard::{Dashboard, Sample};
+use ::apps::endpoint::resolve;
+use ::apps::event;
+use ::apps::fanout::{Control, Destination, FanOut};
+use ::apps::impairment::{DelayQueue, Impairer, Impairments};
+use ::apps::logging::Logging;
+use ::apps::loopback::Loopback;
+use ::apps::placement::Placement;
+use ::apps::posix::Posix;
+use ::apps::rules::{Direction, RuleSet, Verdict};
+use ::apps::session::{Session, SessionTable};
+use ::apps::sim::Simulator;
+use ::apps::timer::TimerWheel;
+use ::apps::tracer::{Traced, Tracing};
+use ::apps::workers::{self, WorkerStatistics, Workers};
+use ::clap::{Arg, ArgMatches, Command};
+use ::demikernel::{LibOS, QDesc, QToken};
+use ::log::{debug, error, info, warn, Level};
+use ::std::borrow::Cow;
+use ::std::net::SocketAddr;
+use ::std::str::FromStr;
+use ::std::sync::atomic::{AtomicUsize, Ordering};
+use ::std::sync::Arc;
+use ::std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+//==============================================================================
+// Program Arguments
+//==============================================================================
+
+/// Program Arguments
+#[derive(Clone, Debug)]
+pub struct ProgramArguments {
+    /// Local socket address.
+    local: SocketAddr,
+    /// Backend socket addresses.
+    backends: Vec<SocketAddr>,
+    /// Backend selection policy.
+    policy: Policy,
+    /// Time without replies after which a backend is deemed unhealthy (in seconds).
+    health_timeout: u64,
+    /// Idle time after which a session expires (in seconds).
+    session_timeout: u64,
+    /// Maximum number of concurrent sessions.
+    max_sessions: usize,
+    /// Range of local ports for upstream sockets (inclusive).
+    session_ports: (u16, u16),
+    /// Impairments applied to traffic from clients to backends.
+    impair_up: Impairments,
+    /// Impairments applied to traffic from backends to clients.
+    impair_down: Impairments,
+    /// Replicate datagrams to every backend?
+    fanout: bool,
+    /// Maximum number of copies in flight per fan-out destination.
+    fanout_window: usize,
+    /// Address for fan-out control messages.
+    control: Option<SocketAddr>,
+    /// Path to the rules file.
+    rules: Option<String>,
+    /// Number of workers.
+    workers: usize,
+    /// Backend to run on.
+    io_backend: BackendKind,
+    /// Placement of the process.
+    placement: Placement,
+    /// Tracing of operations.
+    tracing: Tracing,
+    /// Show a live dashboard?
+    tui: bool,
+    /// Control socket.
+    control_socket: ControlSocket,
+}
+
+/// Associate functions for Program Arguments
+impl ProgramArguments {
+    /// Default local address.
+    const DEFAULT_LOCAL: &'static str = "127.0.0.1:12345";
+
+    /// Default host address.
+    const DEFAULT_REMOTE: &'static str = "127.0.0.1:23456";
+
+    /// Default maximum number of copies in flight per fan-out destination.
+    const DEFAULT_FANOUT_WINDOW: usize = 64;
+
+    /// Default health timeout (in seconds). Health tracking is opt-in, since backends that never
+    /// reply, such as sinks, would be deemed unhealthy.
+    const DEFAULT_HEALTH_TIMEOUT: u64 = 0;
+
+    /// Default session timeout (in seconds).
+    const DEFAULT_SESSION_TIMEOUT: u64 = 60;
+
+    /// Default maximum number of concurrent sessions.
+    const DEFAULT_MAX_SESSIONS: usize = 1024;
+
+    /// Default range of local ports for upstream sockets.
+    const DEFAULT_SESSION_PORTS: (u16, u16) = (49152, 65535);
+
+    /// Parses the program arguments from the command line `argv`.
+    pub fn new(
+        app_name: &str,
+        app_author: &str,
+        app_about: &str,
+        argv: Vec<String>,
+    ) -> Result<Self> {
+        let command: Command = Command::new(app_name)
+            .author(app_author)
+            .about(app_about)
+            .arg(
+                Arg::new("local")
+                    .long("local")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("HOST:PORT")
+                    .help("Sets local address"),
+            )
+            .arg(
+                Arg::new("remote")
+                    .long("remote")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("HOST:PORT")
+                    .help("Sets remote address"),
+            )
+            .arg(
+                Arg::new("backend")
+                    .long("backend")
+                    .takes_value(true)
+                    .required(false)
+                    .multiple_occurrences(true)
+                    .value_name("HOST:PORT")
+                    .help("Adds the address of a backend server"),
+            )
+            .arg(
+                Arg::new("policy")
+                    .long("policy")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("round-robin|random|least-loaded|source-hash")
+                    .help("Sets backend selection policy"),
+            )
+            .arg(
+                Arg::new("health_timeout")
+                    .long("health_timeout")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("SECONDS")
+                    .help(
+                        "Sets time without replies after which a backend is skipped (default: off)",
+                    ),
+            )
+            .arg(
+                Arg::new("session_timeout")
+                    .long("session_timeout")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("SECONDS")
+                    .help("Sets idle time after which a session expires"),
+            )
+            .arg(
+                Arg::new("max_sessions")
+                    .long("max_sessions")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("COUNT")
+                    .help("Sets maximum number of concurrent sessions"),
+            )
+            .arg(
+                Arg::new("session_ports")
+                    .long("session_ports")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("FIRST-LAST")
+                    .help("Sets range of local ports for upstream sockets"),
+            )
+            .arg(
+                Arg::new("impair_up")
+                    .long("impair_up")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("SPEC")
+                    .help("Impairs traffic to backends (e.g. loss=1%,delay=10ms,jitter=2ms)"),
+            )
+            .arg(
+                Arg::new("impair_down")
+                    .long("impair_down")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("SPEC")
+                    .help("Impairs traffic to clients (e.g. ge=1%/10%,dup=1%,rate=10Mbps)"),
+            )
+            .arg(
+                Arg::new("rules")
+                    .long("rules")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("FILE")
+                    .help("Sets rules file (TOML or YAML), reloaded on SIGHUP"),
+            )
+            .arg(
+                Arg::new("fanout")
+                    .long("fanout")
+                    .takes_value(false)
+                    .required(false)
+                    .help("Replicates every datagram to all backends"),
+            )
+            .arg(
+                Arg::new("fanout_window")
+                    .long("fanout_window")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("COUNT")
+                    .help("Sets maximum number of copies in flight per fan-out destination"),
+            )
+            .arg(
+                Arg::new("control")
+                    .long("control")
+                    .takes_value(true)
+                    .required(false)
+                    .requires("fanout")
+                    .value_name("HOST:PORT")
+                    .help("Sets address for consumers to subscribe or unsubscribe in fan-out mode"),
+            )
+            .arg(
+                Arg::new("workers")
+                    .long("workers")
+                    .takes_value(true)
+                    .required(false)
+                    .conflicts_with("fanout")
+                    .value_name("COUNT")
+                    .help(
+                        "Sets number of worker threads, which share the port on the POSIX backend \
+                         and listen on consecutive ports on the libOS",
+                    ),
+            )
+            .arg(
+                Arg::new("io_backend")
+                    .long("io_backend")
+                    .takes_value(true)
+                    .required(false)
+                    .value_name("libos|posix|loopback|sim")
+                    .help("Sets backend to run on"),
+            )
+            .args(Placement::args())
+            .args(Logging::args())
+            .args(Tracing::args())
+            .args(Dashboard::args())
+            .args(ControlSocket::args())
+            .args(Config::args());
+        let matches: ArgMatches = Config::get_matches_from(command, argv)?;
+
+        // Default arguments.
+        let mut args: ProgramArguments = ProgramArguments {
+            local: SocketAddr::from_str(Self::DEFAULT_LOCAL)?,
+            backends: Vec::new(),
+            policy: Policy::RoundRobin,
+            health_timeout: Self::DEFAULT_HEALTH_TIMEOUT,
+            session_timeout: Self::DEFAULT_SESSION_TIMEOUT,
+            max_sessions: Self::DEFAULT_MAX_SESSIONS,
+            session_ports: Self::DEFAULT_SESSION_PORTS,
+            impair_up: Impairments::default(),
+            impair_down: Impairments::default(),
+            fanout: false,
+            fanout_window: Self::DEFAULT_FANOUT_WINDOW,
+            control: None,
+            rules: None,
+            workers: 1,
+            io_backend: BackendKind::LibOS,
+            placement: Placement::from_matches(&matches)?,
+            tracing: Tracing::from_matches(&matches)?,
+            tui: matches.is_present("tui"),
+            control_socket: ControlSocket::from_matches(&matches)?,
+        };
+
+        // Local address.
+        if let Some(addr) = matches.value_of("local") {
+            args.set_local_addr(addr)?;
+        }
+
+        // Rules file.
+        if let Some(rules) = matches.value_of("rules") {
+            args.rules = Some(rules.to_string());
+        }
+
+        // Fan-out mode.
+        if matches.is_present("fanout") {
+            args.fanout = true;
+        }
+        if let Some(fanout_window) = matches.value_of("fanout_window") {
+            args.set_fanout_window(fanout_window)?;
+        }
+        if let Some(addr) = matches.value_of("control") {
+            args.control = Some(resolve(addr)?);
+        }
+
+        // Remote address.
+        if let Some(addr) = matches.value_of("remote") {
+            args.add_backend_addr(addr)?;
+        }
+
+        // Backend addresses.
+        if let Some(addrs) = matches.values_of("backend") {
+            for addr in addrs {
+                args.add_backend_addr(addr)?;
+            }
+        }
+        // In fan-out mode, consumers may also join later on.
+        if args.backends.is_empty() {
+            if !args.fanout {
+                args.add_backend_addr(Self::DEFAULT_REMOTE)?;
+            } else if args.control.is_none() {
+                bail!("fan-out mode needs backends or a control address")
+            }
+        }
+
+        // Backend selection policy.
+        if let Some(policy) = matches.value_of("policy") {
+            args.set_policy(policy)?;
+        }
+
+        // Health timeout.
+        if let Some(health_timeout) = matches.value_of("health_timeout") {
+            args.set_health_timeout(health_timeout)?;
+        }
+
+        // Session timeout.
+        if let Some(session_timeout) = matches.value_of("session_timeout") {
+            args.set_session_timeout(session_timeout)?;
+        }
+
+        // Maximum number of sessions.
+        if let Some(max_sessions) = matches.value_of("max_sessions") {
+            args.set_max_sessions(max_sessions)?;
+        }
+
+        // Session ports.
+        if let Some(session_ports) = matches.value_of("session_ports") {
+            args.set_session_ports(session_ports)?;
+        }
+
+        // Number of workers.
+        if let Some(workers) = matches.value_of("workers") {
+            args.set_workers(workers)?;
+        }
+
+        // Impairments.
+        if let Some(spec) = matches.value_of("impair_up") {
+            args.impair_up = spec.parse()?;
+        }
+        if let Some(spec) = matches.value_of("impair_down") {
+            args.impair_down = spec.parse()?;
+        }
+
+        // Backend.
+        if let Some(backend) = matches.value_of("io_backend") {
+            args.set_io_backend(backend)?;
+        }
+
+        Ok(args)
+    }
+
+    /// Returns the placement parameter stored in the target program arguments.
+    pub fn get_placement(&self) -> &Placement {
+        &self.placement
+    }
+
+    /// Returns the tracing parameter stored in the target program arguments.
+    pub fn get_tracing(&self) -> &Tracing {
+        &self.tracing
+    }
+
+    /// Returns the dashboard parameter stored in the target program arguments.
+    pub fn is_tui(&self) -> bool {
+        self.tui
+    }
+
+    /// Returns the control socket parameter stored in the target program arguments.
+    pub fn get_control_socket(&self) -> &ControlSocket {
+        &self.control_socket
+    }
+
+    /// Returns the backend parameter stored in the target program arguments.
+    pub fn get_io_backend(&self) -> BackendKind {
+        self.io_backend
+    }
+
+    /// Returns the local endpoint address parameter stored in the target program arguments.
+    pub fn get_local(&self) -> SocketAddr {
+        self.local
+    }
+
+    /// Returns the backend addresses parameter stored in the target program arguments.
+    pub fn get_backends(&self) -> &[SocketAddr] {
+        &self.backends
+    }
+
+    /// Returns the backend selection policy parameter stored in the target program arguments.
+    pub fn get_policy(&self) -> Policy {
+        self.policy
+    }
+
+    /// Returns the health timeout parameter stored in the target program arguments.
+    pub fn get_health_timeout(&self) -> u64 {
+        self.health_timeout
+    }
+
+    /// Returns the session timeout parameter stored in the target program arguments.
+    pub fn get_session_timeout(&self) -> u64 {
+        self.session_timeout
+    }
+
+    /// Returns the maximum number of sessions parameter stored in the target program arguments.
+    pub fn get_max_sessions(&self) -> usize {
+        self.max_sessions
+    }
+
+    /// Returns the number of workers parameter stored in the target program arguments.
+    pub fn get_workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Returns the session ports parameter stored in the target program arguments.
+    pub fn get_session_ports(&self) -> (u16, u16) {
+        self.session_ports
+    }
+
+    /// Returns the upstream impairments parameter stored in the target program arguments.
+    pub fn get_impair_up(&self) -> &Impairments {
+        &self.impair_up
+    }
+
+    /// Returns the downstream impairments parameter stored in the target program arguments.
+    pub fn get_impair_down(&self) -> &Impairments {
+        &self.impair_down
+    }
+
+    /// Returns the rules file parameter stored in the target program arguments.
+    pub fn get_rules(&self) -> Option<&str> {
+        self.rules.as_deref()
+    }
+
+    /// Asserts if the fan-out mode is enabled in the target program arguments.
+    pub fn is_fanout(&self) -> bool {
+        self.fanout
+    }
+
+    /// Returns the fan-out window parameter stored in the target program arguments.
+    pub fn get_fanout_window(&self) -> usize {
+        self.fanout_window
+    }
+
+    /// Returns the control address parameter stored in the target program arguments.
+    pub fn get_control(&self) -> Option<SocketAddr> {
+        self.control
+    }
+
+    /// Sets the local address and port number parameters in the target program arguments.
+    fn set_local_addr(&mut self, addr: &str) -> Result<()> {
+        self.local = resolve(addr)?;
+        Ok(())
+    }
+
+    /// Adds a backend address and port number to the target program arguments.
+    fn add_backend_addr(&mut self, addr: &str) -> Result<()> {
+        let addr: SocketAddr = resolve(addr)?;
+        if self.backends.contains(&addr) {
+            bail!("duplicate backend address")
+        }
+        self.backends.push(addr);
+        Ok(())
+    }
+
+    /// Sets the backend selection policy parameter in the target program arguments.
+    fn set_policy(&mut self, policy_str: &str) -> Result<()> {
+        self.policy = policy_str.parse()?;
+        Ok(())
+    }
+
+    /// Sets the health timeout parameter in the target program arguments.
+    fn set_health_timeout(&mut self, health_timeout_str: &str) -> Result<()> {
+        self.health_timeout = health_timeout_str.parse()?;
+        Ok(())
+    }
+
+    /// Sets the session timeout parameter in the target program arguments.
+    fn set_session_timeout(&mut self, session_timeout_str: &str) -> Result<()> {
+        let session_timeout: u64 = session_timeout_str.parse()?;
+        if session_timeout > 0 {
+            self.session_timeout = session_timeout;
+            Ok(())
+        } else {
+            bail!("invalid session timeout")
+        }
+    }
+
+    /// Sets the maximum number of sessions parameter in the target program arguments.
+    fn set_max_sessions(&mut self, max_sessions_str: &str) -> Result<()> {
+        let max_sessions: usize = max_sessions_str.parse()?;
+        if max_sessions > 0 {
+            self.max_sessions = max_sessions;
+            Ok(())
+        } else {
+            bail!("invalid maximum number of sessions")
+        }
+    }
+
+    /// Sets the fan-out window parameter in the target program arguments.
+    fn set_fanout_window(&mut self, fanout_window_str: &str) -> Result<()> {
+        let fanout_window: usize = fanout_window_str.parse()?;
+        if fanout_window > 0 {
+            self.fanout_window = fanout_window;
+            Ok(())
+        } else {
+            bail!("invalid fan-out window")
+        }
+    }
+
+    /// Sets the number of workers parameter in the target program arguments.
+    fn set_workers(&mut self, workers_str: &str) -> Result<()> {
+        let workers: usize = workers_str.parse()?;
+        if workers > 0 {
+            self.workers = workers;
+            Ok(())
+        } else {
+            bail!("invalid number of workers")
+        }
+    }
+
+    /// Sets the session ports parameter in the target program arguments.
+    fn set_session_ports(&mut self, session_ports_str: &str) -> Result<()> {
+        let (first, last): (u16, u16) = match session_ports_str.split_once('-') {
+            Some((first, last)) => (first.parse()?, last.parse()?),
+            None => bail!("invalid port range"),
+        };
+        if first > 0 && first <= last {
+            self.session_ports = (first, last);
+            Ok(())
+        } else {
+            bail!("invalid port range")
+        }
+    }
+
+    /// Sets the backend parameter in the target program arguments.
+    fn set_io_backend(&mut self, backend_str: &str) -> Result<()> {
+        self.io_backend = backend_str.parse()?;
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Application
+//==============================================================================
+
+/// Timers of the Run Loop
+#[derive(Debug)]
+enum Timer {
+    /// Emits statistics.
+    Log,
+    /// Expires idle sessions.
+    Expire,
+}
+
+/// Application
+struct Application<B: Backend> {
+    /// Underlying libOS.
+    libos: B,
+    // Local socket descriptor.
+    sockqd: QDesc,
+    /// Local endpoint.
+    local: SocketAddr,
+    /// Backends.
+    balancer: Balancer,
+    /// Sessions of clients.
+    sessions: SessionTable,
+    /// Impairer of traffic from clients to backends.
+    impair_up: Impairer,
+    /// Impairer of traffic from backends to clients.
+    impair_down: Impairer,
+    /// Delayed datagrams, along with the socket and address they should be sent through and to.
+    delayed: DelayQueue<(QDesc, SocketAddr, Vec<u8>)>,
+    /// Filtering and rewriting rules.
+    rules: RuleSet,
+    /// Path to the rules file.
+    rules_path: Option<String>,
+    /// Number of reload requests served.
+    nreloads: usize,
+    /// Statistics published when running as a worker.
+    stats: Option<Arc<WorkerStatistics>>,
+    /// Clock read in the run loop.
+    clock: Clock,
+    /// Controller of the control socket, if any.
+    controller: Option<Controller>,
+    /// Live dashboard, if any.
+    dashboard: Option<Dashboard>,
+}
+
+/// Associated Functions for the Application
+impl<B: Backend> Application<B> {
+    /// Logging interval (in seconds).
+    const LOG_INTERVAL: u64 = 5;
+
+    /// Granularity of timers.
+    const TIMER_GRANULARITY: Duration = Duration::from_millis(1);
+
+    /// Number of slots in the timer wheel.
+    const TIMER_SLOTS: usize = 8192;
+
+    /// Instantiates the application.
+    pub fn new(mut libos: B, args: &ProgramArguments) -> Result<Self> {
+        // Extract arguments.
+        let local: SocketAddr = args.get_local();
+        let health_timeout: Option<Duration> = match args.get_health_timeout() {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+        let balancer: Balancer =
+            Balancer::new(args.get_backends(), args.get_policy(), health_timeout)?;
+        let sessions: SessionTable = SessionTable::new(
+            args.get_session_ports(),
+            args.get_max_sessions(),
+            Duration::from_secs(args.get_session_timeout()),
+        )?;
+        let seed: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_nanos() as u64)
+            .unwrap_or(0);
+        let impair_up: Impairer = Impairer::new(args.get_impair_up().clone(), seed);
+        let impair_down: Impairer = Impairer::new(args.get_impair_down().clone(), !seed);
+        if (args.get_impair_up().delays() || args.get_impair_down().delays())
+            && !B::has_timed_waits()
+        {
+            bail!("delaying impairments need timed waits, which the backend does not offer")
+        }
+
+        // Create UDP socket.
+        let sockqd: QDesc = match libos.socket(domain(&local), libc::SOCK_DGRAM, 0) {
+            Ok(qd) => qd,
+            Err(e) => panic!("failed to create socket: {:?}", e.cause),
+        };
+
+        // Bind to local address.
+        match workers::bind(&mut libos, sockqd, local, args.get_workers()) {
+            Ok(()) => (),
+            Err(e) => panic!("failed to bind socket: {:?}", e.cause),
+        };
+
+        info!("Local Address:  {:?}", local);
+        for backend in args.get_backends() {
+            info!("Backend Address: {:?}", backend);
+        }
+        info!("Backend Policy: {}", args.get_policy());
+        info!("Upstream Impairments: {}", args.get_impair_up());
+        info!("Downstream Impairments: {}", args.get_impair_down());
+
+        // Load rules and reload them on SIGHUP.
+        let rules: RuleSet = match args.get_rules() {
+            Some(path) => {
+                let rules: RuleSet = RuleSet::load(path)?;
+                info!("Rules File: {}", path);
+                for rule in rules.get_rules() {
+                    info!("  {}", rule);
+                }
+                unsafe { libc::signal(libc::SIGHUP, on_sighup as *const () as libc::sighandler_t) };
+                rules
+            }
+            None => RuleSet::default(),
+        };
+
+        let clock: Clock = libos.clock();
+        Ok(Self {
+            libos,
+            sockqd,
+            local,
+            balancer,
+            sessions,
+            impair_up,
+            impair_down,
+            delayed: DelayQueue::new(),
+            rules,
+            rules_path: args.get_rules().map(str::to_string),
+            nreloads: RELOADS.load(Ordering::Relaxed),
+            stats: None,
+            clock,
+            controller: None,
+            dashboard: None,
+        })
+    }
+
+    /// Makes the target application publish its statistics instead of dumping them.
+    pub fn set_worker_statistics(&mut self, stats: Arc<WorkerStatistics>) {
+        self.stats = Some(stats);
+    }
+
+    /// Makes the target application apply commands of a control socket.
+    pub fn set_controller(&mut self, controller: Option<Controller>) {
+        self.controller = controller;
+    }
+
+    /// Makes the target application show a live dashboard instead of dumping statistics.
+    pub fn set_dashboard(&mut self, dashboard: Option<Dashboard>) {
+        self.dashboard = dashboard;
+    }
+
+    /// Runs the target relay server.
+    ///
+    /// Datagrams of each client are relayed to a backend through an upstream socket that is
+    /// dedicated to the client, and replies of the backend are relayed back to the client. A
+    /// client sticks to the same backend for the lifetime of its session, unless the backend
+    /// becomes unhealthy.
+    ///
+    /// While impaired datagrams are held back, waits time out when the next one is due, so that
+    /// it is released on time.
+    pub fn run(&mut self) -> Result<()> {
+        let start: Instant = self.clock.now();
+        let mut nbytes: usize = 0;
+        let mut ndropped: usize = 0;
+        let mut nfiltered: usize = 0;
+        let mut since: Instant = start;
+        let mut last: Instant = start;
+        let mut sample: Sample = Sample::default();
+        let mut qtokens: Vec<QToken> = Vec::new();
+        let mut timers: TimerWheel<Timer> =
+            TimerWheel::new(Self::TIMER_GRANULARITY, Self::TIMER_SLOTS, start);
+        timers.schedule(
+            start + Duration::from_secs(Self::LOG_INTERVAL),
+            Timer::Expire,
+        );
+        if self.stats.is_none() {
+            timers.schedule(start + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+        }
+
+        // Pop first packet.
+        let qt: QToken = match self.libos.pop(self.sockqd) {
+            Ok(qt) => qt,
+            Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+        };
+        qtokens.push(qt);
+
+        loop {
+            // Publish statistics when running as a worker.
+            if let Some(stats) = &self.stats {
+                stats.set_nbytes(nbytes);
+            }
+
+            let now: Instant = self.clock.now();
+
+            // Apply commands of the control socket.
+            if let Some(command) = self.controller.as_ref().and_then(Controller::poll) {
+                match command.get_request().clone() {
+                    Request::Stats => {
+                        command.reply(&format!(
+                            "bytes: {}\nelapsed_us: {}\nsessions: {}\ndropped: {}\nfiltered: {}",
+                            nbytes,
+                            (now - since).as_micros(),
+                            self.sessions.len(),
+                            ndropped,
+                            nfiltered
+                        ));
+                    }
+                    Request::Connections => {
+                        let lines: Vec<String> = self
+                            .sessions
+                            .iter()
+                            .map(|s| {
+                                format!(
+                                    "{} -> {} (qd {}): {:?} B up, {:?} B down",
+                                    s.get_client(),
+                                    self.balancer.get_addr(s.get_backend()),
+                                    u32::from(s.get_upstream()),
+                                    s.get_nbytes_up(),
+                                    s.get_nbytes_down()
+                                )
+                            })
+                            .collect();
+                        command.reply(&lines.join("\n"));
+                    }
+                    Request::ResetCounters => {
+                        nbytes = 0;
+                        ndropped = 0;
+                        nfiltered = 0;
+                        since = now;
+                        command.reply("ok");
+                    }
+                    Request::Shutdown => {
+                        command.reply("ok");
+                        break;
+                    }
+                    _ => command.unsupported(),
+                }
+            }
+
+            for timer in timers.expire(now) {
+                let interval: Duration = Duration::from_secs(Self::LOG_INTERVAL);
+                match timer {
+                    // Refresh the dashboard.
+                    Timer::Log if self.dashboard.is_some() => {
+                        if let Some(dashboard) = &self.dashboard {
+                            sample.set_nconnections(self.sessions.len());
+                            dashboard.update(sample.close(now - last));
+                            last = now;
+                        }
+                        timers.schedule(now + interval, Timer::Log);
+                    }
+                    // Dump statistics.
+                    Timer::Log => {
+                        self.dump_statistics(since, nbytes, ndropped, nfiltered);
+                        timers.schedule(now + interval, Timer::Log);
+                    }
+                    // Expire idle sessions.
+                    Timer::Expire => {
+                        self.expire_sessions(&mut qtokens);
+                        timers.schedule(now + interval, Timer::Expire);
+                    }
+                }
+            }
+
+            let nreloads: usize = RELOADS.load(Ordering::Relaxed);
+            if nreloads != self.nreloads {
+                self.nreloads = nreloads;
+                self.reload_rules();
+            }
+            self.balancer.check_health(now);
+            self.release_delayed(&mut qtokens);
+            // Wake up when the next datagram is due, or in time to refresh the dashboard.
+            let deadline: Option<Instant> = match (&self.dashboard, timers.next_deadline()) {
+                (Some(_), Some(refresh)) => Some(
+                    self.delayed
+                        .next_deadline()
+                        .map_or(refresh, |deadline| deadline.min(refresh)),
+                ),
+                _ => self.delayed.next_deadline(),
+            };
+            let timeout: Option<Duration> =
+                deadline.map(|deadline| deadline.saturating_duration_since(self.clock.now()));
+            let timeout: Option<Duration> =
+                Controller::timeout::<B>(self.controller.as_ref(), timeout);
+
+            // TODO: add type annotation to the following variable once we drop generics on OperationResult.
+            let (i, qd, result) = match self.libos.wait_any_timeout(&qtokens, timeout) {
+                Ok((i, qd, result)) => (i, qd, result),
+                Err(e) if e.is_timeout() => continue,
+                Err(e) if e.is_shutdown() => break,
+                Err(e) => panic!("operation failed: {:?}", e),
+            };
+            qtokens.swap_remove(i);
+
+            // Parse result.
+            match result {
+                // Pop completed on the local socket, so data comes from a client.
+                OperationResult::Pop(client, buf) if qd == self.sockqd => {
+                    // Pop another packet.
+                    let qt: QToken = match self.libos.pop(self.sockqd) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+
+                    // Relay packet to the backend of the client.
+                    let client: SocketAddr = match client {
+                        Some(client) => client,
+                        None => {
+                            ndropped += 1;
+                            continue;
+                        }
+                    };
+                    nbytes += buf.len();
+                    if self.dashboard.is_some() {
+                        sample.record(buf.len(), None, Some(client));
+                    }
+
+                    // Run packet through rules.
+                    let now: Instant = self.clock.now();
+                    let (buf, to): (Cow<[u8]>, Option<SocketAddr>) =
+                        match self.rules.apply(Direction::Up, client, &buf, now) {
+                            Verdict::Pass(buf, to) => (buf, to),
+                            Verdict::Drop => {
+                                nfiltered += 1;
+                                continue;
+                            }
+                        };
+
+                    let upstream: QDesc = match self.open_session(client, &mut qtokens) {
+                        Some(upstream) => upstream,
+                        None => {
+                            ndropped += 1;
+                            continue;
+                        }
+                    };
+                    let backend: usize = match self.sessions.get_mut(&client) {
+                        Some(session) => {
+                            session.record_up(buf.len(), now);
+                            // Move away from a backend that went down.
+                            if let Some(backend) =
+                                self.balancer.reassign(session.get_backend(), client)
+                            {
+                                session.set_backend(backend);
+                            }
+                            session.get_backend()
+                        }
+                        None => {
+                            ndropped += 1;
+                            continue;
+                        }
+                    };
+                    let remote: SocketAddr = match to {
+                        Some(to) => to,
+                        None => {
+                            self.balancer.on_send(backend, now);
+                            self.balancer.get_addr(backend)
+                        }
+                    };
+                    self.forward(true, upstream, &buf, remote, &mut qtokens);
+                }
+                // Pop completed on an upstream socket, so data comes from a backend.
+                OperationResult::Pop(remote, buf) => {
+                    nbytes += buf.len();
+
+                    // Replies prove that the backend is alive.
+                    if let Some(backend) = remote.and_then(|addr| self.balancer.find(addr)) {
+                        self.balancer.on_reply(backend);
+                    }
+
+                    // Drop replies to sessions that have expired meanwhile.
+                    let session: &mut Session = match self.sessions.get_by_upstream_mut(qd) {
+                        Some(session) => session,
+                        None => {
+                            ndropped += 1;
+                            continue;
+                        }
+                    };
+                    session.record_down(buf.len(), self.clock.now());
+                    let client: SocketAddr = session.get_client();
+
+                    // Pop another packet.
+                    let qt: QToken = match self.libos.pop(qd) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+                    };
+                    session.set_pop_qt(qt);
+                    qtokens.push(qt);
+
+                    // Run packet through rules.
+                    let (buf, to): (Cow<[u8]>, Option<SocketAddr>) = match remote {
+                        Some(remote) => {
+                            match self
+                                .rules
+                                .apply(Direction::Down, remote, &buf, self.clock.now())
+                            {
+                                Verdict::Pass(buf, to) => (buf, to),
+                                Verdict::Drop => {
+                                    nfiltered += 1;
+                                    continue;
+                                }
+                            }
+                        }
+                        None => (Cow::Borrowed(&buf[..]), None),
+                    };
+
+                    // Relay packet back to the client.
+                    self.forward(false, self.sockqd, &buf, to.unwrap_or(client), &mut qtokens);
+                }
+                // Push completed.
+                OperationResult::Push => (),
+                OperationResult::Failed(e) => panic!("operation failed: {:?}", e),
+                _ => panic!("unexpected result"),
+            };
+        }
+
+        // Dump final statistics, and close upstream sockets and the local one.
+        self.dump_statistics(since, nbytes, ndropped, nfiltered);
+        let qds: Vec<QDesc> = self
+            .sessions
+            .iter()
+            .map(Session::get_upstream)
+            .chain([self.sockqd])
+            .collect();
+        for qd in qds {
+            if let Err(e) = self.libos.close(qd) {
+                event!(
+                    Level::Warn,
+                    qd,
+                    None,
+                    "failed to close socket: {:?}",
+                    e.cause
+                );
+            }
+        }
+
+        self.libos.status()
+    }
+
+    /// Reloads rules from the rules file. The current rules are kept if the file is invalid.
+    fn reload_rules(&mut self) {
+        let path: &str = match &self.rules_path {
+            Some(path) => path,
+            None => return,
+        };
+        match RuleSet::load(path) {
+            Ok(rules) => {
+                info!("rules reloaded from {}", path);
+                for rule in rules.get_rules() {
+                    info!("  {}", rule);
+                }
+                self.rules = rules;
+            }
+            Err(e) => error!("failed to reload rules from {}: {:?}", path, e),
+        }
+    }
+
+    /// Sends a datagram through `qd` to `to`, applying the impairments of its direction.
+    fn forward(
+        &mut self,
+        up: bool,
+        qd: QDesc,
+        buf: &[u8],
+        to: SocketAddr,
+        qtokens: &mut Vec<QToken>,
+    ) {
+        let impairer: &mut Impairer = if up {
+            &mut self.impair_up
+        } else {
+            &mut self.impair_down
+        };
+
+        // Fast path.
+        if impairer.is_noop() {
+            let qt: QToken = match self.libos.pushto(qd, buf, to) {
+                Ok(qt) => qt,
+                Err(e) => panic!("failed to push data to socket: {:?}", e.cause),
+            };
+            qtokens.push(qt);
+            return;
+        }
+
+        let now: Instant = self.clock.now();
+        for (departure, payload) in impairer.process(buf, now) {
+            if departure <= now {
+                let qt: QToken = match self.libos.pushto(qd, &payload, to) {
+                    Ok(qt) => qt,
+                    Err(e) => panic!("failed to push data to socket: {:?}", e.cause),
+                };
+                qtokens.push(qt);
+            } else {
+                self.delayed.push(departure, (qd, to, payload));
+            }
+        }
+    }
+
+    /// Sends delayed datagrams that are due.
+    fn release_delayed(&mut self, qtokens: &mut Vec<QToken>) {
+        while let Some((qd, to, payload)) = self.delayed.pop_expired(self.clock.now()) {
+            let qt: QToken = match self.libos.pushto(qd, &payload, to) {
+                Ok(qt) => qt,
+                Err(e) => panic!("failed to push data to socket: {:?}", e.cause),
+            };
+            qtokens.push(qt);
+        }
+    }
+
+    /// Returns the upstream socket of a client, opening a new session if needed. `None` means
+    /// that no more sessions may be opened.
+    fn open_session(&mut self, client: SocketAddr, qtokens: &mut Vec<QToken>) -> Option<QDesc> {
+        if let Some(session) = self.sessions.get_mut(&client) {
+            return Some(session.get_upstream());
+        }
+
+        let port: u16 = self.sessions.alloc_port()?;
+        let backend: usize = match self.balancer.select(Some(client)) {
+            Some(backend) => backend,
+            None => {
+                self.sessions.free_port(port);
+                return None;
+            }
+        };
+
+        // Create UDP socket.
+        let upstream: QDesc = match self.libos.socket(domain(&self.local), libc::SOCK_DGRAM, 0) {
+            Ok(qd) => qd,
+            Err(e) => panic!("failed to create socket: {:?}", e.cause),
+        };
+
+        // Bind to a local address of its own.
+        let addr: SocketAddr = SocketAddr::new(self.local.ip(), port);
+        if let Err(e) = self.libos.bind(upstream, addr) {
+            event!(
+                Level::Warn,
+                upstream,
+                Some(client),
+                "failed to bind socket to {:?}: {:?}",
+                addr,
+                e.cause
+            );
+            self.sessions.free_port(port);
+            self.balancer.release(backend);
+            if let Err(e) = self.libos.close(upstream) {
+                event!(
+                    Level::Warn,
+                    upstream,
+                    Some(client),
+                    "failed to close socket: {:?}",
+                    e.cause
+                );
+            }
+            return None;
+        }
+
+        // Wait for replies of the backend.
+        let qt: QToken = match self.libos.pop(upstream) {
+            Ok(qt) => qt,
+            Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+        };
+        qtokens.push(qt);
+
+        event!(
+            Level::Debug,
+            upstream,
+            Some(client),
+            "session opened: {:?} <-> {:?} -> {:?}",
+            client,
+            addr,
+            self.balancer.get_addr(backend)
+        );
+        let session: &mut Session = self.sessions.insert(Session::new(
+            client,
+            upstream,
+            port,
+            backend,
+            self.clock.now(),
+        ));
+        session.set_pop_qt(qt);
+
+        Some(upstream)
+    }
+
+    /// Dumps statistics of the target relay server, counted since `since`.
+    fn dump_statistics(&self, since: Instant, nbytes: usize, ndropped: usize, nfiltered: usize) {
+        let elapsed: Duration = self.clock.now() - since;
+        let (nbytes_up, nbytes_down): (usize, usize) =
+            self.sessions.iter().fold((0, 0), |(up, down), s| {
+                (up + s.get_nbytes_up(), down + s.get_nbytes_down())
+            });
+        info!(
+            "{:?} B / {:?} us ({} sessions, {:?} B up, {:?} B down, {} dropped, \
+             {} filtered)",
+            nbytes,
+            elapsed.as_micros(),
+            self.sessions.len(),
+            nbytes_up,
+            nbytes_down,
+            ndropped,
+            nfiltered
+        );
+        for rule in self.rules.get_rules() {
+            info!("  rule {}: {} hits", rule.get_name(), rule.get_nhits());
+        }
+        for backend in self.balancer.get_backends() {
+            info!(
+                "  backend {:?}: {} sessions, {} sent, {} received{}",
+                backend.get_addr(),
+                backend.get_nsessions(),
+                backend.get_nsent(),
+                backend.get_nreceived(),
+                if backend.is_healthy() { "" } else { " (down)" }
+            );
+        }
+        if !self.impair_up.is_noop() {
+            info!("  up: {}", self.impair_up.get_counters());
+        }
+        if !self.impair_down.is_noop() {
+            info!("  down: {}", self.impair_down.get_counters());
+        }
+    }
+
+    /// Closes sessions that have been idle for too long.
+    fn expire_sessions(&mut self, qtokens: &mut Vec<QToken>) {
+        for session in self.sessions.expire(self.clock.now()) {
+            // Stop waiting for replies on the upstream socket.
+            if let Some(qt) = session.get_pop_qt() {
+                if let Some(i) = qtokens.iter().position(|t| *t == qt) {
+                    qtokens.swap_remove(i);
+                }
+            }
+            self.balancer.release(session.get_backend());
+            let upstream: QDesc = session.get_upstream();
+            self.delayed.retain(|(qd, _, _)| *qd != upstream);
+            let client: Option<SocketAddr> = Some(session.get_client());
+            if let Err(e) = self.libos.close(upstream) {
+                event!(
+                    Level::Warn,
+                    upstream,
+                    client,
+                    "failed to close socket: {:?}",
+                    e.cause
+                );
+            }
+            event!(
+                Level::Debug,
+                upstream,
+                client,
+                "session expired: {:?}",
+                session.get_client()
+            );
+        }
+    }
+}
+
+//==============================================================================
+// Signal Handling
+//==============================================================================
+
+/// Number of times that rules were requested to be reloaded. Each worker keeps track of the
+/// requests that it served.
+static RELOADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Requests rules to be reloaded. They are reloaded the next time the relay wakes up.
+extern "C" fn on_sighup(_: libc::c_int) {
+    RELOADS.fetch_add(1, Ordering::Relaxed);
+}
+
+//==============================================================================
+// Fan-Out Application
+//==============================================================================
+
+/// Owner of a pending operation of the fan-out application.
+#[derive(Clone, Copy, Debug)]
+enum Owner {
+    /// Pop on the feed socket.
+    Feed,
+    /// Pop on the control socket.
+    Control,
+    /// Copy of `nbytes` pushed to a destination.
+    Copy(u64, usize),
+    /// Reply to a control message.
+    Reply,
+}
+
+/// Fan-Out Application
+struct FanOutApplication<B: Backend> {
+    /// Underlying libOS.
+    libos: B,
+    /// Feed socket descriptor.
+    sockqd: QDesc,
+    /// Control socket descriptor.
+    controlqd: Option<QDesc>,
+    /// Destinations.
+    fanout: FanOut,
+    /// Where commands of the control socket come from.
+    controller: Option<Controller>,
+    /// Live dashboard, if any.
+    dashboard: Option<Dashboard>,
+}
+
+/// Associated Functions for the Fan-Out Application
+impl<B: Backend> FanOutApplication<B> {
+    /// Logging interval (in seconds).
+    const LOG_INTERVAL: u64 = 5;
+
+    /// Granularity of timers.
+    const TIMER_GRANULARITY: Duration = Duration::from_millis(1);
+
+    /// Number of slots in the timer wheel.
+    const TIMER_SLOTS: usize = 8192;
+
+    /// Instantiates the application.
+    pub fn new(mut libos: B, args: &ProgramArguments) -> Result<Self> {
+        // Extract arguments.
+        let local: SocketAddr = args.get_local();
+        let fanout: FanOut = FanOut::new(args.get_backends(), args.get_fanout_window())?;
+
+        // Create UDP socket.
+        let sockqd: QDesc = match libos.socket(domain(&local), libc::SOCK_DGRAM, 0) {
+            Ok(qd) => qd,
+            Err(e) => panic!("failed to create socket: {:?}", e.cause),
+        };
+
+        // Bind to local address.
+        match libos.bind(sockqd, local) {
+            Ok(()) => (),
+            Err(e) => panic!("failed to bind socket: {:?}", e.cause),
+        };
+
+        // Create control socket.
+        let controlqd: Option<QDesc> = match args.get_control() {
+            Some(control) => {
+                let qd: QDesc = match libos.socket(domain(&control), libc::SOCK_DGRAM, 0) {
+                    Ok(qd) => qd,
+                    Err(e) => panic!("failed to create socket: {:?}", e.cause),
+                };
+                match libos.bind(qd, control) {
+                    Ok(()) => (),
+                    Err(e) => panic!("failed to bind socket: {:?}", e.cause),
+                };
+                info!("Control Address: {:?}", control);
+                Some(qd)
+            }
+            None => None,
+        };
+
+        info!("Local Address:  {:?}", local);
+        for destination in args.get_backends() {
+            info!("Destination Address: {:?}", destination);
+        }
+
+        Ok(Self {
+            libos,
+            sockqd,
+            controlqd,
+            fanout,
+            controller: None,
+            dashboard: None,
+        })
+    }
+
+    /// Makes the target application apply commands of a control socket.
+    pub fn set_controller(&mut self, controller: Option<Controller>) {
+        self.controller = controller;
+    }
+
+    /// Makes the target application show a live dashboard instead of dumping statistics.
+    pub fn set_dashboard(&mut self, dashboard: Option<Dashboard>) {
+        self.dashboard = dashboard;
+    }
+
+    /// Runs the target fan-out relay.
+    ///
+    /// Every datagram that is popped from the feed socket is pushed to each destination, without
+    /// waiting for previous copies to complete.
+    pub fn run(&mut self) -> Result<()> {
+        let clock: Clock = self.libos.clock();
+        let start: Instant = clock.now();
+        let mut since: Instant = start;
+        let mut nbytes: usize = 0;
+        let mut ndropped: usize = 0;
+        let mut last: Instant = start;
+        let mut sample: Sample = Sample::default();
+        let mut qtokens: Vec<QToken> = Vec::new();
+        let mut owners: Vec<Owner> = Vec::new();
+        let mut timers: TimerWheel<Timer> =
+            TimerWheel::new(Self::TIMER_GRANULARITY, Self::TIMER_SLOTS, start);
+        timers.schedule(start + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+
+        // Pop first packet and first control message.
+        self.pop(self.sockqd, Owner::Feed, &mut qtokens, &mut owners);
+        if let Some(controlqd) = self.controlqd {
+            self.pop(controlqd, Owner::Control, &mut qtokens, &mut owners);
+        }
+
+        loop {
+            let now: Instant = clock.now();
+
+            // Apply commands of the control socket.
+            if let Some(command) = self.controller.as_ref().and_then(Controller::poll) {
+                match command.get_request().clone() {
+                    Request::Stats => {
+                        command.reply(&format!(
+                            "bytes: {}\nelapsed_us: {}\ndestinations: {}\ndropped: {}",
+                            nbytes,
+                            (now - since).as_micros(),
+                            self.fanout.get_destinations().len(),
+                            ndropped
+                        ));
+                    }
+                    Request::Connections => {
+                        let lines: Vec<String> = self
+                            .fanout
+                            .get_destinations()
+                            .iter()
+                            .map(Self::describe)
+                            .collect();
+                        command.reply(&lines.join("\n"));
+                    }
+                    Request::ResetCounters => {
+                        nbytes = 0;
+                        ndropped = 0;
+                        since = now;
+                        command.reply("ok");
+                    }
+                    Request::Shutdown => {
+                        command.reply("ok");
+                        break;
+                    }
+                    _ => command.unsupported(),
+                }
+            }
+
+            // Dump statistics.
+            for timer in timers.expire(now) {
+                match timer {
+                    Timer::Log if self.dashboard.is_some() => {
+                        if let Some(dashboard) = &self.dashboard {
+                            sample.set_nconnections(self.fanout.get_destinations().len());
+                            dashboard.update(sample.close(now - last));
+                            last = now;
+                        }
+                        timers.schedule(now + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+                    }
+                    Timer::Log => {
+                        let elapsed: Duration = now - since;
+                        info!(
+                            "{:?} B / {:?} us ({} destinations, {} dropped)",
+                            nbytes,
+                            elapsed.as_micros(),
+                            self.fanout.get_destinations().len(),
+                            ndropped
+                        );
+                        for destination in self.fanout.get_destinations() {
+                            info!("  {}", Self::describe(destination));
+                        }
+                        timers.schedule(now + Duration::from_secs(Self::LOG_INTERVAL), Timer::Log);
+                    }
+                    Timer::Expire => unreachable!("fan-out keeps no sessions"),
+                }
+            }
+
+            // Wait for datagrams, waking up in time to refresh the dashboard.
+            let timeout: Option<Duration> = match self.dashboard {
+                Some(_) => timers
+                    .next_deadline()
+                    .map(|deadline| deadline.saturating_duration_since(clock.now())),
+                None => None,
+            };
+            let timeout: Option<Duration> =
+                Controller::timeout::<B>(self.controller.as_ref(), timeout);
+            let (i, _, result) = match self.libos.wait_any_timeout(&qtokens, timeout) {
+                Ok((i, qd, result)) => (i, qd, result),
+                Err(e) if e.is_timeout() => continue,
+                Err(e) if e.is_shutdown() => break,
+                Err(e) => panic!("operation failed: {:?}", e),
+            };
+            qtokens.swap_remove(i);
+            let owner: Owner = owners.swap_remove(i);
+
+            // Parse result.
+            match (owner, result) {
+                (Owner::Feed, OperationResult::Pop(source, buf)) => {
+                    self.pop(self.sockqd, Owner::Feed, &mut qtokens, &mut owners);
+
+                    // Do not loop datagrams back to destinations.
+                    if matches!(source, Some(addr) if self.fanout.contains(addr)) {
+                        ndropped += 1;
+                        continue;
+                    }
+                    nbytes += buf.len();
+                    if self.dashboard.is_some() {
+                        sample.record(buf.len(), None, source);
+                    }
+
+                    // Push a copy to every destination that keeps up.
+                    for (id, addr) in self.fanout.reserve() {
+                        let qt: QToken = match self.libos.pushto(self.sockqd, &buf, addr) {
+                            Ok(qt) => qt,
+                            Err(e) => panic!("failed to push data to socket: {:?}", e.cause),
+                        };
+                        qtokens.push(qt);
+                        owners.push(Owner::Copy(id, buf.len()));
+                    }
+                }
+                (Owner::Control, OperationResult::Pop(sender, buf)) => {
+                    let controlqd: QDesc = self.controlqd.expect("missing control socket");
+                    self.pop(controlqd, Owner::Control, &mut qtokens, &mut owners);
+                    let sender: SocketAddr = match sender {
+                        Some(sender) => sender,
+                        None => continue,
+                    };
+
+                    // Apply control message and reply to the sender.
+                    let reply: String = match self.control(&buf, sender) {
+                        Ok(()) => "ok\n".to_string(),
+                        Err(e) => format!("error: {}\n", e),
+                    };
+                    let qt: QToken = match self.libos.pushto(controlqd, reply.as_bytes(), sender) {
+                        Ok(qt) => qt,
+                        Err(e) => panic!("failed to push data to socket: {:?}", e.cause),
+                    };
+                    qtokens.push(qt);
+                    owners.push(Owner::Reply);
+                }
+                (Owner::Copy(id, nbytes), OperationResult::Push) => {
+                    self.fanout.complete(id, Some(nbytes))
+                }
+                // A destination that fails does not hold back the others.
+                (Owner::Copy(id, _), OperationResult::Failed(e)) => {
+                    debug!("failed to push copy: {:?}", e.cause);
+                    self.fanout.complete(id, None)
+                }
+                (Owner::Reply, OperationResult::Push) => (),
+                (Owner::Reply, OperationResult::Failed(e)) => {
+                    warn!("failed to reply to control message: {:?}", e.cause)
+                }
+                (_, OperationResult::Failed(e)) => panic!("operation failed: {:?}", e),
+                _ => panic!("unexpected result"),
+            };
+        }
+
+        // Close sockets.
+        for qd in self.controlqd.into_iter().chain([self.sockqd]) {
+            if let Err(e) = self.libos.close(qd) {
+                warn!("failed to close socket: {:?}", e.cause);
+            }
+        }
+
+        self.libos.status()
+    }
+
+    /// Describes a destination.
+    fn describe(destination: &Destination) -> String {
+        format!(
+            "{:?}{}: {} sent, {:?} B, {} skipped, {} failed, {} in flight",
+            destination.get_addr(),
+            if destination.is_subscribed() {
+                " (subscribed)"
+            } else {
+                ""
+            },
+            destination.get_nsent(),
+            destination.get_nbytes(),
+            destination.get_nskipped(),
+            destination.get_nfailed(),
+            destination.get_inflight()
+        )
+    }
+
+    /// Pops data from a socket.
+    fn pop(&mut self, qd: QDesc, owner: Owner, qtokens: &mut Vec<QToken>, owners: &mut Vec<Owner>) {
+        let qt: QToken = match self.libos.pop(qd) {
+            Ok(qt) => qt,
+            Err(e) => panic!("failed to pop data from socket: {:?}", e.cause),
+        };
+        qtokens.push(qt);
+        owners.push(owner);
+    }
+
+    /// Applies a control message sent by `sender`, which acts on the sender only.
+    fn control(&mut self, buf: &[u8], sender: SocketAddr) -> Result<()> {
+        let message: Control = std::str::from_utf8(buf)?.parse()?;
+        match message {
+            Control::Subscribe => {
+                if !self.fanout.add(sender, true) {
+                    bail!("{:?} is already a destination", sender)
+                }
+                info!("subscribed: {:?}", sender);
+            }
+            Control::Unsubscribe => {
+                self.fanout.remove(sender)?;
+                info!("unsubscribed: {:?}", sender);
+            }
+        }
+        Ok(())
+    }
+}
+
+//==============================================================================
+
+/// Runs the application over a backend of type `B`.
+fn start<B: Backend + 'static>(args: ProgramArguments) -> Result<()> {
+    if args.is_tui() && !B::has_timed_waits() {
+        bail!("the dashboard needs timed waits, which the backend does not offer")
+    }
+    if args.is_tui() && args.get_workers() > 1 {
+        bail!("the dashboard is only supported by relays without workers")
+    }
+
+    // Run one relay server per worker, each with a share of the session ports.
+    if args.get_workers() > 1 {
+        let n: usize = args.get_workers();
+        let controllers: Controllers = args.get_control_socket().start_workers(n)?;
+        let workers: Workers = Workers::spawn(n, move |i, stats| {
+            let mut args: ProgramArguments = args.clone();
+            args.local = workers::worker_addr::<B>(args.local, i)?;
+            args.session_ports = workers::worker_range(args.session_ports, i, n)?;
+            let mut app: Application<B> = Application::new(B::create()?, &args)?;
+            app.set_worker_statistics(stats);
+            app.set_controller(controllers.take(i));
+            app.run()
+        })?;
+        return workers.report(Duration::from_secs(Application::<B>::LOG_INTERVAL));
+    }
+
+    let libos: B = B::create()?;
+    let dashboard: Option<Dashboard> = if args.is_tui() {
+        Some(Dashboard::start("udp-relay")?)
+    } else {
+        None
+    };
+
+    if args.is_fanout() {
+        let mut app: FanOutApplication<B> = FanOutApplication::new(libos, &args)?;
+        app.set_controller(args.get_control_socket().start()?);
+        app.set_dashboard(dashboard);
+        return app.run();
+    }
+    let mut app: Application<B> = Application::new(libos, &args)?;
+    app.set_controller(args.get_control_socket().start()?);
+    app.set_dashboard(dashboard);
+    app.run()
+}
+
+/// Describes the application.
+pub const ABOUT: &str = "Relays UDP packets.";
+
+/// Runs the application with the given command line.
+pub fn main(argv: Vec<String>) -> Result<()> {
+    let args: ProgramArguments = ProgramArguments::new(
+        "udp-relay",
+        "Pedro Henrique Penna <ppenna@microsoft.com>",
+        ABOUT,
+        argv,
+    )?;
+
+    args.get_placement().apply()?;
+    args.get_tracing().start()?;
+
+    match args.get_io_backend() {
+        BackendKind::LibOS => start::<Traced<LibOS>>(args),
+        BackendKind::Posix => start::<Traced<Posix>>(args),
+        BackendKind::Loopback => start::<Traced<Loopback>>(args),
+        BackendKind::Simulator => start::<Traced<Simulator>>(args),
+    }
+}
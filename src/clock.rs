@@ -0,0 +1,230 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Clock
+///
+/// Reads time from the time-stamp counter of the processor, which is much cheaper than asking
+/// the operating system. Readings are converted to instants with a rate that is calibrated
+/// against the monotonic clock of the operating system when the clock is created.
+///
+/// The time-stamp counter is only used when the processor reports it as invariant, that is,
+/// ticking at a constant rate regardless of frequency scaling and sleep states. Otherwise, the
+/// clock falls back to `Instant::now()`.
+///
+/// Simulated clocks read no counter at all: their time only moves when whoever drives the
+/// simulation advances it.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    /// Instant at which the clock was calibrated.
+    base: Instant,
+    /// Reading of the time-stamp counter at `base`.
+    base_tsc: u64,
+    /// Nanoseconds per cycle, as a 32.32 fixed-point number. Zero if the time-stamp counter is
+    /// not used.
+    ns_per_cycle: u64,
+    /// Time elapsed since `base` (in nanoseconds), if the clock is simulated.
+    simulated: Option<Arc<AtomicU64>>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Clocks
+impl Clock {
+    /// Time spent calibrating a clock.
+    const CALIBRATION: Duration = Duration::from_millis(10);
+
+    /// Creates a clock. Calibration blocks the caller for about 10 ms.
+    pub fn new() -> Self {
+        if !has_invariant_tsc() {
+            return Self {
+                base: Instant::now(),
+                base_tsc: 0,
+                ns_per_cycle: 0,
+                simulated: None,
+            };
+        }
+
+        let (start, start_tsc): (Instant, u64) = (Instant::now(), rdtsc());
+        while start.elapsed() < Self::CALIBRATION {}
+        let (end, end_tsc): (Instant, u64) = (Instant::now(), rdtsc());
+        let ns: u128 = (end - start).as_nanos();
+        let cycles: u128 = end_tsc.saturating_sub(start_tsc).max(1) as u128;
+
+        Self {
+            base: end,
+            base_tsc: end_tsc,
+            ns_per_cycle: ((ns << 32) / cycles) as u64,
+            simulated: None,
+        }
+    }
+
+    /// Creates a simulated clock, which reads the time elapsed since its creation from `elapsed`
+    /// (in nanoseconds).
+    pub fn simulated(elapsed: Arc<AtomicU64>) -> Self {
+        Self {
+            base: Instant::now(),
+            base_tsc: 0,
+            ns_per_cycle: 0,
+            simulated: Some(elapsed),
+        }
+    }
+
+    /// Returns the current instant.
+    #[inline]
+    pub fn now(&self) -> Instant {
+        if let Some(elapsed) = &self.simulated {
+            return self.base + Duration::from_nanos(elapsed.load(Ordering::Relaxed));
+        }
+        if self.ns_per_cycle == 0 {
+            return Instant::now();
+        }
+        let cycles: u64 = rdtsc().saturating_sub(self.base_tsc);
+        let ns: u64 = ((cycles as u128 * self.ns_per_cycle as u128) >> 32) as u64;
+        self.base + Duration::from_nanos(ns)
+    }
+
+    /// Returns the time elapsed since `since`.
+    #[inline]
+    pub fn elapsed(&self, since: Instant) -> Duration {
+        self.now().saturating_duration_since(since)
+    }
+
+    /// Asserts if the target clock reads the time-stamp counter.
+    pub fn is_tsc(&self) -> bool {
+        self.ns_per_cycle != 0
+    }
+
+    /// Returns the calibrated frequency of the time-stamp counter (in Hz), if it is used.
+    pub fn get_frequency(&self) -> Option<u64> {
+        match self.ns_per_cycle {
+            0 => None,
+            ns_per_cycle => Some(((1_000_000_000u128 << 32) / ns_per_cycle as u128) as u64),
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Reads the time-stamp counter.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+fn rdtsc() -> u64 {
+    unsafe { ::core::arch::x86_64::_rdtsc() }
+}
+
+/// Reads the time-stamp counter.
+#[cfg(not(target_arch = "x86_64"))]
+#[inline]
+fn rdtsc() -> u64 {
+    0
+}
+
+/// Asserts if the processor has an invariant time-stamp counter.
+#[cfg(target_arch = "x86_64")]
+#[allow(unused_unsafe)] // `__cpuid()` is safe on recent toolchains.
+fn has_invariant_tsc() -> bool {
+    use ::core::arch::x86_64::__cpuid;
+    unsafe {
+        // Advanced power management leaf, whose bit 8 of EDX reports an invariant counter.
+        __cpuid(0x8000_0000).eax >= 0x8000_0007 && __cpuid(0x8000_0007).edx & (1 << 8) != 0
+    }
+}
+
+/// Asserts if the processor has an invariant time-stamp counter.
+#[cfg(not(target_arch = "x86_64"))]
+fn has_invariant_tsc() -> bool {
+    false
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::std::thread;
+
+    #[test]
+    fn clocks_are_monotonic() {
+        let clock: Clock = Clock::new();
+        let mut last: Instant = clock.now();
+        for _ in 0..100_000 {
+            let now: Instant = clock.now();
+            assert!(now >= last);
+            last = now;
+        }
+    }
+
+    #[test]
+    fn clocks_keep_up_with_the_operating_system() {
+        let clock: Clock = Clock::new();
+        let (start, start_os): (Instant, Instant) = (clock.now(), Instant::now());
+        thread::sleep(Duration::from_millis(100));
+        let (elapsed, elapsed_os): (Duration, Duration) =
+            (clock.elapsed(start), start_os.elapsed());
+
+        // Allow for 5% of calibration error, and for the time between both readings.
+        let error: Duration = elapsed_os / 20 + Duration::from_millis(1);
+        assert!(elapsed + error >= elapsed_os && elapsed <= elapsed_os + error);
+    }
+
+    #[test]
+    fn frequencies_are_reported_for_time_stamp_counters_only() {
+        let clock: Clock = Clock::new();
+        assert_eq!(clock.is_tsc(), clock.get_frequency().is_some());
+        if let Some(frequency) = clock.get_frequency() {
+            assert!(frequency > 1_000_000, "{} Hz", frequency);
+        }
+        let fallback: Clock = Clock {
+            base: Instant::now(),
+            base_tsc: 0,
+            ns_per_cycle: 0,
+            simulated: None,
+        };
+        assert!(!fallback.is_tsc() && fallback.get_frequency().is_none());
+        assert!(fallback.now() >= fallback.base);
+    }
+
+    #[test]
+    fn simulated_clocks_only_move_when_advanced() {
+        let elapsed: Arc<AtomicU64> = Arc::new(AtomicU64::new(0));
+        let clock: Clock = Clock::simulated(elapsed.clone());
+        let start: Instant = clock.now();
+        thread::sleep(Duration::from_millis(1));
+        assert_eq!(clock.now(), start);
+        elapsed.store(1_500, Ordering::Relaxed);
+        assert_eq!(clock.elapsed(start), Duration::from_nanos(1_500));
+        assert!(!clock.is_tsc());
+    }
+}
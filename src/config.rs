@@ -0,0 +1,318 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::logging::Logging;
+use ::anyhow::{bail, Context, Result};
+use ::clap::{Arg, ArgMatches, Command};
+use ::log::info;
+use ::serde::Deserialize;
+use ::std::{collections::HashMap, env, fmt, fs, path::Path};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Configuration File
+///
+/// Arguments of a binary may be set in a TOML or YAML file, under a section named after it:
+///
+/// ```yaml
+/// tcp-echo:
+///   local: 127.0.0.1:12345
+///   bufsize: 1024
+///   workers: 4
+/// udp-relay:
+///   local: 127.0.0.1:53
+///   backend: [10.0.0.2:53, 10.0.0.3:53]
+///   fanout: true
+/// ```
+///
+/// Keys are the long names of arguments. Lists stand for repeated arguments and booleans for
+/// flags. Other sections are ignored, so that the file may be shared with Demikernel.
+///
+/// An argument is resolved from the command line, then from a `<BINARY>_<ARGUMENT>` environment
+/// variable (e.g. `TCP_ECHO_BUFSIZE`), then from the file, and finally from its default value.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Path to the configuration file, if any.
+    path: Option<String>,
+    /// Values of arguments set in the file.
+    values: HashMap<String, Vec<String>>,
+}
+
+/// Configuration Setting
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Setting {
+    /// Flag.
+    Flag(bool),
+    /// Integer value.
+    Integer(i64),
+    /// Floating point value.
+    Float(f64),
+    /// String value.
+    String(String),
+    /// Repeated argument.
+    List(Vec<Setting>),
+}
+
+/// Source of an Argument
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source {
+    /// Command line.
+    Cli,
+    /// Environment variable.
+    Env,
+    /// Configuration file.
+    File,
+    /// Default value.
+    Default,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Configuration Files
+impl Config {
+    /// Environment variable that names the configuration file when `--config` is not given.
+    const CONFIG_PATH: &'static str = "CONFIG_PATH";
+
+    /// Returns command line arguments that describe a configuration file.
+    pub fn args<'help>() -> [Arg<'help>; 1] {
+        [Arg::new("config")
+            .long("config")
+            .takes_value(true)
+            .required(false)
+            .value_name("FILE")
+            .help(
+                "Loads arguments from a TOML or YAML file, under a section named after the binary",
+            )]
+    }
+
+    /// Loads the section of a binary from a configuration file.
+    pub fn load(path: &str, section: &str) -> Result<Self> {
+        let contents: String =
+            fs::read_to_string(path).with_context(|| format!("failed to read {:?}", path))?;
+        let settings: Option<HashMap<String, Setting>> =
+            match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+                Some("toml") => match toml::from_str::<toml::Value>(&contents)?.get(section) {
+                    Some(value) => Some(value.clone().try_into()?),
+                    None => None,
+                },
+                Some("yaml") | Some("yml") => {
+                    match serde_yaml::from_str::<serde_yaml::Value>(&contents)?.get(section) {
+                        Some(value) => Some(serde_yaml::from_value(value.clone())?),
+                        None => None,
+                    }
+                }
+                _ => bail!(
+                    "unknown configuration format for {:?} (expected .toml, .yaml or .yml)",
+                    path
+                ),
+            };
+
+        let mut values: HashMap<String, Vec<String>> = HashMap::new();
+        for (key, setting) in settings.unwrap_or_default() {
+            let mut strings: Vec<String> = Vec::new();
+            setting
+                .flatten(&mut strings)
+                .with_context(|| format!("invalid value for {:?} in section {:?}", key, section))?;
+            values.insert(key.replace('-', "_"), strings);
+        }
+
+        Ok(Self {
+            path: Some(path.to_string()),
+            values,
+        })
+    }
+
+    /// Parses the arguments of a command from the command line `argv`, filling in those that are
+    /// missing from the environment and from the configuration file. Logging is then set up from
+    /// the resolved arguments, and the resolved configuration is logged.
+    pub fn get_matches_from(command: Command, argv: Vec<String>) -> Result<ArgMatches> {
+        let name: String = command.get_name().to_string();
+
+        // Load the configuration file.
+        let config: Config = match Self::path(&argv) {
+            Some(path) => Self::load(&path, &name)?,
+            None => Self::default(),
+        };
+        for key in config.values.keys() {
+            if !options(&command).any(|arg| arg.get_long() == Some(key.as_str())) {
+                bail!("unknown argument {:?} in section {:?}", key, name)
+            }
+        }
+
+        // Resolve arguments that are missing on the command line.
+        let mut resolved: Vec<String> = argv[..1].to_vec();
+        let mut sources: HashMap<&str, Source> = HashMap::new();
+        for arg in options(&command) {
+            let long: &str = arg.get_long().unwrap_or_default();
+            let source: Source = if cli_has(&argv, arg) {
+                Source::Cli
+            } else if let Ok(value) = env::var(env_name(&name, long)) {
+                push(&mut resolved, arg, &[value])
+                    .with_context(|| format!("invalid {}", env_name(&name, long)))?;
+                Source::Env
+            } else if let Some(values) = config.values.get(long) {
+                push(&mut resolved, arg, values).with_context(|| {
+                    format!("invalid value for {:?} in section {:?}", long, name)
+                })?;
+                Source::File
+            } else {
+                continue;
+            };
+            sources.insert(arg.get_id(), source);
+        }
+        resolved.extend_from_slice(&argv[1..]);
+
+        let matches: ArgMatches = command.clone().get_matches_from(resolved);
+        Logging::from_matches(&matches)?.apply()?;
+        config.log(&command, &matches, &sources);
+        Ok(matches)
+    }
+
+    /// Returns the path to the configuration file: the one given with `--config`, or the one in
+    /// `CONFIG_PATH` if it exists.
+    fn path(argv: &[String]) -> Option<String> {
+        for (i, token) in argv.iter().enumerate() {
+            if token == "--config" {
+                return argv.get(i + 1).cloned();
+            }
+            if let Some(path) = token.strip_prefix("--config=") {
+                return Some(path.to_string());
+            }
+        }
+        match env::var(Self::CONFIG_PATH) {
+            Ok(path) if Path::new(&path).is_file() => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Logs the resolved value and source of every argument that is set.
+    fn log(&self, command: &Command, matches: &ArgMatches, sources: &HashMap<&str, Source>) {
+        match &self.path {
+            Some(path) => info!("Configuration ({}):", path),
+            None => info!("Configuration:"),
+        }
+        for arg in options(command) {
+            let (id, long): (&str, &str) = (arg.get_id(), arg.get_long().unwrap_or_default());
+            if !matches.is_present(id) {
+                continue;
+            }
+            let value: String = match matches.values_of(id) {
+                Some(values) if arg.is_takes_value_set() => values.collect::<Vec<&str>>().join(","),
+                _ => "true".to_string(),
+            };
+            let source: Source = sources.get(id).copied().unwrap_or(Source::Default);
+            info!("  {} = {} ({})", long, value, source);
+        }
+
+        // Positional arguments can only be set on the command line.
+        for arg in command.get_arguments().filter(|arg| arg.is_positional()) {
+            if let Some(value) = matches.value_of(arg.get_id()) {
+                info!("  {} = {} ({})", arg.get_id(), value, Source::Cli);
+            }
+        }
+    }
+}
+
+/// Associate Functions for Configuration Settings
+impl Setting {
+    /// Appends the values of the target setting to `values`.
+    fn flatten(self, values: &mut Vec<String>) -> Result<()> {
+        match self {
+            Setting::Flag(flag) => values.push(flag.to_string()),
+            Setting::Integer(integer) => values.push(integer.to_string()),
+            Setting::Float(float) => values.push(float.to_string()),
+            Setting::String(string) => values.push(string),
+            Setting::List(list) => {
+                for setting in list {
+                    match setting {
+                        Setting::List(_) => bail!("nested lists are not supported"),
+                        setting => setting.flatten(values)?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Display Trait Implementation for Argument Sources
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Cli => write!(f, "cli"),
+            Source::Env => write!(f, "env"),
+            Source::File => write!(f, "file"),
+            Source::Default => write!(f, "default"),
+        }
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns the arguments of a command that may be configured: those with a long name, except for
+/// the configuration file itself and the ones that clap adds.
+fn options<'a, 'help>(command: &'a Command<'help>) -> impl Iterator<Item = &'a Arg<'help>> {
+    command.get_arguments().filter(|arg| {
+        arg.get_long().is_some() && !matches!(arg.get_id(), "config" | "help" | "version")
+    })
+}
+
+/// Checks whether an argument is given on the command line, by its long name or, for flags, by
+/// its short name (e.g. `-vv`).
+fn cli_has(argv: &[String], arg: &Arg) -> bool {
+    let long: &str = arg.get_long().unwrap_or_default();
+    argv[1..].iter().any(|token| {
+        if let Some(token) = token.strip_prefix("--") {
+            return token == long || token.starts_with(&format!("{}=", long));
+        }
+        match (token.strip_prefix('-'), arg.get_short()) {
+            (Some(shorts), Some(short)) if !arg.is_takes_value_set() => {
+                !shorts.is_empty() && shorts.chars().all(|c| c == short)
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Returns the environment variable that sets an argument of a binary.
+fn env_name(name: &str, long: &str) -> String {
+    format!("{}_{}", name, long)
+        .replace('-', "_")
+        .to_uppercase()
+}
+
+/// Appends an argument with the given values to a command line.
+fn push(argv: &mut Vec<String>, arg: &Arg, values: &[String]) -> Result<()> {
+    let long: &str = arg.get_long().unwrap_or_default();
+    if !arg.is_takes_value_set() {
+        // Flags are set by booleans.
+        match values {
+            [value] if value == "true" || value == "1" => argv.push(format!("--{}", long)),
+            [value] if value == "false" || value == "0" => (),
+            _ => bail!("expected a boolean"),
+        }
+        return Ok(());
+    }
+    if values.len() > 1 && !arg.is_multiple_occurrences_set() {
+        bail!("expected a single value");
+    }
+    for value in values {
+        argv.push(format!("--{}={}", long, value));
+    }
+    Ok(())
+}
@@ -0,0 +1,406 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::{backend::Backend, logging::Logging, schedule::Rate};
+use ::anyhow::{bail, Context, Result};
+use ::clap::{Arg, ArgMatches};
+use ::log::{info, warn};
+use ::std::{
+    cell::Cell,
+    fmt, fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    str::FromStr,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Control Socket
+///
+/// Opt-in Unix-domain socket through which a running application is inspected and reconfigured.
+/// Clients write one command per line, and every reply ends with an empty line. Failed commands
+/// are answered with a line that starts with `error:`. Commands are:
+///
+/// - `stats`: shows the counters of the application.
+/// - `connections`: lists active connections, or sessions.
+/// - `set rate RATE`: sets the injection rate of a generator, such as `5Gbps` (see [Rate]).
+/// - `set log-level FILTER`: replaces the log filter, such as `debug` (see [Logging]).
+/// - `reset-counters`: clears the counters of the application.
+/// - `shutdown`: stops the application, which closes its sockets and dumps final statistics.
+///
+/// The socket is served by a thread of its own on the kernel stack, which hands commands over
+/// to the run loop of the application. The run loop applies them between two iterations, so
+/// that the data path is never shared. Run loops that wait for traffic wake up on every
+/// [Controller::POLL_INTERVAL] to do so, on backends with timed waits. Over the libOS, which has
+/// none, commands wait for traffic, and fail if none comes in time.
+///
+/// Applications that run workers hand each command over to every worker, and reply once all of
+/// them did. Replies that differ between workers are listed per worker.
+#[derive(Clone, Debug)]
+pub struct ControlSocket {
+    /// Path of the socket. The control socket is disabled if `None`.
+    path: Option<String>,
+}
+
+/// Controller
+///
+/// Run loop side of a control socket.
+#[derive(Debug)]
+pub struct Controller {
+    /// Where commands are received from.
+    rx: Receiver<Command>,
+    /// Where the serving thread tells that the reply to `shutdown` went out.
+    ack: Receiver<()>,
+    /// Path of the socket, which is removed on `shutdown`.
+    path: String,
+    /// Was `shutdown` requested?
+    stopping: Cell<bool>,
+}
+
+/// Controllers of Workers
+///
+/// Hands one controller over to each worker of an application.
+#[derive(Debug, Default)]
+pub struct Controllers(Mutex<Vec<Option<Controller>>>);
+
+/// Control Request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Request {
+    /// Shows counters.
+    Stats,
+    /// Lists active connections.
+    Connections,
+    /// Sets the injection rate.
+    SetRate(Rate),
+    /// Replaces the log filter.
+    SetLogLevel(String),
+    /// Clears counters.
+    ResetCounters,
+    /// Stops the application.
+    Shutdown,
+}
+
+/// Control Command
+///
+/// A request, along with where its reply goes.
+#[derive(Debug)]
+pub struct Command {
+    /// Request.
+    request: Request,
+    /// Where the reply goes.
+    reply: Sender<String>,
+}
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// How long the control thread waits for the application to apply a command.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Control Sockets
+impl ControlSocket {
+    /// Returns command line arguments that configure the control socket.
+    pub fn args<'help>() -> [Arg<'help>; 1] {
+        [Arg::new("control_socket")
+            .long("control_socket")
+            .takes_value(true)
+            .required(false)
+            .value_name("PATH")
+            .help("Serves runtime commands on a Unix-domain socket")]
+    }
+
+    /// Builds a control socket configuration from command line arguments.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        Ok(Self {
+            path: matches.value_of("control_socket").map(str::to_string),
+        })
+    }
+
+    /// Returns whether the target control socket is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Starts serving the control socket, if enabled, and returns the controller that the run
+    /// loop polls for commands.
+    pub fn start(&self) -> Result<Option<Controller>> {
+        Ok(self.start_workers(1)?.take(0))
+    }
+
+    /// Starts serving the control socket, if enabled, for an application that runs `n` workers,
+    /// and returns their controllers.
+    pub fn start_workers(&self, n: usize) -> Result<Controllers> {
+        let path: &str = match &self.path {
+            Some(path) => path,
+            None => return Ok(Controllers::default()),
+        };
+
+        // Remove a socket that a previous run left behind.
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if metadata.file_type().is_socket() {
+                fs::remove_file(path)
+                    .with_context(|| format!("failed to remove control socket {:?}", path))?;
+            }
+        }
+        let listener: UnixListener = UnixListener::bind(path)
+            .with_context(|| format!("failed to bind control socket {:?}", path))?;
+
+        let mut links: Vec<(Sender<Command>, Sender<()>)> = Vec::with_capacity(n);
+        let mut controllers: Vec<Option<Controller>> = Vec::with_capacity(n);
+        for _ in 0..n {
+            let (tx, rx): (Sender<Command>, Receiver<Command>) = mpsc::channel();
+            let (ack_tx, ack): (Sender<()>, Receiver<()>) = mpsc::channel();
+            links.push((tx, ack_tx));
+            controllers.push(Some(Controller {
+                rx,
+                ack,
+                path: path.to_string(),
+                stopping: Cell::new(false),
+            }));
+        }
+        thread::Builder::new()
+            .name("control".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let result: io::Result<()> = stream.and_then(|s| serve(s, &links));
+                    if let Err(e) = result {
+                        warn!("control socket: {}", e);
+                    }
+                }
+            })?;
+
+        info!("Control Socket: {}", path);
+        Ok(Controllers(Mutex::new(controllers)))
+    }
+}
+
+/// Associate Functions for Controllers of Workers
+impl Controllers {
+    /// Takes the controller of worker `i`, if the control socket is enabled.
+    pub fn take(&self, i: usize) -> Option<Controller> {
+        let mut controllers = self.0.lock().expect("poisoned controllers");
+        controllers.get_mut(i).and_then(Option::take)
+    }
+}
+
+/// Associate Functions for Controllers
+impl Controller {
+    /// Longest time that a run loop waits for traffic before it checks for commands.
+    pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// Returns how long a run loop over a backend of type `B` should wait for traffic, given
+    /// that it would wait for `timeout` otherwise. Loops with a controller wake up in time to
+    /// check for commands, unless the backend has no timed waits.
+    pub fn timeout<B: Backend>(
+        controller: Option<&Controller>,
+        timeout: Option<Duration>,
+    ) -> Option<Duration> {
+        match (controller, timeout) {
+            (Some(_), Some(timeout)) if B::has_timed_waits() => {
+                Some(timeout.min(Self::POLL_INTERVAL))
+            }
+            (Some(_), None) if B::has_timed_waits() => Some(Self::POLL_INTERVAL),
+            _ => timeout,
+        }
+    }
+
+    /// Returns the next command that the application has to apply, if any. `set log-level`, which
+    /// is common to all applications, is applied here. On `shutdown`, the socket is removed here,
+    /// and the application breaks out of its run loop.
+    pub fn poll(&self) -> Option<Command> {
+        let command: Command = self.rx.try_recv().ok()?;
+        match &command.request {
+            Request::SetLogLevel(filter) => {
+                match Logging::set_filter(filter) {
+                    Ok(()) => {
+                        info!("log filter set to {}", filter);
+                        command.reply("ok");
+                    }
+                    Err(e) => command.fail(e),
+                }
+                None
+            }
+            Request::Shutdown => {
+                info!("shutting down");
+                let _ = fs::remove_file(&self.path);
+                self.stopping.set(true);
+                Some(command)
+            }
+            _ => Some(command),
+        }
+    }
+}
+
+/// Associate Functions for Control Commands
+impl Command {
+    /// Returns the request of the target command.
+    pub fn get_request(&self) -> &Request {
+        &self.request
+    }
+
+    /// Replies to the target command.
+    pub fn reply(self, text: &str) {
+        let _ = self.reply.send(text.to_string());
+    }
+
+    /// Replies to the target command with an error.
+    pub fn fail(self, e: impl fmt::Display) {
+        let text: String = format!("error: {}", e);
+        self.reply(&text);
+    }
+
+    /// Replies to the target command that the application does not support it.
+    pub fn unsupported(self) {
+        let text: String = format!("{} is not supported by this application", self.request);
+        self.fail(text);
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// From String Trait Implementation for Control Requests
+impl FromStr for Request {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        match words.as_slice() {
+            ["stats"] => Ok(Request::Stats),
+            ["connections"] => Ok(Request::Connections),
+            ["set", "rate", rate] => Ok(Request::SetRate(rate.parse()?)),
+            ["set", "log-level", filter] => Ok(Request::SetLogLevel(filter.to_string())),
+            ["reset-counters"] => Ok(Request::ResetCounters),
+            ["shutdown"] => Ok(Request::Shutdown),
+            _ => bail!("unknown command {:?}", s.trim()),
+        }
+    }
+}
+
+/// Display Trait Implementation for Control Requests
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Request::Stats => write!(f, "stats"),
+            Request::Connections => write!(f, "connections"),
+            Request::SetRate(rate) => write!(f, "set rate {}", rate),
+            Request::SetLogLevel(filter) => write!(f, "set log-level {}", filter),
+            Request::ResetCounters => write!(f, "reset-counters"),
+            Request::Shutdown => write!(f, "shutdown"),
+        }
+    }
+}
+
+/// Drop Trait Implementation for Controllers
+///
+/// Applications stop right after they reply to `shutdown`, so the reply is waited for to go out
+/// before the application goes away.
+impl Drop for Controller {
+    fn drop(&mut self) {
+        if self.stopping.get() {
+            let _ = self.ack.recv_timeout(TIMEOUT);
+        }
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Serves a client of the control socket, handing each of its commands over to every run loop
+/// in `links`, along with where to tell that the reply to `shutdown` went out.
+fn serve(stream: UnixStream, links: &[(Sender<Command>, Sender<()>)]) -> io::Result<()> {
+    let mut writer: UnixStream = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line: String = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Request = match Request::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                write!(writer, "error: {}\n\n", e)?;
+                continue;
+            }
+        };
+
+        // Hand the command over to every run loop at once, and then collect their replies.
+        let rxs: Vec<Option<Receiver<String>>> = links
+            .iter()
+            .map(|(tx, _)| {
+                let (reply, rx): (Sender<String>, Receiver<String>) = mpsc::channel();
+                let request: Request = request.clone();
+                tx.send(Command { request, reply }).ok().map(|()| rx)
+            })
+            .collect();
+        let deadline: Instant = Instant::now() + TIMEOUT;
+        let replies: Vec<String> = rxs
+            .into_iter()
+            .map(|rx| match rx {
+                Some(rx) => {
+                    match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(text) => text,
+                        Err(RecvTimeoutError::Timeout) => {
+                            "error: timed out waiting for the application".to_string()
+                        }
+                        Err(RecvTimeoutError::Disconnected) => "error: no reply".to_string(),
+                    }
+                }
+                None => "error: the application is gone".to_string(),
+            })
+            .collect();
+        let text: String = merge(replies);
+        if text.is_empty() {
+            writer.write_all(b"\n")?;
+        } else {
+            write!(writer, "{}\n\n", text.trim_end())?;
+        }
+        if request == Request::Shutdown {
+            writer.flush()?;
+            for (_, ack) in links {
+                let _ = ack.send(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Merges the replies of several run loops to the same command. Replies that are all alike are
+/// given once, whereas others are listed per worker, with their lines indented.
+fn merge(mut replies: Vec<String>) -> String {
+    if replies
+        .iter()
+        .all(|reply| reply.trim_end() == replies[0].trim_end())
+    {
+        return replies.swap_remove(0);
+    }
+    let mut text: String = String::new();
+    for (i, reply) in replies.iter().enumerate() {
+        text.push_str(&format!("worker {}:\n", i));
+        for line in reply.trim_end().lines() {
+            text.push_str(&format!("  {}\n", line));
+        }
+    }
+    text
+}
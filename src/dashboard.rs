@@ -0,0 +1,337 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::Result;
+use ::clap::Arg;
+use ::std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write as _,
+    io::{self, Write},
+    net::SocketAddr,
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Dashboard
+///
+/// Live view of a running application in the terminal: a throughput graph, packets per second,
+/// latency percentiles, active connections and top sources. The run loop sends a [`Sample`] on
+/// every statistics interval, and a thread of its own renders it, so that the data path only
+/// counts. Run loops wait for traffic no longer than the next interval, so the dashboard needs a
+/// backend with timed waits.
+pub struct Dashboard {
+    /// Where samples are sent to.
+    tx: Sender<Sample>,
+    /// Rendering thread.
+    _handle: JoinHandle<()>,
+}
+
+/// Dashboard Sample
+///
+/// Activity of an application over a statistics interval.
+#[derive(Debug, Default)]
+pub struct Sample {
+    /// Length of the interval.
+    interval: Duration,
+    /// Number of bytes received.
+    nbytes: usize,
+    /// Number of packets received.
+    npackets: usize,
+    /// Number of active connections, for connection-oriented applications.
+    nconnections: Option<usize>,
+    /// Latencies of pops, from issue to completion, for applications that time them.
+    latencies: Histogram,
+    /// Number of packets and bytes received from every source.
+    sources: HashMap<SocketAddr, (usize, usize)>,
+}
+
+/// Latency Histogram
+///
+/// Log-linear buckets of nanoseconds, with 16 buckets per power of two, so that percentiles are
+/// within about 6% of exact ones.
+#[derive(Debug)]
+struct Histogram {
+    /// Number of values in every bucket.
+    buckets: Box<[u64]>,
+    /// Number of values.
+    count: u64,
+}
+
+/// Renderer of a Dashboard
+struct Renderer {
+    /// Name of the application.
+    title: String,
+    /// Throughput of past intervals, in bits per second, from the oldest.
+    history: VecDeque<f64>,
+    /// Time elapsed since the application started.
+    elapsed: Duration,
+    /// Does the application time its pops?
+    timed: bool,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Dashboards
+impl Dashboard {
+    /// Returns command line arguments that enable the dashboard.
+    pub fn args<'help>() -> [Arg<'help>; 1] {
+        [Arg::new("tui")
+            .long("tui")
+            .takes_value(false)
+            .required(false)
+            .help("Shows a live dashboard instead of statistics lines")]
+    }
+
+    /// Starts rendering a dashboard for the application named `title`.
+    pub fn start(title: &str) -> Result<Self> {
+        let (tx, rx): (Sender<Sample>, Receiver<Sample>) = mpsc::channel();
+        let mut renderer: Renderer = Renderer::new(title);
+        let handle: JoinHandle<()> =
+            thread::Builder::new()
+                .name("dashboard".to_string())
+                .spawn(move || {
+                    for sample in rx {
+                        renderer.render(&sample);
+                    }
+                })?;
+        Ok(Self {
+            tx,
+            _handle: handle,
+        })
+    }
+
+    /// Hands a sample over to the rendering thread, which is never waited on.
+    pub fn update(&self, sample: Sample) {
+        let _ = self.tx.send(sample);
+    }
+}
+
+/// Associate Functions for Dashboard Samples
+impl Sample {
+    /// Accounts a received packet, along with the latency of the pop that received it and its
+    /// source, if known.
+    #[inline]
+    pub fn record(&mut self, nbytes: usize, latency: Option<Duration>, source: Option<SocketAddr>) {
+        self.nbytes += nbytes;
+        self.npackets += 1;
+        if let Some(latency) = latency {
+            self.latencies.record(latency);
+        }
+        if let Some(source) = source {
+            let (npackets, total): &mut (usize, usize) = self.sources.entry(source).or_default();
+            *npackets += 1;
+            *total += nbytes;
+        }
+    }
+
+    /// Sets the number of active connections.
+    pub fn set_nconnections(&mut self, nconnections: usize) {
+        self.nconnections = Some(nconnections);
+    }
+
+    /// Closes the target sample after `interval`, and returns it, leaving an empty one that
+    /// keeps the number of connections.
+    pub fn close(&mut self, interval: Duration) -> Sample {
+        let nconnections: Option<usize> = self.nconnections;
+        let mut sample: Sample = std::mem::take(self);
+        sample.interval = interval;
+        self.nconnections = nconnections;
+        sample
+    }
+}
+
+/// Associate Functions for Histograms
+impl Histogram {
+    /// Number of buckets per power of two, as a power of two.
+    const SUB_BITS: u32 = 4;
+
+    /// Number of buckets.
+    const NBUCKETS: usize = ((64 - Self::SUB_BITS as usize) + 1) << Self::SUB_BITS;
+
+    /// Returns the bucket of a value.
+    fn bucket(value: u64) -> usize {
+        if value < 1 << Self::SUB_BITS {
+            return value as usize;
+        }
+        let exp: u32 = 63 - value.leading_zeros();
+        let sub: u64 = (value >> (exp - Self::SUB_BITS)) & ((1 << Self::SUB_BITS) - 1);
+        (((exp - Self::SUB_BITS + 1) as usize) << Self::SUB_BITS) + sub as usize
+    }
+
+    /// Returns the smallest value of a bucket.
+    fn lower_bound(bucket: usize) -> u64 {
+        if bucket < 1 << Self::SUB_BITS {
+            return bucket as u64;
+        }
+        let exp: u32 = (bucket >> Self::SUB_BITS) as u32 + Self::SUB_BITS - 1;
+        let sub: u64 = (bucket & ((1 << Self::SUB_BITS) - 1)) as u64;
+        ((1 << Self::SUB_BITS) + sub) << (exp - Self::SUB_BITS)
+    }
+
+    /// Records a value.
+    #[inline]
+    fn record(&mut self, latency: Duration) {
+        self.buckets[Self::bucket(latency.as_nanos() as u64)] += 1;
+        self.count += 1;
+    }
+
+    /// Returns the value below which a fraction `q` of values fall, if there are any values.
+    fn percentile(&self, q: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank: u64 = ((q * self.count as f64).ceil() as u64).clamp(1, self.count);
+        let mut seen: u64 = 0;
+        for (bucket, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Some(Duration::from_nanos(Self::lower_bound(bucket)));
+            }
+        }
+        None
+    }
+}
+
+/// Associate Functions for Renderers
+impl Renderer {
+    /// Number of intervals shown on the throughput graph.
+    const WIDTH: usize = 60;
+
+    /// Number of rows of the throughput graph.
+    const HEIGHT: usize = 8;
+
+    /// Number of sources shown.
+    const TOP: usize = 5;
+
+    /// Creates a renderer.
+    fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            history: VecDeque::with_capacity(Self::WIDTH),
+            elapsed: Duration::ZERO,
+            timed: false,
+        }
+    }
+
+    /// Draws a sample over the previous one.
+    fn render(&mut self, sample: &Sample) {
+        let secs: f64 = sample.interval.as_secs_f64().max(f64::EPSILON);
+        let bps: f64 = sample.nbytes as f64 * 8.0 / secs;
+        if self.history.len() == Self::WIDTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(bps);
+        self.elapsed += sample.interval;
+
+        // Move home and clear the screen.
+        let mut screen: String = String::from("\x1b[H\x1b[2J");
+        let _ = writeln!(
+            screen,
+            "{} - {}/s, {:.0} packets/s (up {}s)\n",
+            self.title,
+            bits(bps),
+            sample.npackets as f64 / secs,
+            self.elapsed.as_secs()
+        );
+
+        // Throughput graph, with eighths of a row.
+        let max: f64 = self.history.iter().copied().fold(0.0, f64::max);
+        let _ = writeln!(screen, "Throughput (max {}/s)", bits(max));
+        for row in (0..Self::HEIGHT).rev() {
+            screen.push_str("  |");
+            for bps in &self.history {
+                let eighths: usize = if max > 0.0 {
+                    (bps / max * (Self::HEIGHT * 8) as f64).round() as usize
+                } else {
+                    0
+                };
+                screen.push(match eighths.saturating_sub(row * 8).min(8) {
+                    0 => ' ',
+                    n => ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'][n - 1],
+                });
+            }
+            screen.push('\n');
+        }
+        let _ = writeln!(screen, "  +{}\n", "-".repeat(Self::WIDTH));
+
+        // Latency percentiles, once the application timed some pop.
+        self.timed |= sample.latencies.count > 0;
+        if self.timed {
+            screen.push_str("Latency (pop):");
+            for (label, q) in [("p50", 0.5), ("p90", 0.9), ("p99", 0.99), ("p99.9", 0.999)] {
+                match sample.latencies.percentile(q) {
+                    Some(latency) => {
+                        let _ = write!(screen, "  {} {:?}", label, latency);
+                    }
+                    None => {
+                        let _ = write!(screen, "  {} -", label);
+                    }
+                }
+            }
+            screen.push('\n');
+        }
+
+        // Connections and sources.
+        if let Some(nconnections) = sample.nconnections {
+            let _ = writeln!(screen, "Connections: {}", nconnections);
+        }
+        if !sample.sources.is_empty() {
+            let mut sources: Vec<(&SocketAddr, &(usize, usize))> = sample.sources.iter().collect();
+            sources.sort_by(|a, b| b.1 .1.cmp(&a.1 .1).then(a.0.cmp(b.0)));
+            let _ = writeln!(screen, "Sources: {}\nTop sources:", sources.len());
+            for (addr, (npackets, nbytes)) in sources.iter().take(Self::TOP) {
+                let _ = writeln!(
+                    screen,
+                    "  {:<40} {:>10} packets {:>14} B",
+                    addr.to_string(),
+                    npackets,
+                    nbytes
+                );
+            }
+        }
+
+        let stdout: io::Stdout = io::stdout();
+        let mut stdout: io::StdoutLock = stdout.lock();
+        let _ = stdout.write_all(screen.as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Default Trait Implementation for Histograms
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; Self::NBUCKETS].into_boxed_slice(),
+            count: 0,
+        }
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Formats a number of bits with a unit.
+fn bits(n: f64) -> String {
+    match n {
+        n if n >= 1e9 => format!("{:.2} Gb", n / 1e9),
+        n if n >= 1e6 => format!("{:.2} Mb", n / 1e6),
+        n if n >= 1e3 => format!("{:.2} kb", n / 1e3),
+        n => format!("{:.0} b", n),
+    }
+}
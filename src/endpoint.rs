@@ -0,0 +1,100 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Context, Result};
+use ::std::{
+    ffi::CStr,
+    io,
+    net::{Ipv4Addr, SocketAddr, ToSocketAddrs},
+    ptr,
+    str::FromStr,
+};
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Resolves an endpoint into a socket address. An endpoint is one of:
+///
+/// - a literal address, such as `10.0.0.1:80` or `[2001:db8::1]:80`;
+/// - `:port`, for any address;
+/// - `iface:port`, for the IPv4 address of a network interface, such as `eth0:80`;
+/// - `host:port`, for an address of a host in `/etc/hosts` or in the DNS, IPv4 first.
+///
+/// Endpoints are resolved once, while arguments are parsed, so that the libOS never waits on a
+/// lookup.
+pub fn resolve(endpoint: &str) -> Result<SocketAddr> {
+    if let Ok(addr) = SocketAddr::from_str(endpoint) {
+        return Ok(addr);
+    }
+
+    let (host, port): (&str, &str) = match endpoint.rsplit_once(':') {
+        Some((host, port)) => (host, port),
+        None => bail!("invalid endpoint {:?} (expected host:port)", endpoint),
+    };
+    let port: u16 = match port.parse() {
+        Ok(port) => port,
+        Err(_) => bail!("invalid port in endpoint {:?}", endpoint),
+    };
+
+    if host.is_empty() {
+        return Ok(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port));
+    }
+    if let Some(ip) = interface_addr(host)? {
+        return Ok(SocketAddr::new(ip.into(), port));
+    }
+
+    let addrs: Vec<SocketAddr> = (host, port)
+        .to_socket_addrs()
+        .with_context(|| format!("failed to resolve {:?}", host))?
+        .collect();
+    // Prefer IPv4, which every libOS supports.
+    match addrs
+        .iter()
+        .find(|addr| addr.is_ipv4())
+        .or_else(|| addrs.first())
+    {
+        Some(addr) => Ok(*addr),
+        None => bail!("no address found for {:?}", host),
+    }
+}
+
+/// Returns the IPv4 address of the network interface named `name`, if there is one.
+fn interface_addr(name: &str) -> Result<Option<Ipv4Addr>> {
+    let mut ifaddrs: *mut libc::ifaddrs = ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut ifaddrs) } != 0 {
+        bail!(
+            "failed to list network interfaces: {}",
+            io::Error::last_os_error()
+        )
+    }
+
+    let mut exists: bool = false;
+    let mut addr: Option<Ipv4Addr> = None;
+    let mut cursor: *mut libc::ifaddrs = ifaddrs;
+    while !cursor.is_null() {
+        let ifa: &libc::ifaddrs = unsafe { &*cursor };
+        cursor = ifa.ifa_next;
+        if unsafe { CStr::from_ptr(ifa.ifa_name) }.to_bytes() != name.as_bytes() {
+            continue;
+        }
+        exists = true;
+        if !ifa.ifa_addr.is_null()
+            && unsafe { (*ifa.ifa_addr).sa_family } as libc::c_int == libc::AF_INET
+        {
+            let sin: &libc::sockaddr_in = unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in) };
+            addr = Some(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)));
+            break;
+        }
+    }
+    unsafe { libc::freeifaddrs(ifaddrs) };
+
+    if exists && addr.is_none() {
+        bail!("interface {:?} has no IPv4 address", name)
+    }
+    Ok(addr)
+}
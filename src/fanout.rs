@@ -0,0 +1,303 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Result};
+use ::std::{net::SocketAddr, str::FromStr};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Fan-Out Destination
+#[derive(Debug)]
+pub struct Destination {
+    /// Identifier, which stays valid when other destinations leave.
+    id: u64,
+    /// Address of the destination.
+    addr: SocketAddr,
+    /// Did the destination join through a control message?
+    subscribed: bool,
+    /// Number of copies pushed but not completed yet.
+    inflight: usize,
+    /// Number of copies sent.
+    nsent: usize,
+    /// Number of bytes sent.
+    nbytes: usize,
+    /// Number of copies skipped because the destination was lagging behind.
+    nskipped: usize,
+    /// Number of copies that failed.
+    nfailed: usize,
+}
+
+/// Fan-Out Table
+///
+/// Tracks the destinations that every datagram is replicated to. Each destination may have at
+/// most `window` copies in flight: copies beyond that are skipped, so that a slow destination
+/// does not hold back the others.
+#[derive(Debug)]
+pub struct FanOut {
+    /// Destinations.
+    destinations: Vec<Destination>,
+    /// Maximum number of copies in flight per destination.
+    window: usize,
+    /// Identifier of the next destination.
+    next_id: u64,
+}
+
+/// Fan-Out Control Message
+///
+/// Messages act on their sender only, so that nobody can point the feed at a third party.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    /// Adds the sender as a destination.
+    Subscribe,
+    /// Removes the sender from the destinations.
+    Unsubscribe,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Fan-Out Destinations
+impl Destination {
+    /// Returns the address of the target destination.
+    pub fn get_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the number of copies in flight to the target destination.
+    pub fn get_inflight(&self) -> usize {
+        self.inflight
+    }
+
+    /// Returns the number of copies sent to the target destination.
+    pub fn get_nsent(&self) -> usize {
+        self.nsent
+    }
+
+    /// Returns the number of bytes sent to the target destination.
+    pub fn get_nbytes(&self) -> usize {
+        self.nbytes
+    }
+
+    /// Returns the number of copies skipped for the target destination.
+    pub fn get_nskipped(&self) -> usize {
+        self.nskipped
+    }
+
+    /// Returns the number of copies to the target destination that failed.
+    pub fn get_nfailed(&self) -> usize {
+        self.nfailed
+    }
+
+    /// Asserts if the target destination joined through a control message.
+    pub fn is_subscribed(&self) -> bool {
+        self.subscribed
+    }
+}
+
+/// Associate Functions for Fan-Out Tables
+impl FanOut {
+    /// Creates a fan-out table with some static destinations.
+    pub fn new(addrs: &[SocketAddr], window: usize) -> Result<Self> {
+        if window == 0 {
+            bail!("invalid fan-out window")
+        }
+        let mut fanout: FanOut = Self {
+            destinations: Vec::new(),
+            window,
+            next_id: 0,
+        };
+        for addr in addrs {
+            fanout.add(*addr, false);
+        }
+        Ok(fanout)
+    }
+
+    /// Returns the destinations of the target fan-out table.
+    pub fn get_destinations(&self) -> &[Destination] {
+        &self.destinations
+    }
+
+    /// Asserts if an address is a destination.
+    pub fn contains(&self, addr: SocketAddr) -> bool {
+        self.destinations.iter().any(|d| d.addr == addr)
+    }
+
+    /// Adds a destination. Returns `false` if it was already there.
+    pub fn add(&mut self, addr: SocketAddr, subscribed: bool) -> bool {
+        if self.contains(addr) {
+            return false;
+        }
+        self.destinations.push(Destination {
+            id: self.next_id,
+            addr,
+            subscribed,
+            inflight: 0,
+            nsent: 0,
+            nbytes: 0,
+            nskipped: 0,
+            nfailed: 0,
+        });
+        self.next_id += 1;
+        true
+    }
+
+    /// Removes a destination that joined through a control message.
+    pub fn remove(&mut self, addr: SocketAddr) -> Result<()> {
+        match self.destinations.iter().position(|d| d.addr == addr) {
+            Some(i) if self.destinations[i].subscribed => {
+                self.destinations.remove(i);
+                Ok(())
+            }
+            Some(_) => bail!("{:?} is a static destination", addr),
+            None => bail!("{:?} is not subscribed", addr),
+        }
+    }
+
+    /// Reserves a slot for a copy to each destination that is keeping up. Returns the identifier
+    /// and address of these destinations.
+    pub fn reserve(&mut self) -> Vec<(u64, SocketAddr)> {
+        let window: usize = self.window;
+        self.destinations
+            .iter_mut()
+            .filter_map(|d| {
+                if d.inflight >= window {
+                    d.nskipped += 1;
+                    None
+                } else {
+                    d.inflight += 1;
+                    Some((d.id, d.addr))
+                }
+            })
+            .collect()
+    }
+
+    /// Accounts a copy to a destination, which either sent `nbytes` or failed. Either way, the
+    /// slot of the copy is released. Destinations that left meanwhile are ignored.
+    pub fn complete(&mut self, id: u64, nbytes: Option<usize>) {
+        if let Some(d) = self.destinations.iter_mut().find(|d| d.id == id) {
+            d.inflight -= 1;
+            match nbytes {
+                Some(nbytes) => {
+                    d.nsent += 1;
+                    d.nbytes += nbytes;
+                }
+                None => d.nfailed += 1,
+            }
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// From String Trait Implementation for Fan-Out Control Messages
+///
+/// Messages are `subscribe` or `unsubscribe`, without arguments.
+impl FromStr for Control {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut tokens = s.split_whitespace();
+        let command: &str = tokens.next().unwrap_or_default();
+        let control: Control = match command.to_ascii_lowercase().as_str() {
+            "subscribe" => Control::Subscribe,
+            "unsubscribe" => Control::Unsubscribe,
+            _ => bail!("unknown command {:?}", command),
+        };
+        if tokens.next().is_some() {
+            bail!(
+                "{} takes no arguments, as it applies to its sender",
+                command
+            )
+        }
+        Ok(control)
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the address of the `i`-th destination.
+    fn addr(i: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], 9000 + i))
+    }
+
+    #[test]
+    fn lagging_destinations_are_skipped() {
+        let mut fanout: FanOut = FanOut::new(&[addr(0), addr(1)], 2).unwrap();
+        let first: Vec<(u64, SocketAddr)> = fanout.reserve();
+        assert_eq!(first.len(), 2);
+        assert_eq!(fanout.reserve().len(), 2);
+
+        // Only the first destination completes its copies, so the second one is skipped.
+        fanout.complete(first[0].0, Some(16));
+        assert_eq!(fanout.reserve(), vec![first[0]]);
+        let destinations: &[Destination] = fanout.get_destinations();
+        assert_eq!(destinations[0].get_inflight(), 2);
+        assert_eq!(destinations[0].get_nsent(), 1);
+        assert_eq!(destinations[0].get_nbytes(), 16);
+        assert_eq!(destinations[1].get_inflight(), 2);
+        assert_eq!(destinations[1].get_nskipped(), 1);
+    }
+
+    #[test]
+    fn failed_copies_release_their_slot() {
+        let mut fanout: FanOut = FanOut::new(&[addr(0)], 1).unwrap();
+        let (id, _): (u64, SocketAddr) = fanout.reserve()[0];
+        fanout.complete(id, None);
+        assert_eq!(fanout.reserve().len(), 1);
+        let destination: &Destination = &fanout.get_destinations()[0];
+        assert_eq!(destination.get_nfailed(), 1);
+        assert_eq!(destination.get_nsent(), 0);
+    }
+
+    #[test]
+    fn only_subscribed_destinations_are_removed() {
+        let mut fanout: FanOut = FanOut::new(&[addr(0)], 1).unwrap();
+        assert!(fanout.add(addr(1), true));
+        assert!(!fanout.add(addr(1), true));
+        assert!(!fanout.add(addr(0), true));
+
+        assert!(fanout.remove(addr(0)).is_err());
+        assert!(fanout.remove(addr(2)).is_err());
+        let (id, _): (u64, SocketAddr) = fanout.reserve()[1];
+        fanout.remove(addr(1)).unwrap();
+        assert!(!fanout.contains(addr(1)));
+        assert!(fanout.contains(addr(0)));
+
+        // Copies to destinations that left are ignored, even if the address joins again.
+        assert!(fanout.add(addr(1), true));
+        fanout.complete(id, Some(16));
+        assert_eq!(fanout.get_destinations()[1].get_nsent(), 0);
+    }
+
+    #[test]
+    fn control_messages_apply_to_their_sender() {
+        assert_eq!("subscribe".parse::<Control>().unwrap(), Control::Subscribe);
+        assert_eq!(
+            " UNSUBSCRIBE\n".parse::<Control>().unwrap(),
+            Control::Unsubscribe
+        );
+        assert!("subscribe 10.0.0.1:9000".parse::<Control>().is_err());
+        assert!("join".parse::<Control>().is_err());
+        assert!("".parse::<Control>().is_err());
+    }
+
+    #[test]
+    fn empty_windows_are_rejected() {
+        assert!(FanOut::new(&[addr(0)], 0).is_err());
+    }
+}
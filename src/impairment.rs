@@ -0,0 +1,647 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::schedule::{parse_duration, Rate, Rng};
+use ::anyhow::{bail, Result};
+use ::std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Network Impairments
+///
+/// Impairments are parsed from comma-separated `key=value` lists, in the spirit of `tc netem`:
+///
+/// - `loss=1%`: random loss.
+/// - `ge=P/R[/H[/K]]`: burst loss (Gilbert-Elliott), where `P` and `R` are the probabilities of
+///   moving to the bad state and back to the good state, and `H` and `K` are the loss
+///   probabilities in the bad (default 100%) and good (default 0%) states.
+/// - `delay=10ms`: fixed delay.
+/// - `jitter=2ms`: delay varies uniformly by up to this amount around `delay`.
+/// - `dup=1%`: duplication.
+/// - `reorder=5%`: packets that are sent right away, overtaking delayed ones.
+/// - `corrupt=0.1%`: a random bit of the payload is flipped.
+/// - `rate=10Mbps`: bandwidth cap.
+///
+/// Probabilities may be written either as percentages or as fractions (`0.01`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Impairments {
+    /// Random loss probability.
+    loss: f64,
+    /// Burst loss model.
+    gilbert: Option<GilbertElliott>,
+    /// Fixed delay.
+    delay: Duration,
+    /// Delay variation.
+    jitter: Duration,
+    /// Duplication probability.
+    duplicate: f64,
+    /// Reordering probability.
+    reorder: f64,
+    /// Corruption probability.
+    corrupt: f64,
+    /// Bandwidth cap (in bits per second).
+    rate: Option<u64>,
+}
+
+/// Gilbert-Elliott Loss Model
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct GilbertElliott {
+    /// Probability of moving from the good to the bad state.
+    p: f64,
+    /// Probability of moving from the bad to the good state.
+    r: f64,
+    /// Loss probability in the bad state.
+    h: f64,
+    /// Loss probability in the good state.
+    k: f64,
+}
+
+/// Impairment Counters
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImpairmentCounters {
+    /// Number of packets that went through the impairer.
+    pub npackets: usize,
+    /// Number of packets lost at random.
+    pub nlost: usize,
+    /// Number of packets lost in bursts.
+    pub nburst_lost: usize,
+    /// Number of packets delayed.
+    pub ndelayed: usize,
+    /// Number of packets duplicated.
+    pub nduplicated: usize,
+    /// Number of packets reordered.
+    pub nreordered: usize,
+    /// Number of packets corrupted.
+    pub ncorrupted: usize,
+    /// Number of packets held back by the bandwidth cap.
+    pub nthrottled: usize,
+}
+
+/// Impairer
+///
+/// Applies impairments to the packets of one direction of a link.
+#[derive(Debug)]
+pub struct Impairer {
+    /// Impairments.
+    impairments: Impairments,
+    /// Random number generator.
+    rng: Rng,
+    /// Is the burst loss model in the bad state?
+    bad: bool,
+    /// Instant at which the link is done sending the previous packet.
+    link_free: Option<Instant>,
+    /// Counters.
+    counters: ImpairmentCounters,
+}
+
+/// Delay Queue
+///
+/// Holds items until their deadline. Items that share a deadline are released in insertion
+/// order.
+#[derive(Debug)]
+pub struct DelayQueue<T> {
+    /// Pending items.
+    heap: BinaryHeap<Delayed<T>>,
+    /// Sequence number of the next item.
+    seq: u64,
+}
+
+/// Delayed Item
+#[derive(Debug)]
+struct Delayed<T> {
+    /// Release time.
+    deadline: Instant,
+    /// Sequence number.
+    seq: u64,
+    /// Item.
+    item: T,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Network Impairments
+impl Impairments {
+    /// Asserts if the target impairments leave packets untouched.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Asserts if the target impairments hold some packets back, which then have to be sent
+    /// later on.
+    pub fn delays(&self) -> bool {
+        !self.delay.is_zero() || !self.jitter.is_zero() || self.rate.is_some()
+    }
+}
+
+/// Associate Functions for Impairers
+impl Impairer {
+    /// Creates an impairer.
+    pub fn new(impairments: Impairments, seed: u64) -> Self {
+        Self {
+            impairments,
+            rng: Rng::new(seed),
+            bad: false,
+            link_free: None,
+            counters: ImpairmentCounters::default(),
+        }
+    }
+
+    /// Asserts if the target impairer leaves packets untouched.
+    pub fn is_noop(&self) -> bool {
+        self.impairments.is_empty()
+    }
+
+    /// Returns the counters of the target impairer.
+    pub fn get_counters(&self) -> &ImpairmentCounters {
+        &self.counters
+    }
+
+    /// Impairs a packet received at `now`. Returns the copies of the packet that should be sent,
+    /// along with the instant at which each of them should leave.
+    pub fn process(&mut self, buf: &[u8], now: Instant) -> Vec<(Instant, Vec<u8>)> {
+        self.counters.npackets += 1;
+
+        // Random loss.
+        if self.chance(self.impairments.loss) {
+            self.counters.nlost += 1;
+            return Vec::new();
+        }
+
+        // Burst loss.
+        if let Some(ge) = self.impairments.gilbert {
+            self.bad = if self.bad {
+                !self.chance(ge.r)
+            } else {
+                self.chance(ge.p)
+            };
+            if self.chance(if self.bad { ge.h } else { ge.k }) {
+                self.counters.nburst_lost += 1;
+                return Vec::new();
+            }
+        }
+
+        let mut buf: Vec<u8> = buf.to_vec();
+
+        // Corruption.
+        if !buf.is_empty() && self.chance(self.impairments.corrupt) {
+            let bit: usize = (self.rng.next_u64() % (buf.len() as u64 * 8)) as usize;
+            buf[bit / 8] ^= 1 << (bit % 8);
+            self.counters.ncorrupted += 1;
+        }
+
+        // Duplication.
+        let ncopies: usize = if self.chance(self.impairments.duplicate) {
+            self.counters.nduplicated += 1;
+            2
+        } else {
+            1
+        };
+
+        let mut packets: Vec<(Instant, Vec<u8>)> = Vec::with_capacity(ncopies);
+        for copy in 0..ncopies {
+            // Delay, which reordered packets skip.
+            let mut departure: Instant = now;
+            if self.chance(self.impairments.reorder) {
+                self.counters.nreordered += 1;
+            } else {
+                let delay: Duration = self.delay();
+                if delay > Duration::ZERO {
+                    self.counters.ndelayed += 1;
+                    departure += delay;
+                }
+            }
+
+            // Bandwidth cap.
+            if let Some(rate) = self.impairments.rate {
+                if let Some(link_free) = self.link_free {
+                    if link_free > departure {
+                        self.counters.nthrottled += 1;
+                        departure = link_free;
+                    }
+                }
+                let nbits: u64 = buf.len() as u64 * 8;
+                self.link_free =
+                    Some(departure + Duration::from_nanos(nbits * 1_000_000_000 / rate));
+            }
+
+            let payload: Vec<u8> = if copy + 1 < ncopies {
+                buf.clone()
+            } else {
+                std::mem::take(&mut buf)
+            };
+            packets.push((departure, payload));
+        }
+        packets
+    }
+
+    /// Draws a random event that happens with probability `p`.
+    fn chance(&mut self, p: f64) -> bool {
+        p > 0.0 && self.rng.next_f64() < p
+    }
+
+    /// Draws a delay.
+    fn delay(&mut self) -> Duration {
+        let delay: Duration = self.impairments.delay;
+        let jitter: Duration = self.impairments.jitter;
+        if jitter == Duration::ZERO {
+            return delay;
+        }
+        let offset: f64 = (self.rng.next_f64() * 2.0 - 1.0) * jitter.as_secs_f64();
+        Duration::from_secs_f64((delay.as_secs_f64() + offset).max(0.0))
+    }
+}
+
+/// Associate Functions for Delay Queues
+impl<T> DelayQueue<T> {
+    /// Creates an empty delay queue.
+    pub fn new() -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            seq: 0,
+        }
+    }
+
+    /// Returns the number of pending items.
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Asserts if the target delay queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Returns the earliest deadline.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.heap.peek().map(|d| d.deadline)
+    }
+
+    /// Holds an item until `deadline`.
+    pub fn push(&mut self, deadline: Instant, item: T) {
+        self.heap.push(Delayed {
+            deadline,
+            seq: self.seq,
+            item,
+        });
+        self.seq += 1;
+    }
+
+    /// Removes and returns an item whose deadline has passed.
+    pub fn pop_expired(&mut self, now: Instant) -> Option<T> {
+        match self.heap.peek() {
+            Some(d) if d.deadline <= now => self.heap.pop().map(|d| d.item),
+            _ => None,
+        }
+    }
+
+    /// Drops items that do not satisfy a predicate.
+    pub fn retain(&mut self, f: impl Fn(&T) -> bool) {
+        let heap: Vec<Delayed<T>> = std::mem::take(&mut self.heap).into_vec();
+        self.heap = heap.into_iter().filter(|d| f(&d.item)).collect();
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// From String Trait Implementation for Network Impairments
+impl FromStr for Impairments {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut impairments: Impairments = Impairments::default();
+        for token in s.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            match token.split_once('=') {
+                Some(("loss", value)) => impairments.loss = parse_probability(value)?,
+                Some(("ge", value)) => impairments.gilbert = Some(value.parse()?),
+                Some(("delay", value)) => impairments.delay = parse_duration(value)?,
+                Some(("jitter", value)) => impairments.jitter = parse_duration(value)?,
+                Some(("dup", value)) => impairments.duplicate = parse_probability(value)?,
+                Some(("reorder", value)) => impairments.reorder = parse_probability(value)?,
+                Some(("corrupt", value)) => impairments.corrupt = parse_probability(value)?,
+                Some(("rate", value)) => match value.parse()? {
+                    Rate::Bitrate(bps) if bps > 0 => impairments.rate = Some(bps),
+                    _ => bail!("invalid bandwidth cap: {:?}", value),
+                },
+                _ => bail!("invalid impairment: {:?}", token),
+            }
+        }
+        Ok(impairments)
+    }
+}
+
+/// From String Trait Implementation for Gilbert-Elliott Loss Models
+impl FromStr for GilbertElliott {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let values: Vec<f64> = s
+            .split('/')
+            .map(parse_probability)
+            .collect::<Result<Vec<f64>>>()?;
+        match values[..] {
+            [p, r] => Ok(Self {
+                p,
+                r,
+                h: 1.0,
+                k: 0.0,
+            }),
+            [p, r, h] => Ok(Self { p, r, h, k: 0.0 }),
+            [p, r, h, k] => Ok(Self { p, r, h, k }),
+            _ => bail!("invalid burst loss model: {:?}", s),
+        }
+    }
+}
+
+/// Display Trait Implementation for Network Impairments
+impl fmt::Display for Impairments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "none");
+        }
+        let mut params: Vec<String> = Vec::new();
+        if self.loss > 0.0 {
+            params.push(format!("loss={}%", self.loss * 100.0));
+        }
+        if let Some(ge) = self.gilbert {
+            params.push(format!(
+                "ge={}%/{}%/{}%/{}%",
+                ge.p * 100.0,
+                ge.r * 100.0,
+                ge.h * 100.0,
+                ge.k * 100.0
+            ));
+        }
+        if self.delay > Duration::ZERO {
+            params.push(format!("delay={:?}", self.delay));
+        }
+        if self.jitter > Duration::ZERO {
+            params.push(format!("jitter={:?}", self.jitter));
+        }
+        if self.duplicate > 0.0 {
+            params.push(format!("dup={}%", self.duplicate * 100.0));
+        }
+        if self.reorder > 0.0 {
+            params.push(format!("reorder={}%", self.reorder * 100.0));
+        }
+        if self.corrupt > 0.0 {
+            params.push(format!("corrupt={}%", self.corrupt * 100.0));
+        }
+        if let Some(rate) = self.rate {
+            params.push(format!("rate={}bps", rate));
+        }
+        write!(f, "{}", params.join(","))
+    }
+}
+
+/// Display Trait Implementation for Impairment Counters
+impl fmt::Display for ImpairmentCounters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} packets, {} lost, {} burst lost, {} delayed, {} duplicated, {} reordered, \
+             {} corrupted, {} throttled",
+            self.npackets,
+            self.nlost,
+            self.nburst_lost,
+            self.ndelayed,
+            self.nduplicated,
+            self.nreordered,
+            self.ncorrupted,
+            self.nthrottled
+        )
+    }
+}
+
+/// Default Trait Implementation for Delay Queues
+impl<T> Default for DelayQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Equality Trait Implementation for Delayed Items
+impl<T> PartialEq for Delayed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline && self.seq == other.seq
+    }
+}
+
+/// Equality Trait Implementation for Delayed Items
+impl<T> Eq for Delayed<T> {}
+
+/// Ordering Trait Implementation for Delayed Items
+impl<T> PartialOrd for Delayed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Ordering Trait Implementation for Delayed Items
+///
+/// The order is reversed, so that the earliest item sits at the top of the (max) heap.
+impl<T> Ord for Delayed<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.deadline, other.seq).cmp(&(self.deadline, self.seq))
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Parses a probability, written either as a percentage (`1%`) or as a fraction (`0.01`).
+fn parse_probability(s: &str) -> Result<f64> {
+    let s: &str = s.trim();
+    let p: f64 = match s.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>()? / 100.0,
+        None => s.parse()?,
+    };
+    if !(0.0..=1.0).contains(&p) {
+        bail!("invalid probability: {:?}", s)
+    }
+    Ok(p)
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Number of packets pushed through impairers whose effects are random.
+    const NPACKETS: usize = 100_000;
+
+    /// Creates an impairer out of a specification.
+    fn build(spec: &str) -> Impairer {
+        Impairer::new(spec.parse().unwrap(), 42)
+    }
+
+    #[test]
+    fn impairments_parse() {
+        let impairments: Impairments =
+            "loss=1%,ge=1%/10%,delay=10ms,jitter=2ms,dup=0.5,reorder=5%,corrupt=0.1%,rate=10Mbps"
+                .parse()
+                .unwrap();
+        assert_eq!(impairments.loss, 0.01);
+        assert_eq!(
+            impairments.gilbert,
+            Some(GilbertElliott {
+                p: 0.01,
+                r: 0.1,
+                h: 1.0,
+                k: 0.0
+            })
+        );
+        assert_eq!(impairments.delay, Duration::from_millis(10));
+        assert_eq!(impairments.jitter, Duration::from_millis(2));
+        assert_eq!(impairments.duplicate, 0.5);
+        assert_eq!(impairments.rate, Some(10_000_000));
+        assert_eq!(
+            impairments.to_string().parse::<Impairments>().unwrap(),
+            impairments
+        );
+
+        assert!("".parse::<Impairments>().unwrap().is_empty());
+        assert_eq!(Impairments::default().to_string(), "none");
+        for invalid in [
+            "loss=2",
+            "loss=-1%",
+            "ge=1%",
+            "ge=1/2/3/4/5",
+            "rate=10us",
+            "lag=1ms",
+        ] {
+            assert!(invalid.parse::<Impairments>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn random_loss_drops_its_share() {
+        let mut impairer: Impairer = build("loss=10%");
+        let now: Instant = Instant::now();
+        let nsent: usize = (0..NPACKETS)
+            .map(|_| impairer.process(&[0; 64], now).len())
+            .sum();
+        let nlost: usize = impairer.get_counters().nlost;
+        assert_eq!(nsent + nlost, NPACKETS);
+        assert!((9_000..11_000).contains(&nlost), "{} lost", nlost);
+    }
+
+    #[test]
+    fn burst_loss_comes_in_bursts() {
+        // Bursts last 1 / r = 10 packets, and a share of p / (p + r) = 1 / 11 of the time.
+        let mut impairer: Impairer = build("ge=1%/10%");
+        let now: Instant = Instant::now();
+        let lost: Vec<bool> = (0..NPACKETS)
+            .map(|_| impairer.process(&[0; 64], now).is_empty())
+            .collect();
+        let nlost: usize = lost.iter().filter(|lost| **lost).count();
+        let nbursts: usize = lost.windows(2).filter(|w| !w[0] && w[1]).count();
+        assert_eq!(impairer.get_counters().nburst_lost, nlost);
+        assert!((7_500..10_500).contains(&nlost), "{} lost", nlost);
+        let mean: f64 = nlost as f64 / nbursts as f64;
+        assert!((8.0..12.0).contains(&mean), "bursts of {} packets", mean);
+
+        // Losses of the good state are independent.
+        let mut impairer: Impairer = build("ge=0%/100%/100%/10%");
+        let nlost: usize = (0..NPACKETS)
+            .filter(|_| impairer.process(&[0; 64], now).is_empty())
+            .count();
+        assert!((9_000..11_000).contains(&nlost), "{} lost", nlost);
+    }
+
+    #[test]
+    fn delays_stay_within_jitter() {
+        let mut impairer: Impairer = build("delay=10ms,jitter=2ms");
+        let now: Instant = Instant::now();
+        for _ in 0..1000 {
+            let packets: Vec<(Instant, Vec<u8>)> = impairer.process(&[1, 2, 3], now);
+            assert_eq!(packets.len(), 1);
+            let delay: Duration = packets[0].0 - now;
+            assert!(delay >= Duration::from_millis(8) && delay <= Duration::from_millis(12));
+            assert_eq!(packets[0].1, [1, 2, 3]);
+        }
+        assert_eq!(impairer.get_counters().ndelayed, 1000);
+    }
+
+    #[test]
+    fn reordered_packets_skip_the_delay() {
+        let mut impairer: Impairer = build("delay=10ms,reorder=100%");
+        let now: Instant = Instant::now();
+        assert_eq!(impairer.process(&[0], now)[0].0, now);
+        assert_eq!(impairer.get_counters().nreordered, 1);
+        assert_eq!(impairer.get_counters().ndelayed, 0);
+    }
+
+    #[test]
+    fn duplicates_and_corruption_alter_copies() {
+        let now: Instant = Instant::now();
+        let mut impairer: Impairer = build("dup=100%");
+        let packets: Vec<(Instant, Vec<u8>)> = impairer.process(&[7; 16], now);
+        assert_eq!(packets, [(now, vec![7; 16]), (now, vec![7; 16])]);
+
+        // A single bit is flipped.
+        let mut impairer: Impairer = build("corrupt=100%");
+        let packets: Vec<(Instant, Vec<u8>)> = impairer.process(&[0; 16], now);
+        let nbits: u32 = packets[0].1.iter().map(|b| b.count_ones()).sum();
+        assert_eq!(nbits, 1);
+        assert!(impairer.process(&[], now)[0].1.is_empty());
+    }
+
+    #[test]
+    fn bandwidth_cap_spaces_packets() {
+        // At 8 Mbps, 1000 B take 1 ms.
+        let mut impairer: Impairer = build("rate=8Mbps");
+        let now: Instant = Instant::now();
+        let departures: Vec<Instant> = (0..3)
+            .map(|_| impairer.process(&[0; 1000], now)[0].0)
+            .collect();
+        let ms = |ms: u64| now + Duration::from_millis(ms);
+        assert_eq!(departures, [ms(0), ms(1), ms(2)]);
+        assert_eq!(impairer.get_counters().nthrottled, 2);
+
+        // An idle link sends right away.
+        assert_eq!(impairer.process(&[0; 1000], ms(10))[0].0, ms(10));
+    }
+
+    #[test]
+    fn delay_queues_release_in_deadline_order() {
+        let now: Instant = Instant::now();
+        let ms = |ms: u64| now + Duration::from_millis(ms);
+        let mut queue: DelayQueue<&str> = DelayQueue::new();
+        queue.push(ms(20), "c");
+        queue.push(ms(10), "a");
+        queue.push(ms(10), "b");
+        queue.push(ms(30), "d");
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.next_deadline(), Some(ms(10)));
+
+        assert_eq!(queue.pop_expired(ms(5)), None);
+        assert_eq!(queue.pop_expired(ms(20)), Some("a"));
+        assert_eq!(queue.pop_expired(ms(20)), Some("b"));
+        assert_eq!(queue.pop_expired(ms(20)), Some("c"));
+        assert_eq!(queue.pop_expired(ms(20)), None);
+
+        queue.retain(|item| *item != "d");
+        assert!(queue.is_empty());
+        assert_eq!(queue.next_deadline(), None);
+    }
+}
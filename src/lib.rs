@@ -0,0 +1,33 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+#![cfg_attr(feature = "strict", deny(warnings))]
+#![deny(clippy::all)]
+
+//==============================================================================
+// Exports
+//==============================================================================
+
+pub mod backend;
+pub mod balancer;
+pub mod clock;
+pub mod config;
+pub mod control;
+pub mod dashboard;
+pub mod endpoint;
+pub mod fanout;
+pub mod impairment;
+pub mod logging;
+pub mod loopback;
+pub mod placement;
+pub mod posix;
+pub mod rules;
+pub mod scenario;
+pub mod schedule;
+pub mod session;
+pub mod sga;
+pub mod sim;
+pub mod splice;
+pub mod timer;
+pub mod tracer;
+pub mod workers;
@@ -0,0 +1,461 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Context, Result};
+use ::clap::{Arg, ArgMatches};
+use ::demikernel::QDesc;
+use ::log::{LevelFilter, Log, Metadata, Record};
+use ::std::{
+    cell::Cell,
+    env,
+    fmt::Write as _,
+    fs::OpenOptions,
+    io::{self, Write},
+    net::SocketAddr,
+    ptr,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Mutex, RwLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Logging
+///
+/// Messages go through the `log` macros, and are written to the standard error or appended to a
+/// file, either as text or as one JSON object per line. Which messages are written is set by a
+/// filter in the style of `env_logger`: a comma-separated list of a default level and of
+/// `target=level` directives, such as `warn,apps::balancer=debug`. Each `-v` raises the default
+/// level by one step, and each `-q` lowers it.
+///
+/// Per-connection events are logged with [`event!`](crate::event), which attaches the queue
+/// descriptor and the peer address of the connection to the message.
+#[derive(Debug)]
+pub struct Logging {
+    /// Filter on messages.
+    filter: Filter,
+    /// Format of messages.
+    format: Format,
+    /// File that messages are appended to, instead of the standard error.
+    file: Option<String>,
+}
+
+/// Log Filter
+#[derive(Clone, Debug)]
+struct Filter {
+    /// Level of targets that no directive matches.
+    level: LevelFilter,
+    /// Levels of targets, by target prefix.
+    directives: Vec<(String, LevelFilter)>,
+}
+
+/// Log Format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable text.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Context of a Connection
+#[derive(Clone, Copy, Debug)]
+struct Connection {
+    /// Queue descriptor of the connection.
+    qd: QDesc,
+    /// Peer address of the connection, if known.
+    peer: Option<SocketAddr>,
+}
+
+/// Logger
+struct Logger {
+    /// Filter on messages, which may be replaced at runtime.
+    filter: RwLock<Filter>,
+    /// Format of messages.
+    format: Format,
+    /// Where messages are written.
+    sink: Mutex<Box<dyn Write + Send>>,
+}
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Level filters, from the quietest to the most verbose.
+const LEVELS: [LevelFilter; 6] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+/// Logger of the process, once installed.
+static LOGGER: AtomicPtr<Logger> = AtomicPtr::new(ptr::null_mut());
+
+thread_local! {
+    /// Connection that messages of the calling thread are about.
+    static CONNECTION: Cell<Option<Connection>> = const { Cell::new(None) };
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Logging
+impl Logging {
+    /// Environment variable that sets the filter when `--log` is not given.
+    const RUST_LOG: &'static str = "RUST_LOG";
+
+    /// Returns command line arguments that configure logging.
+    pub fn args<'help>() -> [Arg<'help>; 5] {
+        [
+            Arg::new("log")
+                .long("log")
+                .takes_value(true)
+                .required(false)
+                .value_name("FILTER")
+                .help("Sets which messages are logged (e.g. warn,apps::balancer=debug)"),
+            Arg::new("log_format")
+                .long("log_format")
+                .takes_value(true)
+                .required(false)
+                .value_name("text|json")
+                .help("Sets the format of logged messages"),
+            Arg::new("log_file")
+                .long("log_file")
+                .takes_value(true)
+                .required(false)
+                .value_name("FILE")
+                .help("Appends logged messages to a file instead of the standard error"),
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .takes_value(false)
+                .multiple_occurrences(true)
+                .required(false)
+                .help("Logs more messages (may be repeated)"),
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .takes_value(false)
+                .multiple_occurrences(true)
+                .required(false)
+                .help("Logs fewer messages (may be repeated)"),
+        ]
+    }
+
+    /// Builds a logging configuration from command line arguments. The filter defaults to the
+    /// one in `RUST_LOG`, and then to `info`.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        let mut filter: Filter = match matches.value_of("log") {
+            Some(filter) => Filter::from_str(filter)?,
+            None => match env::var(Self::RUST_LOG) {
+                Ok(filter) => Filter::from_str(&filter)
+                    .with_context(|| format!("invalid {}", Self::RUST_LOG))?,
+                Err(_) => Filter::default(),
+            },
+        };
+        filter.shift(
+            matches.occurrences_of("verbose") as isize - matches.occurrences_of("quiet") as isize,
+        );
+
+        let format: Format = match matches.value_of("log_format") {
+            Some(format) => Format::from_str(format)?,
+            None => Format::Text,
+        };
+
+        Ok(Self {
+            filter,
+            format,
+            file: matches.value_of("log_file").map(|file| file.to_string()),
+        })
+    }
+
+    /// Installs the target configuration as the logger of the process.
+    pub fn apply(self) -> Result<()> {
+        let sink: Box<dyn Write + Send> = match &self.file {
+            Some(path) => Box::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("failed to open log file {:?}", path))?,
+            ),
+            None => Box::new(io::stderr()),
+        };
+        let max_level: LevelFilter = self.filter.max_level();
+        let logger: &'static Logger = Box::leak(Box::new(Logger {
+            filter: RwLock::new(self.filter),
+            format: self.format,
+            sink: Mutex::new(sink),
+        }));
+        if log::set_logger(logger).is_err() {
+            bail!("a logger is already installed")
+        }
+        LOGGER.store(logger as *const Logger as *mut Logger, Ordering::Release);
+        log::set_max_level(max_level);
+        Ok(())
+    }
+
+    /// Replaces the filter of the installed logger, e.g. to raise its level while it runs.
+    pub fn set_filter(filter: &str) -> Result<()> {
+        let filter: Filter = Filter::from_str(filter)?;
+        let logger: &Logger = match unsafe { LOGGER.load(Ordering::Acquire).as_ref() } {
+            Some(logger) => logger,
+            None => bail!("no logger is installed"),
+        };
+        let max_level: LevelFilter = filter.max_level();
+        match logger.filter.write() {
+            Ok(mut current) => *current = filter,
+            Err(_) => bail!("failed to replace log filter"),
+        }
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+/// Associate Functions for Log Filters
+impl Filter {
+    /// Returns the level of messages of a target, from the directive with the longest matching
+    /// prefix.
+    fn level(&self, target: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .filter(|(prefix, _)| target == prefix || target.starts_with(&format!("{}::", prefix)))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level)
+    }
+
+    /// Returns the most verbose level of the target filter.
+    fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, Ord::max)
+    }
+
+    /// Raises (positive `steps`) or lowers (negative `steps`) the default level.
+    fn shift(&mut self, steps: isize) {
+        let current: isize = LEVELS
+            .iter()
+            .position(|level| *level == self.level)
+            .unwrap_or(0) as isize;
+        let shifted: isize = (current + steps).clamp(0, LEVELS.len() as isize - 1);
+        self.level = LEVELS[shifted as usize];
+    }
+}
+
+/// Associate Functions for Loggers
+impl Logger {
+    /// Formats a message as a line of text.
+    fn text(record: &Record, connection: Option<Connection>) -> String {
+        let mut line: String = format!(
+            "[{} {:<5} {}] {}",
+            timestamp(SystemTime::now()),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        if let Some(connection) = connection {
+            let _ = write!(line, " qd={}", u32::from(connection.qd));
+            if let Some(peer) = connection.peer {
+                let _ = write!(line, " peer={}", peer);
+            }
+        }
+        line.push('\n');
+        line
+    }
+
+    /// Formats a message as a JSON object.
+    fn json(record: &Record, connection: Option<Connection>) -> String {
+        let mut line: String = format!(
+            "{{\"ts\":\"{}\",\"level\":\"{}\",\"target\":{},\"message\":{}",
+            timestamp(SystemTime::now()),
+            record.level(),
+            json_string(record.target()),
+            json_string(&record.args().to_string())
+        );
+        if let Some(connection) = connection {
+            let _ = write!(line, ",\"qd\":{}", u32::from(connection.qd));
+            if let Some(peer) = connection.peer {
+                let _ = write!(line, ",\"peer\":\"{}\"", peer);
+            }
+        }
+        line.push_str("}\n");
+        line
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Default Trait Implementation for Log Filters
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            directives: Vec::new(),
+        }
+    }
+}
+
+/// From String Trait Implementation for Log Filters
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter: Filter = Filter::default();
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let level: LevelFilter = match LevelFilter::from_str(level) {
+                        Ok(level) => level,
+                        Err(_) => bail!("invalid log level {:?} for {:?}", level, target),
+                    };
+                    filter.directives.push((target.to_string(), level));
+                }
+                None => match LevelFilter::from_str(directive) {
+                    Ok(level) => filter.level = level,
+                    Err(_) => bail!("invalid log level {:?}", directive),
+                },
+            }
+        }
+        Ok(filter)
+    }
+}
+
+/// From String Trait Implementation for Log Formats
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => bail!("invalid log format {:?} (expected text or json)", s),
+        }
+    }
+}
+
+/// Log Trait Implementation for Loggers
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        match self.filter.read() {
+            Ok(filter) => metadata.level() <= filter.level(metadata.target()),
+            Err(_) => false,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let connection: Option<Connection> = CONNECTION.with(|c| c.get());
+        let line: String = match self.format {
+            Format::Text => Self::text(record, connection),
+            Format::Json => Self::json(record, connection),
+        };
+        if let Ok(mut sink) = self.sink.lock() {
+            let _ = sink.write_all(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut sink) = self.sink.lock() {
+            let _ = sink.flush();
+        }
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Runs `f` with messages that it logs on the calling thread attached to a connection. This is
+/// what [`event!`](crate::event) expands to.
+pub fn with_connection<R>(qd: QDesc, peer: Option<SocketAddr>, f: impl FnOnce() -> R) -> R {
+    let previous: Option<Connection> =
+        CONNECTION.with(|c| c.replace(Some(Connection { qd, peer })));
+    let result: R = f();
+    CONNECTION.with(|c| c.set(previous));
+    result
+}
+
+/// Formats a point in time as an RFC 3339 timestamp in UTC, with microseconds.
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs: u64 = since_epoch.as_secs();
+    let (days, secs_of_day): (u64, u64) = (secs / 86400, secs % 86400);
+
+    // Convert days since the epoch to a civil date.
+    let z: u64 = days + 719468;
+    let era: u64 = z / 146097;
+    let doe: u64 = z - era * 146097;
+    let yoe: u64 = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy: u64 = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp: u64 = (5 * doy + 2) / 153;
+    let day: u64 = doy - (153 * mp + 2) / 5 + 1;
+    let month: u64 = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year: u64 = yoe + era * 400 + (month <= 2) as u64;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_micros()
+    )
+}
+
+/// Formats a string as a JSON string literal.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut quoted: String = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+//==============================================================================
+// Macros
+//==============================================================================
+
+/// Logs a message about a connection, given its queue descriptor and its peer address if known:
+///
+/// ```ignore
+/// event!(Level::Debug, qd, Some(peer), "connection accepted");
+/// ```
+#[macro_export]
+macro_rules! event {
+    ($lvl:expr, $qd:expr, $peer:expr, $($arg:tt)+) => {
+        $crate::logging::with_connection($qd, $peer, || ::log::log!($lvl, $($arg)+))
+    };
+}
@@ -0,0 +1,745 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::backend::{Backend, Buffer, Fail, OperationResult};
+use ::anyhow::Result;
+use ::demikernel::{QDesc, QToken};
+use ::std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    rc::Rc,
+    thread,
+    time::Duration,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Identifier of a Socket in a Network
+type SocketId = usize;
+
+/// State of a Socket in a Network
+#[derive(Debug)]
+enum State {
+    /// Stream socket that is neither listening nor connected.
+    Unconnected,
+    /// Listening stream socket, along with connections that are yet to be accepted.
+    Listening(VecDeque<SocketId>),
+    /// Connected stream socket, along with its peer, if still open, and received bytes.
+    Connected(Option<SocketId>, VecDeque<u8>),
+    /// Datagram socket, along with received datagrams and their senders.
+    Datagram(VecDeque<(SocketAddr, Vec<u8>)>),
+}
+
+/// Socket in a Network
+#[derive(Debug)]
+struct NetSocket {
+    /// Local address, if bound.
+    local: Option<SocketAddr>,
+    /// State of the socket.
+    state: State,
+    /// Did the socket shut its sending side down?
+    shut: bool,
+}
+
+/// Sockets of a Network
+#[derive(Debug, Default)]
+struct Fabric {
+    /// Sockets, indexed by identifier. Closed sockets leave a hole.
+    sockets: Vec<Option<NetSocket>>,
+    /// Next ephemeral port.
+    next_port: u16,
+}
+
+/// In-Memory Network
+///
+/// Sockets of every loopback backend created on the same network can reach each other, whatever
+/// the addresses that they are bound to. Data is delivered as soon as it is pushed, in order, and
+/// is never lost, so runs over the network are deterministic.
+#[derive(Clone, Debug, Default)]
+pub struct Network(Rc<RefCell<Fabric>>);
+
+/// Pending Operation
+#[derive(Debug)]
+enum Op {
+    /// Accept a connection.
+    Accept,
+    /// Pop data.
+    Pop,
+    /// Operation that completed on submission.
+    Done(OperationResult),
+}
+
+/// Loopback Backend
+///
+/// Runs operations on an in-memory network. Both ends of an exchange are driven from the same
+/// thread, typically by a test that creates a client and a server on the same [Network].
+///
+/// Waiting never blocks, as nothing else could make progress in the meantime: when none of the
+/// operations waited on can complete, the wait fails with `EWOULDBLOCK`. That only means that the
+/// backend is idle. The operations stay pending, and are waited on again once the other end has
+/// made progress. Timed waits time out right away instead.
+///
+/// Applications that run over the backend, through `--io_backend loopback`, are alone on their
+/// network. Once they are idle, nothing can happen anymore besides timeouts, so timed waits sleep
+/// for their timeout and other waits fail with `ESHUTDOWN`, so that the application winds down.
+#[derive(Debug)]
+pub struct Loopback {
+    /// Underlying network.
+    network: Network,
+    /// Sockets of the network that are open through this backend.
+    sockets: HashMap<QDesc, SocketId>,
+    /// Pending operations, along with the socket they run on.
+    ops: HashMap<QToken, (QDesc, Op)>,
+    /// Next socket descriptor.
+    next_qd: u32,
+    /// Next operation token.
+    next_qt: u64,
+    /// Is the backend alone on its network?
+    alone: bool,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Fabrics
+impl Fabric {
+    /// First ephemeral port.
+    const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+    /// Maximum number of bytes returned by a pop on a stream socket.
+    const MAX_POP_SIZE: usize = 65536;
+
+    /// Adds a socket.
+    fn insert(&mut self, state: State) -> SocketId {
+        self.sockets.push(Some(NetSocket {
+            local: None,
+            state,
+            shut: false,
+        }));
+        self.sockets.len() - 1
+    }
+
+    /// Returns a socket.
+    fn get(&mut self, id: SocketId) -> Result<&mut NetSocket, Fail> {
+        match self.sockets.get_mut(id) {
+            Some(Some(socket)) => Ok(socket),
+            _ => Err(Fail::new(libc::EBADF, "invalid queue descriptor")),
+        }
+    }
+
+    /// Looks up the socket bound to an address that matches `addr`, among those for which
+    /// `filter` holds.
+    fn lookup(&self, addr: SocketAddr, filter: impl Fn(&State) -> bool) -> Option<SocketId> {
+        self.sockets.iter().position(|socket| match socket {
+            Some(NetSocket {
+                local: Some(local),
+                state,
+                ..
+            }) => {
+                local.port() == addr.port()
+                    && (local.ip() == addr.ip() || local.ip().is_unspecified())
+                    && filter(state)
+            }
+            _ => false,
+        })
+    }
+
+    /// Binds a socket to a local address, picking an ephemeral port if none is given.
+    fn bind(&mut self, id: SocketId, local: SocketAddr) -> Result<SocketAddr, Fail> {
+        let is_datagram = |state: &State| matches!(state, State::Datagram(_));
+        let datagram: bool = is_datagram(&self.get(id)?.state);
+        let local: SocketAddr = match local.port() {
+            0 => loop {
+                if self.next_port < Self::FIRST_EPHEMERAL_PORT {
+                    self.next_port = Self::FIRST_EPHEMERAL_PORT;
+                }
+                let port: u16 = self.next_port;
+                self.next_port = self.next_port.wrapping_add(1);
+                let candidate: SocketAddr = SocketAddr::new(local.ip(), port);
+                if self
+                    .lookup(candidate, |s| is_datagram(s) == datagram)
+                    .is_none()
+                {
+                    break candidate;
+                }
+            },
+            _ => local,
+        };
+        if self.lookup(local, |s| is_datagram(s) == datagram).is_some() {
+            return Err(Fail::new(libc::EADDRINUSE, "address in use"));
+        }
+        self.get(id)?.local = Some(local);
+        Ok(local)
+    }
+
+    /// Returns the local address of a socket, binding it to an ephemeral port if needed, on the
+    /// loopback address of the same family as `peer`.
+    fn local(&mut self, id: SocketId, peer: SocketAddr) -> Result<SocketAddr, Fail> {
+        let localhost: IpAddr = match peer {
+            SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        };
+        match self.get(id)?.local {
+            Some(local) => Ok(local),
+            None => self.bind(id, SocketAddr::new(localhost, 0)),
+        }
+    }
+
+    /// Asserts if more data may come in on a connected socket, that is, if its peer is open and
+    /// did not shut its sending side down.
+    fn is_open(&mut self, id: SocketId) -> bool {
+        match self.get(id).map(|s| &s.state) {
+            Ok(State::Connected(Some(peer), _)) => {
+                let peer: SocketId = *peer;
+                matches!(self.get(peer), Ok(NetSocket { shut: false, .. }))
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the local address of the peer of a connected socket, if it is still open.
+    fn peer(&mut self, id: SocketId) -> Option<SocketAddr> {
+        match self.get(id).ok()?.state {
+            State::Connected(Some(peer), _) => self.get(peer).ok()?.local,
+            _ => None,
+        }
+    }
+
+    /// Removes a socket. Peers of a connected socket see the end of the stream, and connections
+    /// that a listening socket did not accept are reset.
+    fn remove(&mut self, id: SocketId) {
+        let socket: NetSocket = match self.sockets.get_mut(id).and_then(Option::take) {
+            Some(socket) => socket,
+            None => return,
+        };
+        match socket.state {
+            State::Connected(Some(peer), _) => {
+                if let Ok(NetSocket {
+                    state: State::Connected(peer_peer, _),
+                    ..
+                }) = self.get(peer)
+                {
+                    *peer_peer = None;
+                }
+            }
+            State::Listening(backlog) => {
+                for pending in backlog {
+                    self.remove(pending);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Associate Functions for Networks
+impl Network {
+    /// Creates an empty network.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Associate Functions for Loopback Backends
+impl Loopback {
+    /// Creates a backend on a network.
+    pub fn new(network: &Network) -> Self {
+        Self {
+            network: network.clone(),
+            sockets: HashMap::new(),
+            ops: HashMap::new(),
+            next_qd: 0,
+            next_qt: 0,
+            alone: false,
+        }
+    }
+
+    /// Returns the network of the target backend.
+    pub fn get_network(&self) -> &Network {
+        &self.network
+    }
+
+    /// Assigns a descriptor to a socket of the network.
+    fn insert(&mut self, id: SocketId) -> QDesc {
+        let qd: QDesc = QDesc::from(self.next_qd);
+        self.next_qd += 1;
+        self.sockets.insert(qd, id);
+        qd
+    }
+
+    /// Returns the socket of the network behind a descriptor.
+    fn get(&self, qd: QDesc) -> Result<SocketId, Fail> {
+        match self.sockets.get(&qd) {
+            Some(id) => Ok(*id),
+            None => Err(Fail::new(libc::EBADF, "invalid queue descriptor")),
+        }
+    }
+
+    /// Registers an operation.
+    fn submit(&mut self, qd: QDesc, op: Op) -> Result<QToken, Fail> {
+        self.get(qd)?;
+        let qt: QToken = QToken::from(self.next_qt);
+        self.next_qt += 1;
+        self.ops.insert(qt, (qd, op));
+        Ok(qt)
+    }
+
+    /// Registers an operation that completed on submission.
+    fn complete(&mut self, qd: QDesc, result: Result<(), Fail>, ok: OperationResult) -> QToken {
+        let result: OperationResult = match result {
+            Ok(()) => ok,
+            Err(e) => OperationResult::Failed(e),
+        };
+        let qt: QToken = QToken::from(self.next_qt);
+        self.next_qt += 1;
+        self.ops.insert(qt, (qd, Op::Done(result)));
+        qt
+    }
+
+    /// Attempts a pending operation, returning its result if it completed.
+    fn attempt(&mut self, qt: QToken) -> Result<Option<(QDesc, OperationResult)>, Fail> {
+        let (qd, op): (QDesc, Op) = match self.ops.remove(&qt) {
+            Some(entry) => entry,
+            None => return Err(Fail::new(libc::EINVAL, "invalid queue token")),
+        };
+        let result: Option<OperationResult> = match op {
+            Op::Done(result) => return Ok(Some((qd, result))),
+            Op::Accept => self.try_accept(qd),
+            Op::Pop => self.try_pop(qd),
+        };
+        match result {
+            Some(result) => Ok(Some((qd, result))),
+            None => {
+                self.ops.insert(qt, (qd, op));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Accepts a connection, if one is pending.
+    fn try_accept(&mut self, qd: QDesc) -> Option<OperationResult> {
+        let id: SocketId = match self.get(qd) {
+            Ok(id) => id,
+            Err(e) => return Some(OperationResult::Failed(e)),
+        };
+        let accepted: SocketId = match self.network.0.borrow_mut().get(id) {
+            Ok(NetSocket {
+                state: State::Listening(backlog),
+                ..
+            }) => backlog.pop_front()?,
+            Ok(_) => {
+                let e: Fail = Fail::new(libc::EINVAL, "socket is not listening");
+                return Some(OperationResult::Failed(e));
+            }
+            Err(e) => return Some(OperationResult::Failed(e)),
+        };
+        let peer: Option<SocketAddr> = self.network.0.borrow_mut().peer(accepted);
+        Some(OperationResult::Accept(self.insert(accepted), peer))
+    }
+
+    /// Pops data, if some was received or the peer closed the connection.
+    fn try_pop(&mut self, qd: QDesc) -> Option<OperationResult> {
+        let id: SocketId = match self.get(qd) {
+            Ok(id) => id,
+            Err(e) => return Some(OperationResult::Failed(e)),
+        };
+        let mut fabric = self.network.0.borrow_mut();
+        let open: bool = fabric.is_open(id);
+        let socket: &mut NetSocket = match fabric.get(id) {
+            Ok(socket) => socket,
+            Err(e) => return Some(OperationResult::Failed(e)),
+        };
+        match &mut socket.state {
+            State::Connected(_, rx) if rx.is_empty() && open => None,
+            State::Connected(_, rx) => {
+                let n: usize = rx.len().min(Fabric::MAX_POP_SIZE);
+                let data: Vec<u8> = rx.drain(..n).collect();
+                Some(OperationResult::Pop(None, Buffer::new(data)))
+            }
+            State::Datagram(rx) => rx
+                .pop_front()
+                .map(|(from, data)| OperationResult::Pop(Some(from), Buffer::new(data))),
+            _ => {
+                let e: Fail = Fail::new(libc::ENOTCONN, "socket is not connected");
+                Some(OperationResult::Failed(e))
+            }
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Backend Trait Implementation for Loopback Backends
+impl Backend for Loopback {
+    /// Creates a backend on a network of its own, which only reaches its own sockets.
+    fn create() -> Result<Self> {
+        let mut backend: Self = Self::new(&Network::new());
+        backend.alone = true;
+        Ok(backend)
+    }
+
+    fn has_timed_waits() -> bool {
+        true
+    }
+
+    fn socket(
+        &mut self,
+        domain: libc::c_int,
+        socket_type: libc::c_int,
+        _protocol: libc::c_int,
+    ) -> Result<QDesc, Fail> {
+        if domain != libc::AF_INET && domain != libc::AF_INET6 {
+            return Err(Fail::new(
+                libc::EAFNOSUPPORT,
+                "address family not supported",
+            ));
+        }
+        let state: State = match socket_type {
+            libc::SOCK_STREAM => State::Unconnected,
+            libc::SOCK_DGRAM => State::Datagram(VecDeque::new()),
+            _ => {
+                return Err(Fail::new(
+                    libc::ESOCKTNOSUPPORT,
+                    "socket type not supported",
+                ))
+            }
+        };
+        let id: SocketId = self.network.0.borrow_mut().insert(state);
+        Ok(self.insert(id))
+    }
+
+    fn bind(&mut self, qd: QDesc, local: SocketAddr) -> Result<(), Fail> {
+        let id: SocketId = self.get(qd)?;
+        let mut fabric = self.network.0.borrow_mut();
+        if fabric.get(id)?.local.is_some() {
+            return Err(Fail::new(libc::EINVAL, "socket already bound"));
+        }
+        fabric.bind(id, local)?;
+        Ok(())
+    }
+
+    fn listen(&mut self, qd: QDesc, _backlog: usize) -> Result<(), Fail> {
+        let id: SocketId = self.get(qd)?;
+        let mut fabric = self.network.0.borrow_mut();
+        let socket: &mut NetSocket = fabric.get(id)?;
+        match (socket.local, &socket.state) {
+            (Some(_), State::Unconnected) => {
+                socket.state = State::Listening(VecDeque::new());
+                Ok(())
+            }
+            (None, _) => Err(Fail::new(libc::EDESTADDRREQ, "socket is not bound")),
+            (_, _) => Err(Fail::new(libc::EINVAL, "socket cannot listen")),
+        }
+    }
+
+    fn accept(&mut self, qd: QDesc) -> Result<QToken, Fail> {
+        self.submit(qd, Op::Accept)
+    }
+
+    fn connect(&mut self, qd: QDesc, remote: SocketAddr) -> Result<QToken, Fail> {
+        let id: SocketId = self.get(qd)?;
+        let mut fabric = self.network.0.borrow_mut();
+        if !matches!(fabric.get(id)?.state, State::Unconnected) {
+            return Err(Fail::new(libc::EISCONN, "socket cannot connect"));
+        }
+        fabric.local(id, remote)?;
+        let listener: Option<SocketId> =
+            fabric.lookup(remote, |s| matches!(s, State::Listening(_)));
+        let result: Result<(), Fail> = match listener {
+            Some(listener) => {
+                // Create the socket that the listener hands out when accepting.
+                let accepted: SocketId = fabric.insert(State::Connected(Some(id), VecDeque::new()));
+                fabric.get(accepted)?.local = Some(remote);
+                fabric.get(id)?.state = State::Connected(Some(accepted), VecDeque::new());
+                if let State::Listening(backlog) = &mut fabric.get(listener)?.state {
+                    backlog.push_back(accepted);
+                }
+                Ok(())
+            }
+            None => Err(Fail::new(libc::ECONNREFUSED, "connection refused")),
+        };
+        drop(fabric);
+        Ok(self.complete(qd, result, OperationResult::Connect))
+    }
+
+    fn push(&mut self, qd: QDesc, data: &[u8]) -> Result<QToken, Fail> {
+        let id: SocketId = self.get(qd)?;
+        let mut fabric = self.network.0.borrow_mut();
+        let socket: &NetSocket = fabric.get(id)?;
+        let result: Result<(), Fail> = match socket.state {
+            State::Connected(..) if socket.shut => Err(Fail::new(libc::EPIPE, "socket shut down")),
+            State::Connected(Some(peer), _) => match &mut fabric.get(peer)?.state {
+                State::Connected(_, rx) => {
+                    rx.extend(data);
+                    Ok(())
+                }
+                _ => Err(Fail::new(libc::ECONNRESET, "connection reset")),
+            },
+            State::Connected(None, _) => Err(Fail::new(libc::EPIPE, "connection closed")),
+            _ => Err(Fail::new(libc::ENOTCONN, "socket is not connected")),
+        };
+        drop(fabric);
+        Ok(self.complete(qd, result, OperationResult::Push))
+    }
+
+    fn pushto(&mut self, qd: QDesc, data: &[u8], to: SocketAddr) -> Result<QToken, Fail> {
+        let id: SocketId = self.get(qd)?;
+        let mut fabric = self.network.0.borrow_mut();
+        if !matches!(fabric.get(id)?.state, State::Datagram(_)) {
+            return Err(Fail::new(
+                libc::EOPNOTSUPP,
+                "socket is not a datagram socket",
+            ));
+        }
+        let from: SocketAddr = fabric.local(id, to)?;
+
+        // Datagrams to addresses that nobody is bound to are silently dropped.
+        if let Some(dest) = fabric.lookup(to, |s| matches!(s, State::Datagram(_))) {
+            if let State::Datagram(rx) = &mut fabric.get(dest)?.state {
+                rx.push_back((from, data.to_vec()));
+            }
+        }
+        drop(fabric);
+        Ok(self.complete(qd, Ok(()), OperationResult::Push))
+    }
+
+    fn pop(&mut self, qd: QDesc) -> Result<QToken, Fail> {
+        self.submit(qd, Op::Pop)
+    }
+
+    fn wait(&mut self, qt: QToken) -> Result<(QDesc, OperationResult), Fail> {
+        let (_, qd, result) = self.wait_any(&[qt])?;
+        Ok((qd, result))
+    }
+
+    fn wait_any(&mut self, qts: &[QToken]) -> Result<(usize, QDesc, OperationResult), Fail> {
+        self.wait_any_timeout(qts, None)
+    }
+
+    fn wait_any_timeout(
+        &mut self,
+        qts: &[QToken],
+        timeout: Option<Duration>,
+    ) -> Result<(usize, QDesc, OperationResult), Fail> {
+        for (i, qt) in qts.iter().enumerate() {
+            if let Some((qd, result)) = self.attempt(*qt)? {
+                return Ok((i, qd, result));
+            }
+        }
+        match (timeout, self.alone) {
+            (Some(timeout), true) => {
+                thread::sleep(timeout);
+                Err(Fail::new(libc::ETIMEDOUT, "wait timed out"))
+            }
+            (Some(_), false) => Err(Fail::new(libc::ETIMEDOUT, "wait timed out")),
+            (None, true) => Err(Fail::new(libc::ESHUTDOWN, "network is idle")),
+            (None, false) => Err(Fail::new(libc::EWOULDBLOCK, "no operation can complete")),
+        }
+    }
+
+    fn shutdown(&mut self, qd: QDesc) -> Result<(), Fail> {
+        let id: SocketId = self.get(qd)?;
+        let mut fabric = self.network.0.borrow_mut();
+        let socket: &mut NetSocket = fabric.get(id)?;
+        match socket.state {
+            State::Connected(..) => {
+                socket.shut = true;
+                Ok(())
+            }
+            _ => Err(Fail::new(libc::ENOTCONN, "socket is not connected")),
+        }
+    }
+
+    fn close(&mut self, qd: QDesc) -> Result<(), Fail> {
+        let id: SocketId = match self.sockets.remove(&qd) {
+            Some(id) => id,
+            None => return Err(Fail::new(libc::EBADF, "invalid queue descriptor")),
+        };
+        self.network.0.borrow_mut().remove(id);
+        Ok(())
+    }
+}
+
+impl Drop for Loopback {
+    fn drop(&mut self) {
+        let mut fabric = self.network.0.borrow_mut();
+        for id in self.sockets.values() {
+            fabric.remove(*id);
+        }
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Waits for an operation that can complete.
+    fn complete(backend: &mut Loopback, qt: QToken) -> OperationResult {
+        match backend.wait(qt) {
+            Ok((_, result)) => result,
+            Err(e) => panic!("failed to wait: {:?}", e.cause),
+        }
+    }
+
+    /// Pops data that is expected to be there.
+    fn pop(backend: &mut Loopback, qd: QDesc) -> (Option<SocketAddr>, Vec<u8>) {
+        let qt: QToken = backend.pop(qd).expect("failed to pop");
+        match complete(backend, qt) {
+            OperationResult::Pop(from, buf) => (from, buf.to_vec()),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn streams_connect_backends_of_a_network() {
+        let network: Network = Network::new();
+        let mut server: Loopback = Loopback::new(&network);
+        let mut client: Loopback = Loopback::new(&network);
+        let addr: SocketAddr = SocketAddr::from(([127, 0, 0, 1], 8080));
+
+        let listener: QDesc = server.socket(libc::AF_INET, libc::SOCK_STREAM, 0).unwrap();
+        server.bind(listener, addr).unwrap();
+        server.listen(listener, 16).unwrap();
+        let accept: QToken = server.accept(listener).unwrap();
+
+        // Nothing to accept yet, which leaves the accept pending.
+        let e: Fail = server.wait_any(&[accept]).unwrap_err();
+        assert_eq!(e.errno, libc::EWOULDBLOCK);
+
+        let qd: QDesc = client.socket(libc::AF_INET, libc::SOCK_STREAM, 0).unwrap();
+        let qt: QToken = client.connect(qd, addr).unwrap();
+        assert!(matches!(
+            complete(&mut client, qt),
+            OperationResult::Connect
+        ));
+        let (connection, peer): (QDesc, Option<SocketAddr>) = match complete(&mut server, accept) {
+            OperationResult::Accept(connection, peer) => (connection, peer),
+            result => panic!("unexpected result: {:?}", result),
+        };
+        assert_eq!(peer, Some(SocketAddr::from(([127, 0, 0, 1], 49152))));
+
+        let qt: QToken = client.push(qd, b"hello").unwrap();
+        assert!(matches!(complete(&mut client, qt), OperationResult::Push));
+        assert_eq!(pop(&mut server, connection), (None, b"hello".to_vec()));
+        let qt: QToken = server.push(connection, b"world").unwrap();
+        assert!(matches!(complete(&mut server, qt), OperationResult::Push));
+        assert_eq!(pop(&mut client, qd), (None, b"world".to_vec()));
+
+        // Shutting one end down shows up as the end of the stream at the other, which may still
+        // send data back.
+        client.shutdown(qd).unwrap();
+        let qt: QToken = client.push(qd, b"late").unwrap();
+        match complete(&mut client, qt) {
+            OperationResult::Failed(e) => assert_eq!(e.errno, libc::EPIPE),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(pop(&mut server, connection), (None, Vec::new()));
+        let qt: QToken = server.push(connection, b"bye").unwrap();
+        assert!(matches!(complete(&mut server, qt), OperationResult::Push));
+        assert_eq!(pop(&mut client, qd), (None, b"bye".to_vec()));
+
+        // Closing one end shows up as the end of the stream at the other.
+        server.close(connection).unwrap();
+        assert_eq!(pop(&mut client, qd), (None, Vec::new()));
+    }
+
+    #[test]
+    fn backends_alone_on_their_network_wind_down_once_idle() {
+        let mut backend: Loopback = Loopback::create().unwrap();
+        let addr: SocketAddr = SocketAddr::from(([127, 0, 0, 1], 9000));
+        let qd: QDesc = backend.socket(libc::AF_INET, libc::SOCK_DGRAM, 0).unwrap();
+        backend.bind(qd, addr).unwrap();
+        let qt: QToken = backend.pop(qd).unwrap();
+
+        let timeout: Duration = Duration::from_millis(1);
+        let e: Fail = backend.wait_any_timeout(&[qt], Some(timeout)).unwrap_err();
+        assert!(e.is_timeout());
+        assert!(backend.wait_any(&[qt]).unwrap_err().is_shutdown());
+
+        // The backend still reaches its own sockets.
+        let push: QToken = backend.pushto(qd, b"self", addr).unwrap();
+        assert!(matches!(
+            complete(&mut backend, push),
+            OperationResult::Push
+        ));
+        assert!(matches!(
+            complete(&mut backend, qt),
+            OperationResult::Pop(Some(from), _) if from == addr
+        ));
+    }
+
+    #[test]
+    fn connect_without_listener_is_refused() {
+        let mut backend: Loopback = Loopback::create().unwrap();
+        let qd: QDesc = backend.socket(libc::AF_INET, libc::SOCK_STREAM, 0).unwrap();
+        let qt: QToken = backend
+            .connect(qd, SocketAddr::from(([127, 0, 0, 1], 8080)))
+            .unwrap();
+        match complete(&mut backend, qt) {
+            OperationResult::Failed(e) => assert_eq!(e.errno, libc::ECONNREFUSED),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn datagrams_carry_their_sender() {
+        let network: Network = Network::new();
+        let mut server: Loopback = Loopback::new(&network);
+        let mut client: Loopback = Loopback::new(&network);
+        let addr: SocketAddr = SocketAddr::from(([0, 0, 0, 0], 9000));
+
+        let listener: QDesc = server.socket(libc::AF_INET, libc::SOCK_DGRAM, 0).unwrap();
+        server.bind(listener, addr).unwrap();
+        let qd: QDesc = client.socket(libc::AF_INET, libc::SOCK_DGRAM, 0).unwrap();
+        let to: SocketAddr = SocketAddr::from(([127, 0, 0, 1], 9000));
+        for data in [&b"first"[..], &b"second"[..]] {
+            let qt: QToken = client.pushto(qd, data, to).unwrap();
+            assert!(matches!(complete(&mut client, qt), OperationResult::Push));
+        }
+
+        // The client was bound to an ephemeral port of the loopback address.
+        let (from, data): (Option<SocketAddr>, Vec<u8>) = pop(&mut server, listener);
+        let from: SocketAddr = from.expect("no sender");
+        assert_eq!(from.ip(), IpAddr::from(Ipv4Addr::LOCALHOST));
+        assert!(from.port() >= Fabric::FIRST_EPHEMERAL_PORT);
+        assert_eq!(data, b"first");
+        assert_eq!(pop(&mut server, listener), (Some(from), b"second".to_vec()));
+
+        let qt: QToken = server.pushto(listener, b"reply", from).unwrap();
+        assert!(matches!(complete(&mut server, qt), OperationResult::Push));
+        assert_eq!(pop(&mut client, qd), (Some(addr), b"reply".to_vec()));
+    }
+
+    #[test]
+    fn addresses_are_not_shared() {
+        let mut backend: Loopback = Loopback::create().unwrap();
+        let addr: SocketAddr = SocketAddr::from(([127, 0, 0, 1], 9000));
+        let first: QDesc = backend.socket(libc::AF_INET, libc::SOCK_DGRAM, 0).unwrap();
+        let second: QDesc = backend.socket(libc::AF_INET, libc::SOCK_DGRAM, 0).unwrap();
+        let stream: QDesc = backend.socket(libc::AF_INET, libc::SOCK_STREAM, 0).unwrap();
+        backend.bind(first, addr).unwrap();
+        assert_eq!(
+            backend.bind(second, addr).unwrap_err().errno,
+            libc::EADDRINUSE
+        );
+        backend.bind(stream, addr).unwrap();
+
+        // The address is free again once closed.
+        backend.close(first).unwrap();
+        backend.bind(second, addr).unwrap();
+    }
+}
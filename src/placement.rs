@@ -0,0 +1,245 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Result};
+use ::clap::{Arg, ArgMatches};
+use ::log::info;
+use ::std::{fmt, mem};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Placement
+///
+/// Where and how the process runs: the cores that it may run on, its scheduling policy and
+/// whether its memory is locked. Polling loops are sensitive to all of these, so they are
+/// applied before the libOS is started and logged, so that runs can be reproduced.
+#[derive(Clone, Debug, Default)]
+pub struct Placement {
+    /// Cores that the process may run on.
+    cpus: Option<Vec<usize>>,
+    /// Real-time priority under `SCHED_FIFO`.
+    fifo_priority: Option<i32>,
+    /// Lock current and future memory?
+    mlock: bool,
+}
+
+/// Effective Placement
+///
+/// Placement that is in force, as reported by the kernel.
+#[derive(Clone, Debug)]
+pub struct EffectivePlacement {
+    /// Cores that the process may run on.
+    cpus: Vec<usize>,
+    /// Scheduling policy.
+    policy: libc::c_int,
+    /// Scheduling priority.
+    priority: libc::c_int,
+    /// Is memory locked?
+    mlock: bool,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Placements
+impl Placement {
+    /// Returns command line arguments that describe a placement.
+    pub fn args<'help>() -> [Arg<'help>; 3] {
+        [
+            Arg::new("cpu")
+                .long("cpu")
+                .takes_value(true)
+                .required(false)
+                .value_name("LIST")
+                .help("Sets cores to run on (e.g. 0-3,8)"),
+            Arg::new("sched_fifo")
+                .long("sched_fifo")
+                .takes_value(true)
+                .required(false)
+                .value_name("PRIORITY")
+                .help("Runs under SCHED_FIFO with the given priority"),
+            Arg::new("mlock")
+                .long("mlock")
+                .takes_value(false)
+                .required(false)
+                .help("Locks current and future memory"),
+        ]
+    }
+
+    /// Builds a placement from command line arguments.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        let mut placement: Placement = Placement::default();
+        if let Some(cpus) = matches.value_of("cpu") {
+            placement.cpus = Some(parse_cpu_list(cpus)?);
+        }
+        if let Some(priority) = matches.value_of("sched_fifo") {
+            placement.set_fifo_priority(priority)?;
+        }
+        placement.mlock = matches.is_present("mlock");
+        Ok(placement)
+    }
+
+    /// Sets the `SCHED_FIFO` priority of the target placement.
+    fn set_fifo_priority(&mut self, priority_str: &str) -> Result<()> {
+        let priority: i32 = priority_str.parse()?;
+        let (min, max): (i32, i32) = unsafe {
+            (
+                libc::sched_get_priority_min(libc::SCHED_FIFO),
+                libc::sched_get_priority_max(libc::SCHED_FIFO),
+            )
+        };
+        if priority < min || priority > max {
+            bail!("invalid priority (expected {}-{})", min, max)
+        }
+        self.fifo_priority = Some(priority);
+        Ok(())
+    }
+
+    /// Applies the target placement to the calling process and logs the effective one. Threads
+    /// spawned afterwards, including those of the libOS, inherit it.
+    pub fn apply(&self) -> Result<EffectivePlacement> {
+        if let Some(cpus) = &self.cpus {
+            set_affinity(cpus)?;
+        }
+
+        if let Some(priority) = self.fifo_priority {
+            let param: libc::sched_param = libc::sched_param {
+                sched_priority: priority,
+            };
+            if unsafe { libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) } != 0 {
+                bail!(
+                    "failed to set SCHED_FIFO: {}",
+                    std::io::Error::last_os_error()
+                )
+            }
+        }
+
+        if self.mlock && unsafe { libc::mlockall(libc::MCL_CURRENT | libc::MCL_FUTURE) } != 0 {
+            bail!("failed to lock memory: {}", std::io::Error::last_os_error())
+        }
+
+        let effective: EffectivePlacement = EffectivePlacement::get(self.mlock)?;
+        for line in effective.to_string().lines() {
+            info!("{}", line);
+        }
+        Ok(effective)
+    }
+}
+
+/// Associate Functions for Effective Placements
+impl EffectivePlacement {
+    /// Queries the placement of the calling process. Whether memory is locked cannot be queried,
+    /// so it is given by the caller.
+    fn get(mlock: bool) -> Result<Self> {
+        let mut param: libc::sched_param = libc::sched_param { sched_priority: 0 };
+        let policy: libc::c_int = unsafe { libc::sched_getscheduler(0) };
+        if policy < 0 || unsafe { libc::sched_getparam(0, &mut param) } != 0 {
+            bail!(
+                "failed to get scheduling policy: {}",
+                std::io::Error::last_os_error()
+            )
+        }
+        Ok(Self {
+            cpus: get_affinity()?,
+            policy,
+            priority: param.sched_priority,
+            mlock,
+        })
+    }
+
+    /// Returns the cores that the process may run on.
+    pub fn get_cpus(&self) -> &[usize] {
+        &self.cpus
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+impl fmt::Display for EffectivePlacement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cpus: Vec<String> = self.cpus.iter().map(|cpu| cpu.to_string()).collect();
+        let policy: &str = match self.policy {
+            libc::SCHED_OTHER => "SCHED_OTHER",
+            libc::SCHED_FIFO => "SCHED_FIFO",
+            libc::SCHED_RR => "SCHED_RR",
+            libc::SCHED_BATCH => "SCHED_BATCH",
+            libc::SCHED_IDLE => "SCHED_IDLE",
+            _ => "unknown",
+        };
+        writeln!(f, "CPU Affinity:   {}", cpus.join(","))?;
+        writeln!(f, "Scheduling:     {} (priority {})", policy, self.priority)?;
+        write!(
+            f,
+            "Memory Locked:  {}",
+            if self.mlock { "yes" } else { "no" }
+        )
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Parses a list of cores, such as `0-3,8`.
+pub fn parse_cpu_list(s: &str) -> Result<Vec<usize>> {
+    let mut cpus: Vec<usize> = Vec::new();
+    for item in s.split(',') {
+        let (first, last): (usize, usize) = match item.trim().split_once('-') {
+            Some((first, last)) => (first.parse()?, last.parse()?),
+            None => {
+                let cpu: usize = item.trim().parse()?;
+                (cpu, cpu)
+            }
+        };
+        if first > last || last >= libc::CPU_SETSIZE as usize {
+            bail!("invalid core range {:?}", item)
+        }
+        cpus.extend(first..=last);
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+/// Returns the cores that the calling thread may run on.
+pub fn get_affinity() -> Result<Vec<usize>> {
+    let cpus: Vec<usize> = unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            bail!(
+                "failed to get affinity: {}",
+                std::io::Error::last_os_error()
+            )
+        }
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|cpu| libc::CPU_ISSET(*cpu, &set))
+            .collect()
+    };
+    Ok(cpus)
+}
+
+/// Restricts the calling thread to a set of cores.
+pub fn set_affinity(cpus: &[usize]) -> Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        for cpu in cpus {
+            libc::CPU_SET(*cpu, &mut set);
+        }
+        if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            bail!(
+                "failed to set affinity: {}",
+                std::io::Error::last_os_error()
+            )
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,481 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::backend::{Backend, Buffer, Fail, OperationResult};
+use ::anyhow::{bail, Result};
+use ::demikernel::{QDesc, QToken};
+use ::std::{
+    collections::HashMap,
+    io::{self, ErrorKind, Read, Write},
+    mem,
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, UdpSocket},
+    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+    ptr,
+    time::{Duration, Instant},
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Socket of the Operating System
+#[derive(Debug)]
+enum Socket {
+    /// Stream socket that is neither listening nor connected.
+    Unconnected(TcpStream),
+    /// Listening stream socket.
+    Listener(TcpListener),
+    /// Connected stream socket, or one that is connecting.
+    Stream(TcpStream),
+    /// Datagram socket.
+    Datagram(UdpSocket),
+}
+
+/// Pending Operation
+#[derive(Debug)]
+enum Op {
+    /// Accept a connection.
+    Accept,
+    /// Finish connecting.
+    Connect,
+    /// Push data, of which `sent` bytes are already sent.
+    Push(Vec<u8>, usize),
+    /// Push a datagram to an address.
+    PushTo(Vec<u8>, SocketAddr),
+    /// Pop data.
+    Pop,
+}
+
+/// POSIX Backend
+///
+/// Runs operations on non-blocking sockets of the operating system. Operations are attempted when
+/// they are waited on, and the caller sleeps in `epoll_wait()` until some socket changes state
+/// when none of them can complete.
+#[derive(Debug)]
+pub struct Posix {
+    /// Epoll instance, in which every socket is registered.
+    epfd: RawFd,
+    /// Open sockets.
+    sockets: HashMap<QDesc, Socket>,
+    /// Pending operations, along with the socket they run on.
+    ops: HashMap<QToken, (QDesc, Op)>,
+    /// Next socket descriptor.
+    next_qd: u32,
+    /// Next operation token.
+    next_qt: u64,
+    /// Buffer into which data is received.
+    recvbuf: Vec<u8>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for the POSIX Backend
+impl Posix {
+    /// Size of the receive buffer.
+    const RECVBUF_SIZE: usize = 65536;
+
+    /// Maximum number of events collected by a call to `epoll_wait()`.
+    const MAX_EVENTS: usize = 64;
+
+    /// Registers a socket in the epoll instance and assigns it a descriptor.
+    fn insert(&mut self, socket: Socket) -> Result<QDesc, Fail> {
+        let qd: QDesc = QDesc::from(self.next_qd);
+        self.next_qd += 1;
+        let mut event: libc::epoll_event = libc::epoll_event {
+            events: (libc::EPOLLIN | libc::EPOLLOUT | libc::EPOLLET) as u32,
+            u64: u32::from(qd) as u64,
+        };
+        let fd: RawFd = socket.as_raw_fd();
+        if unsafe { libc::epoll_ctl(self.epfd, libc::EPOLL_CTL_ADD, fd, &mut event) } != 0 {
+            return Err(last_fail());
+        }
+        self.sockets.insert(qd, socket);
+        Ok(qd)
+    }
+
+    /// Registers a pending operation.
+    fn submit(&mut self, qd: QDesc, op: Op) -> Result<QToken, Fail> {
+        if !self.sockets.contains_key(&qd) {
+            return Err(Fail::new(libc::EBADF, "invalid queue descriptor"));
+        }
+        let qt: QToken = QToken::from(self.next_qt);
+        self.next_qt += 1;
+        self.ops.insert(qt, (qd, op));
+        Ok(qt)
+    }
+
+    /// Attempts a pending operation, returning its result if it completed.
+    fn attempt(&mut self, qt: QToken) -> Result<Option<(QDesc, OperationResult)>, Fail> {
+        let (qd, op): &mut (QDesc, Op) = match self.ops.get_mut(&qt) {
+            Some(entry) => entry,
+            None => return Err(Fail::new(libc::EINVAL, "invalid queue token")),
+        };
+        let qd: QDesc = *qd;
+        let mut accepted: Option<TcpStream> = None;
+        let outcome: io::Result<OperationResult> = match (self.sockets.get_mut(&qd), op) {
+            (None, _) => Err(io::Error::from_raw_os_error(libc::EBADF)),
+            // The accepted socket is registered below, under the next descriptor.
+            (Some(Socket::Listener(listener)), Op::Accept) => match listener.accept() {
+                Ok((stream, peer)) => stream.set_nonblocking(true).map(|_| {
+                    accepted = Some(stream);
+                    OperationResult::Accept(QDesc::from(self.next_qd), Some(peer))
+                }),
+                Err(e) => Err(e),
+            },
+            (Some(Socket::Stream(stream)), Op::Connect) => match stream.peer_addr() {
+                Ok(_) => Ok(OperationResult::Connect),
+                Err(e) if e.kind() == ErrorKind::NotConnected => match stream.take_error() {
+                    Ok(None) => Err(ErrorKind::WouldBlock.into()),
+                    Ok(Some(e)) | Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            },
+            (Some(Socket::Stream(stream)), Op::Push(data, sent)) => loop {
+                match stream.write(&data[*sent..]) {
+                    Ok(n) => {
+                        *sent += n;
+                        if *sent == data.len() {
+                            break Ok(OperationResult::Push);
+                        }
+                    }
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => break Err(e),
+                }
+            },
+            (Some(Socket::Datagram(socket)), Op::Push(data, _)) => {
+                socket.send(data).map(|_| OperationResult::Push)
+            }
+            (Some(Socket::Datagram(socket)), Op::PushTo(data, to)) => {
+                socket.send_to(data, *to).map(|_| OperationResult::Push)
+            }
+            (Some(Socket::Stream(stream)), Op::Pop) => match stream.read(&mut self.recvbuf) {
+                Ok(n) => Ok(OperationResult::Pop(
+                    None,
+                    Buffer::new(self.recvbuf[..n].to_vec()),
+                )),
+                Err(e) => Err(e),
+            },
+            (Some(Socket::Datagram(socket)), Op::Pop) => {
+                match socket.recv_from(&mut self.recvbuf) {
+                    Ok((n, addr)) => Ok(OperationResult::Pop(
+                        Some(addr),
+                        Buffer::new(self.recvbuf[..n].to_vec()),
+                    )),
+                    Err(e) => Err(e),
+                }
+            }
+            (Some(_), _) => Err(io::Error::from_raw_os_error(libc::EINVAL)),
+        };
+
+        let result: OperationResult = match outcome {
+            Ok(result) => result,
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::Interrupted => {
+                return Ok(None)
+            }
+            Err(e) => OperationResult::Failed(io_fail(&e)),
+        };
+        if let Some(stream) = accepted {
+            self.insert(Socket::Stream(stream))?;
+        }
+        self.ops.remove(&qt);
+        Ok(Some((qd, result)))
+    }
+
+    /// Sleeps until some socket changes state, or for at most `timeout`, if any.
+    fn block(&mut self, timeout: Option<Duration>) -> Result<(), Fail> {
+        // Round up, so that the deadline has passed when epoll gives up.
+        let timeout: libc::c_int = match timeout {
+            Some(timeout) => {
+                let ms: u128 = (timeout + Duration::from_nanos(999_999)).as_millis();
+                ms.min(libc::c_int::MAX as u128) as libc::c_int
+            }
+            None => -1,
+        };
+        let mut events: [libc::epoll_event; Self::MAX_EVENTS] =
+            [libc::epoll_event { events: 0, u64: 0 }; Self::MAX_EVENTS];
+        let n: libc::c_int = unsafe {
+            libc::epoll_wait(
+                self.epfd,
+                events.as_mut_ptr(),
+                Self::MAX_EVENTS as libc::c_int,
+                timeout,
+            )
+        };
+        if n < 0 && io::Error::last_os_error().kind() != ErrorKind::Interrupted {
+            return Err(last_fail());
+        }
+        Ok(())
+    }
+
+    /// Returns the raw descriptor of a socket.
+    fn raw_fd(&self, qd: QDesc) -> Result<RawFd, Fail> {
+        match self.sockets.get(&qd) {
+            Some(socket) => Ok(socket.as_raw_fd()),
+            None => Err(Fail::new(libc::EBADF, "invalid queue descriptor")),
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Socket::Unconnected(stream) | Socket::Stream(stream) => stream.as_raw_fd(),
+            Socket::Listener(listener) => listener.as_raw_fd(),
+            Socket::Datagram(socket) => socket.as_raw_fd(),
+        }
+    }
+}
+
+/// Backend Trait Implementation for the POSIX Backend
+impl Backend for Posix {
+    fn create() -> Result<Self> {
+        let epfd: RawFd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epfd < 0 {
+            bail!(
+                "failed to create epoll instance: {}",
+                io::Error::last_os_error()
+            )
+        }
+        Ok(Self {
+            epfd,
+            sockets: HashMap::new(),
+            ops: HashMap::new(),
+            next_qd: 0,
+            next_qt: 0,
+            recvbuf: vec![0; Self::RECVBUF_SIZE],
+        })
+    }
+
+    fn shares_ports() -> bool {
+        true
+    }
+
+    fn has_timed_waits() -> bool {
+        true
+    }
+
+    fn socket(
+        &mut self,
+        domain: libc::c_int,
+        socket_type: libc::c_int,
+        _protocol: libc::c_int,
+    ) -> Result<QDesc, Fail> {
+        if domain != libc::AF_INET && domain != libc::AF_INET6 {
+            return Err(Fail::new(
+                libc::EAFNOSUPPORT,
+                "address family not supported",
+            ));
+        }
+        // Like the libOS, pick the protocol from the socket type.
+        let flags: libc::c_int = libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC;
+        let fd: RawFd = unsafe { libc::socket(domain, socket_type | flags, 0) };
+        if fd < 0 {
+            return Err(last_fail());
+        }
+        let socket: Socket = match socket_type {
+            libc::SOCK_STREAM => {
+                enable(fd, libc::SO_REUSEADDR);
+                Socket::Unconnected(unsafe { TcpStream::from_raw_fd(fd) })
+            }
+            _ => Socket::Datagram(unsafe { UdpSocket::from_raw_fd(fd) }),
+        };
+        self.insert(socket)
+    }
+
+    fn bind(&mut self, qd: QDesc, local: SocketAddr) -> Result<(), Fail> {
+        let fd: RawFd = self.raw_fd(qd)?;
+        let (addr, len): (libc::sockaddr_storage, libc::socklen_t) = sockaddr(local);
+        let addr_ptr: *const libc::sockaddr = &addr as *const libc::sockaddr_storage as *const _;
+        if unsafe { libc::bind(fd, addr_ptr, len) } != 0 {
+            return Err(last_fail());
+        }
+        Ok(())
+    }
+
+    fn bind_shared(&mut self, qd: QDesc, local: SocketAddr) -> Result<(), Fail> {
+        // Let workers share the port, which the kernel balances traffic between.
+        enable(self.raw_fd(qd)?, libc::SO_REUSEPORT);
+        self.bind(qd, local)
+    }
+
+    fn listen(&mut self, qd: QDesc, backlog: usize) -> Result<(), Fail> {
+        let stream: TcpStream = match self.sockets.remove(&qd) {
+            Some(Socket::Unconnected(stream)) => stream,
+            Some(socket) => {
+                self.sockets.insert(qd, socket);
+                return Err(Fail::new(libc::EINVAL, "socket cannot listen"));
+            }
+            None => return Err(Fail::new(libc::EBADF, "invalid queue descriptor")),
+        };
+        let fd: RawFd = stream.into_raw_fd();
+        let listener: TcpListener = unsafe { TcpListener::from_raw_fd(fd) };
+        if unsafe { libc::listen(fd, backlog as libc::c_int) } != 0 {
+            return Err(last_fail());
+        }
+        self.sockets.insert(qd, Socket::Listener(listener));
+        Ok(())
+    }
+
+    fn accept(&mut self, qd: QDesc) -> Result<QToken, Fail> {
+        self.submit(qd, Op::Accept)
+    }
+
+    fn connect(&mut self, qd: QDesc, remote: SocketAddr) -> Result<QToken, Fail> {
+        let stream: TcpStream = match self.sockets.remove(&qd) {
+            Some(Socket::Unconnected(stream)) => stream,
+            Some(socket) => {
+                self.sockets.insert(qd, socket);
+                return Err(Fail::new(libc::EISCONN, "socket cannot connect"));
+            }
+            None => return Err(Fail::new(libc::EBADF, "invalid queue descriptor")),
+        };
+        let (addr, len): (libc::sockaddr_storage, libc::socklen_t) = sockaddr(remote);
+        let addr_ptr: *const libc::sockaddr = &addr as *const libc::sockaddr_storage as *const _;
+        if unsafe { libc::connect(stream.as_raw_fd(), addr_ptr, len) } != 0 {
+            let e: io::Error = io::Error::last_os_error();
+            if e.raw_os_error() != Some(libc::EINPROGRESS) {
+                return Err(io_fail(&e));
+            }
+        }
+        self.sockets.insert(qd, Socket::Stream(stream));
+        self.submit(qd, Op::Connect)
+    }
+
+    fn push(&mut self, qd: QDesc, data: &[u8]) -> Result<QToken, Fail> {
+        self.submit(qd, Op::Push(data.to_vec(), 0))
+    }
+
+    fn pushto(&mut self, qd: QDesc, data: &[u8], to: SocketAddr) -> Result<QToken, Fail> {
+        self.submit(qd, Op::PushTo(data.to_vec(), to))
+    }
+
+    fn pop(&mut self, qd: QDesc) -> Result<QToken, Fail> {
+        self.submit(qd, Op::Pop)
+    }
+
+    fn wait(&mut self, qt: QToken) -> Result<(QDesc, OperationResult), Fail> {
+        let (_, qd, result) = self.wait_any(&[qt])?;
+        Ok((qd, result))
+    }
+
+    fn wait_any(&mut self, qts: &[QToken]) -> Result<(usize, QDesc, OperationResult), Fail> {
+        self.wait_any_timeout(qts, None)
+    }
+
+    fn wait_any_timeout(
+        &mut self,
+        qts: &[QToken],
+        timeout: Option<Duration>,
+    ) -> Result<(usize, QDesc, OperationResult), Fail> {
+        let deadline: Option<Instant> = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            for (i, qt) in qts.iter().enumerate() {
+                if let Some((qd, result)) = self.attempt(*qt)? {
+                    return Ok((i, qd, result));
+                }
+            }
+            let timeout: Option<Duration> = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(timeout) if !timeout.is_zero() => Some(timeout),
+                    _ => return Err(Fail::new(libc::ETIMEDOUT, "wait timed out")),
+                },
+                None => None,
+            };
+            self.block(timeout)?;
+        }
+    }
+
+    fn shutdown(&mut self, qd: QDesc) -> Result<(), Fail> {
+        match self.sockets.get(&qd) {
+            Some(Socket::Stream(stream)) => {
+                stream.shutdown(Shutdown::Write).map_err(|e| io_fail(&e))
+            }
+            Some(_) => Err(Fail::new(libc::ENOTCONN, "socket is not connected")),
+            None => Err(Fail::new(libc::EBADF, "invalid queue descriptor")),
+        }
+    }
+
+    fn close(&mut self, qd: QDesc) -> Result<(), Fail> {
+        match self.sockets.remove(&qd) {
+            Some(_) => Ok(()),
+            None => Err(Fail::new(libc::EBADF, "invalid queue descriptor")),
+        }
+    }
+}
+
+impl Drop for Posix {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.epfd) };
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Converts an address into the representation of the operating system, along with its length.
+fn sockaddr(addr: SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len: usize = match addr {
+        SocketAddr::V4(addr) => {
+            let sin: libc::sockaddr_in = libc::sockaddr_in {
+                sin_family: libc::AF_INET as libc::sa_family_t,
+                sin_port: addr.port().to_be(),
+                sin_addr: libc::in_addr {
+                    s_addr: u32::from(*addr.ip()).to_be(),
+                },
+                sin_zero: [0; 8],
+            };
+            unsafe { ptr::write(&mut storage as *mut _ as *mut libc::sockaddr_in, sin) };
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let sin6: libc::sockaddr_in6 = libc::sockaddr_in6 {
+                sin6_family: libc::AF_INET6 as libc::sa_family_t,
+                sin6_port: addr.port().to_be(),
+                sin6_flowinfo: addr.flowinfo(),
+                sin6_addr: libc::in6_addr {
+                    s6_addr: addr.ip().octets(),
+                },
+                sin6_scope_id: addr.scope_id(),
+            };
+            unsafe { ptr::write(&mut storage as *mut _ as *mut libc::sockaddr_in6, sin6) };
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, len as libc::socklen_t)
+}
+
+/// Converts an I/O error into a failure.
+fn io_fail(e: &io::Error) -> Fail {
+    Fail::new(e.raw_os_error().unwrap_or(libc::EIO), &e.to_string())
+}
+
+/// Returns the failure of the last system call.
+fn last_fail() -> Fail {
+    io_fail(&io::Error::last_os_error())
+}
+
+/// Enables a boolean option of a socket. Failures are ignored, as options only tune sockets.
+fn enable(fd: RawFd, option: libc::c_int) {
+    let one: libc::c_int = 1;
+    unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            option,
+            &one as *const libc::c_int as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+}
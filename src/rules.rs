@@ -0,0 +1,757 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::schedule::Rate;
+use ::anyhow::{bail, Context, Result};
+use ::serde::Deserialize;
+use ::std::{
+    borrow::Cow,
+    fmt, fs,
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    time::Instant,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Rules File
+///
+/// Rules are loaded from a TOML or YAML file and evaluated in order, the first matching rule
+/// deciding the fate of a datagram. Datagrams that match no rule are relayed as usual.
+///
+/// ```toml
+/// [[rules]]
+/// name = "no-probes"
+/// src_addr = "10.0.0.0/8"
+/// prefix = "PROBE"
+/// action = "drop"
+///
+/// [[rules]]
+/// direction = "up"
+/// src_port = "5000-5100"
+/// size = "0-64"
+/// action = "forward"
+/// to = "127.0.0.1:7000"
+///
+/// [[rules]]
+/// offset = 4
+/// bytes = "0xcafe"
+/// action = "rewrite"
+/// at = 4
+/// data = "0xbeef"
+///
+/// [[rules]]
+/// action = "rate-limit"
+/// rate = "1000pps"
+/// burst = 10
+/// ```
+///
+/// Byte strings starting with `0x` are read as hexadecimal, others as plain text.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    /// Rules.
+    #[serde(default)]
+    rules: Vec<RuleSpec>,
+}
+
+/// Rule Specification
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    /// Label used in statistics.
+    name: Option<String>,
+    /// Direction the rule applies to (`up`, `down` or `both`).
+    direction: Option<String>,
+    /// Source address or subnet (e.g. `10.0.0.1`, `10.0.0.0/8` or `2001:db8::/32`).
+    src_addr: Option<String>,
+    /// Source port or port range.
+    src_port: Option<Ports>,
+    /// Payload prefix.
+    prefix: Option<String>,
+    /// Offset of `bytes` in the payload.
+    offset: Option<usize>,
+    /// Bytes to look for at `offset`.
+    bytes: Option<String>,
+    /// Size range (e.g. `64-1500`).
+    size: Option<String>,
+    /// Action (`drop`, `forward`, `rewrite`, `truncate` or `rate-limit`).
+    action: String,
+    /// Destination of `forward`.
+    to: Option<String>,
+    /// Offset of the bytes written by `rewrite`.
+    at: Option<usize>,
+    /// Bytes written by `rewrite`.
+    data: Option<String>,
+    /// Length kept by `truncate`.
+    length: Option<usize>,
+    /// Rate of `rate-limit` (e.g. `1000pps` or `10Mbps`).
+    rate: Option<String>,
+    /// Burst of `rate-limit`, in packets or bytes depending on the rate.
+    burst: Option<u64>,
+}
+
+/// Port Field
+///
+/// Ports may be written either as plain numbers or as ranges.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Ports {
+    Single(u16),
+    Range(String),
+}
+
+/// Traffic Direction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// From clients to backends.
+    Up,
+    /// From backends to clients.
+    Down,
+}
+
+/// Rule Action
+#[derive(Debug)]
+enum Action {
+    /// Drops the datagram.
+    Drop,
+    /// Sends the datagram to a given destination instead of its usual one.
+    Forward(SocketAddr),
+    /// Overwrites bytes of the payload.
+    Rewrite(usize, Vec<u8>),
+    /// Cuts the payload down to some length.
+    Truncate(usize),
+    /// Drops datagrams beyond some rate.
+    RateLimit(TokenBucket),
+}
+
+/// Token Bucket
+#[derive(Debug)]
+struct TokenBucket {
+    /// Are tokens bytes (rather than packets)?
+    bytes: bool,
+    /// Tokens added per second.
+    rate: f64,
+    /// Maximum number of tokens.
+    burst: f64,
+    /// Available tokens.
+    tokens: f64,
+    /// Last refill.
+    last: Option<Instant>,
+}
+
+/// Rule
+#[derive(Debug)]
+pub struct Rule {
+    /// Label used in statistics.
+    name: String,
+    /// Directions the rule applies to.
+    directions: (bool, bool),
+    /// Source subnet, as an address and a prefix length.
+    src_addr: Option<(IpAddr, u32)>,
+    /// Source port range (inclusive).
+    src_port: Option<(u16, u16)>,
+    /// Payload prefix.
+    prefix: Option<Vec<u8>>,
+    /// Bytes expected at some offset of the payload.
+    bytes: Option<(usize, Vec<u8>)>,
+    /// Size range (inclusive).
+    size: Option<(usize, usize)>,
+    /// Action.
+    action: Action,
+    /// Number of datagrams that matched the rule.
+    nhits: usize,
+}
+
+/// Rule Set
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    /// Rules.
+    rules: Vec<Rule>,
+}
+
+/// Verdict of a Rule Set
+#[derive(Debug)]
+pub enum Verdict<'a> {
+    /// The datagram should be dropped.
+    Drop,
+    /// The datagram should be sent with this payload, to the given destination, if any, or to
+    /// its usual one otherwise.
+    Pass(Cow<'a, [u8]>, Option<SocketAddr>),
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Rules
+impl Rule {
+    /// Returns the name of the target rule.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of datagrams that matched the target rule.
+    pub fn get_nhits(&self) -> usize {
+        self.nhits
+    }
+
+    /// Asserts if a datagram matches the target rule.
+    fn matches(&self, direction: Direction, src: SocketAddr, buf: &[u8]) -> bool {
+        let applies: bool = match direction {
+            Direction::Up => self.directions.0,
+            Direction::Down => self.directions.1,
+        };
+        if !applies {
+            return false;
+        }
+        if let Some((addr, len)) = self.src_addr {
+            if !in_subnet(src.ip(), addr, len) {
+                return false;
+            }
+        }
+        if let Some((first, last)) = self.src_port {
+            if src.port() < first || src.port() > last {
+                return false;
+            }
+        }
+        if let Some(prefix) = &self.prefix {
+            if !buf.starts_with(prefix) {
+                return false;
+            }
+        }
+        if let Some((offset, bytes)) = &self.bytes {
+            if buf.get(*offset..*offset + bytes.len()) != Some(&bytes[..]) {
+                return false;
+            }
+        }
+        if let Some((min, max)) = self.size {
+            if buf.len() < min || buf.len() > max {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Associate Functions for Token Buckets
+impl TokenBucket {
+    /// Takes tokens for a datagram of `nbytes`. Returns `false` if there are not enough of them.
+    fn take(&mut self, nbytes: usize, now: Instant) -> bool {
+        if let Some(last) = self.last {
+            let elapsed: f64 = now.saturating_duration_since(last).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        }
+        self.last = Some(now);
+        let cost: f64 = if self.bytes { nbytes as f64 } else { 1.0 };
+        if self.tokens >= cost {
+            self.tokens -= cost;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Associate Functions for Rule Sets
+impl RuleSet {
+    /// Loads rules from a file. The format is picked from the file extension.
+    pub fn load(path: &str) -> Result<Self> {
+        let contents: String =
+            fs::read_to_string(path).with_context(|| format!("failed to read {:?}", path))?;
+        let file: RulesFile = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&contents)?,
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&contents)?,
+            _ => bail!(
+                "unknown rules format for {:?} (expected .toml, .yaml or .yml)",
+                path
+            ),
+        };
+        let mut rules: Vec<Rule> = Vec::with_capacity(file.rules.len());
+        for (i, spec) in file.rules.into_iter().enumerate() {
+            let name: String = spec.name.clone().unwrap_or_else(|| format!("rule {}", i));
+            let rule: Rule = spec
+                .into_rule(name.clone())
+                .with_context(|| format!("invalid rule {:?}", name))?;
+            rules.push(rule);
+        }
+        Ok(Self { rules })
+    }
+
+    /// Returns the rules of the target rule set.
+    pub fn get_rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Runs a datagram of `src` through the target rule set.
+    pub fn apply<'a>(
+        &mut self,
+        direction: Direction,
+        src: SocketAddr,
+        buf: &'a [u8],
+        now: Instant,
+    ) -> Verdict<'a> {
+        let rule: &mut Rule = match self
+            .rules
+            .iter_mut()
+            .find(|r| r.matches(direction, src, buf))
+        {
+            Some(rule) => rule,
+            None => return Verdict::Pass(Cow::Borrowed(buf), None),
+        };
+        rule.nhits += 1;
+
+        match &mut rule.action {
+            Action::Drop => Verdict::Drop,
+            Action::Forward(to) => Verdict::Pass(Cow::Borrowed(buf), Some(*to)),
+            Action::Rewrite(at, data) => {
+                let mut buf: Vec<u8> = buf.to_vec();
+                let end: usize = (*at + data.len()).min(buf.len());
+                if *at < end {
+                    buf[*at..end].copy_from_slice(&data[..end - *at]);
+                }
+                Verdict::Pass(Cow::Owned(buf), None)
+            }
+            Action::Truncate(length) => {
+                Verdict::Pass(Cow::Borrowed(&buf[..buf.len().min(*length)]), None)
+            }
+            Action::RateLimit(bucket) => {
+                if bucket.take(buf.len(), now) {
+                    Verdict::Pass(Cow::Borrowed(buf), None)
+                } else {
+                    Verdict::Drop
+                }
+            }
+        }
+    }
+}
+
+/// Associate Functions for Rule Specifications
+impl RuleSpec {
+    /// Builds a rule out of the target specification.
+    fn into_rule(self, name: String) -> Result<Rule> {
+        let directions: (bool, bool) = match self.direction.as_deref() {
+            None | Some("both") => (true, true),
+            Some("up") => (true, false),
+            Some("down") => (false, true),
+            Some(direction) => bail!("invalid direction {:?}", direction),
+        };
+
+        let src_addr: Option<(IpAddr, u32)> = match &self.src_addr {
+            Some(subnet) => {
+                let (addr, len): (&str, Option<&str>) = match subnet.split_once('/') {
+                    Some((addr, len)) => (addr, Some(len)),
+                    None => (subnet, None),
+                };
+                let addr: IpAddr = addr.parse()?;
+                let max: u32 = if addr.is_ipv4() { 32 } else { 128 };
+                let len: u32 = match len {
+                    Some(len) => len.parse()?,
+                    None => max,
+                };
+                if len > max {
+                    bail!("invalid subnet {:?}", subnet)
+                }
+                Some((addr, len))
+            }
+            None => None,
+        };
+
+        let src_port: Option<(u16, u16)> = match &self.src_port {
+            Some(Ports::Single(port)) => Some((*port, *port)),
+            Some(Ports::Range(range)) => Some(parse_range(range)?),
+            None => None,
+        };
+
+        let prefix: Option<Vec<u8>> = self.prefix.as_deref().map(parse_bytes).transpose()?;
+
+        let bytes: Option<(usize, Vec<u8>)> = match (self.offset, &self.bytes) {
+            (Some(offset), Some(bytes)) => Some((offset, parse_bytes(bytes)?)),
+            (None, None) => None,
+            _ => bail!("offset and bytes go together"),
+        };
+
+        let size: Option<(usize, usize)> = self.size.as_deref().map(parse_range).transpose()?;
+
+        let action: Action = match self.action.as_str() {
+            "drop" => Action::Drop,
+            "forward" => match &self.to {
+                Some(to) => Action::Forward(to.parse()?),
+                None => bail!("forward needs a destination (to)"),
+            },
+            "rewrite" => match &self.data {
+                Some(data) => Action::Rewrite(self.at.unwrap_or(0), parse_bytes(data)?),
+                None => bail!("rewrite needs some data"),
+            },
+            "truncate" => match self.length {
+                Some(length) => Action::Truncate(length),
+                None => bail!("truncate needs a length"),
+            },
+            "rate-limit" => {
+                let rate: Rate = match &self.rate {
+                    Some(rate) => rate.parse()?,
+                    None => bail!("rate-limit needs a rate"),
+                };
+                let (bytes, rate): (bool, f64) = match rate {
+                    Rate::Packets(pps) => (false, pps as f64),
+                    Rate::Bitrate(bps) => (true, bps as f64 / 8.0),
+                    Rate::Interval(interval) if !interval.is_zero() => {
+                        (false, 1.0 / interval.as_secs_f64())
+                    }
+                    _ => bail!("invalid rate limit {}", rate),
+                };
+                // Defaults to 10 ms worth of traffic, but at least a full-size datagram.
+                let burst: f64 = match self.burst {
+                    Some(burst) => burst as f64,
+                    None if bytes => (rate / 100.0).max(1500.0),
+                    None => (rate / 100.0).max(1.0),
+                };
+                Action::RateLimit(TokenBucket {
+                    bytes,
+                    rate,
+                    burst,
+                    tokens: burst,
+                    last: None,
+                })
+            }
+            action => bail!("unknown action {:?}", action),
+        };
+
+        Ok(Rule {
+            name,
+            directions,
+            src_addr,
+            src_port,
+            prefix,
+            bytes,
+            size,
+            action,
+            nhits: 0,
+        })
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Display Trait Implementation for Rule Actions
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Drop => write!(f, "drop"),
+            Action::Forward(to) => write!(f, "forward to {:?}", to),
+            Action::Rewrite(at, data) => write!(f, "rewrite {} bytes at {}", data.len(), at),
+            Action::Truncate(length) => write!(f, "truncate to {} bytes", length),
+            Action::RateLimit(bucket) => write!(
+                f,
+                "rate-limit to {} {}/s",
+                bucket.rate,
+                if bucket.bytes { "B" } else { "packets" }
+            ),
+        }
+    }
+}
+
+/// Display Trait Implementation for Rules
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.action)
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Checks whether an address is in a subnet. Addresses of another family never are.
+fn in_subnet(ip: IpAddr, subnet: IpAddr, len: u32) -> bool {
+    match (ip, subnet) {
+        (IpAddr::V4(ip), IpAddr::V4(subnet)) => {
+            let mask: u32 = u32::MAX.checked_shl(32 - len).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(subnet) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(subnet)) => {
+            let mask: u128 = u128::MAX.checked_shl(128 - len).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(subnet) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Parses an inclusive range (e.g. `64-1500`) or a single value.
+fn parse_range<T>(s: &str) -> Result<(T, T)>
+where
+    T: std::str::FromStr + PartialOrd + Copy,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let (first, last): (T, T) = match s.split_once('-') {
+        Some((first, last)) => (first.trim().parse()?, last.trim().parse()?),
+        None => {
+            let value: T = s.trim().parse()?;
+            (value, value)
+        }
+    };
+    if first > last {
+        bail!("invalid range {:?}", s)
+    }
+    Ok((first, last))
+}
+
+/// Parses a byte string, written either in hexadecimal (`0xcafe`) or as plain text.
+fn parse_bytes(s: &str) -> Result<Vec<u8>> {
+    let hex: &str = match s.strip_prefix("0x") {
+        Some(hex) => hex,
+        None => return Ok(s.as_bytes().to_vec()),
+    };
+    if hex.len() % 2 == 1 {
+        bail!("odd number of hexadecimal digits in {:?}", s)
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).with_context(|| format!("in {:?}", s)))
+        .collect()
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::std::{env, time::Duration};
+
+    /// Builds a rule set out of a TOML document.
+    fn rules(toml: &str) -> Result<RuleSet> {
+        let file: RulesFile = toml::from_str(toml)?;
+        let rules: Vec<Rule> = file
+            .rules
+            .into_iter()
+            .map(|spec| spec.into_rule(String::from("test")))
+            .collect::<Result<Vec<Rule>>>()?;
+        Ok(RuleSet { rules })
+    }
+
+    /// Returns the address of a client of the 10.0.0.0/24 subnet.
+    fn client(host: u8, port: u16) -> SocketAddr {
+        SocketAddr::from(([10, 0, 0, host], port))
+    }
+
+    /// Asserts if a verdict drops its datagram.
+    fn dropped(verdict: Verdict) -> bool {
+        matches!(verdict, Verdict::Drop)
+    }
+
+    #[test]
+    fn first_matching_rule_decides() {
+        let mut set: RuleSet = rules(
+            r#"
+            [[rules]]
+            src_addr = "10.0.0.0/30"
+            action = "drop"
+            [[rules]]
+            action = "forward"
+            to = "127.0.0.1:7000"
+            "#,
+        )
+        .unwrap();
+        let now: Instant = Instant::now();
+        assert!(dropped(set.apply(Direction::Up, client(3, 1), b"x", now)));
+        match set.apply(Direction::Up, client(4, 1), b"x", now) {
+            Verdict::Pass(buf, to) => {
+                assert_eq!(&buf[..], b"x");
+                assert_eq!(to, Some("127.0.0.1:7000".parse().unwrap()));
+            }
+            Verdict::Drop => panic!("dropped"),
+        }
+        let nhits: Vec<usize> = set.get_rules().iter().map(|r| r.get_nhits()).collect();
+        assert_eq!(nhits, [1, 1]);
+    }
+
+    #[test]
+    fn unmatched_datagrams_pass_untouched() {
+        let mut set: RuleSet = RuleSet::default();
+        match set.apply(Direction::Down, client(1, 1), b"abc", Instant::now()) {
+            Verdict::Pass(Cow::Borrowed(buf), None) => assert_eq!(buf, b"abc"),
+            verdict => panic!("unexpected verdict {:?}", verdict),
+        }
+    }
+
+    #[test]
+    fn match_criteria_combine() {
+        let mut set: RuleSet = rules(
+            r#"
+            [[rules]]
+            direction = "up"
+            src_port = "5000-5100"
+            prefix = "GET"
+            offset = 4
+            bytes = "0x2f61"
+            size = "0-16"
+            action = "drop"
+            "#,
+        )
+        .unwrap();
+        let now: Instant = Instant::now();
+        let mut drops = |direction: Direction, port: u16, buf: &[u8]| -> bool {
+            dropped(set.apply(direction, client(1, port), buf, now))
+        };
+        assert!(drops(Direction::Up, 5000, b"GET /a"));
+        assert!(drops(Direction::Up, 5100, b"GET /abc"));
+        assert!(!drops(Direction::Down, 5000, b"GET /a"));
+        assert!(!drops(Direction::Up, 5101, b"GET /a"));
+        assert!(!drops(Direction::Up, 5000, b"PUT /a"));
+        assert!(!drops(Direction::Up, 5000, b"GET /b"));
+        assert!(!drops(Direction::Up, 5000, b"GET "));
+        assert!(!drops(Direction::Up, 5000, b"GET /a0123456789abcdef"));
+    }
+
+    #[test]
+    fn subnets_match_their_family_only() {
+        let v4: IpAddr = "10.1.2.3".parse().unwrap();
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        assert!(in_subnet(v4, "10.0.0.0".parse().unwrap(), 8));
+        assert!(!in_subnet(v4, "10.0.0.0".parse().unwrap(), 16));
+        assert!(in_subnet(v4, "192.168.0.1".parse().unwrap(), 0));
+        assert!(in_subnet(v4, v4, 32));
+        assert!(in_subnet(v6, "2001:db8::".parse().unwrap(), 32));
+        assert!(!in_subnet(v6, "2001:db9::".parse().unwrap(), 32));
+        assert!(!in_subnet(v6, "10.0.0.0".parse().unwrap(), 0));
+    }
+
+    #[test]
+    fn payloads_are_rewritten_and_truncated() {
+        let mut set: RuleSet = rules(
+            r#"
+            [[rules]]
+            prefix = "a"
+            action = "rewrite"
+            at = 2
+            data = "0xbeef"
+            [[rules]]
+            action = "truncate"
+            length = 2
+            "#,
+        )
+        .unwrap();
+        let now: Instant = Instant::now();
+        let payload = |verdict: Verdict| -> Vec<u8> {
+            match verdict {
+                Verdict::Pass(buf, None) => buf.into_owned(),
+                verdict => panic!("unexpected verdict {:?}", verdict),
+            }
+        };
+        let src: SocketAddr = client(1, 1);
+        assert_eq!(
+            payload(set.apply(Direction::Up, src, b"abcde", now)),
+            b"ab\xbe\xefe"
+        );
+        assert_eq!(
+            payload(set.apply(Direction::Up, src, b"abc", now)),
+            b"ab\xbe"
+        );
+        assert_eq!(payload(set.apply(Direction::Up, src, b"a", now)), b"a");
+        assert_eq!(payload(set.apply(Direction::Up, src, b"xyz", now)), b"xy");
+        assert_eq!(payload(set.apply(Direction::Up, src, b"x", now)), b"x");
+    }
+
+    #[test]
+    fn rate_limits_refill_over_time() {
+        let mut set: RuleSet = rules(
+            r#"
+            [[rules]]
+            action = "rate-limit"
+            rate = "100pps"
+            burst = 2
+            "#,
+        )
+        .unwrap();
+        let now: Instant = Instant::now();
+        let src: SocketAddr = client(1, 1);
+        assert!(!dropped(set.apply(Direction::Up, src, b"x", now)));
+        assert!(!dropped(set.apply(Direction::Up, src, b"x", now)));
+        assert!(dropped(set.apply(Direction::Up, src, b"x", now)));
+        let later: Instant = now + Duration::from_millis(10);
+        assert!(!dropped(set.apply(Direction::Up, src, b"x", later)));
+        assert!(dropped(set.apply(Direction::Up, src, b"x", later)));
+
+        // Bit rates take bytes.
+        let mut set: RuleSet = rules(
+            r#"
+            [[rules]]
+            action = "rate-limit"
+            rate = "8kbps"
+            burst = 1000
+            "#,
+        )
+        .unwrap();
+        assert!(!dropped(set.apply(Direction::Up, src, &[0; 600], now)));
+        assert!(dropped(set.apply(Direction::Up, src, &[0; 600], now)));
+        assert!(!dropped(set.apply(Direction::Up, src, &[0; 400], now)));
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        for invalid in [
+            r#"action = "explode""#,
+            r#"action = "drop"
+            direction = "sideways""#,
+            r#"action = "drop"
+            src_addr = "10.0.0.0/33""#,
+            r#"action = "drop"
+            src_port = "2-1""#,
+            r#"action = "drop"
+            offset = 1"#,
+            r#"action = "drop"
+            prefix = "0xabc""#,
+            r#"action = "forward""#,
+            r#"action = "rewrite""#,
+            r#"action = "truncate""#,
+            r#"action = "rate-limit""#,
+            r#"action = "rate-limit"
+            rate = "off""#,
+            r#"action = "drop"
+            colour = "red""#,
+        ] {
+            let toml: String = format!("[[rules]]\n{}", invalid);
+            assert!(rules(&toml).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn byte_strings_and_ranges_parse() {
+        assert_eq!(parse_bytes("0xCafe").unwrap(), [0xca, 0xfe]);
+        assert!(parse_bytes("0x").unwrap().is_empty());
+        assert_eq!(parse_bytes("cafe").unwrap(), b"cafe");
+        assert!(parse_bytes("0xzz").is_err());
+        assert_eq!(parse_range::<u16>("10 - 20").unwrap(), (10, 20));
+        assert_eq!(parse_range::<usize>("64").unwrap(), (64, 64));
+        assert!(parse_range::<u16>("1-70000").is_err());
+    }
+
+    #[test]
+    fn rule_files_load() {
+        let path: String = env::temp_dir()
+            .join(format!("demikernel-rules-{}.yaml", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        fs::write(
+            &path,
+            "rules:\n  - name: probes\n    prefix: PROBE\n    action: drop\n",
+        )
+        .unwrap();
+        let set: RuleSet = RuleSet::load(&path).unwrap();
+        assert_eq!(set.get_rules()[0].get_name(), "probes");
+        assert_eq!(set.get_rules()[0].to_string(), "probes: drop");
+        fs::remove_file(&path).unwrap();
+        assert!(RuleSet::load(&path).is_err());
+        assert!(RuleSet::load("rules.json").is_err());
+    }
+}
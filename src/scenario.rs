@@ -0,0 +1,366 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::schedule::{parse_duration, Phase, Rate, Schedule, SizeDistribution};
+use ::anyhow::{bail, Context, Result};
+use ::serde::Deserialize;
+use ::std::{fs, path::Path, time::Duration};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Traffic Scenario
+///
+/// A scenario is a reproducible description of a benchmark, loaded from a TOML or YAML file:
+///
+/// ```toml
+/// repeat = false
+///
+/// [[phases]]
+/// name = "ramp"
+/// pattern = "ramp"
+/// duration = "10s"
+/// rate = "1Gbps"
+/// rate_to = "10Gbps"
+/// steps = 10
+///
+/// [[phases]]
+/// name = "spike"
+/// pattern = "on-off"
+/// duration = "1s"
+/// on = "10ms"
+/// off = "90ms"
+/// rate = "0us"
+/// size = "64-1500"
+/// burst = 32
+/// connections = 4
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Cycle through phases forever?
+    #[serde(default)]
+    repeat: bool,
+    /// Phases.
+    phases: Vec<ScenarioPhase>,
+}
+
+/// Scenario Phase
+///
+/// Missing fields are taken from the command line arguments.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioPhase {
+    /// Label used to tag statistics.
+    name: Option<String>,
+    /// Traffic pattern.
+    #[serde(default)]
+    pattern: Pattern,
+    /// Duration (e.g. `10s`).
+    duration: Option<String>,
+    /// Injection rate (e.g. `10us`, `1Gbps` or `100000pps`).
+    rate: Option<String>,
+    /// Final injection rate of a ramp.
+    rate_to: Option<String>,
+    /// Number of steps of a ramp.
+    steps: Option<usize>,
+    /// On period of an on/off pattern.
+    on: Option<String>,
+    /// Off period of an on/off pattern.
+    off: Option<String>,
+    /// Packet size distribution (e.g. `1024`, `64-1500` or `64|512|1500`).
+    size: Option<Size>,
+    /// Number of back-to-back pushes per tick.
+    burst: Option<usize>,
+    /// Number of connections.
+    connections: Option<usize>,
+}
+
+/// Traffic Pattern
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Pattern {
+    /// Constant rate for the whole phase.
+    Constant,
+    /// Rate changes from `rate` to `rate_to` in `steps` equal steps.
+    Ramp,
+    /// Alternates between `on` periods at `rate` and `off` periods.
+    OnOff,
+}
+
+/// Packet Size Field
+///
+/// Sizes may be written either as plain numbers or as distribution strings.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Size {
+    Fixed(usize),
+    Distribution(String),
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Traffic Scenarios
+impl Scenario {
+    /// Loads a scenario from a file. The format is picked from the file extension.
+    pub fn load(path: &str) -> Result<Self> {
+        let contents: String =
+            fs::read_to_string(path).with_context(|| format!("failed to read {:?}", path))?;
+        let scenario: Scenario = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&contents)?,
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&contents)?,
+            _ => bail!(
+                "unknown scenario format for {:?} (expected .toml, .yaml or .yml)",
+                path
+            ),
+        };
+        if scenario.phases.is_empty() {
+            bail!("scenario {:?} has no phases", path)
+        }
+        Ok(scenario)
+    }
+
+    /// Builds a traffic schedule out of the target scenario. Fields that are missing in a phase
+    /// are taken from `default`.
+    pub fn to_schedule(&self, default: &Phase) -> Result<Schedule> {
+        let mut phases: Vec<Phase> = Vec::new();
+        for (i, phase) in self.phases.iter().enumerate() {
+            let name: String = match &phase.name {
+                Some(name) => name.clone(),
+                None => format!("phase {}", i),
+            };
+            phase
+                .expand(&name, default, &mut phases)
+                .with_context(|| format!("invalid scenario phase {:?}", name))?;
+        }
+        Schedule::new(phases, self.repeat)
+    }
+}
+
+/// Associate Functions for Scenario Phases
+impl ScenarioPhase {
+    /// Expands the target scenario phase into schedule phases.
+    fn expand(&self, name: &str, default: &Phase, phases: &mut Vec<Phase>) -> Result<()> {
+        let duration: Option<Duration> =
+            self.duration.as_deref().map(parse_duration).transpose()?;
+        let rate: Rate = match &self.rate {
+            Some(rate) => rate.parse()?,
+            None => default.get_rate(),
+        };
+        let sizes: SizeDistribution = match &self.size {
+            Some(Size::Fixed(size)) => SizeDistribution::Fixed(*size),
+            Some(Size::Distribution(sizes)) => sizes.parse()?,
+            None => default.get_sizes().clone(),
+        };
+        let burst: usize = self.burst.unwrap_or_else(|| default.get_burst());
+        let connections: usize = self
+            .connections
+            .unwrap_or_else(|| default.get_connections());
+
+        // Builds a single schedule phase.
+        let mk = |label: &str, duration: Option<Duration>, rate: Rate| -> Result<Phase> {
+            let mut phase: Phase = Phase::new(duration, rate, sizes.clone(), burst)?;
+            phase.set_name(label);
+            phase.set_connections(connections)?;
+            Ok(phase)
+        };
+
+        match self.pattern {
+            Pattern::Constant => phases.push(mk(name, duration, rate)?),
+            Pattern::Ramp => {
+                let duration: Duration = match duration {
+                    Some(duration) => duration,
+                    None => bail!("a ramp needs a duration"),
+                };
+                let rate_to: Rate = match &self.rate_to {
+                    Some(rate_to) => rate_to.parse()?,
+                    None => bail!("a ramp needs a final rate (rate_to)"),
+                };
+                let steps: usize = self.steps.unwrap_or(10).max(1);
+                for step in 0..steps {
+                    let t: f64 = if steps > 1 {
+                        step as f64 / (steps - 1) as f64
+                    } else {
+                        1.0
+                    };
+                    let label: String = format!("{}/{}", name, step);
+                    let rate: Rate = interpolate(rate, rate_to, t)?;
+                    phases.push(mk(&label, Some(duration / steps as u32), rate)?);
+                }
+            }
+            Pattern::OnOff => {
+                let (on, off): (Duration, Duration) = match (&self.on, &self.off) {
+                    (Some(on), Some(off)) => (parse_duration(on)?, parse_duration(off)?),
+                    _ => bail!("an on/off pattern needs both on and off periods"),
+                };
+                // Without a duration, a single period is played, which is only useful when the
+                // scenario repeats.
+                let duration: Duration = duration.unwrap_or(on + off);
+                let mut phase: Phase = mk(name, Some(duration), rate)?;
+                phase.set_cycle(on, off)?;
+                phases.push(phase);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Default Trait Implementation for Traffic Patterns
+impl Default for Pattern {
+    fn default() -> Self {
+        Pattern::Constant
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Linearly interpolates between two rates of the same kind.
+fn interpolate(from: Rate, to: Rate, t: f64) -> Result<Rate> {
+    let lerp = |a: f64, b: f64| -> f64 { a + (b - a) * t };
+    match (from, to) {
+        (Rate::Interval(a), Rate::Interval(b)) => Ok(Rate::Interval(Duration::from_secs_f64(
+            lerp(a.as_secs_f64(), b.as_secs_f64()),
+        ))),
+        (Rate::Bitrate(a), Rate::Bitrate(b)) => Ok(Rate::Bitrate(lerp(a as f64, b as f64) as u64)),
+        (Rate::Packets(a), Rate::Packets(b)) => Ok(Rate::Packets(lerp(a as f64, b as f64) as u64)),
+        _ => bail!("cannot ramp between {} and {}", from, to),
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a phase to take missing parameters from.
+    fn default() -> Phase {
+        Phase::new(None, Rate::Off, SizeDistribution::Fixed(64), 1).unwrap()
+    }
+
+    /// Builds the schedule of a TOML scenario.
+    fn schedule(toml: &str) -> Result<Schedule> {
+        let scenario: Scenario = toml::from_str(toml)?;
+        scenario.to_schedule(&default())
+    }
+
+    #[test]
+    fn missing_fields_are_defaulted() {
+        let schedule: Schedule = schedule(
+            r#"
+            repeat = true
+
+            [[phases]]
+            duration = "1s"
+            rate = "100000pps"
+            size = 128
+
+            [[phases]]
+            name = "mixed"
+            duration = "2s"
+            size = "64|1500"
+            burst = 8
+            connections = 2
+            "#,
+        )
+        .unwrap();
+        let phases: &[Phase] = schedule.get_phases();
+        assert_eq!(phases.len(), 2);
+        assert_eq!(phases[0].get_name(), Some("phase 0"));
+        assert_eq!(phases[0].get_rate(), Rate::Packets(100_000));
+        assert_eq!(phases[0].get_sizes(), &SizeDistribution::Fixed(128));
+        assert_eq!(phases[0].get_burst(), 1);
+        assert_eq!(phases[1].get_name(), Some("mixed"));
+        assert_eq!(phases[1].get_rate(), Rate::Off);
+        assert_eq!(
+            phases[1].get_sizes(),
+            &SizeDistribution::Choice(vec![64, 1500])
+        );
+        assert_eq!(phases[1].get_burst(), 8);
+        assert_eq!(schedule.get_max_connections(), 2);
+    }
+
+    #[test]
+    fn ramps_are_split_into_steps() {
+        let schedule: Schedule = schedule(
+            r#"
+            [[phases]]
+            name = "ramp"
+            pattern = "ramp"
+            duration = "10s"
+            rate = "1Gbps"
+            rate_to = "5Gbps"
+            steps = 5
+            "#,
+        )
+        .unwrap();
+        let rates: Vec<Rate> = schedule.get_phases().iter().map(Phase::get_rate).collect();
+        let expected: Vec<Rate> = (1..=5).map(|g| Rate::Bitrate(g * 1_000_000_000)).collect();
+        assert_eq!(rates, expected);
+        for (i, phase) in schedule.get_phases().iter().enumerate() {
+            assert_eq!(phase.get_duration(), Some(Duration::from_secs(2)));
+            assert_eq!(phase.get_name(), Some(format!("ramp/{}", i).as_str()));
+        }
+    }
+
+    #[test]
+    fn invalid_phases_are_rejected() {
+        let invalid: [&str; 5] = [
+            // A ramp without a duration, a final rate, or a final rate of the same kind.
+            r#"{ pattern = "ramp", rate = "1Gbps", rate_to = "2Gbps" }"#,
+            r#"{ pattern = "ramp", duration = "1s", rate = "1Gbps" }"#,
+            r#"{ pattern = "ramp", duration = "1s", rate = "1Gbps", rate_to = "10us" }"#,
+            // An on/off pattern without an off period, or with an empty period.
+            r#"{ pattern = "on-off", on = "1ms" }"#,
+            r#"{ pattern = "on-off", on = "0ms", off = "0ms" }"#,
+        ];
+        for phase in invalid {
+            assert!(
+                schedule(&format!("phases = [{}]", phase)).is_err(),
+                "{}",
+                phase
+            );
+        }
+        assert!(schedule("phases = []").is_err());
+        assert!(toml::from_str::<Scenario>(r#"phases = [{ speed = "fast" }]"#).is_err());
+    }
+
+    #[test]
+    fn on_off_patterns_are_not_expanded() {
+        let scenario: Scenario = toml::from_str(
+            r#"
+            [[phases]]
+            pattern = "on-off"
+            duration = "3600s"
+            on = "1us"
+            off = "1us"
+            rate = "1Gbps"
+            "#,
+        )
+        .unwrap();
+        let schedule: Schedule = scenario.to_schedule(&default()).unwrap();
+        let phases: &[Phase] = schedule.get_phases();
+        assert_eq!(phases.len(), 1);
+        assert_eq!(phases[0].get_duration(), Some(Duration::from_secs(3600)));
+        assert_eq!(
+            phases[0].get_cycle(),
+            Some((Duration::from_micros(1), Duration::from_micros(1)))
+        );
+    }
+}
@@ -0,0 +1,886 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Result};
+use ::log::info;
+use ::std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Injection Rate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rate {
+    /// Fixed interval between two consecutive ticks.
+    Interval(Duration),
+    /// Target throughput (in bits per second).
+    Bitrate(u64),
+    /// Target packet rate (in packets per second).
+    Packets(u64),
+    /// No traffic at all.
+    Off,
+}
+
+/// Packet Size Distribution
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SizeDistribution {
+    /// All packets have the same size.
+    Fixed(usize),
+    /// Sizes are uniformly distributed in an inclusive range.
+    Uniform(usize, usize),
+    /// Sizes are uniformly picked from a set of values.
+    Choice(Vec<usize>),
+}
+
+/// Traffic Phase
+///
+/// A phase describes how a generator behaves for some amount of time: how often it ticks, how
+/// many packets it pushes back-to-back on every tick and how large these packets are.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Phase {
+    /// Label of the phase, used to tag statistics.
+    name: Option<String>,
+    /// Duration of the phase (`None` means forever).
+    duration: Option<Duration>,
+    /// Injection rate.
+    rate: Rate,
+    /// Packet sizes (in bytes).
+    sizes: SizeDistribution,
+    /// Number of back-to-back pushes per tick.
+    burst: usize,
+    /// Number of connections to spread traffic over.
+    connections: usize,
+    /// On and off periods of a phase that alternates between its rate and no traffic.
+    cycle: Option<(Duration, Duration)>,
+}
+
+/// Traffic Schedule
+///
+/// A sequence of phases that is either cycled through forever or played once.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    /// Phases.
+    phases: Vec<Phase>,
+    /// Cycle through phases forever?
+    repeat: bool,
+    /// Index of the current phase.
+    current: usize,
+    /// Instant at which the current phase started.
+    phase_start: Instant,
+    /// Idle phase, which stands for phases that are in an off period.
+    idle: Phase,
+}
+
+/// Per-Phase Statistics
+///
+/// Phases that share a name share statistics, so that expanded patterns (e.g. ramps) are reported
+/// as a whole.
+#[derive(Debug)]
+pub struct PhaseStatistics {
+    /// Labels of statistics slots.
+    labels: Vec<String>,
+    /// Statistics slot of each phase.
+    slots: Vec<usize>,
+    /// Number of bytes transferred in each slot.
+    nbytes: Vec<usize>,
+    /// Time spent in each slot.
+    elapsed: Vec<Duration>,
+    /// Current slot.
+    current: usize,
+    /// Instant at which the current slot was entered.
+    since: Instant,
+}
+
+/// Pseudo-Random Number Generator
+///
+/// A xorshift64* generator, which is good enough to pick packet sizes and cheap enough to be
+/// called on the data path.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    /// Internal state.
+    state: u64,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Injection Rates
+impl Rate {
+    /// Returns the interval between two ticks for the target rate, given the number of packets
+    /// and the mean number of bytes that are pushed on every tick. `None` means no traffic.
+    pub fn interval(&self, packets_per_tick: usize, bytes_per_tick: f64) -> Option<Duration> {
+        match *self {
+            Rate::Interval(interval) => Some(interval),
+            Rate::Bitrate(bps) if bps > 0 => {
+                Some(Duration::from_secs_f64(bytes_per_tick * 8.0 / bps as f64))
+            }
+            Rate::Packets(pps) if pps > 0 => Some(Duration::from_secs_f64(
+                packets_per_tick as f64 / pps as f64,
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Associate Functions for Packet Size Distributions
+impl SizeDistribution {
+    /// Returns the largest size in the target distribution.
+    pub fn max(&self) -> usize {
+        match self {
+            SizeDistribution::Fixed(size) => *size,
+            SizeDistribution::Uniform(_, max) => *max,
+            SizeDistribution::Choice(sizes) => sizes.iter().copied().max().unwrap_or(0),
+        }
+    }
+
+    /// Returns the mean size of the target distribution.
+    pub fn mean(&self) -> f64 {
+        match self {
+            SizeDistribution::Fixed(size) => *size as f64,
+            SizeDistribution::Uniform(min, max) => (*min + *max) as f64 / 2.0,
+            SizeDistribution::Choice(sizes) => {
+                sizes.iter().sum::<usize>() as f64 / sizes.len() as f64
+            }
+        }
+    }
+
+    /// Draws a size from the target distribution.
+    pub fn sample(&self, rng: &mut Rng) -> usize {
+        match self {
+            SizeDistribution::Fixed(size) => *size,
+            SizeDistribution::Uniform(min, max) => {
+                min + (rng.next_u64() % (max - min + 1) as u64) as usize
+            }
+            SizeDistribution::Choice(sizes) => {
+                sizes[(rng.next_u64() % sizes.len() as u64) as usize]
+            }
+        }
+    }
+}
+
+/// Associate Functions for Traffic Phases
+impl Phase {
+    /// Creates a phase.
+    pub fn new(
+        duration: Option<Duration>,
+        rate: Rate,
+        sizes: SizeDistribution,
+        burst: usize,
+    ) -> Result<Self> {
+        if sizes.max() == 0 {
+            bail!("invalid buffer size")
+        }
+        if burst == 0 {
+            bail!("invalid burst size")
+        }
+        if duration == Some(Duration::ZERO) {
+            bail!("invalid phase duration")
+        }
+        Ok(Self {
+            name: None,
+            duration,
+            rate,
+            sizes,
+            burst,
+            connections: 1,
+            cycle: None,
+        })
+    }
+
+    /// Parses a phase specification.
+    ///
+    /// A specification is a comma-separated list of `key=value` pairs. Recognized keys are
+    /// `name`, `duration`, `rate` (see [Rate]), `size` (see [SizeDistribution]), `burst`,
+    /// `connections`, and `on` and `off`, which make the phase alternate between periods at its
+    /// rate and periods without traffic. The `off` keyword makes the phase idle. Time values take
+    /// a unit suffix (`ns`, `us`, `ms` or `s`). Missing keys are taken from `default`.
+    ///
+    /// Example: `duration=1s,rate=0us,burst=32,on=10ms,off=90ms` sends bursts at line rate for
+    /// 10 ms out of every 100 ms.
+    pub fn parse(spec: &str, default: &Phase) -> Result<Self> {
+        let mut name: Option<String> = default.name.clone();
+        let mut duration: Option<Duration> = None;
+        let mut rate: Rate = default.rate;
+        let mut sizes: SizeDistribution = default.sizes.clone();
+        let mut burst: usize = default.burst;
+        let mut connections: usize = default.connections;
+        let (mut on, mut off): (Option<Duration>, Option<Duration>) = match default.cycle {
+            Some((on, off)) => (Some(on), Some(off)),
+            None => (None, None),
+        };
+
+        for token in spec.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            match token.split_once('=') {
+                Some(("name", value)) => name = Some(value.to_string()),
+                Some(("duration", value)) => duration = Some(parse_duration(value)?),
+                Some(("rate", value)) => rate = value.parse()?,
+                Some(("size", value)) => sizes = value.parse()?,
+                Some(("burst", value)) => burst = value.parse()?,
+                Some(("connections", value)) => connections = value.parse()?,
+                Some(("on", value)) => on = Some(parse_duration(value)?),
+                Some(("off", value)) => off = Some(parse_duration(value)?),
+                None if token == "off" => rate = Rate::Off,
+                _ => bail!("invalid phase parameter: {:?}", token),
+            }
+        }
+
+        let mut phase: Phase = Self::new(duration, rate, sizes, burst)?;
+        phase.name = name;
+        phase.set_connections(connections)?;
+        match (on, off) {
+            (Some(on), Some(off)) => phase.set_cycle(on, off)?,
+            (None, None) => (),
+            _ => bail!("an on/off pattern needs both on and off periods"),
+        }
+        Ok(phase)
+    }
+
+    /// Returns the name of the target phase.
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the duration of the target phase.
+    pub fn get_duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// Returns the injection rate of the target phase.
+    pub fn get_rate(&self) -> Rate {
+        self.rate
+    }
+
+    /// Returns the interval between two ticks of the target phase.
+    pub fn get_interval(&self) -> Option<Duration> {
+        self.rate
+            .interval(self.burst, self.sizes.mean() * self.burst as f64)
+    }
+
+    /// Returns the packet size distribution of the target phase.
+    pub fn get_sizes(&self) -> &SizeDistribution {
+        &self.sizes
+    }
+
+    /// Returns the number of back-to-back pushes per tick of the target phase.
+    pub fn get_burst(&self) -> usize {
+        self.burst
+    }
+
+    /// Returns the number of connections of the target phase.
+    pub fn get_connections(&self) -> usize {
+        self.connections
+    }
+
+    /// Returns the on and off periods of the target phase, if it alternates between them.
+    pub fn get_cycle(&self) -> Option<(Duration, Duration)> {
+        self.cycle
+    }
+
+    /// Sets the name of the target phase.
+    pub fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    /// Sets the injection rate of the target phase.
+    pub fn set_rate(&mut self, rate: Rate) {
+        self.rate = rate;
+    }
+
+    /// Makes the target phase alternate between `on` periods at its rate and `off` periods without
+    /// traffic, starting with an on period.
+    pub fn set_cycle(&mut self, on: Duration, off: Duration) -> Result<()> {
+        if on + off > Duration::ZERO {
+            self.cycle = Some((on, off));
+            Ok(())
+        } else {
+            bail!("an on/off pattern needs a non-empty period")
+        }
+    }
+
+    /// Sets the number of connections of the target phase.
+    pub fn set_connections(&mut self, connections: usize) -> Result<()> {
+        if connections > 0 {
+            self.connections = connections;
+            Ok(())
+        } else {
+            bail!("invalid number of connections")
+        }
+    }
+}
+
+/// Associate Functions for Traffic Schedules
+impl Schedule {
+    /// Creates a schedule.
+    pub fn new(phases: Vec<Phase>, repeat: bool) -> Result<Self> {
+        if phases.is_empty() {
+            bail!("empty schedule")
+        }
+        if phases[..phases.len() - 1]
+            .iter()
+            .any(|p| p.duration.is_none())
+        {
+            bail!("only the last phase of a schedule may last forever")
+        }
+        if repeat && phases.len() > 1 && phases.iter().any(|p| p.duration.is_none()) {
+            bail!("phases of a repeating schedule must have a duration")
+        }
+        Ok(Self {
+            phases,
+            repeat,
+            current: 0,
+            phase_start: Instant::now(),
+            idle: Phase::new(None, Rate::Off, SizeDistribution::Fixed(1), 1)?,
+        })
+    }
+
+    /// Returns the phases of the target schedule.
+    pub fn get_phases(&self) -> &[Phase] {
+        &self.phases
+    }
+
+    /// Returns the largest packet size across all phases of the target schedule.
+    pub fn get_max_bufsize(&self) -> usize {
+        self.phases.iter().map(|p| p.sizes.max()).max().unwrap_or(0)
+    }
+
+    /// Returns the largest number of connections across all phases of the target schedule.
+    pub fn get_max_connections(&self) -> usize {
+        self.phases.iter().map(|p| p.connections).max().unwrap_or(1)
+    }
+
+    /// Sets the injection rate of all phases of the target schedule, overriding their own.
+    pub fn set_rate(&mut self, rate: Rate) {
+        for phase in &mut self.phases {
+            phase.set_rate(rate);
+        }
+    }
+
+    /// Restarts the target schedule from its first phase.
+    pub fn restart(&mut self, now: Instant) {
+        self.current = 0;
+        self.phase_start = now;
+    }
+
+    /// Returns the index and the parameters of the phase that is active at `now`, advancing the
+    /// target schedule past any phase that has expired. `None` means that a non-repeating
+    /// schedule is over.
+    pub fn poll(&mut self, now: Instant) -> Option<(usize, &Phase)> {
+        while let Some(duration) = self.phases.get(self.current)?.duration {
+            let elapsed: Duration = now.saturating_duration_since(self.phase_start);
+            if elapsed < duration {
+                break;
+            }
+            self.phase_start += duration;
+            self.current += 1;
+            if self.repeat {
+                self.current %= self.phases.len();
+            }
+
+            // Do not try to catch up with phases that we have entirely missed.
+            let next: Option<Duration> = self.phases.get(self.current).and_then(|p| p.duration);
+            if matches!(next, Some(next) if now.saturating_duration_since(self.phase_start) > next)
+            {
+                self.phase_start = now;
+            }
+        }
+
+        // Phases that are in an off period are idle.
+        let phase: &Phase = &self.phases[self.current];
+        if let Some((on, off)) = phase.cycle {
+            let elapsed: Duration = now.saturating_duration_since(self.phase_start);
+            if elapsed.as_nanos() % (on + off).as_nanos() >= on.as_nanos() {
+                return Some((self.current, &self.idle));
+            }
+        }
+        Some((self.current, phase))
+    }
+}
+
+/// Associate Functions for Per-Phase Statistics
+impl PhaseStatistics {
+    /// Creates per-phase statistics for a schedule.
+    pub fn new(schedule: &Schedule, now: Instant) -> Self {
+        let mut labels: Vec<String> = Vec::new();
+        let mut slots: Vec<usize> = Vec::new();
+        for (i, phase) in schedule.get_phases().iter().enumerate() {
+            let label: String = match phase.get_name() {
+                Some(name) => name.to_string(),
+                None => format!("phase {}", i),
+            };
+            match labels.iter().position(|l| *l == label) {
+                Some(slot) => slots.push(slot),
+                None => {
+                    slots.push(labels.len());
+                    labels.push(label);
+                }
+            }
+        }
+        let n: usize = labels.len();
+        Self {
+            labels,
+            slots,
+            nbytes: vec![0; n],
+            elapsed: vec![Duration::ZERO; n],
+            current: 0,
+            since: now,
+        }
+    }
+
+    /// Switches the target statistics to phase `i`.
+    pub fn switch(&mut self, i: usize, now: Instant) {
+        let slot: usize = self.slots[i];
+        if slot != self.current {
+            self.elapsed[self.current] += now.saturating_duration_since(self.since);
+            self.current = slot;
+            self.since = now;
+        }
+    }
+
+    /// Accounts `nbytes` transferred in the current phase.
+    pub fn record(&mut self, nbytes: usize) {
+        self.nbytes[self.current] += nbytes;
+    }
+
+    /// Returns the label and the statistics of the current phase.
+    pub fn current(&self, now: Instant) -> (&str, usize, Duration) {
+        let elapsed: Duration =
+            self.elapsed[self.current] + now.saturating_duration_since(self.since);
+        (
+            &self.labels[self.current],
+            self.nbytes[self.current],
+            elapsed,
+        )
+    }
+
+    /// Clears the statistics of all slots.
+    pub fn reset(&mut self, now: Instant) {
+        self.nbytes.iter_mut().for_each(|n| *n = 0);
+        self.elapsed.iter_mut().for_each(|e| *e = Duration::ZERO);
+        self.since = now;
+    }
+
+    /// Logs a summary of all slots.
+    pub fn dump(&mut self, now: Instant) {
+        self.elapsed[self.current] += now.saturating_duration_since(self.since);
+        self.since = now;
+        for (i, label) in self.labels.iter().enumerate() {
+            info!(
+                "[{}] {:?} B / {:?} us",
+                label,
+                self.nbytes[i],
+                self.elapsed[i].as_micros()
+            );
+        }
+    }
+}
+
+/// Associate Functions for the Pseudo-Random Number Generator
+impl Rng {
+    /// Creates a generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift.
+        Self { state: seed | 1 }
+    }
+
+    /// Returns the next pseudo-random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Returns the next pseudo-random number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// From String Trait Implementation for Injection Rates
+///
+/// Rates are either an interval between ticks (`10us`), a throughput (`500Mbps`, `10Gbps`), a
+/// packet rate (`100000pps`) or `off`.
+impl FromStr for Rate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s: &str = s.trim();
+        if s == "off" {
+            return Ok(Rate::Off);
+        }
+        if let Some(pps) = s.strip_suffix("pps") {
+            return Ok(Rate::Packets(pps.parse()?));
+        }
+        if let Some(bps) = s.strip_suffix("bps") {
+            let (value, multiplier): (&str, f64) = match bps.chars().last() {
+                Some('K') | Some('k') => (&bps[..bps.len() - 1], 1e3),
+                Some('M') => (&bps[..bps.len() - 1], 1e6),
+                Some('G') => (&bps[..bps.len() - 1], 1e9),
+                _ => (bps, 1.0),
+            };
+            let value: f64 = value.parse()?;
+            return Ok(Rate::Bitrate((value * multiplier) as u64));
+        }
+        Ok(Rate::Interval(parse_duration(s)?))
+    }
+}
+
+/// From String Trait Implementation for Packet Size Distributions
+///
+/// Distributions are either a fixed size (`1024`), an inclusive range (`64-1500`) or a set of
+/// sizes (`64|512|1500`).
+impl FromStr for SizeDistribution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s: &str = s.trim();
+        if let Some((min, max)) = s.split_once('-') {
+            let (min, max): (usize, usize) = (min.trim().parse()?, max.trim().parse()?);
+            if min == 0 || min > max {
+                bail!("invalid size range: {:?}", s)
+            }
+            return Ok(SizeDistribution::Uniform(min, max));
+        }
+        if s.contains('|') {
+            let sizes: Vec<usize> = s
+                .split('|')
+                .map(|size| size.trim().parse())
+                .collect::<Result<Vec<usize>, _>>()?;
+            if sizes.contains(&0) {
+                bail!("invalid size set: {:?}", s)
+            }
+            return Ok(SizeDistribution::Choice(sizes));
+        }
+        Ok(SizeDistribution::Fixed(s.parse()?))
+    }
+}
+
+/// Display Trait Implementation for Injection Rates
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rate::Interval(interval) => write!(f, "{:?}", interval),
+            Rate::Bitrate(bps) => write!(f, "{}bps", bps),
+            Rate::Packets(pps) => write!(f, "{}pps", pps),
+            Rate::Off => write!(f, "off"),
+        }
+    }
+}
+
+/// Display Trait Implementation for Packet Size Distributions
+impl fmt::Display for SizeDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SizeDistribution::Fixed(size) => write!(f, "{}", size),
+            SizeDistribution::Uniform(min, max) => write!(f, "{}-{}", min, max),
+            SizeDistribution::Choice(sizes) => {
+                let sizes: Vec<String> = sizes.iter().map(|size| size.to_string()).collect();
+                write!(f, "{}", sizes.join("|"))
+            }
+        }
+    }
+}
+
+/// Display Trait Implementation for Traffic Phases
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.name {
+            write!(f, "name={}, ", name)?;
+        }
+        match self.duration {
+            Some(duration) => write!(f, "duration={:?}", duration)?,
+            None => write!(f, "duration=forever")?,
+        }
+        match self.rate {
+            Rate::Off => write!(f, ", off")?,
+            rate => write!(
+                f,
+                ", rate={}, size={}, burst={}, connections={}",
+                rate, self.sizes, self.burst, self.connections
+            )?,
+        }
+        match self.cycle {
+            Some((on, off)) => write!(f, ", on={:?}, off={:?}", on, off),
+            None => Ok(()),
+        }
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Parses a duration with a unit suffix (`ns`, `us`, `ms` or `s`).
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s: &str = s.trim();
+    let split: usize = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit): (&str, &str) = s.split_at(split);
+    let value: u64 = value.parse()?;
+    match unit {
+        "ns" => Ok(Duration::from_nanos(value)),
+        "us" => Ok(Duration::from_micros(value)),
+        "ms" => Ok(Duration::from_millis(value)),
+        "s" => Ok(Duration::from_secs(value)),
+        _ => bail!("invalid time unit in {:?} (expected ns, us, ms or s)", s),
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a phase to take missing parameters from.
+    fn default() -> Phase {
+        Phase::new(None, Rate::Off, SizeDistribution::Fixed(64), 1).unwrap()
+    }
+
+    /// Returns the number of ticks per second of a phase.
+    fn ticks_per_sec(phase: &Phase) -> f64 {
+        1.0 / phase.get_interval().expect("no traffic").as_secs_f64()
+    }
+
+    #[test]
+    fn packet_rates_account_for_bursts() {
+        let sizes: SizeDistribution = SizeDistribution::Fixed(64);
+        let phase: Phase = Phase::new(None, Rate::Packets(100_000), sizes.clone(), 32).unwrap();
+        let pps: f64 = ticks_per_sec(&phase) * 32.0;
+        assert!((pps - 100_000.0).abs() < 1.0, "{} pps", pps);
+
+        let phase: Phase = Phase::new(None, Rate::Bitrate(1_000_000), sizes, 32).unwrap();
+        let bps: f64 = ticks_per_sec(&phase) * 32.0 * 64.0 * 8.0;
+        assert!((bps - 1_000_000.0).abs() < 1.0, "{} bps", bps);
+    }
+
+    #[test]
+    fn on_off_phases_idle_during_off_periods() {
+        let mut phase: Phase = Phase::parse("duration=1s,rate=10us", &default()).unwrap();
+        phase
+            .set_cycle(Duration::from_millis(10), Duration::from_millis(90))
+            .unwrap();
+        let mut schedule: Schedule = Schedule::new(vec![phase], false).unwrap();
+        let start: Instant = Instant::now();
+        schedule.restart(start);
+
+        let at = |schedule: &mut Schedule, ms: u64| -> Option<Duration> {
+            let (i, phase): (usize, &Phase) = schedule
+                .poll(start + Duration::from_millis(ms))
+                .expect("schedule is over");
+            assert_eq!(i, 0);
+            phase.get_interval()
+        };
+        assert_eq!(at(&mut schedule, 5), Some(Duration::from_micros(10)));
+        assert_eq!(at(&mut schedule, 50), None);
+        assert_eq!(at(&mut schedule, 105), Some(Duration::from_micros(10)));
+        assert_eq!(at(&mut schedule, 999), None);
+        assert!(schedule.poll(start + Duration::from_secs(1)).is_none());
+    }
+
+    #[test]
+    fn rates_parse() {
+        assert_eq!(
+            "10us".parse::<Rate>().unwrap(),
+            Rate::Interval(Duration::from_micros(10))
+        );
+        assert_eq!(
+            "500Mbps".parse::<Rate>().unwrap(),
+            Rate::Bitrate(500_000_000)
+        );
+        assert_eq!(
+            "2.5Gbps".parse::<Rate>().unwrap(),
+            Rate::Bitrate(2_500_000_000)
+        );
+        assert_eq!("64kbps".parse::<Rate>().unwrap(), Rate::Bitrate(64_000));
+        assert_eq!("100000pps".parse::<Rate>().unwrap(), Rate::Packets(100_000));
+        assert_eq!(" off ".parse::<Rate>().unwrap(), Rate::Off);
+        assert!("10".parse::<Rate>().is_err());
+        assert!("fastpps".parse::<Rate>().is_err());
+        assert!("10h".parse::<Rate>().is_err());
+    }
+
+    #[test]
+    fn size_distributions_parse_and_sample() {
+        assert_eq!(
+            "1024".parse::<SizeDistribution>().unwrap(),
+            SizeDistribution::Fixed(1024)
+        );
+        let uniform: SizeDistribution = "64-1500".parse().unwrap();
+        assert_eq!(uniform, SizeDistribution::Uniform(64, 1500));
+        assert_eq!(uniform.max(), 1500);
+        assert_eq!(uniform.mean(), 782.0);
+        let choice: SizeDistribution = "64|512|1500".parse().unwrap();
+        assert_eq!(choice, SizeDistribution::Choice(vec![64, 512, 1500]));
+        assert_eq!(choice.to_string(), "64|512|1500");
+        assert!("0-10".parse::<SizeDistribution>().is_err());
+        assert!("1500-64".parse::<SizeDistribution>().is_err());
+        assert!("64|0".parse::<SizeDistribution>().is_err());
+
+        let mut rng: Rng = Rng::new(1);
+        for _ in 0..1000 {
+            let size: usize = uniform.sample(&mut rng);
+            assert!((64..=1500).contains(&size));
+            assert!([64, 512, 1500].contains(&choice.sample(&mut rng)));
+        }
+    }
+
+    #[test]
+    fn phases_parse() {
+        let phase: Phase = Phase::parse(
+            "name=burst,duration=10ms,rate=1Gbps,size=64-128,burst=32,connections=4",
+            &default(),
+        )
+        .unwrap();
+        assert_eq!(phase.get_name(), Some("burst"));
+        assert_eq!(phase.get_duration(), Some(Duration::from_millis(10)));
+        assert_eq!(phase.get_rate(), Rate::Bitrate(1_000_000_000));
+        assert_eq!(phase.get_sizes(), &SizeDistribution::Uniform(64, 128));
+        assert_eq!(phase.get_burst(), 32);
+        assert_eq!(phase.get_connections(), 4);
+
+        // Missing keys are taken from the default phase.
+        let phase: Phase = Phase::parse("duration=90ms,off", &phase).unwrap();
+        assert_eq!(phase.get_rate(), Rate::Off);
+        assert_eq!(phase.get_interval(), None);
+        assert_eq!(phase.get_burst(), 32);
+
+        assert!(Phase::parse("burst=0", &default()).is_err());
+        assert!(Phase::parse("connections=0", &default()).is_err());
+        assert!(Phase::parse("duration=0ms", &default()).is_err());
+        assert!(Phase::parse("speed=fast", &default()).is_err());
+    }
+
+    #[test]
+    fn on_off_phases_parse() {
+        let phase: Phase =
+            Phase::parse("duration=1s,rate=0us,on=10ms,off=90ms", &default()).unwrap();
+        assert_eq!(
+            phase.get_cycle(),
+            Some((Duration::from_millis(10), Duration::from_millis(90)))
+        );
+        assert!(phase.to_string().ends_with(", on=10ms, off=90ms"));
+        assert_eq!(
+            Phase::parse("duration=1s", &default()).unwrap().get_cycle(),
+            None
+        );
+        assert!(Phase::parse("duration=1s,on=10ms", &default()).is_err());
+        assert!(Phase::parse("duration=1s,on=0ms,off=0ms", &default()).is_err());
+    }
+
+    #[test]
+    fn schedules_advance_through_phases() {
+        let phases: Vec<Phase> = ["duration=10ms,rate=1us", "duration=20ms,rate=2us"]
+            .iter()
+            .map(|spec| Phase::parse(spec, &default()).unwrap())
+            .collect();
+        let start: Instant = Instant::now();
+        let ms = |ms: u64| start + Duration::from_millis(ms);
+
+        let mut once: Schedule = Schedule::new(phases.clone(), false).unwrap();
+        once.restart(start);
+        assert_eq!(once.poll(ms(0)).map(|(i, _)| i), Some(0));
+        assert_eq!(once.poll(ms(15)).map(|(i, _)| i), Some(1));
+        assert_eq!(once.poll(ms(29)).map(|(i, _)| i), Some(1));
+        assert!(once.poll(ms(30)).is_none());
+
+        let mut repeated: Schedule = Schedule::new(phases, true).unwrap();
+        repeated.restart(start);
+        assert_eq!(repeated.poll(ms(15)).map(|(i, _)| i), Some(1));
+        assert_eq!(repeated.poll(ms(35)).map(|(i, _)| i), Some(0));
+
+        // Phases that were entirely missed are not caught up with.
+        assert_eq!(repeated.poll(ms(1000)).map(|(i, _)| i), Some(1));
+        assert_eq!(repeated.poll(ms(1010)).map(|(i, _)| i), Some(1));
+    }
+
+    #[test]
+    fn phases_after_a_late_poll_last_their_own_duration() {
+        let phases: Vec<Phase> = ["duration=10ms,rate=1us", "duration=100ms,rate=2us"]
+            .iter()
+            .map(|spec| Phase::parse(spec, &default()).unwrap())
+            .collect();
+        let start: Instant = Instant::now();
+        let ms = |ms: u64| start + Duration::from_millis(ms);
+
+        // The second phase started at 10 ms, which is not entirely missed at 25 ms.
+        let mut schedule: Schedule = Schedule::new(phases, true).unwrap();
+        schedule.restart(start);
+        assert_eq!(schedule.poll(ms(25)).map(|(i, _)| i), Some(1));
+        assert_eq!(schedule.poll(ms(105)).map(|(i, _)| i), Some(1));
+        assert_eq!(schedule.poll(ms(115)).map(|(i, _)| i), Some(0));
+    }
+
+    #[test]
+    fn schedules_reject_unreachable_phases() {
+        let forever: Phase = Phase::parse("rate=1us", &default()).unwrap();
+        let timed: Phase = Phase::parse("duration=1s,rate=1us", &default()).unwrap();
+        assert!(Schedule::new(Vec::new(), false).is_err());
+        assert!(Schedule::new(vec![forever.clone(), timed.clone()], false).is_err());
+        assert!(Schedule::new(vec![timed.clone(), forever.clone()], true).is_err());
+        assert!(Schedule::new(vec![timed, forever.clone()], false).is_ok());
+        assert!(Schedule::new(vec![forever], true).is_ok());
+    }
+
+    #[test]
+    fn statistics_are_shared_by_name() {
+        let phases: Vec<Phase> = ["name=a,duration=1s", "name=b,duration=1s", "name=a"]
+            .iter()
+            .map(|spec| Phase::parse(spec, &default()).unwrap())
+            .collect();
+        let schedule: Schedule = Schedule::new(phases, false).unwrap();
+        let start: Instant = Instant::now();
+        let mut stats: PhaseStatistics = PhaseStatistics::new(&schedule, start);
+        stats.record(10);
+        stats.switch(1, start + Duration::from_secs(1));
+        stats.record(20);
+        stats.switch(2, start + Duration::from_secs(2));
+        stats.record(30);
+        let (label, nbytes, elapsed): (&str, usize, Duration) =
+            stats.current(start + Duration::from_secs(3));
+        assert_eq!((label, nbytes, elapsed), ("a", 40, Duration::from_secs(2)));
+
+        stats.reset(start + Duration::from_secs(3));
+        let (_, nbytes, elapsed): (&str, usize, Duration) =
+            stats.current(start + Duration::from_secs(4));
+        assert_eq!((nbytes, elapsed), (0, Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn generators_are_reproducible() {
+        let (mut a, mut b): (Rng, Rng) = (Rng::new(42), Rng::new(42));
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+            let x: f64 = a.next_f64();
+            assert!((0.0..1.0).contains(&x));
+            b.next_f64();
+        }
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+
+    #[test]
+    fn durations_parse() {
+        assert_eq!(parse_duration("250ns").unwrap(), Duration::from_nanos(250));
+        assert_eq!(parse_duration("10us").unwrap(), Duration::from_micros(10));
+        assert_eq!(parse_duration("5ms").unwrap(), Duration::from_millis(5));
+        assert_eq!(parse_duration("2s").unwrap(), Duration::from_secs(2));
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("ms").is_err());
+        assert!(parse_duration("1.5s").is_err());
+    }
+}
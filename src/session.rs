@@ -0,0 +1,335 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::anyhow::{bail, Result};
+use ::demikernel::{QDesc, QToken};
+use ::std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// UDP Session
+///
+/// A session ties a client to the upstream socket that the relay uses to talk to the remote on
+/// its behalf, so that replies of the remote can be relayed back to the right client.
+#[derive(Debug)]
+pub struct Session {
+    /// Client endpoint.
+    client: SocketAddr,
+    /// Upstream socket descriptor.
+    upstream: QDesc,
+    /// Local port of the upstream socket.
+    port: u16,
+    /// Index of the backend that the session is bound to.
+    backend: usize,
+    /// Pending pop on the upstream socket.
+    pop_qt: Option<QToken>,
+    /// Last time some traffic went through the session.
+    last_active: Instant,
+    /// Number of bytes relayed from the client to the remote.
+    nbytes_up: usize,
+    /// Number of bytes relayed from the remote to the client.
+    nbytes_down: usize,
+}
+
+/// UDP Session Table
+#[derive(Debug)]
+pub struct SessionTable {
+    /// Sessions indexed by client endpoint.
+    sessions: HashMap<SocketAddr, Session>,
+    /// Client endpoints indexed by upstream socket descriptor.
+    clients: HashMap<QDesc, SocketAddr>,
+    /// Ports that were released by expired sessions.
+    free_ports: Vec<u16>,
+    /// Next port that was never allocated.
+    next_port: u32,
+    /// Last port of the range (inclusive).
+    last_port: u16,
+    /// Maximum number of concurrent sessions.
+    max_sessions: usize,
+    /// Idle time after which a session expires.
+    timeout: Duration,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for UDP Sessions
+impl Session {
+    /// Creates a session.
+    pub fn new(
+        client: SocketAddr,
+        upstream: QDesc,
+        port: u16,
+        backend: usize,
+        now: Instant,
+    ) -> Self {
+        Self {
+            client,
+            upstream,
+            port,
+            backend,
+            pop_qt: None,
+            last_active: now,
+            nbytes_up: 0,
+            nbytes_down: 0,
+        }
+    }
+
+    /// Returns the client endpoint of the target session.
+    pub fn get_client(&self) -> SocketAddr {
+        self.client
+    }
+
+    /// Returns the upstream socket descriptor of the target session.
+    pub fn get_upstream(&self) -> QDesc {
+        self.upstream
+    }
+
+    /// Returns the index of the backend that the target session is bound to.
+    pub fn get_backend(&self) -> usize {
+        self.backend
+    }
+
+    /// Returns the pending pop on the upstream socket of the target session.
+    pub fn get_pop_qt(&self) -> Option<QToken> {
+        self.pop_qt
+    }
+
+    /// Returns the number of bytes relayed from the client to the remote.
+    pub fn get_nbytes_up(&self) -> usize {
+        self.nbytes_up
+    }
+
+    /// Returns the number of bytes relayed from the remote to the client.
+    pub fn get_nbytes_down(&self) -> usize {
+        self.nbytes_down
+    }
+
+    /// Sets the pending pop on the upstream socket of the target session.
+    pub fn set_pop_qt(&mut self, qt: QToken) {
+        self.pop_qt = Some(qt);
+    }
+
+    /// Binds the target session to another backend.
+    pub fn set_backend(&mut self, backend: usize) {
+        self.backend = backend;
+    }
+
+    /// Accounts `nbytes` relayed from the client to the remote.
+    pub fn record_up(&mut self, nbytes: usize, now: Instant) {
+        self.nbytes_up += nbytes;
+        self.last_active = now;
+    }
+
+    /// Accounts `nbytes` relayed from the remote to the client.
+    pub fn record_down(&mut self, nbytes: usize, now: Instant) {
+        self.nbytes_down += nbytes;
+        self.last_active = now;
+    }
+}
+
+/// Associate Functions for UDP Session Tables
+impl SessionTable {
+    /// Creates a session table whose upstream sockets are bound to ports in `ports` (inclusive).
+    pub fn new(ports: (u16, u16), max_sessions: usize, timeout: Duration) -> Result<Self> {
+        if ports.0 == 0 || ports.0 > ports.1 {
+            bail!("invalid port range")
+        }
+        if max_sessions == 0 {
+            bail!("invalid maximum number of sessions")
+        }
+        Ok(Self {
+            sessions: HashMap::new(),
+            clients: HashMap::new(),
+            free_ports: Vec::new(),
+            next_port: ports.0 as u32,
+            last_port: ports.1,
+            max_sessions,
+            timeout,
+        })
+    }
+
+    /// Returns the number of active sessions.
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Asserts if the target session table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Returns the session of a client.
+    pub fn get_mut(&mut self, client: &SocketAddr) -> Option<&mut Session> {
+        self.sessions.get_mut(client)
+    }
+
+    /// Returns the session that owns an upstream socket.
+    pub fn get_by_upstream_mut(&mut self, upstream: QDesc) -> Option<&mut Session> {
+        let client: SocketAddr = *self.clients.get(&upstream)?;
+        self.sessions.get_mut(&client)
+    }
+
+    /// Iterates over active sessions.
+    pub fn iter(&self) -> impl Iterator<Item = &Session> {
+        self.sessions.values()
+    }
+
+    /// Reserves a port for a new session. `None` means that the table is full.
+    pub fn alloc_port(&mut self) -> Option<u16> {
+        if self.sessions.len() >= self.max_sessions {
+            return None;
+        }
+        if let Some(port) = self.free_ports.pop() {
+            return Some(port);
+        }
+        if self.next_port <= self.last_port as u32 {
+            let port: u16 = self.next_port as u16;
+            self.next_port += 1;
+            return Some(port);
+        }
+        None
+    }
+
+    /// Releases a port that was reserved but not used.
+    pub fn free_port(&mut self, port: u16) {
+        self.free_ports.push(port);
+    }
+
+    /// Inserts a session.
+    pub fn insert(&mut self, session: Session) -> &mut Session {
+        let client: SocketAddr = session.client;
+        self.clients.insert(session.upstream, client);
+        self.sessions.entry(client).or_insert(session)
+    }
+
+    /// Removes and returns sessions that have been idle for too long. Their ports are released,
+    /// but their upstream sockets are left for the caller to close.
+    pub fn expire(&mut self, now: Instant) -> Vec<Session> {
+        let timeout: Duration = self.timeout;
+        let expired: Vec<SocketAddr> = self
+            .sessions
+            .values()
+            .filter(|s| now.saturating_duration_since(s.last_active) > timeout)
+            .map(|s| s.client)
+            .collect();
+
+        let mut sessions: Vec<Session> = Vec::with_capacity(expired.len());
+        for client in expired {
+            if let Some(session) = self.sessions.remove(&client) {
+                self.clients.remove(&session.upstream);
+                self.free_ports.push(session.port);
+                sessions.push(session);
+            }
+        }
+        sessions
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the address of a client.
+    fn client(port: u16) -> SocketAddr {
+        SocketAddr::from(([192, 0, 2, 1], port))
+    }
+
+    /// Opens a session for a client, on the next port of a table.
+    fn open(table: &mut SessionTable, port: u16, now: Instant) -> Option<u16> {
+        let upstream: u16 = table.alloc_port()?;
+        let session: Session =
+            Session::new(client(port), QDesc::from(upstream as u32), upstream, 0, now);
+        table.insert(session);
+        Some(upstream)
+    }
+
+    #[test]
+    fn sessions_are_found_by_client_and_upstream() {
+        let now: Instant = Instant::now();
+        let mut table: SessionTable =
+            SessionTable::new((5000, 5009), 10, Duration::from_secs(60)).unwrap();
+        assert_eq!(open(&mut table, 1, now), Some(5000));
+        assert_eq!(open(&mut table, 2, now), Some(5001));
+        assert_eq!(table.len(), 2);
+
+        let session: &mut Session = table.get_mut(&client(2)).unwrap();
+        assert_eq!(session.get_upstream(), QDesc::from(5001));
+        session.record_up(100, now);
+        session.record_down(50, now);
+        let session: &mut Session = table.get_by_upstream_mut(QDesc::from(5001)).unwrap();
+        assert_eq!(session.get_client(), client(2));
+        assert_eq!(
+            (session.get_nbytes_up(), session.get_nbytes_down()),
+            (100, 50)
+        );
+        assert!(table.get_mut(&client(3)).is_none());
+        assert!(table.get_by_upstream_mut(QDesc::from(5002)).is_none());
+    }
+
+    #[test]
+    fn idle_sessions_expire_and_release_their_ports() {
+        let start: Instant = Instant::now();
+        let timeout: Duration = Duration::from_secs(60);
+        let mut table: SessionTable = SessionTable::new((5000, 5001), 10, timeout).unwrap();
+        open(&mut table, 1, start);
+        open(&mut table, 2, start);
+        assert_eq!(table.alloc_port(), None);
+
+        // Traffic keeps a session alive.
+        let later: Instant = start + Duration::from_secs(50);
+        table.get_mut(&client(2)).unwrap().record_up(1, later);
+        assert!(table.expire(start + timeout).is_empty());
+        let expired: Vec<Session> = table.expire(start + Duration::from_secs(61));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].get_client(), client(1));
+        assert!(table.get_by_upstream_mut(QDesc::from(5000)).is_none());
+        assert_eq!(table.len(), 1);
+
+        assert_eq!(table.alloc_port(), Some(5000));
+        let expired: Vec<Session> = table.expire(later + Duration::from_secs(61));
+        assert_eq!(expired.len(), 1);
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn sessions_are_capped() {
+        let now: Instant = Instant::now();
+        let mut table: SessionTable =
+            SessionTable::new((5000, 5009), 2, Duration::from_secs(60)).unwrap();
+        open(&mut table, 1, now);
+        open(&mut table, 2, now);
+        assert_eq!(open(&mut table, 3, now), None);
+
+        // Ports that were reserved but not used are handed out again.
+        let mut table: SessionTable =
+            SessionTable::new((5000, 5009), 2, Duration::from_secs(60)).unwrap();
+        let port: u16 = table.alloc_port().unwrap();
+        table.free_port(port);
+        assert_eq!(table.alloc_port(), Some(port));
+    }
+
+    #[test]
+    fn invalid_tables_are_rejected() {
+        let timeout: Duration = Duration::from_secs(60);
+        assert!(SessionTable::new((0, 10), 10, timeout).is_err());
+        assert!(SessionTable::new((10, 9), 10, timeout).is_err());
+        assert!(SessionTable::new((10, 10), 0, timeout).is_err());
+        assert!(SessionTable::new((65535, 65535), 1, timeout).is_ok());
+    }
+}
@@ -0,0 +1,320 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::backend::{ipv4, Backend, Fail};
+use ::demikernel::{demi_opcode_t, demi_qresult_t, demi_sgarray_t, LibOS, QDesc, QToken};
+use ::std::{
+    fmt,
+    net::{Ipv4Addr, SocketAddr},
+    slice,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Zero-Copy Statistics
+///
+/// Counts the copies that were saved by handing scatter-gather arrays to the libOS, instead of
+/// going through `push2()` and `pushto2()`, which copy their input into a new buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ZeroCopyStatistics {
+    /// Number of copies avoided.
+    ncopies: usize,
+    /// Number of bytes that were not copied.
+    nbytes: usize,
+}
+
+/// Buffer Pool
+///
+/// Scatter-gather arrays that are allocated and filled once, then cycled through: a buffer is
+/// taken for every push and given back when the push completes. On libOSes that run on DPDK, the
+/// arrays are backed by mbufs, so that generators do not allocate on the data path.
+pub struct SgaPool {
+    /// Buffers.
+    bufs: Vec<demi_sgarray_t>,
+    /// Indices of free buffers.
+    free: Vec<usize>,
+    /// Number of times that a buffer was requested while none was free.
+    nexhausted: usize,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Zero-Copy Statistics
+impl ZeroCopyStatistics {
+    /// Accounts a copy of `nbytes` bytes that was avoided.
+    pub fn record(&mut self, nbytes: usize) {
+        self.ncopies += 1;
+        self.nbytes += nbytes;
+    }
+
+    /// Returns the number of copies avoided.
+    pub fn get_ncopies(&self) -> usize {
+        self.ncopies
+    }
+
+    /// Returns the number of bytes that were not copied.
+    pub fn get_nbytes(&self) -> usize {
+        self.nbytes
+    }
+}
+
+/// Associate Functions for Buffer Pools
+impl SgaPool {
+    /// Allocates `count` buffers of `len` bytes filled with `fill_char`.
+    pub fn new(libos: &LibOS, count: usize, len: usize, fill_char: u8) -> Self {
+        Self {
+            bufs: (0..count)
+                .map(|_| sga_mkbuf(libos, len, fill_char))
+                .collect(),
+            free: (0..count).rev().collect(),
+            nexhausted: 0,
+        }
+    }
+
+    /// Returns the number of buffers in the target pool.
+    pub fn get_capacity(&self) -> usize {
+        self.bufs.len()
+    }
+
+    /// Returns the number of free buffers in the target pool.
+    pub fn get_navailable(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Returns the number of times that the target pool ran out of buffers.
+    pub fn get_nexhausted(&self) -> usize {
+        self.nexhausted
+    }
+
+    /// Takes a free buffer, along with its index.
+    pub fn get(&mut self) -> Option<(usize, demi_sgarray_t)> {
+        match self.free.pop() {
+            Some(i) => Some((i, self.bufs[i])),
+            None => {
+                self.nexhausted += 1;
+                None
+            }
+        }
+    }
+
+    /// Gives back the buffer at index `i`.
+    pub fn put(&mut self, i: usize) {
+        debug_assert!(!self.free.contains(&i));
+        self.free.push(i);
+    }
+
+    /// Releases every buffer of the target pool.
+    pub fn release(self, libos: &LibOS) {
+        for sga in self.bufs {
+            sga_free(libos, sga);
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+impl fmt::Display for ZeroCopyStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} copies avoided, {:?} B", self.ncopies, self.nbytes)
+    }
+}
+
+impl fmt::Display for SgaPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} buffers free, exhausted {} times",
+            self.free.len(),
+            self.bufs.len(),
+            self.nexhausted
+        )
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns the libOS underneath a backend. Scatter-gather arrays are only offered by the libOS,
+/// so zero-copy modes should be rejected for other backends before they run.
+pub fn libos<B: Backend>(backend: &mut B) -> &mut LibOS {
+    match backend.as_libos() {
+        Some(libos) => libos,
+        None => panic!("zero-copy requires the libos backend"),
+    }
+}
+
+/// Pushes a scatter-gather array to a connected socket, without copying it.
+pub fn push<B: Backend>(backend: &mut B, qd: QDesc, sga: &demi_sgarray_t) -> Result<QToken, Fail> {
+    libos(backend)
+        .push(qd, sga)
+        .map_err(|e| Fail::new(e.errno, &e.cause))
+}
+
+/// Pushes a scatter-gather array to a remote address, without copying it.
+pub fn pushto<B: Backend>(
+    backend: &mut B,
+    qd: QDesc,
+    sga: &demi_sgarray_t,
+    to: SocketAddr,
+) -> Result<QToken, Fail> {
+    libos(backend)
+        .pushto(qd, sga, ipv4(to)?)
+        .map_err(|e| Fail::new(e.errno, &e.cause))
+}
+
+/// Returns the number of bytes in a scatter-gather array.
+pub fn sga_len(sga: &demi_sgarray_t) -> usize {
+    sga.sga_segs[..sga.sga_numsegs as usize]
+        .iter()
+        .map(|seg| seg.sgaseg_len as usize)
+        .sum()
+}
+
+/// Returns the first segment of a scatter-gather array, which is the only one that the libOS
+/// allocates.
+pub fn sga_as_mut_slice(sga: &mut demi_sgarray_t) -> &mut [u8] {
+    let seg = &sga.sga_segs[0];
+    unsafe { slice::from_raw_parts_mut(seg.sgaseg_buf as *mut u8, seg.sgaseg_len as usize) }
+}
+
+/// Returns a view of the first `len` bytes of a scatter-gather array. The view shares the
+/// underlying buffer, so only the original array should be released.
+pub fn sga_truncate(sga: &demi_sgarray_t, len: usize) -> demi_sgarray_t {
+    let mut view: demi_sgarray_t = *sga;
+    assert!(len <= view.sga_segs[0].sgaseg_len as usize);
+    view.sga_segs[0].sgaseg_len = len as u32;
+    view
+}
+
+/// Returns the address from which a scatter-gather array was received.
+pub fn sga_addr(sga: &demi_sgarray_t) -> SocketAddr {
+    let ip: Ipv4Addr = Ipv4Addr::from(u32::from_be(sga.sga_addr.sin_addr.s_addr));
+    SocketAddr::new(ip.into(), u16::from_be(sga.sga_addr.sin_port))
+}
+
+/// Allocates a scatter-gather array of `len` bytes filled with `fill_char`.
+pub fn sga_mkbuf(libos: &LibOS, len: usize, fill_char: u8) -> demi_sgarray_t {
+    let mut sga: demi_sgarray_t = match libos.sgaalloc(len) {
+        Ok(sga) => sga,
+        Err(e) => panic!("failed to allocate scatter-gather array: {:?}", e.cause),
+    };
+    sga_as_mut_slice(&mut sga).fill(fill_char);
+    sga
+}
+
+/// Releases a scatter-gather array.
+pub fn sga_free(libos: &LibOS, sga: demi_sgarray_t) {
+    if let Err(e) = libos.sgafree(sga) {
+        panic!("failed to release scatter-gather array: {:?}", e.cause)
+    }
+}
+
+/// Returns the socket on which the operation of a result completed.
+pub fn qr_qd(qr: &demi_qresult_t) -> QDesc {
+    QDesc::from(qr.qr_qd)
+}
+
+/// Returns the scatter-gather array carried by the result of a pop. The caller owns it and is
+/// responsible for releasing it.
+pub fn qr_sga(qr: &demi_qresult_t) -> demi_sgarray_t {
+    assert!(qr.qr_opcode == demi_opcode_t::DEMI_OPC_POP);
+    unsafe { qr.qr_value.sga }
+}
+
+/// Returns the socket carried by the result of an accept.
+pub fn qr_accepted(qr: &demi_qresult_t) -> QDesc {
+    assert!(qr.qr_opcode == demi_opcode_t::DEMI_OPC_ACCEPT);
+    QDesc::from(unsafe { qr.qr_value.ares.qd } as u32)
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::std::mem;
+
+    /// Builds a scatter-gather array whose only segment is `buf`, as the libOS would allocate.
+    fn sga(buf: &mut [u8]) -> demi_sgarray_t {
+        let mut sga: demi_sgarray_t = unsafe { mem::zeroed() };
+        sga.sga_buf = buf.as_mut_ptr() as *mut libc::c_void;
+        sga.sga_numsegs = 1;
+        sga.sga_segs[0].sgaseg_buf = buf.as_mut_ptr() as *mut libc::c_void;
+        sga.sga_segs[0].sgaseg_len = buf.len() as u32;
+        sga
+    }
+
+    /// Builds a pool of `count` buffers that are carved out of `storage`.
+    fn pool(storage: &mut [u8], count: usize) -> SgaPool {
+        let len: usize = storage.len() / count;
+        SgaPool {
+            bufs: storage.chunks_mut(len).map(sga).collect(),
+            free: (0..count).rev().collect(),
+            nexhausted: 0,
+        }
+    }
+
+    #[test]
+    fn saved_copies_are_counted() {
+        let mut stats: ZeroCopyStatistics = ZeroCopyStatistics::default();
+        stats.record(1500);
+        stats.record(64);
+        assert_eq!((stats.get_ncopies(), stats.get_nbytes()), (2, 1564));
+        assert_eq!(stats.to_string(), "2 copies avoided, 1564 B");
+    }
+
+    #[test]
+    fn pools_hand_out_free_buffers_only() {
+        let mut storage: [u8; 30] = [0; 30];
+        let mut pool: SgaPool = pool(&mut storage, 3);
+        let taken: Vec<usize> = (0..3).map(|_| pool.get().unwrap().0).collect();
+        assert_eq!(taken, [0, 1, 2]);
+        assert!(pool.get().is_none());
+        assert!(pool.get().is_none());
+        assert_eq!(pool.get_nexhausted(), 2);
+        assert_eq!(pool.to_string(), "0/3 buffers free, exhausted 2 times");
+
+        // A buffer that is given back is taken again.
+        pool.put(1);
+        let (i, sga): (usize, demi_sgarray_t) = pool.get().unwrap();
+        assert_eq!(i, 1);
+        assert_eq!(
+            sga.sga_segs[0].sgaseg_buf,
+            pool.bufs[1].sga_segs[0].sgaseg_buf
+        );
+        assert_eq!((pool.get_capacity(), pool.get_navailable()), (3, 0));
+    }
+
+    #[test]
+    fn views_share_the_segment() {
+        let mut buf: [u8; 8] = [0; 8];
+        let mut whole: demi_sgarray_t = sga(&mut buf);
+        sga_as_mut_slice(&mut whole).fill(b'x');
+        let mut view: demi_sgarray_t = sga_truncate(&whole, 3);
+        assert_eq!((sga_len(&whole), sga_len(&view)), (8, 3));
+        sga_as_mut_slice(&mut view)[0] = b'y';
+        assert_eq!(&buf, b"yxxxxxxx");
+    }
+
+    #[test]
+    fn senders_are_decoded_from_network_order() {
+        let mut buf: [u8; 1] = [0; 1];
+        let mut sga: demi_sgarray_t = sga(&mut buf);
+        sga.sga_addr.sin_addr.s_addr = u32::from(Ipv4Addr::new(10, 0, 0, 1)).to_be();
+        sga.sga_addr.sin_port = 8080u16.to_be();
+        assert_eq!(sga_addr(&sga), SocketAddr::from(([10, 0, 0, 1], 8080)));
+    }
+}
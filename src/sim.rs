@@ -0,0 +1,1228 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::{
+    backend::{Backend, Buffer, Fail, OperationResult},
+    clock::Clock,
+    schedule::Rng,
+};
+use ::anyhow::{bail, Result};
+use ::demikernel::{QDesc, QToken};
+use ::std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    env, fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Injected Faults
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Faults {
+    /// Pops on stream sockets return only part of the received bytes.
+    pub partial: bool,
+    /// Datagrams are lost now and then.
+    pub drop: bool,
+    /// Clients reset their connections now and then.
+    pub reset: bool,
+}
+
+/// Simulation Parameters
+#[derive(Clone, Debug, Default)]
+pub struct SimConfig {
+    /// Seed from which every decision of the simulation derives.
+    pub seed: u64,
+    /// Faults to inject.
+    pub faults: Faults,
+    /// Number of clients per listening socket, or that send datagrams. Random if unset.
+    pub clients: Option<usize>,
+}
+
+/// Identifier of a Simulated Peer
+type PeerId = usize;
+
+/// Connection of the Application
+#[derive(Debug)]
+struct Connection {
+    /// Peer at the other end.
+    peer: PeerId,
+    /// Received bytes that were not popped yet.
+    rx: VecDeque<u8>,
+    /// Was the connection reset by the peer?
+    reset: bool,
+    /// Did the peer close its end?
+    eof: bool,
+    /// Did the application shut its sending side down?
+    shut: bool,
+}
+
+/// State of a Socket of the Application
+#[derive(Debug)]
+enum State {
+    /// Stream socket that is neither listening nor connected.
+    Unconnected,
+    /// Listening stream socket, along with connections that are yet to be accepted.
+    Listening(VecDeque<QDesc>),
+    /// Connected stream socket.
+    Connected(Connection),
+    /// Datagram socket, along with received datagrams and their senders.
+    Datagram(VecDeque<(SocketAddr, Vec<u8>)>),
+}
+
+/// Socket of the Application
+#[derive(Debug)]
+struct Socket {
+    /// Local address, if bound.
+    local: Option<SocketAddr>,
+    /// State of the socket.
+    state: State,
+    /// Is the socket known to the application? Connections are not until they are accepted.
+    visible: bool,
+}
+
+/// Client that Connects to a Listening Socket
+#[derive(Debug)]
+struct StreamClient {
+    /// Socket of the application at the other end, once connected.
+    qd: Option<QDesc>,
+    /// Sizes of the messages that are yet to be sent.
+    messages: VecDeque<usize>,
+    /// Bytes sent so far.
+    sent: Vec<u8>,
+    /// Number of sent bytes that came back.
+    echoed: usize,
+    /// Did the connection end?
+    closed: bool,
+}
+
+/// Client that Sends Datagrams
+#[derive(Debug)]
+struct DatagramClient {
+    /// Address of the client.
+    addr: SocketAddr,
+    /// Address of the application.
+    target: SocketAddr,
+    /// Number of datagrams that are yet to be sent.
+    remaining: usize,
+    /// Datagrams sent so far.
+    sent: Vec<Vec<u8>>,
+}
+
+/// Simulated Peer
+#[derive(Debug)]
+enum Peer {
+    /// Client of a listening socket of the application.
+    StreamClient(StreamClient),
+    /// Server reached by a connection that the application opened, which echoes what it gets.
+    /// Echoes leave in order, so the time of the last one is kept.
+    StreamEcho(Duration),
+    /// Client that sends datagrams to the application.
+    DatagramClient(DatagramClient),
+}
+
+/// Simulated Event
+#[derive(Debug)]
+enum Event {
+    /// A stream client connects.
+    Connect(PeerId),
+    /// A stream client sends its next message.
+    Send(PeerId),
+    /// A stream client resets its connection.
+    Reset(PeerId),
+    /// A datagram client sends its next datagram.
+    SendTo(PeerId),
+    /// Bytes reach a connection of the application.
+    Deliver(QDesc, Vec<u8>),
+    /// The peer of a connection of the application closes its end.
+    Close(QDesc),
+    /// A datagram reaches an address, from another one.
+    DeliverTo(SocketAddr, SocketAddr, Vec<u8>),
+    /// An operation that is in flight completes.
+    Complete(QToken),
+}
+
+/// Operation of the Application
+#[derive(Debug)]
+enum Op {
+    /// Accept a connection.
+    Accept,
+    /// Pop data.
+    Pop,
+    /// Operation whose result is known, but that is still in flight.
+    InFlight(OperationResult),
+    /// Operation that completed.
+    Done(OperationResult),
+}
+
+/// Simulation Statistics
+#[derive(Debug, Default)]
+struct Statistics {
+    /// Bytes sent by stream clients.
+    stream_sent: usize,
+    /// Bytes that came back to stream clients.
+    stream_echoed: usize,
+    /// Datagrams sent by datagram clients.
+    datagrams_sent: usize,
+    /// Datagrams that came back to datagram clients.
+    datagrams_returned: usize,
+    /// Events run.
+    events: usize,
+    /// Calls to wait on operations.
+    waits: usize,
+}
+
+/// Simulator Backend
+///
+/// Runs the application against a simulated network, in which every decision, from the order in
+/// which ready operations complete to the faults that are injected, derives from a single seed.
+/// Runs are reproduced by running again with the same seed.
+///
+/// The network is populated with scripted peers:
+/// - Every listening socket is connected to by clients, each of which sends a few messages and
+///   checks that whatever comes back is an in-order copy of what it sent.
+/// - The first datagram socket that is bound is sent datagrams by clients, which check that
+///   whatever comes back is a copy of something that they sent.
+/// - Connections that the application opens, and datagrams that it sends to any other address,
+///   reach servers that echo them. Servers close their end of a connection once they echoed
+///   everything after the application shut its own end down.
+///
+/// Time is virtual: events are scheduled at instants of a simulated clock, which jumps to the next
+/// event whenever no operation that is waited on can complete. Applications read it through the
+/// clock of the backend. Once nothing else can happen, the simulator checks that the application
+/// was left in a sound state. It then reports on the run, as it does as soon as any check fails,
+/// and fails every wait with `ESHUTDOWN`, so that the application winds down. The status of the
+/// backend tells whether the run passed.
+#[derive(Debug)]
+pub struct Simulator {
+    /// Parameters of the simulation.
+    config: SimConfig,
+    /// Random number generator.
+    rng: Rng,
+    /// Virtual time.
+    now: Duration,
+    /// Virtual time, as read by clocks of the application (in nanoseconds).
+    elapsed: Arc<AtomicU64>,
+    /// Scheduled events, ordered by time and by sequence number.
+    queue: BinaryHeap<Reverse<(Duration, u64)>>,
+    /// Events, by sequence number.
+    events: HashMap<u64, Event>,
+    /// Next sequence number of an event.
+    next_seq: u64,
+    /// Sockets of the application.
+    sockets: HashMap<QDesc, Socket>,
+    /// Operations of the application, along with the socket they run on.
+    ops: HashMap<QToken, (QDesc, Op)>,
+    /// Simulated peers.
+    peers: Vec<Peer>,
+    /// Did datagram clients show up?
+    has_datagram_clients: bool,
+    /// Next socket descriptor.
+    next_qd: u32,
+    /// Next operation token.
+    next_qt: u64,
+    /// Next ephemeral port.
+    next_port: u16,
+    /// Statistics of the run.
+    stats: Statistics,
+    /// Checks that failed.
+    problems: Vec<String>,
+    /// Virtual time of the last event other than the completion of an operation, that is, at
+    /// which a client acted or data moved.
+    last_activity: Duration,
+    /// Is the simulation over?
+    over: bool,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Simulation Parameters
+impl SimConfig {
+    /// Reads simulation parameters from the `SIM_SEED`, `SIM_FAULTS` and `SIM_CLIENTS`
+    /// environment variables.
+    pub fn from_env() -> Result<Self> {
+        let mut config: SimConfig = SimConfig::default();
+        if let Ok(seed) = env::var("SIM_SEED") {
+            config.seed = match seed.parse() {
+                Ok(seed) => seed,
+                Err(_) => bail!("invalid simulation seed"),
+            };
+        }
+        if let Ok(faults) = env::var("SIM_FAULTS") {
+            config.faults = faults.parse()?;
+        }
+        if let Ok(clients) = env::var("SIM_CLIENTS") {
+            config.clients = match clients.parse() {
+                Ok(clients) if clients > 0 => Some(clients),
+                _ => bail!("invalid number of simulated clients"),
+            };
+        }
+        Ok(config)
+    }
+}
+
+/// Associate Functions for Simulators
+impl Simulator {
+    /// First ephemeral port.
+    const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+    /// Maximum number of bytes returned by a pop on a stream socket.
+    const MAX_POP_SIZE: usize = 65536;
+
+    /// Maximum number of clients picked at random.
+    const MAX_CLIENTS: u64 = 4;
+
+    /// Maximum number of messages sent by a stream client.
+    const MAX_MESSAGES: u64 = 16;
+
+    /// Maximum size of a message sent by a stream client.
+    const MAX_MESSAGE_SIZE: u64 = 4096;
+
+    /// Maximum number of datagrams sent by a datagram client.
+    const MAX_DATAGRAMS: u64 = 32;
+
+    /// Maximum size of a datagram sent by a datagram client.
+    const MAX_DATAGRAM_SIZE: u64 = 1472;
+
+    /// Size of the tag that identifies a datagram of a client.
+    const TAG_SIZE: usize = 4;
+
+    /// Maximum time between two actions of a client (in microseconds).
+    const MAX_GAP: u64 = 1000;
+
+    /// Maximum latency of the network (in microseconds).
+    const MAX_LATENCY: u64 = 100;
+
+    /// Odds that a datagram is lost, when datagrams are dropped.
+    const DROP_ODDS: u64 = 20;
+
+    /// Odds that a client resets its connection, when connections are reset.
+    const RESET_ODDS: u64 = 4;
+
+    /// Number of waits after which the application is deemed to spin.
+    const MAX_WAITS: usize = 1_000_000;
+
+    /// Virtual time without activity after which a run is over, even if the application still
+    /// waits with a timeout, for instance to serve periodic timers.
+    const MAX_IDLE: Duration = Duration::from_secs(60);
+
+    /// Creates a simulator.
+    pub fn new(config: SimConfig) -> Self {
+        Self {
+            // Spread seeds apart, as the generator ignores the lowest bit of its seed.
+            rng: Rng::new(((config.seed << 1) | 1).wrapping_mul(0x9e37_79b9_7f4a_7c15)),
+            config,
+            now: Duration::ZERO,
+            elapsed: Arc::new(AtomicU64::new(0)),
+            queue: BinaryHeap::new(),
+            events: HashMap::new(),
+            next_seq: 0,
+            sockets: HashMap::new(),
+            ops: HashMap::new(),
+            peers: Vec::new(),
+            has_datagram_clients: false,
+            next_qd: 0,
+            next_qt: 0,
+            next_port: Self::FIRST_EPHEMERAL_PORT,
+            stats: Statistics::default(),
+            problems: Vec::new(),
+            last_activity: Duration::ZERO,
+            over: false,
+        }
+    }
+
+    /// Returns the virtual time elapsed since the start of the simulation.
+    pub fn get_time(&self) -> Duration {
+        self.now
+    }
+
+    /// Returns a random number in `[lo, hi]`.
+    fn random(&mut self, lo: u64, hi: u64) -> u64 {
+        lo + self.rng.next_u64() % (hi - lo + 1)
+    }
+
+    /// Asserts if an event with `1 / odds` chances happens.
+    fn chance(&mut self, odds: u64) -> bool {
+        self.random(1, odds) == 1
+    }
+
+    /// Returns a random delay between two actions of a client.
+    fn gap(&mut self) -> Duration {
+        Duration::from_micros(self.random(0, Self::MAX_GAP))
+    }
+
+    /// Returns a random latency of the network.
+    fn latency(&mut self) -> Duration {
+        Duration::from_micros(self.random(1, Self::MAX_LATENCY))
+    }
+
+    /// Returns the number of clients to create.
+    fn nclients(&mut self) -> usize {
+        match self.config.clients {
+            Some(n) => n,
+            None => self.random(1, Self::MAX_CLIENTS) as usize,
+        }
+    }
+
+    /// Schedules an event after a delay.
+    fn schedule(&mut self, delay: Duration, event: Event) {
+        let seq: u64 = self.next_seq;
+        self.next_seq += 1;
+        self.queue.push(Reverse((self.now + delay, seq)));
+        self.events.insert(seq, event);
+    }
+
+    /// Runs the next event, advancing virtual time. Returns `false` if no event is left.
+    fn step(&mut self) -> bool {
+        let (time, seq): (Duration, u64) = match self.queue.pop() {
+            Some(Reverse(entry)) => entry,
+            None => return false,
+        };
+        self.advance(time);
+        self.stats.events += 1;
+        if let Some(event) = self.events.remove(&seq) {
+            if !matches!(event, Event::Complete(_)) {
+                self.last_activity = time;
+            }
+            self.run(event);
+        }
+        true
+    }
+
+    /// Advances virtual time.
+    fn advance(&mut self, time: Duration) {
+        self.now = time;
+        self.elapsed
+            .store(time.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Runs an event.
+    fn run(&mut self, event: Event) {
+        match event {
+            Event::Connect(peer) => self.client_connect(peer),
+            Event::Send(peer) => self.client_send(peer),
+            Event::Reset(peer) => self.client_reset(peer),
+            Event::SendTo(peer) => self.client_sendto(peer),
+            Event::Deliver(qd, data) => {
+                if let Some(Socket {
+                    state: State::Connected(connection),
+                    ..
+                }) = self.sockets.get_mut(&qd)
+                {
+                    if !connection.reset {
+                        connection.rx.extend(data);
+                    }
+                }
+            }
+            Event::Close(qd) => {
+                if let Some(Socket {
+                    state: State::Connected(connection),
+                    ..
+                }) = self.sockets.get_mut(&qd)
+                {
+                    connection.eof = true;
+                }
+            }
+            Event::DeliverTo(to, from, data) => {
+                if let Some(qd) = self.lookup(to, |s| matches!(s, State::Datagram(_))) {
+                    if let Some(Socket {
+                        state: State::Datagram(rx),
+                        ..
+                    }) = self.sockets.get_mut(&qd)
+                    {
+                        rx.push_back((from, data));
+                    }
+                }
+            }
+            Event::Complete(qt) => {
+                if let Some((qd, Op::InFlight(result))) = self.ops.remove(&qt) {
+                    self.ops.insert(qt, (qd, Op::Done(result)));
+                }
+            }
+        }
+    }
+
+    /// Assigns a descriptor to a socket.
+    fn insert(&mut self, state: State, visible: bool) -> QDesc {
+        let qd: QDesc = QDesc::from(self.next_qd);
+        self.next_qd += 1;
+        let socket: Socket = Socket {
+            local: None,
+            state,
+            visible,
+        };
+        self.sockets.insert(qd, socket);
+        qd
+    }
+
+    /// Returns a socket that is known to the application.
+    fn get(&mut self, qd: QDesc) -> Result<&mut Socket, Fail> {
+        match self.sockets.get_mut(&qd) {
+            Some(socket) if socket.visible => Ok(socket),
+            _ => Err(Fail::new(libc::EBADF, "invalid queue descriptor")),
+        }
+    }
+
+    /// Looks up the socket bound to an address that matches `addr`, among those for which
+    /// `filter` holds.
+    fn lookup(&self, addr: SocketAddr, filter: impl Fn(&State) -> bool) -> Option<QDesc> {
+        let mut matches: Vec<QDesc> = self
+            .sockets
+            .iter()
+            .filter(|(_, socket)| match socket.local {
+                Some(local) => {
+                    local.port() == addr.port()
+                        && (local.ip() == addr.ip() || local.ip().is_unspecified())
+                        && filter(&socket.state)
+                }
+                None => false,
+            })
+            .map(|(qd, _)| *qd)
+            .collect();
+        // Do not depend on the iteration order of the map.
+        matches.sort_by_key(|qd| u32::from(*qd));
+        matches.first().copied()
+    }
+
+    /// Returns the local address of a socket, binding it to an ephemeral port if needed.
+    fn local(&mut self, qd: QDesc) -> Result<SocketAddr, Fail> {
+        if let Some(local) = self.get(qd)?.local {
+            return Ok(local);
+        }
+        let port: u16 = self.next_port;
+        self.next_port = self
+            .next_port
+            .checked_add(1)
+            .unwrap_or(Self::FIRST_EPHEMERAL_PORT);
+        let local: SocketAddr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port);
+        self.get(qd)?.local = Some(local);
+        Ok(local)
+    }
+
+    /// Registers an operation.
+    fn submit(&mut self, qd: QDesc, op: Op) -> QToken {
+        let qt: QToken = QToken::from(self.next_qt);
+        self.next_qt += 1;
+        self.ops.insert(qt, (qd, op));
+        qt
+    }
+
+    /// Registers an operation that completes after some latency.
+    fn in_flight(&mut self, qd: QDesc, result: OperationResult) -> QToken {
+        let qt: QToken = self.submit(qd, Op::InFlight(result));
+        let latency: Duration = self.latency();
+        self.schedule(latency, Event::Complete(qt));
+        qt
+    }
+
+    /// Asserts if an operation can complete.
+    fn is_ready(&self, qt: QToken) -> Result<bool, Fail> {
+        let (qd, op): &(QDesc, Op) = match self.ops.get(&qt) {
+            Some(entry) => entry,
+            None => return Err(Fail::new(libc::EINVAL, "invalid queue token")),
+        };
+        let state: Option<&State> = self.sockets.get(qd).map(|socket| &socket.state);
+        Ok(match (op, state) {
+            (Op::Done(_), _) => true,
+            (Op::InFlight(_), _) => false,
+            (Op::Accept, Some(State::Listening(backlog))) => !backlog.is_empty(),
+            (Op::Pop, Some(State::Connected(connection))) => {
+                connection.reset || connection.eof || !connection.rx.is_empty()
+            }
+            (Op::Pop, Some(State::Datagram(rx))) => !rx.is_empty(),
+            // Operations on sockets that do not support them fail right away.
+            (_, _) => true,
+        })
+    }
+
+    /// Completes an operation that is ready.
+    fn complete(&mut self, qt: QToken) -> (QDesc, OperationResult) {
+        let (qd, op): (QDesc, Op) = self.ops.remove(&qt).expect("invalid queue token");
+        let partial: bool = self.config.faults.partial;
+        let chunk: usize = self.rng.next_u64() as usize;
+        let socket: Option<&mut Socket> = self.sockets.get_mut(&qd);
+        let result: OperationResult = match (op, socket.map(|socket| &mut socket.state)) {
+            (Op::Done(result), _) => result,
+            (Op::Accept, Some(State::Listening(backlog))) => {
+                let accepted: QDesc = backlog.pop_front().expect("no pending connection");
+                let mut peer: Option<SocketAddr> = None;
+                if let Some(socket) = self.sockets.get_mut(&accepted) {
+                    socket.visible = true;
+                    if let (Some(local), State::Connected(connection)) =
+                        (socket.local, &socket.state)
+                    {
+                        peer = Some(client_addr(local, connection.peer));
+                    }
+                }
+                OperationResult::Accept(accepted, peer)
+            }
+            (Op::Pop, Some(State::Connected(connection))) => {
+                if connection.reset {
+                    OperationResult::Failed(Fail::new(libc::ECONNRESET, "connection reset"))
+                } else {
+                    let mut n: usize = connection.rx.len().min(Self::MAX_POP_SIZE);
+                    if partial && n > 0 {
+                        n = 1 + chunk % n;
+                    }
+                    let data: Vec<u8> = connection.rx.drain(..n).collect();
+                    OperationResult::Pop(None, Buffer::new(data))
+                }
+            }
+            (Op::Pop, Some(State::Datagram(rx))) => {
+                let (from, data): (SocketAddr, Vec<u8>) =
+                    rx.pop_front().expect("no pending datagram");
+                OperationResult::Pop(Some(from), Buffer::new(data))
+            }
+            (_, _) => OperationResult::Failed(Fail::new(libc::EINVAL, "invalid operation")),
+        };
+        (qd, result)
+    }
+
+    /// Creates clients that connect to a listening socket.
+    fn add_stream_clients(&mut self) {
+        for _ in 0..self.nclients() {
+            let nmessages: u64 = self.random(1, Self::MAX_MESSAGES);
+            let messages: VecDeque<usize> = (0..nmessages)
+                .map(|_| self.random(1, Self::MAX_MESSAGE_SIZE) as usize)
+                .collect();
+            let reset: bool = self.config.faults.reset && self.chance(Self::RESET_ODDS);
+            let peer: PeerId = self.peers.len();
+            self.peers.push(Peer::StreamClient(StreamClient {
+                qd: None,
+                messages,
+                sent: Vec::new(),
+                echoed: 0,
+                closed: false,
+            }));
+            let gap: Duration = self.gap();
+            self.schedule(gap, Event::Connect(peer));
+            if reset {
+                let when: Duration =
+                    Duration::from_micros(self.random(0, nmessages * Self::MAX_GAP));
+                self.schedule(when, Event::Reset(peer));
+            }
+        }
+    }
+
+    /// Creates clients that send datagrams to a socket bound to `target`, from documentation
+    /// addresses of the same family.
+    fn add_datagram_clients(&mut self, target: SocketAddr) {
+        self.has_datagram_clients = true;
+        for i in 0..self.nclients() {
+            let peer: PeerId = self.peers.len();
+            let addr: SocketAddr = client_addr(target, i);
+            let remaining: usize = self.random(1, Self::MAX_DATAGRAMS) as usize;
+            self.peers.push(Peer::DatagramClient(DatagramClient {
+                addr,
+                target,
+                remaining,
+                sent: Vec::new(),
+            }));
+            let gap: Duration = self.gap();
+            self.schedule(gap, Event::SendTo(peer));
+        }
+    }
+
+    /// Returns a stream client.
+    fn stream_client(&mut self, peer: PeerId) -> &mut StreamClient {
+        match &mut self.peers[peer] {
+            Peer::StreamClient(client) => client,
+            _ => unreachable!("peer is not a stream client"),
+        }
+    }
+
+    /// Connects a stream client to the first listening socket.
+    fn client_connect(&mut self, peer: PeerId) {
+        let mut listeners: Vec<QDesc> = self
+            .sockets
+            .iter()
+            .filter(|(_, socket)| matches!(socket.state, State::Listening(_)))
+            .map(|(qd, _)| *qd)
+            .collect();
+        listeners.sort_by_key(|qd| u32::from(*qd));
+        let listener: QDesc = match listeners.first() {
+            Some(listener) => *listener,
+            None => {
+                self.stream_client(peer).closed = true;
+                return;
+            }
+        };
+
+        // Create the socket that the listener hands out when accepting.
+        let local: Option<SocketAddr> = self.sockets[&listener].local;
+        let connection: Connection = Connection {
+            peer,
+            rx: VecDeque::new(),
+            reset: false,
+            eof: false,
+            shut: false,
+        };
+        let qd: QDesc = self.insert(State::Connected(connection), false);
+        if let Some(socket) = self.sockets.get_mut(&qd) {
+            socket.local = local;
+        }
+        if let Some(Socket {
+            state: State::Listening(backlog),
+            ..
+        }) = self.sockets.get_mut(&listener)
+        {
+            backlog.push_back(qd);
+        }
+        self.stream_client(peer).qd = Some(qd);
+        let gap: Duration = self.gap();
+        self.schedule(gap, Event::Send(peer));
+    }
+
+    /// Sends the next message of a stream client.
+    fn client_send(&mut self, peer: PeerId) {
+        let client: &mut StreamClient = self.stream_client(peer);
+        let (qd, size): (QDesc, usize) =
+            match (client.qd, client.closed, client.messages.pop_front()) {
+                (Some(qd), false, Some(size)) => (qd, size),
+                _ => return,
+            };
+        let data: Vec<u8> = (0..size).map(|_| self.rng.next_u64() as u8).collect();
+        self.stats.stream_sent += size;
+        let client: &mut StreamClient = self.stream_client(peer);
+        client.sent.extend(&data);
+        let more: bool = !client.messages.is_empty();
+        match self.sockets.get_mut(&qd) {
+            Some(Socket {
+                state: State::Connected(connection),
+                ..
+            }) => connection.rx.extend(data),
+            _ => self.stream_client(peer).closed = true,
+        }
+        if more {
+            let gap: Duration = self.gap();
+            self.schedule(gap, Event::Send(peer));
+        }
+    }
+
+    /// Resets the connection of a stream client.
+    fn client_reset(&mut self, peer: PeerId) {
+        let client: &mut StreamClient = self.stream_client(peer);
+        if client.closed {
+            return;
+        }
+        client.closed = true;
+        if let Some(qd) = client.qd {
+            if let Some(Socket {
+                state: State::Connected(connection),
+                ..
+            }) = self.sockets.get_mut(&qd)
+            {
+                connection.reset = true;
+                connection.rx.clear();
+            }
+        }
+    }
+
+    /// Sends the next datagram of a datagram client.
+    fn client_sendto(&mut self, peer: PeerId) {
+        let size: usize = self.random(Self::TAG_SIZE as u64, Self::MAX_DATAGRAM_SIZE) as usize;
+        let mut data: Vec<u8> = (0..size).map(|_| self.rng.next_u64() as u8).collect();
+        let client: &mut DatagramClient = match &mut self.peers[peer] {
+            Peer::DatagramClient(client) => client,
+            _ => unreachable!("peer is not a datagram client"),
+        };
+        let tag: u32 = client.sent.len() as u32;
+        data[..Self::TAG_SIZE].copy_from_slice(&tag.to_le_bytes());
+        client.sent.push(data.clone());
+        client.remaining -= 1;
+        let (from, to, more): (SocketAddr, SocketAddr, bool) =
+            (client.addr, client.target, client.remaining > 0);
+        self.stats.datagrams_sent += 1;
+        self.transmit(to, from, data);
+        if more {
+            let gap: Duration = self.gap();
+            self.schedule(gap, Event::SendTo(peer));
+        }
+    }
+
+    /// Transmits a datagram on the network, which may lose it.
+    fn transmit(&mut self, to: SocketAddr, from: SocketAddr, data: Vec<u8>) {
+        if self.config.faults.drop && self.chance(Self::DROP_ODDS) {
+            return;
+        }
+        let latency: Duration = self.latency();
+        self.schedule(latency, Event::DeliverTo(to, from, data));
+    }
+
+    /// Hands bytes that the application pushed on a connection to the peer at the other end.
+    fn receive(&mut self, qd: QDesc, peer: PeerId, data: &[u8]) {
+        let latency: Duration = self.latency();
+        match &mut self.peers[peer] {
+            Peer::StreamClient(client) => {
+                let offset: usize = client.echoed;
+                let expected: Option<&[u8]> = client.sent.get(offset..offset + data.len());
+                if expected != Some(data) {
+                    self.problems.push(format!(
+                        "client of {:?} got {} B at offset {} that it did not send",
+                        qd,
+                        data.len(),
+                        offset
+                    ));
+                }
+                client.echoed += data.len();
+                self.stats.stream_echoed += data.len();
+            }
+            Peer::StreamEcho(last) => {
+                // Echoes leave in order.
+                let when: Duration = (self.now + latency).max(*last);
+                *last = when;
+                let delay: Duration = when - self.now;
+                self.schedule(delay, Event::Deliver(qd, data.to_vec()));
+            }
+            Peer::DatagramClient(_) => unreachable!("datagram clients do not connect"),
+        }
+    }
+
+    /// Hands a datagram that the application pushed to the network.
+    fn receive_datagram(&mut self, from: SocketAddr, to: SocketAddr, data: &[u8]) {
+        if self.config.faults.drop && self.chance(Self::DROP_ODDS) {
+            return;
+        }
+        let client: Option<&DatagramClient> = self.peers.iter().find_map(|peer| match peer {
+            Peer::DatagramClient(client) if client.addr == to => Some(client),
+            _ => None,
+        });
+        match client {
+            // Clients only expect copies of what they sent.
+            Some(client) => {
+                let tag: Option<usize> = data
+                    .get(..Self::TAG_SIZE)
+                    .map(|tag| u32::from_le_bytes([tag[0], tag[1], tag[2], tag[3]]) as usize);
+                if tag.and_then(|tag| client.sent.get(tag)).map(Vec::as_slice) != Some(data) {
+                    self.problems.push(format!(
+                        "client {:?} got a datagram of {} B that it did not send",
+                        to,
+                        data.len()
+                    ));
+                }
+                self.stats.datagrams_returned += 1;
+            }
+            // Sockets of the application get the datagram, and any other address echoes it.
+            None => match self.lookup(to, |s| matches!(s, State::Datagram(_))) {
+                Some(_) => self.transmit(to, from, data.to_vec()),
+                None => self.transmit(from, to, data.to_vec()),
+            },
+        }
+    }
+
+    /// Checks that the application was left in a sound state, once nothing else can happen while
+    /// it waits on `qts`.
+    fn check(&mut self, qts: &[QToken]) {
+        let mut problems: Vec<String> = Vec::new();
+
+        // Completions that nobody waits for are lost.
+        for (qt, (qd, _)) in &self.ops {
+            if !qts.contains(qt) && self.is_ready(*qt).unwrap_or(false) {
+                problems.push(format!(
+                    "{:?} on {:?} completed, but is not waited on (leaked token)",
+                    qt, qd
+                ));
+            }
+        }
+
+        // Sockets with pending data or connections need an operation to drain them.
+        for (qd, socket) in &self.sockets {
+            let has = |op: fn(&Op) -> bool| {
+                self.ops
+                    .values()
+                    .any(|(op_qd, pending)| op_qd == qd && op(pending))
+            };
+            match &socket.state {
+                State::Listening(backlog)
+                    if !backlog.is_empty() && !has(|op| matches!(op, Op::Accept)) =>
+                {
+                    problems.push(format!(
+                        "{:?} has {} connections to accept, but no accept (not re-armed)",
+                        qd,
+                        backlog.len()
+                    ));
+                }
+                State::Connected(connection)
+                    if socket.visible
+                        && !connection.rx.is_empty()
+                        && !has(|op| matches!(op, Op::Pop)) =>
+                {
+                    problems.push(format!(
+                        "{:?} has {} B to pop, but no pop (not re-armed)",
+                        qd,
+                        connection.rx.len()
+                    ));
+                }
+                State::Datagram(rx) if !rx.is_empty() && !has(|op| matches!(op, Op::Pop)) => {
+                    problems.push(format!(
+                        "{:?} has {} datagrams to pop, but no pop (not re-armed)",
+                        qd,
+                        rx.len()
+                    ));
+                }
+                _ => (),
+            }
+        }
+
+        problems.sort();
+        self.problems.extend(problems);
+    }
+
+    /// Reports on the run and ends the simulation. Returns the failure of waits from now on.
+    fn report(&mut self) -> Fail {
+        self.over = true;
+        for problem in &self.problems {
+            println!("simulation: {}", problem);
+        }
+        println!(
+            "simulation: seed={} faults={} events={} waits={} time={}us",
+            self.config.seed,
+            self.config.faults,
+            self.stats.events,
+            self.stats.waits,
+            self.now.as_micros()
+        );
+        println!(
+            "simulation: stream_sent={} stream_echoed={} datagrams_sent={} datagrams_returned={}",
+            self.stats.stream_sent,
+            self.stats.stream_echoed,
+            self.stats.datagrams_sent,
+            self.stats.datagrams_returned
+        );
+        match self.problems.is_empty() {
+            true => println!("simulation: seed {} passed", self.config.seed),
+            false => println!("simulation: seed {} failed", self.config.seed),
+        }
+        Self::shutdown()
+    }
+
+    /// Returns the failure of waits once the simulation is over.
+    fn shutdown() -> Fail {
+        Fail::new(libc::ESHUTDOWN, "simulation is over")
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// From String Trait Implementation for Injected Faults
+///
+/// Faults are given as a comma-separated list of `partial`, `drop` and `reset`, or as `none`.
+impl FromStr for Faults {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut faults: Faults = Faults::default();
+        for fault in s.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            match fault {
+                "partial" => faults.partial = true,
+                "drop" => faults.drop = true,
+                "reset" => faults.reset = true,
+                "none" => (),
+                _ => bail!("invalid fault: {:?}", fault),
+            }
+        }
+        Ok(faults)
+    }
+}
+
+impl fmt::Display for Faults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = [
+            (self.partial, "partial"),
+            (self.drop, "drop"),
+            (self.reset, "reset"),
+        ]
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, name)| *name)
+        .collect();
+        match names.is_empty() {
+            true => write!(f, "none"),
+            false => write!(f, "{}", names.join(",")),
+        }
+    }
+}
+
+/// Backend Trait Implementation for Simulators
+impl Backend for Simulator {
+    /// Creates a simulator with the parameters given in the environment.
+    fn create() -> Result<Self> {
+        Ok(Self::new(SimConfig::from_env()?))
+    }
+
+    fn has_timed_waits() -> bool {
+        true
+    }
+
+    fn socket(
+        &mut self,
+        domain: libc::c_int,
+        socket_type: libc::c_int,
+        _protocol: libc::c_int,
+    ) -> Result<QDesc, Fail> {
+        if domain != libc::AF_INET && domain != libc::AF_INET6 {
+            return Err(Fail::new(
+                libc::EAFNOSUPPORT,
+                "address family not supported",
+            ));
+        }
+        let state: State = match socket_type {
+            libc::SOCK_STREAM => State::Unconnected,
+            libc::SOCK_DGRAM => State::Datagram(VecDeque::new()),
+            _ => {
+                return Err(Fail::new(
+                    libc::ESOCKTNOSUPPORT,
+                    "socket type not supported",
+                ))
+            }
+        };
+        Ok(self.insert(state, true))
+    }
+
+    fn bind(&mut self, qd: QDesc, local: SocketAddr) -> Result<(), Fail> {
+        let socket: &mut Socket = self.get(qd)?;
+        if socket.local.is_some() {
+            return Err(Fail::new(libc::EINVAL, "socket already bound"));
+        }
+        let datagram: bool = matches!(socket.state, State::Datagram(_));
+        let is_datagram = |state: &State| matches!(state, State::Datagram(_));
+        if self.lookup(local, |s| is_datagram(s) == datagram).is_some() {
+            return Err(Fail::new(libc::EADDRINUSE, "address in use"));
+        }
+        self.get(qd)?.local = Some(local);
+
+        // Clients start sending to the first datagram socket.
+        if datagram && !self.has_datagram_clients {
+            self.add_datagram_clients(local);
+        }
+        Ok(())
+    }
+
+    fn listen(&mut self, qd: QDesc, _backlog: usize) -> Result<(), Fail> {
+        let socket: &mut Socket = self.get(qd)?;
+        match (socket.local, &socket.state) {
+            (Some(_), State::Unconnected) => socket.state = State::Listening(VecDeque::new()),
+            (None, _) => return Err(Fail::new(libc::EDESTADDRREQ, "socket is not bound")),
+            (_, _) => return Err(Fail::new(libc::EINVAL, "socket cannot listen")),
+        }
+        self.add_stream_clients();
+        Ok(())
+    }
+
+    fn accept(&mut self, qd: QDesc) -> Result<QToken, Fail> {
+        self.get(qd)?;
+        Ok(self.submit(qd, Op::Accept))
+    }
+
+    fn connect(&mut self, qd: QDesc, _remote: SocketAddr) -> Result<QToken, Fail> {
+        if !matches!(self.get(qd)?.state, State::Unconnected) {
+            return Err(Fail::new(libc::EISCONN, "socket cannot connect"));
+        }
+        self.local(qd)?;
+
+        // Whatever the remote address, an echo server answers.
+        let peer: PeerId = self.peers.len();
+        self.peers.push(Peer::StreamEcho(Duration::ZERO));
+        self.get(qd)?.state = State::Connected(Connection {
+            peer,
+            rx: VecDeque::new(),
+            reset: false,
+            eof: false,
+            shut: false,
+        });
+        Ok(self.in_flight(qd, OperationResult::Connect))
+    }
+
+    fn push(&mut self, qd: QDesc, data: &[u8]) -> Result<QToken, Fail> {
+        let result: OperationResult = match &self.get(qd)?.state {
+            State::Connected(connection) if connection.reset => {
+                OperationResult::Failed(Fail::new(libc::ECONNRESET, "connection reset"))
+            }
+            State::Connected(connection) if connection.shut => {
+                OperationResult::Failed(Fail::new(libc::EPIPE, "socket shut down"))
+            }
+            State::Connected(connection) => {
+                let peer: PeerId = connection.peer;
+                self.receive(qd, peer, data);
+                OperationResult::Push
+            }
+            _ => OperationResult::Failed(Fail::new(libc::ENOTCONN, "socket is not connected")),
+        };
+        Ok(self.in_flight(qd, result))
+    }
+
+    fn pushto(&mut self, qd: QDesc, data: &[u8], to: SocketAddr) -> Result<QToken, Fail> {
+        if !matches!(self.get(qd)?.state, State::Datagram(_)) {
+            return Err(Fail::new(
+                libc::EOPNOTSUPP,
+                "socket is not a datagram socket",
+            ));
+        }
+        let from: SocketAddr = self.local(qd)?;
+        self.receive_datagram(from, to, data);
+        Ok(self.in_flight(qd, OperationResult::Push))
+    }
+
+    fn pop(&mut self, qd: QDesc) -> Result<QToken, Fail> {
+        self.get(qd)?;
+        Ok(self.submit(qd, Op::Pop))
+    }
+
+    fn wait(&mut self, qt: QToken) -> Result<(QDesc, OperationResult), Fail> {
+        let (_, qd, result) = self.wait_any(&[qt])?;
+        Ok((qd, result))
+    }
+
+    fn wait_any(&mut self, qts: &[QToken]) -> Result<(usize, QDesc, OperationResult), Fail> {
+        self.wait_any_timeout(qts, None)
+    }
+
+    fn wait_any_timeout(
+        &mut self,
+        qts: &[QToken],
+        timeout: Option<Duration>,
+    ) -> Result<(usize, QDesc, OperationResult), Fail> {
+        if self.over {
+            return Err(Self::shutdown());
+        }
+        self.stats.waits += 1;
+        if self.stats.waits > Self::MAX_WAITS {
+            self.problems.push(String::from(
+                "too many waits, the application may be spinning",
+            ));
+        }
+        let deadline: Option<Duration> = timeout.map(|timeout| self.now + timeout);
+        loop {
+            if !self.problems.is_empty() {
+                return Err(self.report());
+            }
+
+            let mut ready: Vec<usize> = Vec::new();
+            for (i, qt) in qts.iter().enumerate() {
+                if self.is_ready(*qt)? {
+                    ready.push(i);
+                }
+            }
+
+            // Is some event due before the wait times out?
+            let next: Option<Duration> = self.queue.peek().map(|Reverse((time, _))| *time);
+            let due: bool = match (next, deadline) {
+                (Some(time), Some(deadline)) => time <= deadline,
+                (next, None) => next.is_some(),
+                (None, Some(_)) => false,
+            };
+
+            // Now and then, let the network make progress before completing ready operations.
+            if !ready.is_empty() && (!due || !self.chance(4)) {
+                let i: usize = ready[self.rng.next_u64() as usize % ready.len()];
+                let (qd, result): (QDesc, OperationResult) = self.complete(qts[i]);
+                return Ok((i, qd, result));
+            }
+            if due {
+                self.step();
+                continue;
+            }
+
+            // Let time pass until the deadline, unless nothing happened for too long.
+            match deadline {
+                Some(deadline)
+                    if next.is_some() || deadline <= self.last_activity + Self::MAX_IDLE =>
+                {
+                    self.advance(deadline.max(self.now));
+                    return Err(Fail::new(libc::ETIMEDOUT, "wait timed out"));
+                }
+                _ => {
+                    self.check(qts);
+                    return Err(self.report());
+                }
+            }
+        }
+    }
+
+    fn shutdown(&mut self, qd: QDesc) -> Result<(), Fail> {
+        let peer: PeerId = match &mut self.get(qd)?.state {
+            State::Connected(connection) => {
+                connection.shut = true;
+                connection.peer
+            }
+            _ => return Err(Fail::new(libc::ENOTCONN, "socket is not connected")),
+        };
+
+        // Echo servers close their end once they echoed everything.
+        let latency: Duration = self.latency();
+        if let Peer::StreamEcho(last) = self.peers[peer] {
+            let when: Duration = (self.now + latency).max(last);
+            self.schedule(when - self.now, Event::Close(qd));
+        }
+        Ok(())
+    }
+
+    fn close(&mut self, qd: QDesc) -> Result<(), Fail> {
+        self.get(qd)?;
+        let socket: Socket = self.sockets.remove(&qd).expect("invalid queue descriptor");
+        self.ops.retain(|_, (op_qd, _)| *op_qd != qd);
+        let mut peers: Vec<PeerId> = Vec::new();
+        match socket.state {
+            State::Connected(connection) => peers.push(connection.peer),
+            State::Listening(backlog) => {
+                for pending in backlog {
+                    if let Some(Socket {
+                        state: State::Connected(connection),
+                        ..
+                    }) = self.sockets.remove(&pending)
+                    {
+                        peers.push(connection.peer);
+                    }
+                }
+            }
+            _ => (),
+        }
+        for peer in peers {
+            if let Peer::StreamClient(client) = &mut self.peers[peer] {
+                client.closed = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn clock(&self) -> Clock {
+        Clock::simulated(self.elapsed.clone())
+    }
+
+    fn status(&self) -> Result<()> {
+        match self.problems.is_empty() {
+            true => Ok(()),
+            false => bail!("simulation with seed {} failed", self.config.seed),
+        }
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns the address of the `i`-th client of a socket bound to `target`, a documentation
+/// address of the same family.
+fn client_addr(target: SocketAddr, i: usize) -> SocketAddr {
+    let ip: IpAddr = match target {
+        SocketAddr::V4(_) => Ipv4Addr::new(192, 0, 2, 1 + (i % 254) as u8).into(),
+        SocketAddr::V6(_) => Ipv6Addr::new(0x2001, 0xdb8, 0, 2, 0, 0, 0, 1 + i as u16).into(),
+    };
+    SocketAddr::new(ip, 10000)
+}
@@ -0,0 +1,687 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::{
+    backend::{Backend, OperationResult},
+    event,
+};
+use ::demikernel::{QDesc, QToken};
+use ::log::Level;
+use ::std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Direction of a Flow
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    /// From the client to the server.
+    Up,
+    /// From the server to the client.
+    Down,
+}
+
+/// Pending Operation of a Flow
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    /// Connection to the server.
+    Connect,
+    /// Pop on the source of a direction.
+    Pop(Direction),
+    /// Push of some bytes to the destination of a direction.
+    Push(Direction, usize),
+}
+
+/// One Direction of a Flow
+#[derive(Debug, Default)]
+struct Half {
+    /// Number of bytes relayed.
+    nbytes: usize,
+    /// Number of bytes pushed but not completed yet.
+    inflight: usize,
+    /// Is a pop pending on the source?
+    popping: bool,
+    /// Did the source close its end?
+    eof: bool,
+    /// Was the end of the stream forwarded to the destination?
+    shut: bool,
+}
+
+/// Spliced Flow
+///
+/// A flow ties a connection accepted from a client to a connection opened to a server.
+#[derive(Debug)]
+struct Flow {
+    /// Client socket descriptor.
+    client: QDesc,
+    /// Server socket descriptor.
+    server: QDesc,
+    /// Opaque value attached by the caller.
+    tag: usize,
+    /// Client to server direction.
+    up: Half,
+    /// Server to client direction.
+    down: Half,
+    /// Pending operations.
+    pending: Vec<(QToken, Op)>,
+    /// Instant at which the flow was opened.
+    since: Instant,
+}
+
+/// Summary of a Closed Flow
+#[derive(Debug)]
+pub struct Closed {
+    /// Flow identifier.
+    pub id: u64,
+    /// Opaque value attached by the caller.
+    pub tag: usize,
+    /// Client connection, which is closed by now.
+    pub client: QDesc,
+    /// Number of bytes relayed from the client to the server.
+    pub nbytes_up: usize,
+    /// Number of bytes relayed from the server to the client.
+    pub nbytes_down: usize,
+    /// Lifetime of the flow.
+    pub duration: Duration,
+    /// Reason of an abnormal termination.
+    pub error: Option<String>,
+}
+
+/// Flow Statistics
+#[derive(Clone, Copy, Debug)]
+pub struct FlowStatistics {
+    /// Flow identifier.
+    pub id: u64,
+    /// Opaque value attached by the caller.
+    pub tag: usize,
+    /// Number of bytes relayed from the client to the server.
+    pub nbytes_up: usize,
+    /// Number of bytes relayed from the server to the client.
+    pub nbytes_down: usize,
+    /// Is the flow still connecting?
+    pub connecting: bool,
+}
+
+/// Splicer
+///
+/// Copies bytes in both directions between pairs of connections.
+///
+/// Each direction has a single pop pending at a time, and stops popping while more than `window`
+/// bytes are pushed but not completed, so that a slow side throttles the other one instead of
+/// piling up data in memory.
+///
+/// When one side closes its end, the end of the stream is forwarded to the other side once the
+/// bytes popped before it went out, and the other direction keeps flowing until it is closed as
+/// well. Flows fail on backends that cannot shut half of a connection down, such as the libOS.
+#[derive(Debug)]
+pub struct Splicer {
+    /// Flows indexed by identifier.
+    flows: HashMap<u64, Flow>,
+    /// Flow identifiers indexed by socket descriptor.
+    owners: HashMap<QDesc, u64>,
+    /// Identifier of the next flow.
+    next_id: u64,
+    /// Maximum number of bytes in flight per direction.
+    window: usize,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Flows
+impl Flow {
+    /// Returns a direction of the target flow.
+    fn half(&mut self, direction: Direction) -> &mut Half {
+        match direction {
+            Direction::Up => &mut self.up,
+            Direction::Down => &mut self.down,
+        }
+    }
+
+    /// Returns the source and destination of a direction of the target flow.
+    fn ends(&self, direction: Direction) -> (QDesc, QDesc) {
+        match direction {
+            Direction::Up => (self.client, self.server),
+            Direction::Down => (self.server, self.client),
+        }
+    }
+
+    /// Asserts if both directions of the target flow are over.
+    fn is_done(&self) -> bool {
+        self.up.eof && self.up.inflight == 0 && self.down.eof && self.down.inflight == 0
+    }
+}
+
+/// Associate Functions for Splicers
+impl Splicer {
+    /// Creates a splicer.
+    pub fn new(window: usize) -> Self {
+        Self {
+            flows: HashMap::new(),
+            owners: HashMap::new(),
+            next_id: 0,
+            window,
+        }
+    }
+
+    /// Returns the number of flows.
+    pub fn len(&self) -> usize {
+        self.flows.len()
+    }
+
+    /// Asserts if the target splicer has no flows.
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+
+    /// Asserts if a socket belongs to a flow.
+    pub fn owns(&self, qd: QDesc) -> bool {
+        self.owners.contains_key(&qd)
+    }
+
+    /// Returns statistics of every flow.
+    pub fn statistics(&self) -> Vec<FlowStatistics> {
+        let mut stats: Vec<FlowStatistics> = self
+            .flows
+            .iter()
+            .map(|(id, flow)| FlowStatistics {
+                id: *id,
+                tag: flow.tag,
+                nbytes_up: flow.up.nbytes,
+                nbytes_down: flow.down.nbytes,
+                connecting: flow.pending.iter().any(|(_, op)| matches!(op, Op::Connect)),
+            })
+            .collect();
+        stats.sort_by_key(|s| s.id);
+        stats
+    }
+
+    /// Registers a flow between a client connection and a server connection that is being
+    /// established by `connect_qt`. Returns the identifier of the flow.
+    pub fn insert(&mut self, client: QDesc, server: QDesc, connect_qt: QToken, tag: usize) -> u64 {
+        let id: u64 = self.next_id;
+        self.next_id += 1;
+        self.flows.insert(
+            id,
+            Flow {
+                client,
+                server,
+                tag,
+                up: Half::default(),
+                down: Half::default(),
+                pending: vec![(connect_qt, Op::Connect)],
+                since: Instant::now(),
+            },
+        );
+        self.owners.insert(client, id);
+        self.owners.insert(server, id);
+        id
+    }
+
+    /// Handles the completion of operation `qt` on socket `qd`, which must belong to a flow.
+    /// Returns a summary of the flow if it got closed.
+    pub fn handle<B: Backend>(
+        &mut self,
+        libos: &mut B,
+        qt: QToken,
+        qd: QDesc,
+        result: OperationResult,
+        qtokens: &mut Vec<QToken>,
+    ) -> Option<Closed> {
+        let window: usize = self.window;
+        let id: u64 = *self.owners.get(&qd)?;
+        let flow: &mut Flow = self.flows.get_mut(&id)?;
+        let op: Op = match flow.pending.iter().position(|(t, _)| *t == qt) {
+            Some(i) => flow.pending.swap_remove(i).1,
+            None => return None,
+        };
+
+        match (op, result) {
+            // Connected to the server, so start relaying in both directions.
+            (Op::Connect, OperationResult::Connect) => {
+                for direction in [Direction::Up, Direction::Down] {
+                    if let Err(e) = Self::pop(libos, flow, direction, window, qtokens) {
+                        return Some(self.close(libos, id, Some(e), qtokens));
+                    }
+                }
+            }
+            // The source closed its end, so tell the destination once all data went out.
+            (Op::Pop(direction), OperationResult::Pop(_, buf)) if buf.is_empty() => {
+                let half: &mut Half = flow.half(direction);
+                half.popping = false;
+                half.eof = true;
+                if let Err(e) = Self::shut(libos, flow, direction) {
+                    return Some(self.close(libos, id, Some(e), qtokens));
+                }
+            }
+            // Data came in, so forward it and keep popping while the window is open.
+            (Op::Pop(direction), OperationResult::Pop(_, buf)) => {
+                let (_, dst): (QDesc, QDesc) = flow.ends(direction);
+                let half: &mut Half = flow.half(direction);
+                half.popping = false;
+                half.nbytes += buf.len();
+                half.inflight += buf.len();
+                let qt: QToken = match libos.push(dst, &buf) {
+                    Ok(qt) => qt,
+                    Err(e) => {
+                        let cause: String = format!("failed to push data to socket: {:?}", e.cause);
+                        return Some(self.close(libos, id, Some(cause), qtokens));
+                    }
+                };
+                flow.pending.push((qt, Op::Push(direction, buf.len())));
+                qtokens.push(qt);
+                if let Err(e) = Self::pop(libos, flow, direction, window, qtokens) {
+                    return Some(self.close(libos, id, Some(e), qtokens));
+                }
+            }
+            // Data went out, so the window may reopen, or the end of the stream may follow.
+            (Op::Push(direction, nbytes), OperationResult::Push) => {
+                flow.half(direction).inflight -= nbytes;
+                if let Err(e) = Self::pop(libos, flow, direction, window, qtokens) {
+                    return Some(self.close(libos, id, Some(e), qtokens));
+                }
+                if let Err(e) = Self::shut(libos, flow, direction) {
+                    return Some(self.close(libos, id, Some(e), qtokens));
+                }
+            }
+            (_, OperationResult::Failed(e)) => {
+                return Some(self.close(libos, id, Some(format!("{:?}", e.cause)), qtokens));
+            }
+            (op, _) => {
+                let cause: String = format!("unexpected result for {:?}", op);
+                return Some(self.close(libos, id, Some(cause), qtokens));
+            }
+        }
+
+        if matches!(self.flows.get(&id), Some(flow) if flow.is_done()) {
+            return Some(self.close(libos, id, None, qtokens));
+        }
+        None
+    }
+
+    /// Pops from the source of a direction, unless a pop is already pending, the source is closed
+    /// or the window is full.
+    fn pop<B: Backend>(
+        libos: &mut B,
+        flow: &mut Flow,
+        direction: Direction,
+        window: usize,
+        qtokens: &mut Vec<QToken>,
+    ) -> Result<(), String> {
+        let (src, _): (QDesc, QDesc) = flow.ends(direction);
+        let half: &mut Half = flow.half(direction);
+        if half.popping || half.eof || half.inflight >= window {
+            return Ok(());
+        }
+        let qt: QToken = match libos.pop(src) {
+            Ok(qt) => qt,
+            Err(e) => return Err(format!("failed to pop data from socket: {:?}", e.cause)),
+        };
+        half.popping = true;
+        flow.pending.push((qt, Op::Pop(direction)));
+        qtokens.push(qt);
+        Ok(())
+    }
+
+    /// Forwards the end of the stream of a direction to its destination, once the source closed
+    /// its end and every byte popped before went out. Flows in which both directions are over are
+    /// closed instead.
+    fn shut<B: Backend>(
+        libos: &mut B,
+        flow: &mut Flow,
+        direction: Direction,
+    ) -> Result<(), String> {
+        let done: bool = flow.is_done();
+        let (_, dst): (QDesc, QDesc) = flow.ends(direction);
+        let half: &mut Half = flow.half(direction);
+        if done || !half.eof || half.inflight > 0 || half.shut {
+            return Ok(());
+        }
+        half.shut = true;
+        match libos.shutdown(dst) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("failed to shut socket down: {:?}", e.cause)),
+        }
+    }
+
+    /// Closes every flow, such as when the application shuts down. Returns summaries of the
+    /// flows, in the order they were opened.
+    pub fn close_all<B: Backend>(
+        &mut self,
+        libos: &mut B,
+        qtokens: &mut Vec<QToken>,
+    ) -> Vec<Closed> {
+        let mut ids: Vec<u64> = self.flows.keys().copied().collect();
+        ids.sort_unstable();
+        ids.into_iter()
+            .map(|id| self.close(libos, id, None, qtokens))
+            .collect()
+    }
+
+    /// Closes both connections of a flow and stops waiting for its pending operations.
+    fn close<B: Backend>(
+        &mut self,
+        libos: &mut B,
+        id: u64,
+        error: Option<String>,
+        qtokens: &mut Vec<QToken>,
+    ) -> Closed {
+        let flow: Flow = self.flows.remove(&id).expect("unknown flow");
+        self.owners.remove(&flow.client);
+        self.owners.remove(&flow.server);
+        for (qt, _) in &flow.pending {
+            if let Some(i) = qtokens.iter().position(|t| t == qt) {
+                qtokens.swap_remove(i);
+            }
+        }
+        for qd in [flow.client, flow.server] {
+            if let Err(e) = libos.close(qd) {
+                event!(
+                    Level::Warn,
+                    qd,
+                    None,
+                    "failed to close socket: {:?}",
+                    e.cause
+                );
+            }
+        }
+        Closed {
+            id,
+            tag: flow.tag,
+            client: flow.client,
+            nbytes_up: flow.up.nbytes,
+            nbytes_down: flow.down.nbytes,
+            duration: flow.since.elapsed(),
+            error,
+        }
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::Fail,
+        loopback::{Loopback, Network},
+    };
+    use ::std::net::SocketAddr;
+
+    /// Address of the relay.
+    const RELAY: ([u8; 4], u16) = ([127, 0, 0, 1], 7000);
+
+    /// Address of the server.
+    const SERVER: ([u8; 4], u16) = ([127, 0, 0, 1], 8000);
+
+    /// Relay, client and server of a spliced flow, each with a backend of its own.
+    struct Testbed {
+        /// Backend of the relay.
+        relay: Loopback,
+        /// Backend of the client.
+        client: Loopback,
+        /// Backend of the server.
+        server: Loopback,
+        /// Client connection, as seen by the client.
+        client_qd: QDesc,
+        /// Server connection, as seen by the server, if it is listening.
+        server_qd: Option<QDesc>,
+        /// Splicer of the relay.
+        splicer: Splicer,
+        /// Operations the relay waits on.
+        qtokens: Vec<QToken>,
+    }
+
+    /// Associate Functions for Testbeds
+    impl Testbed {
+        /// Connects a client to a server through a relay, unless the server is not `listening`.
+        fn new(window: usize, listening: bool) -> Self {
+            let network: Network = Network::new();
+            let mut relay: Loopback = Loopback::new(&network);
+            let mut client: Loopback = Loopback::new(&network);
+            let mut server: Loopback = Loopback::new(&network);
+
+            let listener: QDesc = relay.socket(libc::AF_INET, libc::SOCK_STREAM, 0).unwrap();
+            relay.bind(listener, SocketAddr::from(RELAY)).unwrap();
+            relay.listen(listener, 16).unwrap();
+            let server_listener: QDesc =
+                server.socket(libc::AF_INET, libc::SOCK_STREAM, 0).unwrap();
+            if listening {
+                server
+                    .bind(server_listener, SocketAddr::from(SERVER))
+                    .unwrap();
+                server.listen(server_listener, 16).unwrap();
+            }
+
+            let client_qd: QDesc = client.socket(libc::AF_INET, libc::SOCK_STREAM, 0).unwrap();
+            let qt: QToken = client.connect(client_qd, SocketAddr::from(RELAY)).unwrap();
+            assert!(matches!(
+                complete(&mut client, qt),
+                OperationResult::Connect
+            ));
+            let qt: QToken = relay.accept(listener).unwrap();
+            let accepted: QDesc = match complete(&mut relay, qt) {
+                OperationResult::Accept(qd, _) => qd,
+                result => panic!("unexpected result: {:?}", result),
+            };
+            let upstream: QDesc = relay.socket(libc::AF_INET, libc::SOCK_STREAM, 0).unwrap();
+            let connect_qt: QToken = relay.connect(upstream, SocketAddr::from(SERVER)).unwrap();
+            let mut splicer: Splicer = Splicer::new(window);
+            splicer.insert(accepted, upstream, connect_qt, 42);
+
+            let server_qd: Option<QDesc> = if listening {
+                let qt: QToken = server.accept(server_listener).unwrap();
+                match complete(&mut server, qt) {
+                    OperationResult::Accept(qd, _) => Some(qd),
+                    result => panic!("unexpected result: {:?}", result),
+                }
+            } else {
+                None
+            };
+
+            Self {
+                relay,
+                client,
+                server,
+                client_qd,
+                server_qd,
+                splicer,
+                qtokens: vec![connect_qt],
+            }
+        }
+
+        /// Hands one completed operation of the relay over to the splicer. Returns `None` if no
+        /// operation could complete.
+        fn step(&mut self) -> Option<Option<Closed>> {
+            let (i, qd, result): (usize, QDesc, OperationResult) =
+                match self.relay.wait_any(&self.qtokens) {
+                    Ok(completed) => completed,
+                    Err(e) if e.errno == libc::EWOULDBLOCK => return None,
+                    Err(e) => panic!("failed to wait: {:?}", e.cause),
+                };
+            let qt: QToken = self.qtokens.swap_remove(i);
+            let relay: &mut Loopback = &mut self.relay;
+            Some(
+                self.splicer
+                    .handle(relay, qt, qd, result, &mut self.qtokens),
+            )
+        }
+
+        /// Runs the relay until it is idle. Returns the flow it closed, if any.
+        fn run(&mut self) -> Option<Closed> {
+            let mut closed: Option<Closed> = None;
+            while let Some(step) = self.step() {
+                closed = closed.or(step);
+            }
+            closed
+        }
+    }
+
+    /// Waits for an operation that can complete.
+    fn complete(backend: &mut Loopback, qt: QToken) -> OperationResult {
+        match backend.wait(qt) {
+            Ok((_, result)) => result,
+            Err(e) => panic!("failed to wait: {:?}", e.cause),
+        }
+    }
+
+    /// Pushes data on a connection.
+    fn push(backend: &mut Loopback, qd: QDesc, data: &[u8]) {
+        let qt: QToken = backend.push(qd, data).unwrap();
+        assert!(matches!(complete(backend, qt), OperationResult::Push));
+    }
+
+    /// Pops data that is expected to be there.
+    fn pop(backend: &mut Loopback, qd: QDesc) -> Vec<u8> {
+        let qt: QToken = backend.pop(qd).unwrap();
+        match complete(backend, qt) {
+            OperationResult::Pop(_, buf) => buf.to_vec(),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn bytes_flow_both_ways_until_both_ends_close() {
+        let mut testbed: Testbed = Testbed::new(1024, true);
+        let server_qd: QDesc = testbed.server_qd.unwrap();
+        assert!(testbed.splicer.statistics()[0].connecting);
+
+        push(&mut testbed.client, testbed.client_qd, b"ping");
+        assert!(testbed.run().is_none());
+        assert_eq!(pop(&mut testbed.server, server_qd), b"ping");
+        push(&mut testbed.server, server_qd, b"pong!");
+        assert!(testbed.run().is_none());
+        assert_eq!(pop(&mut testbed.client, testbed.client_qd), b"pong!");
+
+        let stats: FlowStatistics = testbed.splicer.statistics()[0];
+        assert_eq!((stats.tag, stats.nbytes_up, stats.nbytes_down), (42, 4, 5));
+        assert!(!stats.connecting);
+
+        // The flow stays open until both ends are closed, and the server sees the end of the
+        // stream meanwhile.
+        testbed.client.close(testbed.client_qd).unwrap();
+        assert!(testbed.run().is_none());
+        assert_eq!(testbed.splicer.len(), 1);
+        assert!(pop(&mut testbed.server, server_qd).is_empty());
+        testbed.server.close(server_qd).unwrap();
+        let closed: Closed = testbed.run().expect("flow not closed");
+        assert_eq!((closed.id, closed.tag), (0, 42));
+        assert_eq!((closed.nbytes_up, closed.nbytes_down), (4, 5));
+        assert!(closed.error.is_none());
+        assert!(!testbed.splicer.owns(closed.client));
+        assert!(testbed.splicer.is_empty());
+        assert!(testbed.qtokens.is_empty());
+    }
+
+    #[test]
+    fn half_closes_are_forwarded() {
+        let mut testbed: Testbed = Testbed::new(1024, true);
+        let server_qd: QDesc = testbed.server_qd.unwrap();
+
+        // The server only replies once it read the whole request.
+        push(&mut testbed.client, testbed.client_qd, b"request");
+        testbed.client.shutdown(testbed.client_qd).unwrap();
+        assert!(testbed.run().is_none());
+        assert_eq!(pop(&mut testbed.server, server_qd), b"request");
+        assert!(pop(&mut testbed.server, server_qd).is_empty());
+
+        push(&mut testbed.server, server_qd, b"reply");
+        testbed.server.close(server_qd).unwrap();
+        let closed: Closed = testbed.run().expect("flow not closed");
+        assert_eq!((closed.nbytes_up, closed.nbytes_down), (7, 5));
+        assert!(closed.error.is_none());
+        assert_eq!(pop(&mut testbed.client, testbed.client_qd), b"reply");
+        assert!(pop(&mut testbed.client, testbed.client_qd).is_empty());
+    }
+
+    #[test]
+    fn data_for_a_closed_end_fails_the_flow() {
+        let mut testbed: Testbed = Testbed::new(1024, true);
+        let server_qd: QDesc = testbed.server_qd.unwrap();
+        testbed.client.close(testbed.client_qd).unwrap();
+        assert!(testbed.run().is_none());
+        push(&mut testbed.server, server_qd, b"late");
+        let closed: Closed = testbed.run().expect("flow not closed");
+        assert_eq!(closed.nbytes_down, 4);
+        assert!(closed.error.unwrap().contains("connection closed"));
+
+        // The server sees the end of the stream.
+        assert!(pop(&mut testbed.server, server_qd).is_empty());
+    }
+
+    #[test]
+    fn full_windows_stop_pops_until_pushes_complete() {
+        let mut testbed: Testbed = Testbed::new(4, true);
+        assert!(matches!(testbed.step(), Some(None)));
+        push(&mut testbed.client, testbed.client_qd, b"12345678");
+
+        // The pop completes, and its data fills the window.
+        assert!(matches!(testbed.step(), Some(None)));
+        let flow: &Flow = &testbed.splicer.flows[&0];
+        assert_eq!(flow.up.inflight, 8);
+        assert!(!flow.up.popping);
+
+        // The push completes, which reopens the window.
+        assert!(matches!(testbed.step(), Some(None)));
+        let flow: &Flow = &testbed.splicer.flows[&0];
+        assert_eq!(flow.up.inflight, 0);
+        assert!(flow.up.popping);
+    }
+
+    #[test]
+    fn refused_connections_close_the_flow() {
+        let mut testbed: Testbed = Testbed::new(1024, false);
+        let closed: Closed = testbed.run().expect("flow not closed");
+        assert!(closed.error.unwrap().contains("connection refused"));
+        assert!(testbed.splicer.is_empty());
+
+        // The client sees the end of the stream.
+        assert!(pop(&mut testbed.client, testbed.client_qd).is_empty());
+    }
+
+    #[test]
+    fn closing_all_flows_closes_both_ends() {
+        let mut testbed: Testbed = Testbed::new(1024, true);
+        let server_qd: QDesc = testbed.server_qd.unwrap();
+        push(&mut testbed.client, testbed.client_qd, b"ping");
+        assert!(testbed.run().is_none());
+
+        let relay: &mut Loopback = &mut testbed.relay;
+        let closed: Vec<Closed> = testbed.splicer.close_all(relay, &mut testbed.qtokens);
+        assert_eq!(closed.len(), 1);
+        assert_eq!((closed[0].nbytes_up, closed[0].nbytes_down), (4, 0));
+        assert!(closed[0].error.is_none());
+        assert!(testbed.splicer.is_empty());
+        assert!(testbed.qtokens.is_empty());
+
+        // Both ends see the end of the stream.
+        assert_eq!(pop(&mut testbed.server, server_qd), b"ping");
+        assert!(pop(&mut testbed.server, server_qd).is_empty());
+        assert!(pop(&mut testbed.client, testbed.client_qd).is_empty());
+    }
+
+    #[test]
+    fn foreign_operations_are_ignored() {
+        let mut testbed: Testbed = Testbed::new(1024, true);
+        let relay: &mut Loopback = &mut testbed.relay;
+        let failed: OperationResult = OperationResult::Failed(Fail::new(libc::EIO, "error"));
+        let qd: QDesc = QDesc::from(1000);
+        let closed: Option<Closed> =
+            testbed
+                .splicer
+                .handle(relay, QToken::from(1000), qd, failed, &mut testbed.qtokens);
+        assert!(closed.is_none());
+        assert_eq!(testbed.splicer.len(), 1);
+    }
+}
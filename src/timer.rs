@@ -0,0 +1,269 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::time::{Duration, Instant};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Timer Identifier
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimerId {
+    /// Unique number of the timer.
+    id: u64,
+    /// Slot in which the timer is stored.
+    slot: usize,
+}
+
+/// Pending Timer
+#[derive(Debug)]
+struct Entry<T> {
+    /// Unique number of the timer.
+    id: u64,
+    /// Tick at which the timer fires.
+    tick: u64,
+    /// Value returned when the timer fires.
+    item: T,
+}
+
+/// Timer Wheel
+///
+/// Time is cut into ticks of fixed granularity, and timers are hashed into slots by the tick at
+/// which they fire. Scheduling and cancelling only touch one slot, and checking for expired timers
+/// costs a comparison until the next tick starts. Timers that fire further away than a full turn
+/// of the wheel share slots with nearer ones, and are skipped until their tick comes.
+///
+/// Timers fire at the first check that follows their tick, so they may fire up to one tick late,
+/// but never early.
+#[derive(Debug)]
+pub struct TimerWheel<T> {
+    /// Slots of pending timers.
+    slots: Vec<Vec<Entry<T>>>,
+    /// Duration of a tick.
+    granularity: Duration,
+    /// Instant at which tick zero starts.
+    origin: Instant,
+    /// First tick that has not been checked yet.
+    cursor: u64,
+    /// Instant at which the cursor tick starts.
+    next_check: Instant,
+    /// Number of pending timers.
+    len: usize,
+    /// Earliest tick of pending timers, if known. Cancelled timers may leave it early.
+    earliest: Option<u64>,
+    /// Unique number of the next timer.
+    next_id: u64,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Timer Wheels
+impl<T> TimerWheel<T> {
+    /// Creates a timer wheel of `nslots` ticks of `granularity` each, starting at `now`.
+    pub fn new(granularity: Duration, nslots: usize, now: Instant) -> Self {
+        assert!(!granularity.is_zero() && nslots > 0);
+        Self {
+            slots: (0..nslots).map(|_| Vec::new()).collect(),
+            granularity,
+            origin: now,
+            cursor: 0,
+            next_check: now,
+            len: 0,
+            earliest: None,
+            next_id: 0,
+        }
+    }
+
+    /// Returns the number of pending timers.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Asserts if no timer is pending.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the tick that contains an instant.
+    fn tick(&self, instant: Instant) -> u64 {
+        let elapsed: Duration = instant.saturating_duration_since(self.origin);
+        (elapsed.as_nanos() / self.granularity.as_nanos()) as u64
+    }
+
+    /// Schedules `item` to be returned once `deadline` has passed. Deadlines in the past fire on
+    /// the next check.
+    pub fn schedule(&mut self, deadline: Instant, item: T) -> TimerId {
+        // Round up to the first tick that starts at or after the deadline.
+        let elapsed: u128 = deadline.saturating_duration_since(self.origin).as_nanos();
+        let granularity: u128 = self.granularity.as_nanos();
+        let rounded: u128 = elapsed + granularity - 1;
+        let tick: u64 = ((rounded / granularity) as u64).max(self.cursor);
+        let slot: usize = (tick % self.slots.len() as u64) as usize;
+        let id: u64 = self.next_id;
+        self.next_id += 1;
+        self.slots[slot].push(Entry { id, tick, item });
+        self.earliest = match (self.earliest, self.len) {
+            (_, 0) => Some(tick),
+            (earliest, _) => earliest.map(|earliest| earliest.min(tick)),
+        };
+        self.len += 1;
+        TimerId { id, slot }
+    }
+
+    /// Cancels a pending timer, returning its item.
+    pub fn cancel(&mut self, timer: TimerId) -> Option<T> {
+        let slot: &mut Vec<Entry<T>> = &mut self.slots[timer.slot];
+        let i: usize = slot.iter().position(|e| e.id == timer.id)?;
+        self.len -= 1;
+        Some(slot.swap_remove(i).item)
+    }
+
+    /// Returns the instant at which the next timer fires, if any, so that callers know how long
+    /// they may sleep. Timers are only looked for when the previous earliest one has fired.
+    pub fn next_deadline(&mut self) -> Option<Instant> {
+        if self.len == 0 {
+            return None;
+        }
+        let tick: u64 = match self.earliest {
+            Some(tick) if tick >= self.cursor => tick,
+            _ => {
+                let tick: u64 = self.slots.iter().flatten().map(|e| e.tick).min()?;
+                self.earliest = Some(tick);
+                tick
+            }
+        };
+        let ns: u64 = (self.granularity.as_nanos() as u64).saturating_mul(tick);
+        Some(self.origin + Duration::from_nanos(ns))
+    }
+
+    /// Returns the items of timers that expired at `now`, in no particular order.
+    pub fn expire(&mut self, now: Instant) -> Vec<T> {
+        let mut expired: Vec<T> = Vec::new();
+        if now < self.next_check {
+            return expired;
+        }
+
+        // Check every slot between the cursor and the current tick, at most once.
+        let target: u64 = self.tick(now);
+        let nslots: u64 = self.slots.len() as u64;
+        let first: u64 = self.cursor.max((target + 1).saturating_sub(nslots));
+        for tick in first..=target {
+            let slot: &mut Vec<Entry<T>> = &mut self.slots[(tick % nslots) as usize];
+            let mut i: usize = 0;
+            while i < slot.len() {
+                if slot[i].tick <= target {
+                    expired.push(slot.swap_remove(i).item);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        self.len -= expired.len();
+        self.cursor = target + 1;
+        let ns: u64 = (self.granularity.as_nanos() as u64).saturating_mul(self.cursor);
+        self.next_check = self.origin + Duration::from_nanos(ns);
+        expired
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the instant that is `ms` milliseconds after `origin`.
+    fn at(origin: Instant, ms: u64) -> Instant {
+        origin + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn timers_fire_within_a_tick_of_their_deadline() {
+        let origin: Instant = Instant::now();
+        let mut wheel: TimerWheel<&str> = TimerWheel::new(Duration::from_millis(10), 8, origin);
+        wheel.schedule(at(origin, 25), "a");
+        wheel.schedule(at(origin, 30), "b");
+        wheel.schedule(at(origin, 45), "c");
+        assert_eq!(wheel.len(), 3);
+
+        assert!(wheel.expire(at(origin, 24)).is_empty());
+        assert!(wheel.expire(at(origin, 29)).is_empty());
+        let mut expired: Vec<&str> = wheel.expire(at(origin, 30));
+        expired.sort_unstable();
+        assert_eq!(expired, ["a", "b"]);
+        assert!(wheel.expire(at(origin, 49)).is_empty());
+        assert_eq!(wheel.expire(at(origin, 50)), ["c"]);
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn past_deadlines_fire_on_the_next_check() {
+        let origin: Instant = Instant::now();
+        let mut wheel: TimerWheel<u32> = TimerWheel::new(Duration::from_millis(10), 8, origin);
+        assert!(wheel.expire(at(origin, 100)).is_empty());
+        wheel.schedule(origin, 1);
+        wheel.schedule(at(origin, 50), 2);
+        assert!(wheel.expire(at(origin, 105)).is_empty());
+        let mut expired: Vec<u32> = wheel.expire(at(origin, 110));
+        expired.sort_unstable();
+        assert_eq!(expired, [1, 2]);
+    }
+
+    #[test]
+    fn far_timers_wait_for_their_turn_of_the_wheel() {
+        let origin: Instant = Instant::now();
+        let mut wheel: TimerWheel<&str> = TimerWheel::new(Duration::from_millis(10), 4, origin);
+        wheel.schedule(at(origin, 20), "near");
+        wheel.schedule(at(origin, 60), "far");
+        wheel.schedule(at(origin, 100), "farther");
+        assert_eq!(wheel.expire(at(origin, 20)), ["near"]);
+        assert_eq!(wheel.expire(at(origin, 60)), ["far"]);
+        assert!(wheel.expire(at(origin, 90)).is_empty());
+
+        // Checks that skip several turns still find every timer.
+        wheel.schedule(at(origin, 130), "late");
+        let mut expired: Vec<&str> = wheel.expire(at(origin, 1000));
+        expired.sort_unstable();
+        assert_eq!(expired, ["farther", "late"]);
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn cancelled_timers_never_fire() {
+        let origin: Instant = Instant::now();
+        let mut wheel: TimerWheel<&str> = TimerWheel::new(Duration::from_millis(10), 8, origin);
+        let first: TimerId = wheel.schedule(at(origin, 10), "first");
+        let second: TimerId = wheel.schedule(at(origin, 10), "second");
+        assert_eq!(wheel.cancel(first), Some("first"));
+        assert_eq!(wheel.cancel(first), None);
+        assert_eq!(wheel.len(), 1);
+        assert_eq!(wheel.expire(at(origin, 10)), ["second"]);
+        assert_eq!(wheel.cancel(second), None);
+    }
+
+    #[test]
+    fn next_deadline_is_the_tick_of_the_earliest_timer() {
+        let origin: Instant = Instant::now();
+        let mut wheel: TimerWheel<&str> = TimerWheel::new(Duration::from_millis(10), 4, origin);
+        assert_eq!(wheel.next_deadline(), None);
+        wheel.schedule(at(origin, 75), "far");
+        assert_eq!(wheel.next_deadline(), Some(at(origin, 80)));
+        wheel.schedule(at(origin, 15), "near");
+        assert_eq!(wheel.next_deadline(), Some(at(origin, 20)));
+
+        // Once the earliest timer fires, the next one is looked for.
+        assert_eq!(wheel.expire(at(origin, 20)), ["near"]);
+        assert_eq!(wheel.next_deadline(), Some(at(origin, 80)));
+        assert_eq!(wheel.expire(at(origin, 80)), ["far"]);
+        assert_eq!(wheel.next_deadline(), None);
+    }
+}
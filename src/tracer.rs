@@ -0,0 +1,931 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::{
+    backend::{Backend, Fail, OperationResult},
+    clock::Clock,
+    logging::json_string,
+};
+use ::anyhow::{bail, Context, Result};
+use ::clap::{Arg, ArgMatches};
+use ::demikernel::{LibOS, QDesc, QToken};
+use ::log::{error, info};
+use ::std::{
+    cell::Cell,
+    collections::HashMap,
+    fs, mem,
+    net::SocketAddr,
+    process, ptr,
+    str::FromStr,
+    sync::{
+        atomic::{fence, AtomicPtr, AtomicU16, AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Tracing
+///
+/// Opt-in tracing of operations: when a trace file is given, the time at which every operation
+/// is issued and completes is recorded, along with its type, socket and size, as well as the
+/// time that is spent waiting. The trace is written when the process exits, including on
+/// `SIGINT` and `SIGTERM`, in the Chrome trace-event format or in the Perfetto format, for
+/// `chrome://tracing` or <https://ui.perfetto.dev>.
+///
+/// Records are kept in a ring of fixed size, so that the last ones are kept on long runs.
+/// Zero-copy operations, which bypass the backend, are not traced.
+#[derive(Clone, Debug)]
+pub struct Tracing {
+    /// File that the trace is written to. Tracing is disabled if `None`.
+    path: Option<String>,
+    /// Format of the trace.
+    format: TraceFormat,
+    /// Number of records that are kept.
+    size: usize,
+}
+
+/// Trace Format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Chrome trace-event JSON.
+    Chrome,
+    /// Perfetto protocol buffers.
+    Perfetto,
+}
+
+/// Traced Backend
+///
+/// Wraps a backend, recording its operations when tracing is enabled. It costs an atomic load
+/// per operation otherwise.
+pub struct Traced<B: Backend>(B);
+
+/// Tracer
+struct Tracer {
+    /// Clock that records are timed with.
+    clock: Clock,
+    /// Instant at which tracing started.
+    start: Instant,
+    /// Records.
+    ring: Ring,
+    /// Identifiers and names of traced threads.
+    threads: Mutex<Vec<(u16, String)>>,
+    /// File that the trace is written to.
+    path: String,
+    /// Format of the trace.
+    format: TraceFormat,
+}
+
+/// Ring of Records
+///
+/// Writers claim slots with an atomic counter and overwrite the oldest records once the ring is
+/// full. Every slot is guarded by a sequence number, so that readers skip slots that are being
+/// written instead of waiting on them.
+struct Ring {
+    /// Slots.
+    slots: Box<[Slot]>,
+    /// Number of records ever written.
+    head: AtomicUsize,
+}
+
+/// Slot of a Ring
+#[derive(Default)]
+struct Slot {
+    /// Position of the record in the slot, plus one. Zero while it is being written.
+    seq: AtomicU64,
+    /// Token of the operation.
+    token: AtomicU64,
+    /// Socket, operation, phase and thread, packed by [`Record::pack`].
+    meta: AtomicU64,
+    /// Size of the operation, or duration of a wait.
+    value: AtomicU64,
+    /// Time of the record, in nanoseconds since tracing started.
+    time: AtomicU64,
+}
+
+/// Trace Record
+#[derive(Clone, Copy, Debug)]
+struct Record {
+    /// Token of the operation.
+    token: u64,
+    /// Socket of the operation.
+    qd: u32,
+    /// Operation.
+    op: Op,
+    /// Phase of the operation.
+    phase: Phase,
+    /// Thread that recorded it.
+    tid: u16,
+    /// Size of the operation, or duration of a wait.
+    value: u64,
+    /// Time of the record, in nanoseconds since tracing started.
+    time: u64,
+}
+
+/// Traced Operation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Op {
+    /// Accept of a connection.
+    Accept,
+    /// Connection to a remote address.
+    Connect,
+    /// Push, to a connected socket or to a remote address.
+    Push,
+    /// Pop.
+    Pop,
+    /// Wait for any operation to complete.
+    Wait,
+}
+
+/// Phase of a Traced Operation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    /// The operation was issued.
+    Issue,
+    /// The operation completed.
+    Complete,
+    /// The operation failed.
+    Fail,
+}
+
+/// Span of an Operation, from its issue to its completion
+#[derive(Debug)]
+struct Span {
+    /// Issue record.
+    issue: Record,
+    /// Completion record, unless the operation was still pending.
+    end: Option<Record>,
+}
+
+/// Begin or End of a Slice in a Perfetto Trace
+#[derive(Debug)]
+struct SliceEvent {
+    /// Time of the event, in nanoseconds since tracing started.
+    time: u64,
+    /// Whether the event ends the slice.
+    end: bool,
+    /// Track of the slice.
+    track: u64,
+    /// Name of the slice, on begins.
+    name: &'static str,
+    /// Annotations of the slice, on begins.
+    args: Vec<(&'static str, u64)>,
+}
+
+/// Protocol Buffer Message
+#[derive(Default)]
+struct Proto(Vec<u8>);
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Tracer of the process, once tracing has started.
+static TRACER: AtomicPtr<Tracer> = AtomicPtr::new(ptr::null_mut());
+
+/// Identifier of the next thread that records an operation.
+static NEXT_TID: AtomicU16 = AtomicU16::new(1);
+
+thread_local! {
+    /// Identifier of the calling thread in traces, or zero if it has not recorded anything yet.
+    static TID: Cell<u16> = const { Cell::new(0) };
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Tracing
+impl Tracing {
+    /// Default number of records that are kept.
+    const DEFAULT_SIZE: usize = 1 << 20;
+
+    /// Returns command line arguments that configure tracing.
+    pub fn args<'help>() -> [Arg<'help>; 3] {
+        [
+            Arg::new("trace")
+                .long("trace")
+                .takes_value(true)
+                .required(false)
+                .value_name("FILE")
+                .help("Traces operations and writes the trace to a file on exit"),
+            Arg::new("trace_format")
+                .long("trace_format")
+                .takes_value(true)
+                .required(false)
+                .value_name("chrome|perfetto")
+                .help("Sets the format of the trace"),
+            Arg::new("trace_size")
+                .long("trace_size")
+                .takes_value(true)
+                .required(false)
+                .value_name("RECORDS")
+                .help("Sets how many of the last records are kept"),
+        ]
+    }
+
+    /// Builds a tracing configuration from command line arguments.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        let mut tracing: Tracing = Tracing {
+            path: matches.value_of("trace").map(|path| path.to_string()),
+            format: TraceFormat::Chrome,
+            size: Self::DEFAULT_SIZE,
+        };
+        if let Some(format) = matches.value_of("trace_format") {
+            tracing.format = format.parse()?;
+        }
+        if let Some(size) = matches.value_of("trace_size") {
+            tracing.size = match size.parse() {
+                Ok(size) if size > 0 => size,
+                _ => bail!("invalid trace size"),
+            };
+        }
+        Ok(tracing)
+    }
+
+    /// Starts tracing, if enabled. This should be called before any thread is spawned, so that
+    /// threads leave `SIGINT` and `SIGTERM` to the one that writes the trace.
+    pub fn start(&self) -> Result<()> {
+        let path: &str = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let tracer: Box<Tracer> = Box::new(Tracer {
+            clock: Clock::new(),
+            start: Instant::now(),
+            ring: Ring::new(self.size),
+            threads: Mutex::new(Vec::new()),
+            path: path.to_string(),
+            format: self.format,
+        });
+        if TRACER
+            .compare_exchange(
+                ptr::null_mut(),
+                Box::into_raw(tracer),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            bail!("tracing has already started")
+        }
+
+        // Write the trace on exit, and turn termination signals into exits.
+        if unsafe { libc::atexit(on_exit) } != 0 {
+            bail!("failed to register exit handler")
+        }
+        let mut signals: libc::sigset_t = unsafe { mem::zeroed() };
+        unsafe {
+            libc::sigemptyset(&mut signals);
+            libc::sigaddset(&mut signals, libc::SIGINT);
+            libc::sigaddset(&mut signals, libc::SIGTERM);
+        }
+        if unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &signals, ptr::null_mut()) } != 0 {
+            bail!("failed to block termination signals")
+        }
+        thread::Builder::new()
+            .name("tracer".to_string())
+            .spawn(move || {
+                let mut signal: libc::c_int = 0;
+                unsafe { libc::sigwait(&signals, &mut signal) };
+                process::exit(128 + signal);
+            })?;
+
+        info!("Trace File: {} ({})", path, self.format);
+        Ok(())
+    }
+}
+
+/// Associate Functions for Traced Backends
+impl<B: Backend> Traced<B> {
+    /// Records the issue of an operation that returned `qt`.
+    fn issue(&self, qd: QDesc, op: Op, size: usize, qt: &Result<QToken, Fail>) {
+        if let (Some(tracer), Ok(qt)) = (tracer(), qt) {
+            tracer.record(
+                u64::from(*qt),
+                qd,
+                op,
+                Phase::Issue,
+                size as u64,
+                tracer.now(),
+            );
+        }
+    }
+
+    /// Records a wait that started at `since`, along with the completion of the operation that
+    /// it returned.
+    fn complete(&self, since: u64, qt: QToken, qd: QDesc, result: &OperationResult) {
+        let tracer: &Tracer = match tracer() {
+            Some(tracer) => tracer,
+            None => return,
+        };
+        let now: u64 = tracer.now();
+        tracer.record(
+            0,
+            qd,
+            Op::Wait,
+            Phase::Complete,
+            now.saturating_sub(since),
+            now,
+        );
+        let (op, phase, size): (Op, Phase, usize) = match result {
+            OperationResult::Connect => (Op::Connect, Phase::Complete, 0),
+            OperationResult::Accept(..) => (Op::Accept, Phase::Complete, 0),
+            OperationResult::Push => (Op::Push, Phase::Complete, 0),
+            OperationResult::Pop(_, buf) => (Op::Pop, Phase::Complete, buf.len()),
+            OperationResult::Failed(_) => (Op::Wait, Phase::Fail, 0),
+        };
+        tracer.record(u64::from(qt), qd, op, phase, size as u64, now);
+    }
+}
+
+/// Associate Functions for Tracers
+impl Tracer {
+    /// Returns the time elapsed since tracing started, in nanoseconds.
+    fn now(&self) -> u64 {
+        self.clock
+            .now()
+            .saturating_duration_since(self.start)
+            .as_nanos() as u64
+    }
+
+    /// Records a phase of an operation on the calling thread.
+    fn record(&self, token: u64, qd: QDesc, op: Op, phase: Phase, value: u64, time: u64) {
+        self.ring.push(&Record {
+            token,
+            qd: u32::from(qd),
+            op,
+            phase,
+            tid: self.tid(),
+            value,
+            time,
+        });
+    }
+
+    /// Returns the identifier of the calling thread, assigning one on first use.
+    fn tid(&self) -> u16 {
+        TID.with(|tid| {
+            if tid.get() == 0 {
+                tid.set(NEXT_TID.fetch_add(1, Ordering::Relaxed));
+                let name: String = match thread::current().name() {
+                    Some(name) => name.to_string(),
+                    None => format!("thread-{}", tid.get()),
+                };
+                if let Ok(mut threads) = self.threads.lock() {
+                    threads.push((tid.get(), name));
+                }
+            }
+            tid.get()
+        })
+    }
+
+    /// Writes the trace.
+    fn dump(&self) -> Result<()> {
+        let records: Vec<Record> = self.ring.records();
+        let (spans, waits): (Vec<Span>, Vec<Record>) = spans(&records);
+        let threads: Vec<(u16, String)> = match self.threads.lock() {
+            Ok(threads) => threads.clone(),
+            Err(_) => Vec::new(),
+        };
+        let contents: Vec<u8> = match self.format {
+            TraceFormat::Chrome => chrome(&spans, &waits, &threads).into_bytes(),
+            TraceFormat::Perfetto => perfetto(&spans, &waits, &threads),
+        };
+        fs::write(&self.path, contents)
+            .with_context(|| format!("failed to write trace to {:?}", self.path))?;
+        info!(
+            "trace of {} operations written to {}",
+            spans.len(),
+            self.path
+        );
+        Ok(())
+    }
+}
+
+/// Associate Functions for Rings
+impl Ring {
+    /// Creates a ring that keeps the last `size` records.
+    fn new(size: usize) -> Self {
+        Self {
+            slots: (0..size).map(|_| Slot::default()).collect(),
+            head: AtomicUsize::new(0),
+        }
+    }
+
+    /// Writes a record, overwriting the oldest one if the ring is full.
+    fn push(&self, record: &Record) {
+        let i: usize = self.head.fetch_add(1, Ordering::Relaxed);
+        let slot: &Slot = &self.slots[i % self.slots.len()];
+        slot.seq.store(0, Ordering::Relaxed);
+        fence(Ordering::Release);
+        slot.token.store(record.token, Ordering::Relaxed);
+        slot.meta.store(record.pack(), Ordering::Relaxed);
+        slot.value.store(record.value, Ordering::Relaxed);
+        slot.time.store(record.time, Ordering::Relaxed);
+        slot.seq.store(i as u64 + 1, Ordering::Release);
+    }
+
+    /// Reads the records in the ring, in time order. Slots that are being written are skipped.
+    fn records(&self) -> Vec<Record> {
+        let head: usize = self.head.load(Ordering::Acquire);
+        let mut records: Vec<Record> = Vec::with_capacity(head.min(self.slots.len()));
+        for i in head.saturating_sub(self.slots.len())..head {
+            let slot: &Slot = &self.slots[i % self.slots.len()];
+            let seq: u64 = slot.seq.load(Ordering::Acquire);
+            let (token, meta, value, time): (u64, u64, u64, u64) = (
+                slot.token.load(Ordering::Relaxed),
+                slot.meta.load(Ordering::Relaxed),
+                slot.value.load(Ordering::Relaxed),
+                slot.time.load(Ordering::Relaxed),
+            );
+            fence(Ordering::Acquire);
+            if seq != i as u64 + 1 || slot.seq.load(Ordering::Relaxed) != seq {
+                continue;
+            }
+            records.push(Record::unpack(token, meta, value, time));
+        }
+        records.sort_by_key(|r| r.time);
+        records
+    }
+}
+
+/// Associate Functions for Records
+impl Record {
+    /// Packs the socket, operation, phase and thread of the target record.
+    fn pack(&self) -> u64 {
+        self.qd as u64
+            | (self.op as u64) << 32
+            | (self.phase as u64) << 40
+            | (self.tid as u64) << 48
+    }
+
+    /// Unpacks a record.
+    fn unpack(token: u64, meta: u64, value: u64, time: u64) -> Self {
+        Self {
+            token,
+            qd: meta as u32,
+            op: [Op::Accept, Op::Connect, Op::Push, Op::Pop, Op::Wait]
+                [(meta >> 32 & 0xff) as usize],
+            phase: [Phase::Issue, Phase::Complete, Phase::Fail][(meta >> 40 & 0xff) as usize],
+            tid: (meta >> 48) as u16,
+            value,
+            time,
+        }
+    }
+}
+
+/// Associate Functions for Operations
+impl Op {
+    /// Returns the name of the target operation.
+    fn name(&self) -> &'static str {
+        match self {
+            Op::Accept => "accept",
+            Op::Connect => "connect",
+            Op::Push => "push",
+            Op::Pop => "pop",
+            Op::Wait => "wait",
+        }
+    }
+}
+
+/// Associate Functions for Slice Events
+impl SliceEvent {
+    /// Creates the beginning of a slice.
+    fn begin(time: u64, track: u64, name: &'static str, args: Vec<(&'static str, u64)>) -> Self {
+        Self {
+            time,
+            end: false,
+            track,
+            name,
+            args,
+        }
+    }
+
+    /// Creates the end of a slice.
+    fn end(time: u64, track: u64) -> Self {
+        Self {
+            time,
+            end: true,
+            track,
+            name: "",
+            args: Vec::new(),
+        }
+    }
+}
+
+/// Associate Functions for Protocol Buffer Messages
+impl Proto {
+    /// Appends a varint.
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    /// Appends an integer field.
+    fn uint(&mut self, field: u32, value: u64) -> &mut Self {
+        self.varint((field as u64) << 3);
+        self.varint(value);
+        self
+    }
+
+    /// Appends a length-delimited field.
+    fn bytes(&mut self, field: u32, bytes: &[u8]) -> &mut Self {
+        self.varint((field as u64) << 3 | 2);
+        self.varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    /// Appends a string field.
+    fn string(&mut self, field: u32, s: &str) -> &mut Self {
+        self.bytes(field, s.as_bytes())
+    }
+
+    /// Appends a message field.
+    fn message(&mut self, field: u32, message: &Proto) -> &mut Self {
+        self.bytes(field, &message.0)
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// From String Trait Implementation for Trace Formats
+impl FromStr for TraceFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chrome" => Ok(TraceFormat::Chrome),
+            "perfetto" => Ok(TraceFormat::Perfetto),
+            _ => bail!("invalid trace format {:?} (expected chrome or perfetto)", s),
+        }
+    }
+}
+
+/// Display Trait Implementation for Trace Formats
+impl std::fmt::Display for TraceFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceFormat::Chrome => write!(f, "chrome"),
+            TraceFormat::Perfetto => write!(f, "perfetto"),
+        }
+    }
+}
+
+/// Backend Trait Implementation for Traced Backends
+impl<B: Backend> Backend for Traced<B> {
+    fn create() -> Result<Self> {
+        Ok(Self(B::create()?))
+    }
+
+    fn shares_ports() -> bool {
+        B::shares_ports()
+    }
+
+    fn has_timed_waits() -> bool {
+        B::has_timed_waits()
+    }
+
+    fn reports_peers() -> bool {
+        B::reports_peers()
+    }
+
+    fn socket(
+        &mut self,
+        domain: libc::c_int,
+        socket_type: libc::c_int,
+        protocol: libc::c_int,
+    ) -> Result<QDesc, Fail> {
+        self.0.socket(domain, socket_type, protocol)
+    }
+
+    fn bind(&mut self, qd: QDesc, local: SocketAddr) -> Result<(), Fail> {
+        self.0.bind(qd, local)
+    }
+
+    fn bind_shared(&mut self, qd: QDesc, local: SocketAddr) -> Result<(), Fail> {
+        self.0.bind_shared(qd, local)
+    }
+
+    fn listen(&mut self, qd: QDesc, backlog: usize) -> Result<(), Fail> {
+        self.0.listen(qd, backlog)
+    }
+
+    fn accept(&mut self, qd: QDesc) -> Result<QToken, Fail> {
+        let qt: Result<QToken, Fail> = self.0.accept(qd);
+        self.issue(qd, Op::Accept, 0, &qt);
+        qt
+    }
+
+    fn connect(&mut self, qd: QDesc, remote: SocketAddr) -> Result<QToken, Fail> {
+        let qt: Result<QToken, Fail> = self.0.connect(qd, remote);
+        self.issue(qd, Op::Connect, 0, &qt);
+        qt
+    }
+
+    fn push(&mut self, qd: QDesc, data: &[u8]) -> Result<QToken, Fail> {
+        let qt: Result<QToken, Fail> = self.0.push(qd, data);
+        self.issue(qd, Op::Push, data.len(), &qt);
+        qt
+    }
+
+    fn pushto(&mut self, qd: QDesc, data: &[u8], to: SocketAddr) -> Result<QToken, Fail> {
+        let qt: Result<QToken, Fail> = self.0.pushto(qd, data, to);
+        self.issue(qd, Op::Push, data.len(), &qt);
+        qt
+    }
+
+    fn pop(&mut self, qd: QDesc) -> Result<QToken, Fail> {
+        let qt: Result<QToken, Fail> = self.0.pop(qd);
+        self.issue(qd, Op::Pop, 0, &qt);
+        qt
+    }
+
+    fn wait(&mut self, qt: QToken) -> Result<(QDesc, OperationResult), Fail> {
+        let since: u64 = tracer().map(|t| t.now()).unwrap_or_default();
+        let (qd, result) = self.0.wait(qt)?;
+        self.complete(since, qt, qd, &result);
+        Ok((qd, result))
+    }
+
+    fn wait_any(&mut self, qts: &[QToken]) -> Result<(usize, QDesc, OperationResult), Fail> {
+        let since: u64 = tracer().map(|t| t.now()).unwrap_or_default();
+        let (i, qd, result) = self.0.wait_any(qts)?;
+        self.complete(since, qts[i], qd, &result);
+        Ok((i, qd, result))
+    }
+
+    fn wait_any_timeout(
+        &mut self,
+        qts: &[QToken],
+        timeout: Option<Duration>,
+    ) -> Result<(usize, QDesc, OperationResult), Fail> {
+        let since: u64 = tracer().map(|t| t.now()).unwrap_or_default();
+        let (i, qd, result) = self.0.wait_any_timeout(qts, timeout)?;
+        self.complete(since, qts[i], qd, &result);
+        Ok((i, qd, result))
+    }
+
+    fn shutdown(&mut self, qd: QDesc) -> Result<(), Fail> {
+        self.0.shutdown(qd)
+    }
+
+    fn close(&mut self, qd: QDesc) -> Result<(), Fail> {
+        self.0.close(qd)
+    }
+
+    fn as_libos(&mut self) -> Option<&mut LibOS> {
+        self.0.as_libos()
+    }
+
+    fn clock(&self) -> Clock {
+        self.0.clock()
+    }
+
+    fn status(&self) -> Result<()> {
+        self.0.status()
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns the tracer of the process, if tracing has started.
+fn tracer() -> Option<&'static Tracer> {
+    unsafe { TRACER.load(Ordering::Acquire).as_ref() }
+}
+
+/// Writes the trace when the process exits.
+extern "C" fn on_exit() {
+    if let Some(tracer) = tracer() {
+        if let Err(e) = tracer.dump() {
+            error!("{:?}", e);
+        }
+    }
+}
+
+/// Pairs the issue and the completion of operations, by thread and token. Also returns waits.
+fn spans(records: &[Record]) -> (Vec<Span>, Vec<Record>) {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut waits: Vec<Record> = Vec::new();
+    let mut pending: HashMap<(u16, u64), usize> = HashMap::new();
+    for record in records {
+        match (record.op, record.phase) {
+            (Op::Wait, Phase::Complete) => waits.push(*record),
+            (_, Phase::Issue) => {
+                pending.insert((record.tid, record.token), spans.len());
+                spans.push(Span {
+                    issue: *record,
+                    end: None,
+                });
+            }
+            // Completions of operations issued before the oldest record are dropped.
+            (_, Phase::Complete) | (_, Phase::Fail) => {
+                if let Some(i) = pending.remove(&(record.tid, record.token)) {
+                    spans[i].end = Some(*record);
+                }
+            }
+        }
+    }
+    (spans, waits)
+}
+
+/// Formats a trace in the Chrome trace-event format. Operations are asynchronous events, which
+/// may overlap, and waits are complete events on the thread that waited.
+fn chrome(spans: &[Span], waits: &[Record], threads: &[(u16, String)]) -> String {
+    let pid: u32 = process::id();
+    let mut events: Vec<String> = Vec::with_capacity(2 * spans.len() + waits.len());
+    for (tid, name) in threads {
+        events.push(format!(
+            "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":{},\"tid\":{},\
+             \"args\":{{\"name\":{}}}}}",
+            pid,
+            tid,
+            json_string(name)
+        ));
+    }
+    for span in spans {
+        let issue: &Record = &span.issue;
+        let id: String = format!("{}.{}", issue.tid, issue.token);
+        events.push(format!(
+            "{{\"name\":\"{}\",\"cat\":\"qd {}\",\"ph\":\"b\",\"id\":\"{}\",\"ts\":{:.3},\
+             \"pid\":{},\"tid\":{},\"args\":{{\"qd\":{},\"qt\":{},\"size\":{}}}}}",
+            issue.op.name(),
+            issue.qd,
+            id,
+            issue.time as f64 / 1000.0,
+            pid,
+            issue.tid,
+            issue.qd,
+            issue.token,
+            issue.value
+        ));
+        if let Some(end) = &span.end {
+            events.push(format!(
+                "{{\"name\":\"{}\",\"cat\":\"qd {}\",\"ph\":\"e\",\"id\":\"{}\",\"ts\":{:.3},\
+                 \"pid\":{},\"tid\":{},\"args\":{{\"size\":{},\"failed\":{}}}}}",
+                issue.op.name(),
+                issue.qd,
+                id,
+                end.time as f64 / 1000.0,
+                pid,
+                end.tid,
+                end.value,
+                end.phase == Phase::Fail
+            ));
+        }
+    }
+    for wait in waits {
+        events.push(format!(
+            "{{\"name\":\"wait\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":{},\"tid\":{},\
+             \"args\":{{\"qd\":{}}}}}",
+            (wait.time - wait.value) as f64 / 1000.0,
+            wait.value as f64 / 1000.0,
+            pid,
+            wait.tid,
+            wait.qd
+        ));
+    }
+
+    let mut trace: String = String::from("{\"displayTimeUnit\":\"ns\",\"traceEvents\":[\n");
+    trace.push_str(&events.join(",\n"));
+    trace.push_str("\n]}\n");
+    trace
+}
+
+/// Formats a trace in the Perfetto format. Every thread has a track of waits, with a child
+/// track per socket and operation. Overlapping operations go to tracks of their own, as slices
+/// of a track must nest.
+fn perfetto(spans: &[Span], waits: &[Record], threads: &[(u16, String)]) -> Vec<u8> {
+    // Field numbers of the Perfetto protocol.
+    const TRACE_PACKET: u32 = 1;
+    const PACKET_TIMESTAMP: u32 = 8;
+    const PACKET_SEQUENCE_ID: u32 = 10;
+    const PACKET_TRACK_EVENT: u32 = 11;
+    const PACKET_SEQUENCE_FLAGS: u32 = 13;
+    const PACKET_TRACK_DESCRIPTOR: u32 = 60;
+    const TRACK_UUID: u32 = 1;
+    const TRACK_NAME: u32 = 2;
+    const TRACK_PARENT_UUID: u32 = 5;
+    const EVENT_ANNOTATIONS: u32 = 4;
+    const EVENT_TYPE: u32 = 9;
+    const EVENT_TRACK_UUID: u32 = 11;
+    const EVENT_NAME: u32 = 23;
+    const ANNOTATION_UINT: u32 = 3;
+    const ANNOTATION_NAME: u32 = 10;
+    const SLICE_BEGIN: u64 = 1;
+    const SLICE_END: u64 = 2;
+    const SEQUENCE: u64 = 1;
+    const INCREMENTAL_STATE_CLEARED: u64 = 1;
+
+    let mut packets: Vec<Proto> = Vec::new();
+    let mut track = |uuid: u64, parent: Option<u64>, name: &str| {
+        let mut descriptor: Proto = Proto::default();
+        descriptor.uint(TRACK_UUID, uuid).string(TRACK_NAME, name);
+        if let Some(parent) = parent {
+            descriptor.uint(TRACK_PARENT_UUID, parent);
+        }
+        let mut packet: Proto = Proto::default();
+        packet.message(PACKET_TRACK_DESCRIPTOR, &descriptor);
+        packets.push(packet);
+    };
+
+    // Tracks of threads, whose identifiers are below those of other tracks.
+    for (tid, name) in threads {
+        track(*tid as u64, None, name);
+    }
+
+    // Tracks of operations, allocated so that slices of a track do not overlap.
+    let mut next_uuid: u64 = u16::MAX as u64 + 1;
+    let mut lanes: HashMap<(u16, u32, Op), Vec<(u64, u64)>> = HashMap::new();
+    let mut events: Vec<SliceEvent> = Vec::new();
+    for span in spans {
+        let issue: &Record = &span.issue;
+        let end: u64 = span.end.map(|end| end.time).unwrap_or(u64::MAX);
+        let lanes: &mut Vec<(u64, u64)> = lanes.entry((issue.tid, issue.qd, issue.op)).or_default();
+        let uuid: u64 = match lanes.iter_mut().find(|(_, busy)| *busy <= issue.time) {
+            Some((uuid, busy)) => {
+                *busy = end;
+                *uuid
+            }
+            None => {
+                let uuid: u64 = next_uuid;
+                next_uuid += 1;
+                let name: String = format!("qd {} {}", issue.qd, issue.op.name());
+                track(uuid, Some(issue.tid as u64), &name);
+                lanes.push((uuid, end));
+                uuid
+            }
+        };
+        let mut args: Vec<(&str, u64)> = vec![("qt", issue.token), ("size", issue.value)];
+        if let Some(end) = &span.end {
+            if end.phase == Phase::Fail {
+                args.push(("failed", 1));
+            } else if issue.op == Op::Pop {
+                args[1].1 = end.value;
+            }
+            events.push(SliceEvent::end(end.time, uuid));
+        }
+        events.push(SliceEvent::begin(issue.time, uuid, issue.op.name(), args));
+    }
+    for wait in waits {
+        let uuid: u64 = wait.tid as u64;
+        let args: Vec<(&str, u64)> = vec![("qd", wait.qd as u64)];
+        events.push(SliceEvent::begin(
+            wait.time - wait.value,
+            uuid,
+            "wait",
+            args,
+        ));
+        events.push(SliceEvent::end(wait.time, uuid));
+    }
+
+    // Ends go before begins at the same time, so that back-to-back slices do not nest.
+    events.sort_by_key(|e| (e.time, !e.end));
+    for e in &events {
+        let mut event: Proto = Proto::default();
+        event
+            .uint(EVENT_TYPE, if e.end { SLICE_END } else { SLICE_BEGIN })
+            .uint(EVENT_TRACK_UUID, e.track);
+        if !e.end {
+            event.string(EVENT_NAME, e.name);
+        }
+        for (name, value) in &e.args {
+            let mut annotation: Proto = Proto::default();
+            annotation
+                .string(ANNOTATION_NAME, name)
+                .uint(ANNOTATION_UINT, *value);
+            event.message(EVENT_ANNOTATIONS, &annotation);
+        }
+        let mut packet: Proto = Proto::default();
+        packet
+            .uint(PACKET_TIMESTAMP, e.time)
+            .uint(PACKET_SEQUENCE_ID, SEQUENCE)
+            .message(PACKET_TRACK_EVENT, &event);
+        packets.push(packet);
+    }
+
+    let mut trace: Proto = Proto::default();
+    for (i, packet) in packets.iter_mut().enumerate() {
+        if i == 0 {
+            packet.uint(PACKET_SEQUENCE_ID, SEQUENCE);
+            packet.uint(PACKET_SEQUENCE_FLAGS, INCREMENTAL_STATE_CLEARED);
+        }
+        trace.message(TRACE_PACKET, packet);
+    }
+    trace.0
+}
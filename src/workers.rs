@@ -0,0 +1,202 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::{
+    backend::{Backend, Fail},
+    placement,
+};
+use ::anyhow::{bail, Result};
+use ::demikernel::QDesc;
+use ::log::{info, warn};
+use ::std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Worker Statistics
+///
+/// Counters that a worker publishes for the reporting thread.
+#[derive(Debug, Default)]
+pub struct WorkerStatistics {
+    /// Number of bytes handled.
+    nbytes: AtomicUsize,
+}
+
+/// Worker Thread
+#[derive(Debug)]
+struct Worker {
+    /// Core on which the worker is pinned.
+    core: usize,
+    /// Published statistics.
+    stats: Arc<WorkerStatistics>,
+    /// Handle of the thread.
+    handle: JoinHandle<()>,
+}
+
+/// Workers
+///
+/// Runs one instance of an application per thread, each with a libOS of its own and pinned to a
+/// core, and reports their statistics together.
+#[derive(Debug)]
+pub struct Workers {
+    /// Worker threads.
+    workers: Vec<Worker>,
+    /// Instant at which workers were started.
+    start: Instant,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Worker Statistics
+impl WorkerStatistics {
+    /// Publishes the number of bytes handled by the worker.
+    pub fn set_nbytes(&self, nbytes: usize) {
+        self.nbytes.store(nbytes, Ordering::Relaxed);
+    }
+
+    /// Returns the number of bytes handled by the worker.
+    pub fn get_nbytes(&self) -> usize {
+        self.nbytes.load(Ordering::Relaxed)
+    }
+}
+
+/// Associate Functions for Workers
+impl Workers {
+    /// How often workers are checked for having exited.
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Starts `n` workers running `f`, which is given the index of the worker and the statistics
+    /// that it should publish. Worker `i` is pinned to the `i`-th core that the process may run
+    /// on, wrapping around.
+    pub fn spawn<F>(n: usize, f: F) -> Result<Self>
+    where
+        F: Fn(usize, Arc<WorkerStatistics>) -> Result<()> + Send + Sync + 'static,
+    {
+        let cores: Vec<usize> = placement::get_affinity()?;
+        let f: Arc<F> = Arc::new(f);
+        let mut workers: Vec<Worker> = Vec::with_capacity(n);
+        for i in 0..n {
+            let core: usize = cores[i % cores.len()];
+            let stats: Arc<WorkerStatistics> = Arc::new(WorkerStatistics::default());
+            let handle: JoinHandle<()> = {
+                let f: Arc<F> = f.clone();
+                let stats: Arc<WorkerStatistics> = stats.clone();
+                thread::Builder::new()
+                    .name(format!("worker-{}", i))
+                    .spawn(move || {
+                        if let Err(e) = placement::set_affinity(&[core]) {
+                            warn!("worker {}: {:?}", i, e);
+                        }
+                        if let Err(e) = f(i, stats) {
+                            panic!("worker {} failed: {:?}", i, e);
+                        }
+                    })?
+            };
+            workers.push(Worker {
+                core,
+                stats,
+                handle,
+            });
+        }
+
+        Ok(Self {
+            workers,
+            start: Instant::now(),
+        })
+    }
+
+    /// Dumps statistics of every worker and their sum on every `interval`, until some worker
+    /// exits. Fails right away if that worker panicked. Otherwise, workers are stopping, such as
+    /// on `shutdown` through a control socket, so the others are waited for.
+    pub fn report(mut self, interval: Duration) -> Result<()> {
+        let mut next: Instant = self.start + interval;
+        loop {
+            thread::sleep(Self::POLL_INTERVAL.min(next.saturating_duration_since(Instant::now())));
+
+            if let Some(i) = self.workers.iter().position(|w| w.handle.is_finished()) {
+                let worker: Worker = self.workers.swap_remove(i);
+                if worker.handle.join().is_err() {
+                    bail!("worker {} panicked", i)
+                }
+                for worker in self.workers {
+                    if worker.handle.join().is_err() {
+                        bail!("a worker panicked")
+                    }
+                }
+                return Ok(());
+            }
+            let now: Instant = Instant::now();
+            if now < next {
+                continue;
+            }
+            next = now + interval;
+
+            let elapsed: Duration = now - self.start;
+            let nbytes: usize = self.workers.iter().map(|w| w.stats.get_nbytes()).sum();
+            info!("{:?} B / {:?} us", nbytes, elapsed.as_micros());
+            for (i, worker) in self.workers.iter().enumerate() {
+                info!(
+                    "  worker {} (core {}): {:?} B",
+                    i,
+                    worker.core,
+                    worker.stats.get_nbytes()
+                );
+            }
+        }
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns the address that worker `i` should listen on, over a backend of type `B`. Workers
+/// share the port of `addr` on backends that allow it, such as the POSIX one. The libOS offers no
+/// way to share a port between sockets, so workers listen on consecutive ports there, starting at
+/// the one of `addr`.
+pub fn worker_addr<B: Backend>(addr: SocketAddr, i: usize) -> Result<SocketAddr> {
+    if B::shares_ports() {
+        return Ok(addr);
+    }
+    match u16::try_from(addr.port() as usize + i) {
+        Ok(port) => Ok(SocketAddr::new(addr.ip(), port)),
+        Err(_) => bail!("port out of range for worker {}", i),
+    }
+}
+
+/// Binds a socket to `local`, on which `n` workers listen. Workers share the port on backends
+/// that allow it, whereas a single server binds it exclusively, so that the usual conflicts with
+/// other processes are reported.
+pub fn bind<B: Backend>(libos: &mut B, qd: QDesc, local: SocketAddr, n: usize) -> Result<(), Fail> {
+    if n > 1 && B::shares_ports() {
+        libos.bind_shared(qd, local)
+    } else {
+        libos.bind(qd, local)
+    }
+}
+
+/// Returns the share of worker `i` out of `n` in an inclusive range of ports.
+pub fn worker_range(range: (u16, u16), i: usize, n: usize) -> Result<(u16, u16)> {
+    let (first, last): (usize, usize) = (range.0 as usize, range.1 as usize);
+    let len: usize = (last - first + 1) / n;
+    if len == 0 {
+        bail!("not enough ports for {} workers", n)
+    }
+    let first: usize = first + i * len;
+    Ok((first as u16, (first + len - 1) as u16))
+}
@@ -0,0 +1,126 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Common Test Helpers
+//!
+//! Helpers that integration tests share to run applets of `demi`. Each test crate uses a subset
+//! of them.
+
+#![allow(dead_code)]
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::{
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::Path,
+    process::{Child, Command, Output, Stdio},
+    thread,
+    time::Duration,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Client of a Control Socket
+pub struct Client {
+    /// Where requests are written.
+    writer: UnixStream,
+    /// Where replies are read from.
+    reader: BufReader<UnixStream>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+impl Client {
+    /// Connects to the control socket at `path`, waiting for it to show up.
+    pub fn connect(path: &Path) -> Self {
+        for _ in 0..50 {
+            if let Ok(stream) = UnixStream::connect(path) {
+                return Self {
+                    writer: stream.try_clone().expect("failed to clone stream"),
+                    reader: BufReader::new(stream),
+                };
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        panic!("failed to connect to control socket {:?}", path)
+    }
+
+    /// Sends a command and returns its reply, without the empty line that ends it.
+    pub fn send(&mut self, command: &str) -> String {
+        writeln!(self.writer, "{}", command).expect("failed to send command");
+        let mut reply: String = String::new();
+        loop {
+            let mut line: String = String::new();
+            let n: usize = self
+                .reader
+                .read_line(&mut line)
+                .expect("failed to read reply");
+            if n == 0 || line == "\n" {
+                return reply;
+            }
+            reply.push_str(&line);
+        }
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns a command that runs an applet of `demi`.
+pub fn demi(applet: &str) -> Command {
+    let mut command: Command = Command::new(env!("CARGO_BIN_EXE_demi"));
+    command.arg(applet);
+    command
+}
+
+/// Runs a command to completion, returning whether it succeeded along with its output.
+pub fn run(command: &mut Command) -> (bool, String) {
+    let result: Output = command.output().expect("failed to run demi");
+    (result.status.success(), output(&result))
+}
+
+/// Concatenates the standard output and the standard error of a finished command.
+pub fn output(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned() + &String::from_utf8_lossy(&output.stderr)
+}
+
+/// Spawns a command with its output captured.
+pub fn spawn(command: &mut Command) -> Child {
+    command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn demi")
+}
+
+/// Collects the output of a spawned command that has exited.
+pub fn collect(child: &mut Child) -> String {
+    let mut stdout: String = String::new();
+    let mut stderr: String = String::new();
+    if let Some(pipe) = child.stdout.as_mut() {
+        pipe.read_to_string(&mut stdout)
+            .expect("failed to read stdout");
+    }
+    if let Some(pipe) = child.stderr.as_mut() {
+        pipe.read_to_string(&mut stderr)
+            .expect("failed to read stderr");
+    }
+    stdout + &stderr
+}
+
+/// Returns the value of a `key: value` line of a control socket reply.
+pub fn value(reply: &str, key: &str) -> u64 {
+    reply
+        .lines()
+        .find_map(|line| line.strip_prefix(&format!("{}: ", key)))
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| panic!("no {} in reply:\n{}", key, reply))
+}
@@ -0,0 +1,80 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Configuration Tests
+//!
+//! Every test writes a configuration file, runs a binary on the simulator backend so that it
+//! terminates, and checks the resolved configuration that it logs.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+mod common;
+
+use ::std::{env, fs, path::PathBuf};
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Writes a configuration file in the temporary directory.
+fn config(name: &str, contents: &str) -> PathBuf {
+    let path: PathBuf = env::temp_dir().join(format!("demikernel-config-{}", name));
+    fs::write(&path, contents).expect("failed to write configuration file");
+    path
+}
+
+/// Runs udp-dump with a configuration file, some environment variables and some arguments.
+fn run(path: &PathBuf, envs: &[(&str, &str)], args: &[&str]) -> (bool, String) {
+    common::run(
+        common::demi("udp-dump")
+            .arg("--config")
+            .arg(path)
+            .args(args)
+            .envs(envs.iter().copied())
+            .env("SIM_CLIENTS", "1"),
+    )
+}
+
+//==============================================================================
+// Tests
+//==============================================================================
+
+#[test]
+fn arguments_are_resolved_in_order() {
+    let path: PathBuf = config(
+        "order.yaml",
+        "demikernel:\n\
+         \x20 local_ipv4_addr: 10.0.0.1\n\
+         udp-dump:\n\
+         \x20 local: 10.0.0.1:9\n\
+         \x20 io_backend: posix\n\
+         \x20 mlock: false\n",
+    );
+    let (success, output): (bool, String) = run(
+        &path,
+        &[("UDP_DUMP_LOCAL", "10.0.0.1:10")],
+        &["--io_backend", "sim"],
+    );
+    assert!(success, "{}", output);
+    assert!(output.contains("  local = 10.0.0.1:10 (env)"), "{}", output);
+    assert!(output.contains("  io_backend = sim (cli)"), "{}", output);
+    assert!(!output.contains("  mlock ="), "{}", output);
+}
+
+#[test]
+fn toml_sections_are_loaded() {
+    let path: PathBuf = config("section.toml", "[udp-dump]\nlocal = \"10.0.0.1:9\"\n");
+    let (success, output): (bool, String) = run(&path, &[], &["--io_backend", "sim"]);
+    assert!(success, "{}", output);
+    assert!(output.contains("  local = 10.0.0.1:9 (file)"), "{}", output);
+}
+
+#[test]
+fn unknown_arguments_are_rejected() {
+    let path: PathBuf = config("unknown.toml", "[udp-dump]\nbogus = 1\n");
+    let (success, output): (bool, String) = run(&path, &[], &["--io_backend", "sim"]);
+    assert!(!success);
+    assert!(output.contains("unknown argument \"bogus\""), "{}", output);
+}
@@ -0,0 +1,210 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Control Socket Tests
+//!
+//! Every test runs a binary with a control socket on the POSIX backend, and drives it through
+//! the socket.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+mod common;
+
+use ::std::{
+    env, fs,
+    net::UdpSocket,
+    path::{Path, PathBuf},
+    process::{Child, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+use common::{value, Client};
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns a fresh path for a control socket in the temporary directory.
+fn socket(name: &str) -> PathBuf {
+    let path: PathBuf = env::temp_dir().join(format!("demikernel-control-{}.sock", name));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+/// Spawns an applet with a control socket.
+fn spawn(applet: &str, args: &[&str], path: &Path) -> Child {
+    common::demi(applet)
+        .args(args)
+        .args(["--io_backend", "posix", "--control_socket"])
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn application")
+}
+
+//==============================================================================
+// Tests
+//==============================================================================
+
+#[test]
+fn pktgen_applies_commands() {
+    let path: PathBuf = socket("pktgen");
+    let mut child: Child = spawn(
+        "udp-pktgen",
+        &[
+            "--local",
+            "127.0.0.1:40803",
+            "--remote",
+            "127.0.0.1:40804",
+            "--bufsize",
+            "64",
+            "--injection_rate",
+            "1000000",
+        ],
+        &path,
+    );
+    let mut client: Client = Client::connect(&path);
+
+    thread::sleep(Duration::from_millis(200));
+    let reply: String = client.send("stats");
+    assert!(value(&reply, "bytes") > 0, "{}", reply);
+    assert!(reply.contains("phase: phase 0"), "{}", reply);
+
+    assert_eq!(client.send("set rate 1Mbps"), "ok\n");
+    assert_eq!(client.send("set log-level debug"), "ok\n");
+    assert!(client.send("set log-level nonsense").starts_with("error: "));
+    assert!(client.send("connections").starts_with("error: "));
+    assert!(client
+        .send("frobnicate")
+        .starts_with("error: unknown command"));
+
+    // At 1 Mbps, 64 B packets are 512 us apart.
+    assert_eq!(client.send("reset-counters"), "ok\n");
+    thread::sleep(Duration::from_millis(100));
+    let reply: String = client.send("stats");
+    let nbytes: u64 = value(&reply, "bytes");
+    assert!(nbytes > 64 && nbytes < 64 * 1000, "{}", reply);
+
+    assert_eq!(client.send("shutdown"), "ok\n");
+    let status: ExitStatus = child.wait().expect("failed to wait for udp-pktgen");
+    assert!(status.success());
+    assert!(!path.exists());
+}
+
+#[test]
+fn echo_server_reports_traffic() {
+    let path: PathBuf = socket("echo");
+    let mut child: Child = spawn(
+        "udp-echo",
+        &["--local", "127.0.0.1:40805", "--remote", "127.0.0.1:40806"],
+        &path,
+    );
+    let mut client: Client = Client::connect(&path);
+
+    // Commands are still applied once traffic stopped.
+    let sender: UdpSocket = UdpSocket::bind("127.0.0.1:0").expect("failed to bind socket");
+    for _ in 0..10 {
+        sender
+            .send_to(&[0; 32], "127.0.0.1:40805")
+            .expect("failed to send datagram");
+    }
+    thread::sleep(Duration::from_millis(200));
+
+    let reply: String = client.send("stats");
+    assert_eq!(value(&reply, "bytes"), 320, "{}", reply);
+    assert!(client.send("set rate 1Gbps").contains("not supported"));
+    assert_eq!(client.send("reset-counters"), "ok\n");
+
+    assert_eq!(client.send("shutdown"), "ok\n");
+    let status: ExitStatus = child.wait().expect("failed to wait for udp-echo");
+    assert!(status.success());
+}
+
+#[test]
+fn idle_server_shuts_down() {
+    let path: PathBuf = socket("idle");
+    let mut child: Child = spawn(
+        "tcp-echo",
+        &[
+            "--peer",
+            "server",
+            "--local",
+            "127.0.0.1:40807",
+            "--bufsize",
+            "64",
+        ],
+        &path,
+    );
+    let mut client: Client = Client::connect(&path);
+
+    let reply: String = client.send("stats");
+    assert_eq!(value(&reply, "bytes"), 0, "{}", reply);
+    assert_eq!(client.send("connections"), "");
+
+    let start: Instant = Instant::now();
+    assert_eq!(client.send("shutdown"), "ok\n");
+    let status: ExitStatus = child.wait().expect("failed to wait for tcp-echo");
+    assert!(status.success());
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(!path.exists());
+}
+
+#[test]
+fn workers_reply_together() {
+    let path: PathBuf = socket("workers");
+    let mut child: Child = spawn(
+        "udp-echo",
+        &[
+            "--local",
+            "127.0.0.1:40808",
+            "--remote",
+            "127.0.0.1:40809",
+            "--workers",
+            "2",
+        ],
+        &path,
+    );
+    let mut client: Client = Client::connect(&path);
+
+    // Replies that differ are listed per worker, others are given once.
+    let reply: String = client.send("stats");
+    assert!(reply.starts_with("worker 0:\n  bytes: 0\n"), "{}", reply);
+    assert!(reply.contains("worker 1:\n  bytes: 0\n"), "{}", reply);
+    assert_eq!(client.send("reset-counters"), "ok\n");
+    assert!(client.send("set rate 1Gbps").contains("not supported"));
+
+    assert_eq!(client.send("shutdown"), "ok\n");
+    let status: ExitStatus = child.wait().expect("failed to wait for udp-echo");
+    assert!(status.success());
+}
+
+#[test]
+fn fanout_relay_applies_commands() {
+    let path: PathBuf = socket("fanout");
+    let mut child: Child = spawn(
+        "udp-relay",
+        &[
+            "--local",
+            "127.0.0.1:40810",
+            "--fanout",
+            "--backend",
+            "127.0.0.1:40811",
+        ],
+        &path,
+    );
+    let mut client: Client = Client::connect(&path);
+
+    let reply: String = client.send("stats");
+    assert_eq!(value(&reply, "destinations"), 1, "{}", reply);
+    assert!(client
+        .send("connections")
+        .starts_with("127.0.0.1:40811: 0 sent"));
+    assert_eq!(client.send("reset-counters"), "ok\n");
+
+    assert_eq!(client.send("shutdown"), "ok\n");
+    let status: ExitStatus = child.wait().expect("failed to wait for udp-relay");
+    assert!(status.success());
+}
@@ -0,0 +1,124 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Dashboard Tests
+//!
+//! Runs applets with a live dashboard, sends them some traffic, and checks what they draw.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+mod common;
+
+use ::std::{
+    io::{Read, Write},
+    net::{TcpStream, UdpSocket},
+    process::{Child, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+//==============================================================================
+// Tests
+//==============================================================================
+
+#[test]
+fn udp_dump_draws_a_dashboard() {
+    let mut child: Child = common::demi("udp-dump")
+        .args([
+            "--local",
+            "127.0.0.1:40802",
+            "--io_backend",
+            "posix",
+            "--tui",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn udp-dump");
+    thread::sleep(Duration::from_millis(500));
+
+    // Send datagrams for a little longer than a statistics interval.
+    let socket: UdpSocket = UdpSocket::bind("127.0.0.1:0").expect("failed to bind socket");
+    let deadline: Instant = Instant::now() + Duration::from_millis(5500);
+    while Instant::now() < deadline {
+        socket
+            .send_to(&[0; 64], "127.0.0.1:40802")
+            .expect("failed to send datagram");
+        thread::sleep(Duration::from_millis(1));
+    }
+    thread::sleep(Duration::from_millis(200));
+    child.kill().expect("failed to kill udp-dump");
+    child.wait().expect("failed to wait for udp-dump");
+
+    let mut stdout: String = String::new();
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut stdout)
+        .expect("failed to read dashboard");
+    let source: String = socket.local_addr().unwrap().to_string();
+    for text in [
+        "udp-dump - ",
+        "packets/s",
+        "Latency (pop):  p50 ",
+        "Top sources:",
+        &source,
+    ] {
+        assert!(stdout.contains(text), "{} is missing:\n{}", text, stdout);
+    }
+}
+
+#[test]
+fn tcp_echo_dashboard_refreshes_while_idle() {
+    let mut child: Child = common::demi("tcp-echo")
+        .args([
+            "--peer",
+            "server",
+            "--local",
+            "127.0.0.1:40812",
+            "--bufsize",
+            "64",
+            "--io_backend",
+            "posix",
+            "--tui",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn tcp-echo");
+    thread::sleep(Duration::from_millis(500));
+
+    // Send a single buffer, and then keep the connection open without traffic for longer than
+    // two statistics intervals.
+    let mut stream: TcpStream =
+        TcpStream::connect("127.0.0.1:40812").expect("failed to connect to tcp-echo");
+    stream.write_all(&[0; 64]).expect("failed to send data");
+    let mut buf: [u8; 64] = [0; 64];
+    stream.read_exact(&mut buf).expect("failed to receive echo");
+    thread::sleep(Duration::from_millis(10500));
+    child.kill().expect("failed to kill tcp-echo");
+    child.wait().expect("failed to wait for tcp-echo");
+
+    let mut stdout: String = String::new();
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut stdout)
+        .expect("failed to read dashboard");
+    let frames: Vec<&str> = stdout.split("\x1b[H\x1b[2J").skip(1).collect();
+    assert!(frames.len() >= 2, "idle frames are missing:\n{}", stdout);
+    let source: String = stream.local_addr().unwrap().to_string();
+    for text in ["tcp-echo - ", "Connections: 1", &source] {
+        assert!(
+            frames[0].contains(text),
+            "{} is missing:\n{}",
+            text,
+            frames[0]
+        );
+    }
+    assert!(frames[1].contains("Connections: 1"), "{}", frames[1]);
+}
@@ -0,0 +1,74 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Multi-Call Tests
+//!
+//! Every test runs `demi` either with an applet name as its first argument, or through a symlink
+//! named after an applet. Applets run on the simulator backend, so that they terminate.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+mod common;
+
+use ::std::{fs, io, os::unix, path::PathBuf, process::Command};
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns a symlink to `demi` named after an applet.
+fn symlink(applet: &str) -> PathBuf {
+    let dir: PathBuf = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("applets");
+    fs::create_dir_all(&dir).expect("failed to create applets directory");
+    let path: PathBuf = dir.join(applet);
+    match unix::fs::symlink(env!("CARGO_BIN_EXE_demi"), &path) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => {
+            panic!("failed to create symlink {:?}: {:?}", path, e)
+        }
+        _ => path,
+    }
+}
+
+//==============================================================================
+// Tests
+//==============================================================================
+
+#[test]
+fn applets_run_as_subcommands() {
+    let (success, output): (bool, String) = common::run(
+        common::demi("tcp-echo")
+            .args(["server", "--local", "10.0.0.1:7", "--bufsize", "64"])
+            .args(["--io_backend", "sim"]),
+    );
+    assert!(success, "{}", output);
+    assert!(output.contains("  peer_type = server (cli)"), "{}", output);
+    assert!(output.contains("simulation: seed 0 passed"), "{}", output);
+}
+
+#[test]
+fn applets_run_through_symlinks() {
+    let (success, output): (bool, String) = common::run(
+        Command::new(symlink("udp-dump"))
+            .args(["--local", "10.0.0.1:9", "--io_backend", "sim"])
+            .env("SIM_CLIENTS", "1"),
+    );
+    assert!(success, "{}", output);
+    assert!(output.contains("simulation: seed 0 passed"), "{}", output);
+}
+
+#[test]
+fn applets_are_listed() {
+    let (success, output): (bool, String) = common::run(&mut common::demi("--help"));
+    assert!(success, "{}", output);
+    for applet in ["tcp-echo", "tcp-lb", "udp-pktgen", "udp-relay"] {
+        assert!(output.contains(applet), "{}", output);
+    }
+}
+
+#[test]
+fn unknown_applets_are_rejected() {
+    let (success, output): (bool, String) = common::run(&mut common::demi("tcp-bogus"));
+    assert!(!success, "{}", output);
+}
@@ -0,0 +1,70 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Endpoint Tests
+//!
+//! Every test runs udp-dump on the simulator backend, so that it terminates, with an endpoint
+//! that needs resolving, and checks the local address that it prints.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+mod common;
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Runs udp-dump on a local endpoint, returning whether it succeeded along with its output.
+fn run(endpoint: &str) -> (bool, String) {
+    common::run(
+        common::demi("udp-dump")
+            .args(["--local", endpoint, "--io_backend", "sim"])
+            .env("SIM_CLIENTS", "1"),
+    )
+}
+
+/// Checks that an endpoint resolves to `addr`.
+fn check(endpoint: &str, addr: &str) {
+    let (success, output): (bool, String) = run(endpoint);
+    assert!(success, "{}", output);
+    assert!(
+        output.contains(&format!("Local Address: {}\n", addr)),
+        "{}",
+        output
+    );
+}
+
+//==============================================================================
+// Tests
+//==============================================================================
+
+#[test]
+fn hostnames_are_resolved() {
+    check("localhost:9", "127.0.0.1:9");
+}
+
+#[test]
+fn interfaces_are_resolved() {
+    check("lo:9", "127.0.0.1:9");
+}
+
+#[test]
+fn missing_hosts_mean_any_address() {
+    check(":9", "0.0.0.0:9");
+}
+
+#[test]
+fn literals_are_kept() {
+    check("10.0.0.1:9", "10.0.0.1:9");
+    check("[2001:db8::1]:9", "[2001:db8::1]:9");
+}
+
+#[test]
+fn invalid_endpoints_are_rejected() {
+    for endpoint in ["no-such-host.invalid:9", "lo:port", "localhost"] {
+        let (success, output): (bool, String) = run(endpoint);
+        assert!(!success, "{} was accepted:\n{}", endpoint, output);
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Logging Tests
+//!
+//! Every test runs a binary on the simulator backend, so that it terminates, with some logging
+//! arguments, and checks the messages that it logs.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+mod common;
+
+use ::std::{env, fs, path::PathBuf};
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Runs tcp-echo as a server with some arguments, and returns its output.
+fn run(args: &[&str]) -> String {
+    let (success, output): (bool, String) = common::run(
+        common::demi("tcp-echo")
+            .args(["server", "--local", "10.0.0.1:7", "--bufsize", "64"])
+            .args(["--io_backend", "sim"])
+            .args(args)
+            .env_remove("RUST_LOG"),
+    );
+    assert!(success, "{}", output);
+    output
+}
+
+//==============================================================================
+// Tests
+//==============================================================================
+
+#[test]
+fn connection_events_need_verbose() {
+    let output: String = run(&[]);
+    assert!(output.contains("Local Address: 10.0.0.1:7"), "{}", output);
+    assert!(!output.contains("connection accepted"), "{}", output);
+
+    let output: String = run(&["-v"]);
+    let line: &str = output
+        .lines()
+        .find(|line| line.contains("connection accepted"))
+        .unwrap_or_else(|| panic!("no connection event:\n{}", output));
+    assert!(line.contains(" DEBUG demi::tcp_echo] "), "{}", line);
+    assert!(line.contains(" qd="), "{}", line);
+}
+
+#[test]
+fn quiet_hides_startup() {
+    let output: String = run(&["-q"]);
+    assert!(!output.contains("Local Address"), "{}", output);
+}
+
+#[test]
+fn filters_apply_to_targets() {
+    let output: String = run(&["--log", "warn,demi::tcp_echo=debug"]);
+    assert!(output.contains("connection accepted"), "{}", output);
+    assert!(!output.contains("Configuration"), "{}", output);
+}
+
+#[test]
+fn json_lines_are_objects() {
+    let output: String = run(&["--log_format", "json", "-v"]);
+    let lines: Vec<&str> = output.lines().filter(|l| l.starts_with('{')).collect();
+    assert!(!lines.is_empty(), "{}", output);
+    for line in &lines {
+        assert!(
+            line.starts_with("{\"ts\":\"") && line.ends_with('}'),
+            "{}",
+            line
+        );
+    }
+    assert!(
+        lines
+            .iter()
+            .any(|l| l.contains("\"level\":\"DEBUG\"") && l.contains("\"qd\":")),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn files_are_appended() {
+    let path: PathBuf = env::temp_dir().join("demikernel-logging.log");
+    let _ = fs::remove_file(&path);
+    let output: String = run(&["--log_file", path.to_str().unwrap()]);
+    assert!(!output.contains("Local Address"), "{}", output);
+    let contents: String = fs::read_to_string(&path).expect("failed to read log file");
+    assert!(
+        contents.contains("Local Address: 10.0.0.1:7"),
+        "{}",
+        contents
+    );
+}
@@ -0,0 +1,534 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Loopback Integration Tests
+//!
+//! Every test spawns a pair or a chain of binaries that talk over 127.0.0.1 or ::1, waits for the
+//! traffic source to run its scenario to completion, and checks the byte counts of every binary.
+//! Sources exit on their own and log what they sent. Servers listen on ports that were free when
+//! the test started, are deemed ready once their control socket answers, and are stopped through
+//! it after traffic settled, which they must survive with a clean exit.
+//!
+//! Binaries run on the libOS when the tests are built with `catnap-libos`, and on sockets of
+//! the operating system otherwise, so that a plain `cargo test` works on any Linux box.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+mod common;
+
+use ::std::{
+    env, fs,
+    net::{TcpListener, UdpSocket},
+    path::PathBuf,
+    process::Child,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+use common::Client;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Server under Test
+struct Server {
+    /// Name of the applet.
+    bin: &'static str,
+    /// Running applet.
+    child: Child,
+    /// Client of its control socket.
+    client: Client,
+}
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Time after which a binary is considered to hang.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Interval at which the counters of a server are polled while traffic settles.
+const SETTLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Scenario run by traffic sources. It outlasts the logging interval of the binaries, so that
+/// they log their statistics at least once.
+const SCENARIO: &str = r#"
+[[phases]]
+name = "steady"
+duration = "6s"
+rate = "1ms"
+size = 128
+"#;
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+impl Server {
+    /// Spawns a server with a control socket, and waits for it to answer.
+    fn start(bin: &'static str, args: &[&str]) -> Self {
+        static NSERVERS: AtomicUsize = AtomicUsize::new(0);
+        let path: PathBuf = env::temp_dir().join(format!(
+            "demikernel-loopback-{}-{}.sock",
+            std::process::id(),
+            NSERVERS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_file(&path);
+        let mut child: Child = spawn(
+            bin,
+            &[args, &["--control_socket", path.to_str().unwrap()]].concat(),
+        );
+
+        // The socket shows up once the server is bound, and workers answer once all of them are.
+        let start: Instant = Instant::now();
+        while !path.exists() {
+            if let Some(status) = child.try_wait().expect("failed to wait for child") {
+                panic!(
+                    "{} exited early with {}:\n{}",
+                    bin,
+                    status,
+                    common::collect(&mut child)
+                );
+            }
+            if start.elapsed() > TIMEOUT {
+                child.kill().ok();
+                child.wait().ok();
+                panic!("{} did not start:\n{}", bin, common::collect(&mut child));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let mut client: Client = Client::connect(&path);
+        client.send("stats");
+
+        Self { bin, child, client }
+    }
+
+    /// Waits for traffic to settle, shuts the server down, and checks that it exited cleanly.
+    /// Returns the number of bytes that the server counted, along with its output.
+    ///
+    /// Traffic settled once no connection is left and counters stopped moving.
+    fn stop(mut self) -> (usize, String) {
+        let start: Instant = Instant::now();
+        let mut last: Option<usize> = None;
+        let nbytes: usize = loop {
+            let reply: String = self.client.send("stats");
+            let nbytes: usize = total(&reply, "bytes");
+            if total(&reply, "connections") == 0 && last == Some(nbytes) {
+                break nbytes;
+            }
+            if start.elapsed() > TIMEOUT {
+                panic!("{} did not settle:\n{}", self.bin, reply);
+            }
+            last = Some(nbytes);
+            thread::sleep(SETTLE_INTERVAL);
+        };
+
+        let reply: String = self.client.send("shutdown");
+        assert_eq!(reply, "ok\n", "{} refused to shut down", self.bin);
+        let output: String = finish(self.bin, self.child);
+        (nbytes, output)
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns the backend that binaries run on.
+fn io_backend() -> &'static str {
+    if cfg!(feature = "catnap-libos") {
+        "libos"
+    } else {
+        "posix"
+    }
+}
+
+/// Returns an address of `host` with a port that is free for both TCP and UDP.
+fn addr(host: &str) -> String {
+    loop {
+        let listener: TcpListener =
+            TcpListener::bind(format!("{}:0", host)).expect("failed to bind socket");
+        let port: u16 = listener.local_addr().expect("failed to get address").port();
+        if UdpSocket::bind(format!("{}:{}", host, port)).is_ok() {
+            return format!("{}:{}", host, port);
+        }
+    }
+}
+
+/// Writes a scenario file for the test named `name`.
+fn scenario(name: &str, contents: &str) -> PathBuf {
+    let path: PathBuf = env::temp_dir().join(format!("{}-{}.toml", name, std::process::id()));
+    fs::write(&path, contents).expect("failed to write scenario file");
+    path
+}
+
+/// Spawns an applet of `demi` with its output captured.
+fn spawn(bin: &str, args: &[&str]) -> Child {
+    common::spawn(
+        common::demi(bin)
+            .args(args)
+            .args(["--io_backend", io_backend()]),
+    )
+}
+
+/// Waits for a binary to exit, and checks that it succeeded.
+fn finish(bin: &str, mut child: Child) -> String {
+    let start: Instant = Instant::now();
+    loop {
+        match child.try_wait().expect("failed to wait for child") {
+            Some(status) => {
+                let output: String = common::collect(&mut child);
+                assert!(
+                    status.success(),
+                    "{} exited with {}:\n{}",
+                    bin,
+                    status,
+                    output
+                );
+                return output;
+            }
+            None if start.elapsed() > TIMEOUT => {
+                child.kill().ok();
+                child.wait().ok();
+                panic!("{} timed out:\n{}", bin, common::collect(&mut child));
+            }
+            None => thread::sleep(Duration::from_millis(100)),
+        }
+    }
+}
+
+/// Runs a traffic source to completion, and returns the number of bytes that it logged.
+fn send(bin: &str, args: &[&str]) -> usize {
+    let output: String = finish(bin, spawn(bin, args));
+    last_count(bin, &output)
+}
+
+/// Sums the values of `key: value` lines of a control socket reply, which are listed per worker
+/// for servers that run workers. Keys that are missing count as zero.
+fn total(reply: &str, key: &str) -> usize {
+    reply
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix(&format!("{}: ", key)))
+        .map(|value| value.parse::<usize>().expect("malformed reply"))
+        .sum()
+}
+
+/// Parses the total byte counts of `N B / M us` statistics lines.
+fn byte_counts(output: &str) -> Vec<usize> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (head, _) = line.split_once(" B / ")?;
+            head.rsplit([' ', ']']).next()?.parse().ok()
+        })
+        .collect()
+}
+
+/// Returns the last byte count logged by a binary, checking that it moved some traffic.
+fn last_count(bin: &str, output: &str) -> usize {
+    match byte_counts(output).last() {
+        Some(&nbytes) if nbytes > 0 => nbytes,
+        _ => panic!("{} logged no traffic:\n{}", bin, output),
+    }
+}
+
+/// Relays a TCP stream from a generator at `host` to a dump server through `bin`, whose
+/// `flag` argument names the dump server, and checks that every byte made it through.
+fn tcp_pktgen_through(name: &str, host: &str, bin: &'static str, flag: &str) {
+    let path: PathBuf = scenario(name, SCENARIO);
+    let (relay, dump): (String, String) = (addr(host), addr(host));
+    let dumper: Server = Server::start("tcp-dump", &["--local", &dump]);
+    let relayer: Server = Server::start(bin, &["--local", &relay, flag, &dump]);
+
+    let sent: usize = send(
+        "tcp-pktgen",
+        &["--remote", &relay, "--scenario", path.to_str().unwrap()],
+    );
+
+    let (relayed, _): (usize, String) = relayer.stop();
+    let (received, _): (usize, String) = dumper.stop();
+    assert_eq!(
+        relayed, sent,
+        "{} relayed {} B out of {} B",
+        bin, relayed, sent
+    );
+    assert_eq!(
+        received, sent,
+        "tcp-dump got {} B out of {} B",
+        received, sent
+    );
+}
+
+//==============================================================================
+// Tests
+//==============================================================================
+
+#[test]
+fn udp_pktgen_to_udp_dump() {
+    let path: PathBuf = scenario("udp-pktgen-to-udp-dump", SCENARIO);
+    let (local, dump): (String, String) = (addr("127.0.0.1"), addr("127.0.0.1"));
+    let server: Server = Server::start("udp-dump", &["--local", &dump]);
+
+    let sent: usize = send(
+        "udp-pktgen",
+        &[
+            "--local",
+            &local,
+            "--remote",
+            &dump,
+            "--scenario",
+            path.to_str().unwrap(),
+        ],
+    );
+
+    // Datagrams may be lost.
+    let (received, _): (usize, String) = server.stop();
+    assert!(
+        received > 0 && received <= sent,
+        "udp-dump got {} B out of {} B",
+        received,
+        sent
+    );
+}
+
+#[test]
+fn tcp_pktgen_to_tcp_dump() {
+    let path: PathBuf = scenario("tcp-pktgen-to-tcp-dump", SCENARIO);
+    let dump: String = addr("127.0.0.1");
+    let server: Server = Server::start("tcp-dump", &["--local", &dump]);
+
+    let sent: usize = send(
+        "tcp-pktgen",
+        &["--remote", &dump, "--scenario", path.to_str().unwrap()],
+    );
+
+    let (received, _): (usize, String) = server.stop();
+    assert_eq!(
+        received, sent,
+        "tcp-dump got {} B out of {} B",
+        received, sent
+    );
+}
+
+#[test]
+fn tcp_echo_server_with_client() {
+    let path: PathBuf = scenario(
+        "tcp-echo-server-with-client",
+        &format!("{}connections = 2\n", SCENARIO),
+    );
+    let echo: String = addr("127.0.0.1");
+    let server: Server = Server::start(
+        "tcp-echo",
+        &["--peer", "server", "--local", &echo, "--bufsize", "128"],
+    );
+
+    let exchanged: usize = send(
+        "tcp-echo",
+        &[
+            "--peer",
+            "client",
+            "--remote",
+            &echo,
+            "--bufsize",
+            "128",
+            "--scenario",
+            path.to_str().unwrap(),
+        ],
+    );
+
+    // The client counts bytes both ways, and the server only those it echoed.
+    let (echoed, _): (usize, String) = server.stop();
+    assert_eq!(
+        2 * echoed,
+        exchanged,
+        "server echoed {} B, client exchanged {} B",
+        echoed,
+        exchanged
+    );
+}
+
+#[test]
+fn udp_pktgen_through_udp_echo() {
+    let path: PathBuf = scenario("udp-pktgen-through-udp-echo", SCENARIO);
+    let (local, echo, dump): (String, String, String) =
+        (addr("127.0.0.1"), addr("127.0.0.1"), addr("127.0.0.1"));
+    let dumper: Server = Server::start("udp-dump", &["--local", &dump]);
+    let echoer: Server = Server::start("udp-echo", &["--local", &echo, "--remote", &dump]);
+
+    let sent: usize = send(
+        "udp-pktgen",
+        &[
+            "--local",
+            &local,
+            "--remote",
+            &echo,
+            "--scenario",
+            path.to_str().unwrap(),
+        ],
+    );
+
+    // Datagrams may be lost on every hop.
+    let (echoed, _): (usize, String) = echoer.stop();
+    let (received, _): (usize, String) = dumper.stop();
+    assert!(
+        echoed > 0 && echoed <= sent,
+        "udp-echo got {} B out of {} B",
+        echoed,
+        sent
+    );
+    assert!(
+        received > 0 && received <= echoed,
+        "udp-dump got {} B out of {} B",
+        received,
+        echoed
+    );
+}
+
+#[test]
+fn udp_pktgen_through_udp_relay() {
+    let path: PathBuf = scenario("udp-pktgen-through-udp-relay", SCENARIO);
+    let (local, relay, dump): (String, String, String) =
+        (addr("127.0.0.1"), addr("127.0.0.1"), addr("127.0.0.1"));
+    let dumper: Server = Server::start("udp-dump", &["--local", &dump]);
+    let relayer: Server = Server::start("udp-relay", &["--local", &relay, "--backend", &dump]);
+
+    let sent: usize = send(
+        "udp-pktgen",
+        &[
+            "--local",
+            &local,
+            "--remote",
+            &relay,
+            "--scenario",
+            path.to_str().unwrap(),
+        ],
+    );
+
+    // Datagrams may be lost on every hop.
+    let (relayed, _): (usize, String) = relayer.stop();
+    let (received, _): (usize, String) = dumper.stop();
+    assert!(
+        relayed > 0 && relayed <= sent,
+        "udp-relay got {} B out of {} B",
+        relayed,
+        sent
+    );
+    assert!(
+        received > 0 && received <= relayed,
+        "udp-dump got {} B out of {} B",
+        received,
+        relayed
+    );
+}
+
+#[test]
+fn tcp_pktgen_through_tcp_relay() {
+    tcp_pktgen_through(
+        "tcp-pktgen-through-tcp-relay",
+        "127.0.0.1",
+        "tcp-relay",
+        "--remote",
+    );
+}
+
+#[test]
+fn tcp_pktgen_through_tcp_lb() {
+    tcp_pktgen_through(
+        "tcp-pktgen-through-tcp-lb",
+        "127.0.0.1",
+        "tcp-lb",
+        "--backend",
+    );
+}
+
+/// The libOS only speaks IPv4, so on it the test checks that IPv6 is reported as unsupported.
+#[test]
+fn tcp_pktgen_through_tcp_relay_over_ipv6() {
+    if io_backend() == "libos" {
+        let mut child: Child = spawn("tcp-dump", &["--local", &addr("[::1]")]);
+        let status = child.wait().expect("failed to wait for child");
+        let output: String = common::collect(&mut child);
+        assert!(!status.success(), "tcp-dump ran over IPv6:\n{}", output);
+        assert!(output.contains("IPv6 is not supported"), "{}", output);
+        return;
+    }
+
+    tcp_pktgen_through(
+        "tcp-pktgen-through-tcp-relay-over-ipv6",
+        "[::1]",
+        "tcp-relay",
+        "--remote",
+    );
+}
+
+#[test]
+fn tcp_echo_workers_share_a_port() {
+    let path: PathBuf = scenario(
+        "tcp-echo-workers-share-a-port",
+        &format!("{}connections = 4\n", SCENARIO),
+    );
+    let echo: String = addr("127.0.0.1");
+    let server: Server = Server::start(
+        "tcp-echo",
+        &[
+            "--peer",
+            "server",
+            "--local",
+            &echo,
+            "--bufsize",
+            "128",
+            "--workers",
+            "2",
+        ],
+    );
+
+    let exchanged: usize = send(
+        "tcp-echo",
+        &[
+            "--peer",
+            "client",
+            "--remote",
+            &echo,
+            "--bufsize",
+            "128",
+            "--scenario",
+            path.to_str().unwrap(),
+        ],
+    );
+
+    // Workers report the bytes they echoed one by one.
+    let (echoed, output): (usize, String) = server.stop();
+    assert!(output.contains("  worker 1 (core "), "{}", output);
+    assert_eq!(
+        2 * echoed,
+        exchanged,
+        "workers echoed {} B, client exchanged {} B",
+        echoed,
+        exchanged
+    );
+}
+
+/// Only workers share ports, so a second server fails to bind the port of a running one.
+#[test]
+fn servers_do_not_share_ports() {
+    let dump: String = addr("127.0.0.1");
+    let first: Server = Server::start("udp-dump", &["--local", &dump]);
+
+    let mut second: Child = spawn("udp-dump", &["--local", &dump]);
+    let status = second.wait().expect("failed to wait for child");
+    let output: String = common::collect(&mut second);
+    first.stop();
+    assert!(!status.success(), "udp-dump shared a port:\n{}", output);
+    assert!(output.contains("failed to bind socket"), "{}", output);
+}
+
+#[test]
+fn byte_counts_are_parsed() {
+    let output: &str = "Local Address: 127.0.0.1:1\n\
+                        1024 B / 5000000 us\n\
+                        [steady] 2048 B / 6000000 us (2048 B / 6000000 us in phase)\n";
+    assert_eq!(byte_counts(output), vec![1024, 2048]);
+}
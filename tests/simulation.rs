@@ -0,0 +1,312 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Simulation Tests
+//!
+//! Every test runs a binary on the simulator backend for many seeds, and reports the seeds for
+//! which the simulator found a problem, along with the command that reproduces the first one.
+//!
+//! The number of seeds is set with `SIM_SEEDS`, and a single seed can be replayed with
+//! `SIM_SEED`. Faults injected by each test are overridden with `SIM_FAULTS`.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+mod common;
+
+use ::std::{collections::HashMap, env, ops::Range, process::Command};
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Number of seeds explored by default.
+const DEFAULT_SEEDS: u64 = 32;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Expected Traffic
+#[derive(Clone, Copy, Debug)]
+enum Expect {
+    /// Everything that clients send comes back.
+    Echo,
+    /// Nothing that clients send comes back.
+    Sink,
+    /// Some of what clients send may come back.
+    Any,
+}
+
+/// Simulation Target
+struct Target {
+    /// Applet of `demi` to run.
+    bin: &'static str,
+    /// Arguments of the binary.
+    args: &'static [&'static str],
+    /// Faults to inject.
+    faults: &'static str,
+    /// Number of clients, if fixed.
+    clients: Option<&'static str>,
+    /// Expected traffic.
+    expect: Expect,
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns the seeds to explore.
+fn seeds() -> Range<u64> {
+    if let Ok(seed) = env::var("SIM_SEED") {
+        let seed: u64 = seed.parse().expect("invalid SIM_SEED");
+        return seed..seed + 1;
+    }
+    let nseeds: u64 = match env::var("SIM_SEEDS") {
+        Ok(nseeds) => nseeds.parse().expect("invalid SIM_SEEDS"),
+        Err(_) => DEFAULT_SEEDS,
+    };
+    0..nseeds
+}
+
+/// Parses the `key=value` statistics that the simulator reports.
+fn statistics(output: &str) -> HashMap<&str, usize> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("simulation: "))
+        .flat_map(str::split_whitespace)
+        .filter_map(|field| {
+            let (key, value) = field.split_once('=')?;
+            Some((key, value.trim_end_matches("us").parse().ok()?))
+        })
+        .collect()
+}
+
+/// Runs a target for a seed, returning a description of the problem if any.
+fn run(target: &Target, faults: &str, seed: u64) -> Result<(), String> {
+    let mut command: Command = common::demi(target.bin);
+    command
+        .args(target.args)
+        .args(["--io_backend", "sim"])
+        .env("SIM_SEED", seed.to_string())
+        .env("SIM_FAULTS", faults);
+    match target.clients {
+        Some(clients) => command.env("SIM_CLIENTS", clients),
+        None => command.env_remove("SIM_CLIENTS"),
+    };
+    let (success, output): (bool, String) = common::run(&mut command);
+    if !success || !output.contains(&format!("simulation: seed {} passed", seed)) {
+        return Err(output);
+    }
+
+    // Check that traffic flowed as expected.
+    let stats: HashMap<&str, usize> = statistics(&output);
+    let sent: usize = stats["stream_sent"] + stats["datagrams_sent"];
+    let returned: usize = stats["stream_echoed"] + stats["datagrams_returned"];
+    let ok: bool = match target.expect {
+        Expect::Echo => sent > 0 && returned == sent,
+        Expect::Sink => sent > 0 && returned == 0,
+        Expect::Any => returned <= sent,
+    };
+    match ok {
+        true => Ok(()),
+        false => Err(format!(
+            "unexpected traffic for {:?}:\n{}",
+            target.expect, output
+        )),
+    }
+}
+
+/// Explores seeds for a target, and fails with the first failing seed.
+fn explore(target: Target) {
+    let faults: String = env::var("SIM_FAULTS").unwrap_or_else(|_| target.faults.to_string());
+    let mut failures: Vec<(u64, String)> = Vec::new();
+    let seeds: Range<u64> = seeds();
+    for seed in seeds.clone() {
+        if let Err(output) = run(&target, &faults, seed) {
+            failures.push((seed, output));
+        }
+    }
+
+    if let Some((seed, output)) = failures.first() {
+        let failed: Vec<u64> = failures.iter().map(|(seed, _)| *seed).collect();
+        let clients: String = match target.clients {
+            Some(clients) => format!("SIM_CLIENTS={} ", clients),
+            None => String::new(),
+        };
+        panic!(
+            "{} of {} seeds failed: {:?}\nreproduce with: SIM_SEED={} SIM_FAULTS={} {}{} {} {} \
+             --io_backend sim\n{}",
+            failures.len(),
+            seeds.end - seeds.start,
+            failed,
+            seed,
+            faults,
+            clients,
+            env!("CARGO_BIN_EXE_demi"),
+            target.bin,
+            target.args.join(" "),
+            output
+        );
+    }
+}
+
+//==============================================================================
+// Tests
+//==============================================================================
+
+#[test]
+fn simulate_tcp_echo() {
+    explore(Target {
+        bin: "tcp-echo",
+        args: &[
+            "--peer",
+            "server",
+            "--local",
+            "10.0.0.1:7",
+            "--bufsize",
+            "1024",
+        ],
+        faults: "partial",
+        clients: None,
+        expect: Expect::Echo,
+    });
+}
+
+/// A single client is simulated, as tcp-dump only serves the first connection that it accepts.
+#[test]
+fn simulate_tcp_dump() {
+    explore(Target {
+        bin: "tcp-dump",
+        args: &["--local", "10.0.0.1:9"],
+        faults: "partial",
+        clients: Some("1"),
+        expect: Expect::Sink,
+    });
+}
+
+#[test]
+fn simulate_udp_dump() {
+    explore(Target {
+        bin: "udp-dump",
+        args: &["--local", "10.0.0.1:9"],
+        faults: "drop",
+        clients: None,
+        expect: Expect::Sink,
+    });
+}
+
+#[test]
+fn simulate_udp_relay() {
+    explore(Target {
+        bin: "udp-relay",
+        args: &["--local", "10.0.0.1:53", "--backend", "10.0.0.2:53"],
+        faults: "none",
+        clients: None,
+        expect: Expect::Echo,
+    });
+}
+
+#[test]
+fn simulate_udp_relay_with_drops() {
+    explore(Target {
+        bin: "udp-relay",
+        args: &["--local", "10.0.0.1:53", "--backend", "10.0.0.2:53"],
+        faults: "drop",
+        clients: None,
+        expect: Expect::Any,
+    });
+}
+
+/// Datagrams are held back, so the relay has to wake up on time to release them.
+#[test]
+fn simulate_udp_relay_with_delays() {
+    explore(Target {
+        bin: "udp-relay",
+        args: &[
+            "--local",
+            "10.0.0.1:53",
+            "--backend",
+            "10.0.0.2:53",
+            "--impair_up",
+            "delay=5ms,jitter=2ms",
+        ],
+        faults: "none",
+        clients: None,
+        expect: Expect::Echo,
+    });
+}
+
+#[test]
+fn simulate_tcp_relay_with_resets() {
+    explore(Target {
+        bin: "tcp-relay",
+        args: &["--local", "10.0.0.1:80", "--remote", "10.0.0.2:80"],
+        faults: "partial,reset",
+        clients: None,
+        expect: Expect::Any,
+    });
+}
+
+/// Probes of health checks keep the load balancer busy, so runs end once clients are done.
+#[test]
+fn simulate_tcp_lb_with_health_checks() {
+    explore(Target {
+        bin: "tcp-lb",
+        args: &[
+            "--local",
+            "10.0.0.1:80",
+            "--backend",
+            "10.0.0.2:80",
+            "--backend",
+            "10.0.0.3:80",
+            "--policy",
+            "source-hash",
+            "--health_interval",
+            "1",
+        ],
+        faults: "partial",
+        clients: None,
+        expect: Expect::Echo,
+    });
+}
+
+#[test]
+fn simulate_tcp_echo_over_ipv6() {
+    explore(Target {
+        bin: "tcp-echo",
+        args: &["--local", "[2001:db8::1]:7", "--bufsize", "1024"],
+        faults: "partial",
+        clients: None,
+        expect: Expect::Echo,
+    });
+}
+
+#[test]
+fn simulate_udp_relay_over_ipv6() {
+    explore(Target {
+        bin: "udp-relay",
+        args: &[
+            "--local",
+            "[2001:db8::1]:53",
+            "--backend",
+            "[2001:db8::2]:53",
+        ],
+        faults: "none",
+        clients: None,
+        expect: Expect::Echo,
+    });
+}
+
+#[test]
+fn statistics_are_parsed() {
+    let output: &str = "connection accepted!\n\
+                        simulation: seed=3 faults=partial events=10 waits=20 time=30us\n\
+                        simulation: stream_sent=40 stream_echoed=40\n";
+    let stats: HashMap<&str, usize> = statistics(output);
+    assert_eq!(stats["seed"], 3);
+    assert_eq!(stats["time"], 30);
+    assert_eq!(stats["stream_echoed"], 40);
+    assert!(!stats.contains_key("faults"));
+}
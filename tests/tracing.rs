@@ -0,0 +1,147 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Tracing Tests
+//!
+//! Every test runs a binary with tracing enabled, and checks the trace that it writes on exit.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+mod common;
+
+use ::std::{
+    env, fs,
+    path::PathBuf,
+    process::{Child, ExitStatus, Stdio},
+    thread,
+    time::Duration,
+};
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Returns a fresh path for a trace in the temporary directory.
+fn trace(name: &str) -> PathBuf {
+    let path: PathBuf = env::temp_dir().join(format!("demikernel-trace-{}", name));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+/// Runs tcp-echo as a server on the simulator backend, with tracing enabled.
+fn run(path: &PathBuf, format: &str) {
+    let (success, output): (bool, String) = common::run(
+        common::demi("tcp-echo")
+            .args(["server", "--local", "10.0.0.1:7", "--bufsize", "64"])
+            .args(["--io_backend", "sim", "--trace_format", format])
+            .arg("--trace")
+            .arg(path),
+    );
+    assert!(success, "{}", output);
+}
+
+/// Decodes a varint at the start of `bytes`. Returns it along with the bytes that follow.
+fn varint(bytes: &[u8]) -> (u64, &[u8]) {
+    let mut value: u64 = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return (value, &bytes[i + 1..]);
+        }
+    }
+    panic!("truncated varint")
+}
+
+/// Splits a protocol buffer message into its fields, as pairs of a field number and a value.
+/// Only integer and length-delimited fields are expected, and values of the former are empty.
+fn fields(mut bytes: &[u8]) -> Vec<(u64, &[u8])> {
+    let mut fields: Vec<(u64, &[u8])> = Vec::new();
+    while !bytes.is_empty() {
+        let (key, rest): (u64, &[u8]) = varint(bytes);
+        let (value, rest): (&[u8], &[u8]) = match key & 0x7 {
+            0 => (&[], varint(rest).1),
+            2 => {
+                let (len, rest): (u64, &[u8]) = varint(rest);
+                assert!(rest.len() >= len as usize, "truncated field {}", key >> 3);
+                rest.split_at(len as usize)
+            }
+            wire => panic!("unexpected wire type {} for field {}", wire, key >> 3),
+        };
+        fields.push((key >> 3, value));
+        bytes = rest;
+    }
+    fields
+}
+
+//==============================================================================
+// Tests
+//==============================================================================
+
+#[test]
+fn chrome_traces_pair_issues_and_completions() {
+    let path: PathBuf = trace("echo.json");
+    run(&path, "chrome");
+    let trace: String = fs::read_to_string(&path).expect("failed to read trace");
+    assert!(trace.starts_with("{\"displayTimeUnit\":\"ns\",\"traceEvents\":["));
+    for event in [
+        "{\"name\":\"accept\",\"cat\":\"qd 0\",\"ph\":\"b\"",
+        "{\"name\":\"pop\",\"cat\":\"qd 1\",\"ph\":\"b\"",
+        "{\"name\":\"pop\",\"cat\":\"qd 1\",\"ph\":\"e\"",
+        "{\"name\":\"push\",\"cat\":\"qd 1\",\"ph\":\"e\"",
+        "{\"name\":\"wait\",\"ph\":\"X\"",
+    ] {
+        assert!(trace.contains(event), "{} is missing:\n{}", event, trace);
+    }
+}
+
+#[test]
+fn perfetto_traces_are_written() {
+    let path: PathBuf = trace("echo.pftrace");
+    run(&path, "perfetto");
+    let perfetto: Vec<u8> = fs::read(&path).expect("failed to read trace");
+
+    // A trace is a sequence of packets, which are length-delimited fields numbered 1. Packets
+    // either describe a track (field 60) or carry an event (field 11).
+    let mut tracks: Vec<&[u8]> = Vec::new();
+    let mut nevents: usize = 0;
+    for (field, packet) in fields(&perfetto) {
+        assert_eq!(field, 1, "unexpected field in trace");
+        for (field, value) in fields(packet) {
+            match field {
+                11 => nevents += 1,
+                60 => tracks.extend(fields(value).iter().filter(|f| f.0 == 2).map(|f| f.1)),
+                _ => (),
+            }
+        }
+    }
+    assert!(tracks.contains(&&b"qd 1 pop"[..]));
+
+    // Operations begin and end, just like waits, which are single events in Chrome traces.
+    let path: PathBuf = trace("echo-perfetto.json");
+    run(&path, "chrome");
+    let chrome: String = fs::read_to_string(&path).expect("failed to read trace");
+    let count = |phase: &str| chrome.matches(&format!("\"ph\":\"{}\"", phase)).count();
+    assert!(nevents > 0);
+    assert_eq!(nevents, count("b") + count("e") + 2 * count("X"));
+}
+
+#[test]
+fn traces_are_written_on_termination() {
+    let path: PathBuf = trace("dump.json");
+    let mut child: Child = common::demi("udp-dump")
+        .args(["--local", "127.0.0.1:40801", "--io_backend", "posix"])
+        .arg("--trace")
+        .arg(&path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn udp-dump");
+    thread::sleep(Duration::from_millis(500));
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) };
+    let status: ExitStatus = child.wait().expect("failed to wait for udp-dump");
+    assert_eq!(status.code(), Some(128 + libc::SIGTERM));
+    let trace: String = fs::read_to_string(&path).expect("failed to read trace");
+    assert!(trace.contains("{\"name\":\"pop\",\"cat\":\"qd 0\",\"ph\":\"b\""));
+}